@@ -89,18 +89,8 @@ function gcr --description 'git commit rename: gcr <commit_hash> "new title" (re
 		return $status
 	end
 
-	# older commit → non-interactive reword. hooks off so the format pre-commit can't jam the replay.
-	set -l msg_file (mktemp)
-	printf '%s\n' "$title" >$msg_file
-	set -l short (git rev-parse --short "$full") # same abbrev git writes into the rebase todo (unique → no false match)
-
-	GIT_SEQUENCE_EDITOR="sed -i 's/^pick $short /reword $short /'" \
-	GIT_EDITOR="cp $msg_file" \
-	git -c core.hooksPath=/dev/null rebase -i "$full^"
-	set -l code $status
-
-	rm -f $msg_file
-	return $code
+	# older commit → in-process reword (merges kept, `git_scripts undo` reverts)
+	$__fish_scripts_dir/git_scripts.rs reword $full "$title"
 end
 
 function gcs --description 'git commit squash: gcs [commit_hash] ["new title"] (meld commit into its parent, defaults to HEAD)'
@@ -120,40 +110,17 @@ function gcs --description 'git commit squash: gcs [commit_hash] ["new title"] (
 		return 1
 	end
 
-	set -l short (git rev-parse --short "$full")
-	set -l pshort (git rev-parse --short "$parent")
-
-	# child → fixup (melds into the preceding parent line). given a title, parent → reword so the result carries it.
-	set -l seq "sed -i -e 's/^pick $short /fixup $short /'"
-	set -l editor true
-	set -l msg_file
+	# meld into the parent, keeping its message unless a new title is given
 	if set -q argv[2]
-		set msg_file (mktemp)
-		printf '%s\n' "$argv[2]" >$msg_file
-		set seq "$seq -e 's/^pick $pshort /reword $pshort /'"
-		set editor "cp $msg_file"
-	end
-
-	# base = grandparent, or --root when the parent itself is the repo root
-	set -l base "$parent^"
-	if not git rev-parse --verify "$parent^" >/dev/null 2>&1
-		set base --root
-	end
-
-	GIT_SEQUENCE_EDITOR="$seq" \
-	GIT_EDITOR="$editor" \
-	git -c core.hooksPath=/dev/null rebase -i $base
-	set -l code $status
-
-	if string length -q -- "$msg_file"
-		rm -f $msg_file
+		$__fish_scripts_dir/git_scripts.rs squash $full -m "$argv[2]"
+	else
+		$__fish_scripts_dir/git_scripts.rs squash $full
 	end
-	return $code
 end
 
 alias gup="git add -A && git commit --fixup (git rev-parse HEAD)"
 alias gupp="gup && git push --follow-tags"
-alias gups="gup && git -c sequence.editor=true rebase -i --autosquash HEAD~2"
+alias gups="gup && $__fish_scripts_dir/git_scripts.rs squash HEAD"
 
 function gbl
 	# get branches sorted by date, with HEAD, name, commit hash, subject and author
@@ -153,63 +153,8 @@ function tn
 	tmux attach-session -t "$session_name:source.0"
 end
 
-# Rebuilds the working set recorded by `smart_shutdown` (`claude_restore.tsv`).
+# Rebuilds the working set recorded by `smart_shutdown` (`workspace.json`): tmux
+# layout, pane cwds, and each claude resumed into its original pane.
 function restore_sessions
-	set -l state_home $XDG_STATE_HOME
-	test -n "$state_home"; or set state_home "$HOME/.local/state"
-	set -l f "$state_home/claude_restore.tsv"
-	if not test -f $f
-		echo "restore_sessions: nothing to restore, $f absent (only smart_shutdown writes it)" >&2
-		return 0
-	end
-
-	set -l failed 0
-	set -l done 0
-	while read -l path n
-		if test -z "$path"; or not string match -qr '^[0-9]+$' -- "$n"
-			echo "restore_sessions: malformed line in $f: path='$path' count='$n' (want tab-separated '<path>	<count>')" >&2
-			set failed 1
-			continue
-		end
-		if not test -d $path
-			echo "restore_sessions: skipping $path — no longer a directory" >&2
-			set failed 1
-			continue
-		end
-		# a session for this path may already be up (hand-made, or we ran late) — reuse it
-		set -l session (tmux list-sessions -F '#{session_path}	#{session_name}' 2>/dev/null | string replace -rf '^'(string escape --style=regex $path)'\t' '')[1]
-		if test -z "$session"
-			set session (cs -t -d $path)
-			if test $status != 0
-				echo "restore_sessions: cs -t -d $path failed: $session" >&2
-				set failed 1
-				continue
-			end
-		end
-		for i in (seq 1 $n)
-			set -l target "$session:claude"
-			if test $i -gt 1
-				set target (tmux new-window -P -t $session -c $path -n claude)
-				if test $status != 0
-					echo "restore_sessions: $session — could not add claude window #$i: $target" >&2
-					set failed 1
-					break
-				end
-			end
-			if not tmux send-keys -t $target "cl -$i" Enter
-				echo "restore_sessions: $session — send-keys to '$target' failed" >&2
-				set failed 1
-				break
-			end
-		end
-		set done (math $done + 1)
-		echo "restore_sessions: $session ← $path ($n claude(s))"
-	end <$f
-
-	if test $failed = 1
-		echo "restore_sessions: some entries failed; keeping $f so a rerun can retry" >&2
-		return 1
-	end
-	echo "restore_sessions: restored $done project(s)"
-	rm -f $f
+	smart_shutdown restore $argv
 end
@@ -12,6 +12,7 @@ colored = "2"
 regex = "1"
 serde = { version = "1.0", features = ["derive"] }
 serde_json = "1.0"
+toml = "0.9"
 
 [dev-dependencies]
 insta = "1"
@@ -31,12 +32,15 @@ use std::process::Command;
 #[derive(Parser)]
 #[command(about = "Track state of Claude Code processes in tmux windows")]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Cmd>,
+
     /// Compact output: hide todos and session summaries
     #[arg(short, long)]
     compact: bool,
 
     /// JSON output for eww integration
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     json: bool,
 
     /// Emit Pango markup (for eww labels) instead of ANSI terminal colors.
@@ -45,9 +49,161 @@ struct Args {
     #[arg(long)]
     markup: bool,
 
-    /// Generate LLM summaries (slow, uses ollama)
+    /// Generate LLM summaries (slow; ollama unless [llm.summary] says otherwise)
     #[arg(long)]
     llm_summaries: bool,
+
+    /// Config file [default: $XDG_CONFIG_HOME/claude-sessions/config.toml]
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Stay resident: poll tmux and the transcripts every --interval seconds and
+    /// serve the table (plus state-transition events) over --socket. Plain runs
+    /// read the table off a live daemon instead of polling themselves.
+    #[arg(long)]
+    daemon: bool,
+
+    /// Stream the daemon's state transitions as newline-delimited JSON
+    #[arg(long, conflicts_with = "daemon")]
+    events: bool,
+
+    /// With --daemon: relaunch a claude that died mid-turn in its pane, the way
+    /// `revive` does (see there for the guards)
+    #[arg(long, requires = "daemon")]
+    auto_revive: bool,
+
+    /// Daemon socket [default: $XDG_RUNTIME_DIR/claude-sessions.sock]
+    #[arg(long)]
+    socket: Option<PathBuf>,
+
+    /// Daemon poll period, in seconds
+    #[arg(long, default_value_t = 1)]
+    interval: u64,
+
+    /// Pane-classification rules (TOML) to use instead of the built-in set
+    #[arg(long, global = true)]
+    rules: Option<PathBuf>,
+
+    /// tmux server to poll; repeat for several. `local`, a tmux socket path
+    /// (anything containing a `/`), or an ssh destination. Listing any
+    /// replaces the default of just `local`.
+    #[arg(long = "host", value_name = "HOST", global = true)]
+    hosts: Vec<String>,
+
+    /// How ssh destinations are reached: the destination and the quoted tmux
+    /// command line are appended to this
+    #[arg(long, default_value = "ssh -o BatchMode=yes -o ConnectTimeout=5", global = true)]
+    ssh: String,
+
+    /// Group the table under a header per project, state or host
+    #[arg(long, value_enum)]
+    group_by: Option<GroupBy>,
+
+    /// Show only windows in these states (comma-separated)
+    #[arg(long, value_delimiter = ',', value_parser = parse_state)]
+    only: Vec<ClaudeState>,
+
+    /// Hide windows in these states (comma-separated)
+    #[arg(long, value_delimiter = ',', value_parser = parse_state)]
+    exclude: Vec<ClaudeState>,
+
+    /// Turn the usage header red (and `usage --json`'s `warn` true) once a
+    /// limit is forecast to run out within this many minutes
+    #[arg(long, value_name = "MINUTES", global = true)]
+    warn_at: Option<u64>,
+}
+
+fn parse_state(s: &str) -> Result<ClaudeState, String> {
+    ClaudeState::parse(s).ok_or(format!("no state {s:?}"))
+}
+
+fn parse_seconds(s: &str) -> Result<std::time::Duration, String> {
+    s.parse::<f64>()
+        .ok()
+        .and_then(|secs| std::time::Duration::try_from_secs_f64(secs).ok())
+        .ok_or(format!("{s:?} isn't a number of seconds"))
+}
+
+#[derive(clap::Subcommand)]
+enum Cmd {
+    /// Time spent per state, per session and per project, from the recorded
+    /// state history — and the longest stretches spent blocked on a human
+    History {
+        /// Look back this many hours
+        #[arg(long, default_value_t = 24)]
+        hours: u64,
+        /// How many of the longest waits to list
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// Tokens and dollars spent, per day, project, model and session, summed
+    /// from the full transcripts
+    Cost {
+        /// Look back this many days, today included
+        #[arg(long, default_value_t = 7)]
+        days: u64,
+        /// How many of the most expensive sessions to list
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// The usage limits, when each runs out at the current pace, and whether
+    /// that's within --warn-at — from the state cache, without a poll
+    Usage,
+    /// Replay the classification rules over the fixtures and report every
+    /// capture that no longer reads as the state its name says
+    CheckRules {
+        #[arg(long, default_value = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures"))]
+        dir: PathBuf,
+    },
+    /// Pick an option of the question a window is asking — by number, or by a
+    /// piece of its label. Refused unless the pane still reads as a question.
+    Answer {
+        /// `[host/]session:window`, host as `--host` takes it
+        target: String,
+        option: String,
+    },
+    /// Type a message at a window's idle prompt and submit it; without TEXT,
+    /// submit the draft already typed there
+    Send {
+        /// `[host/]session:window`, host as `--host` takes it
+        target: String,
+        text: Option<String>,
+        /// Send only if the window still reads as this — the state it showed
+        /// when the message was written
+        #[arg(long, value_parser = parse_state)]
+        expect: Option<ClaudeState>,
+    },
+    /// Relaunch `claude --resume <id>` where a claude exited and left its resume
+    /// hint — in TARGET, or in every such window on --host. A session revived
+    /// in the last 10 minutes, or already running, is left alone.
+    Revive {
+        /// `[host/]session:window`, host as `--host` takes it
+        target: Option<String>,
+    },
+    /// Snapshot a window into a `<state>__<name>` classifier fixture, named by
+    /// what it reads as now — or, with --frames, into a timeline for `replay`
+    Record {
+        /// `[host/]session:window`, host as `--host` takes it
+        target: String,
+        /// The fixture's `<desc>`, or the timeline's directory name
+        name: String,
+        /// Name it after the state it should read as, when it's being
+        /// recorded because it reads wrong
+        #[arg(long, value_parser = parse_state)]
+        expect: Option<ClaudeState>,
+        /// Capture this many frames as a timeline instead of one fixture
+        #[arg(long, default_value_t = 1)]
+        frames: usize,
+        /// Seconds between timeline frames
+        #[arg(long, default_value = "1", value_parser = parse_seconds)]
+        every: std::time::Duration,
+        /// Default: tests/fixtures for a fixture, tests/timelines for a timeline
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+    /// Classify a recorded timeline's frames again and show where the reading
+    /// flipped, and which frames the rules now read differently
+    Replay { dir: PathBuf },
 }
 
 #[derive(Deserialize)]
@@ -86,7 +242,7 @@ const COLOR_INFO_GOOD: &str = "#b8d8b4";
 const COLOR_DEBUG: &str = "#000000";
 const COLOR_TRACE: &str = "#6b6b6b";
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 enum ClaudeState {
     Empty,    // No claude running (shell prompt)
@@ -140,10 +296,17 @@ impl ClaudeState {
             ClaudeState::Ongoing => "ongoing",
         }
     }
+
+    /// Inverse of as_str, for state names read back off disk.
+    fn parse(s: &str) -> Option<ClaudeState> {
+        serde_json::from_value(serde_json::Value::String(s.to_string())).ok()
+    }
 }
 
 #[derive(Debug)]
 struct ClaudeWindow {
+    /// Which tmux server the window lives on; None for the local default one.
+    host: Option<String>,
     session: String,
     window_index: u32,
     state: ClaudeState,
@@ -158,10 +321,46 @@ struct ClaudeWindow {
     model: Option<String>,
     /// Context size of the last turn, in tokens (see context_tokens)
     context: Option<u64>,
+    /// Context size per turn, oldest first (see cost::growth)
+    growth: Vec<u64>,
+    /// The pane's working directory (tmux's pane_current_path).
+    cwd: PathBuf,
+    /// Set when a claude exited here leaving its resume chrome (see mod revive).
+    resume: Option<Resume>,
+    /// The conversation's id (its transcript's file stem) — what `claude --resume`
+    /// takes, live or dead.
+    session_id: Option<String>,
+}
+
+/// A claude that exited in its pane, as much of it as `claude --resume` needs.
+#[derive(Debug, Clone)]
+struct Resume {
+    id: String,
+    /// Where its transcript is filed: the shell's cwd, read off /proc — the
+    /// pane_current_path reading on a remote host.
+    cwd: PathBuf,
+    /// The transcript ends on a turn still in flight — it died working,
+    /// rather than being quit at an idle prompt.
+    mid_turn: bool,
+}
+
+impl ClaudeWindow {
+    /// `session:window_index`, prefixed with `host/` off the local server —
+    /// the key the cache, the history and the daemon's transitions all use.
+    /// Local windows keep the bare key they've always had.
+    fn key(&self) -> String {
+        match &self.host {
+            Some(host) => format!("{host}/{}:{}", self.session, self.window_index),
+            None => format!("{}:{}", self.session, self.window_index),
+        }
+    }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct SessionEntry {
+    /// None for the local tmux server (see ClaudeWindow::host)
+    #[serde(default)]
+    host: Option<String>,
     name: String,
     window_index: u32,
     state: ClaudeState,
@@ -172,6 +371,32 @@ struct SessionEntry {
     model: Option<String>,
     /// Context size of the last turn, in tokens (see context_tokens)
     context: Option<u64>,
+    /// Context size per turn, oldest first (see cost::growth)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    growth: Vec<u64>,
+    /// path_to_project_name of the pane's cwd
+    #[serde(default)]
+    project: Option<String>,
+    /// See ClaudeWindow::session_id
+    #[serde(default)]
+    session_id: Option<String>,
+}
+
+impl SessionEntry {
+    fn group_key(&self, by: GroupBy) -> String {
+        match by {
+            GroupBy::Project => self.project.clone().unwrap_or_else(|| "?".to_string()),
+            GroupBy::State => self.state.as_str().to_string(),
+            GroupBy::Host => self.host.clone().unwrap_or_else(|| "local".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum GroupBy {
+    Project,
+    State,
+    Host,
 }
 
 #[derive(Debug)]
@@ -179,14 +404,20 @@ struct Sessions {
     entries: Vec<SessionEntry>,
     compact: bool,
     markup: bool,
+    group_by: Option<GroupBy>,
+    /// Windows per state, counted before --only/--exclude, so a filtered view
+    /// still says what it's hiding.
+    counts: std::collections::BTreeMap<ClaudeState, usize>,
 }
 
 impl Sessions {
-    fn new(compact: bool, markup: bool) -> Self {
+    fn new(compact: bool, markup: bool, group_by: Option<GroupBy>) -> Self {
         Self {
             entries: Vec::new(),
             compact,
             markup,
+            group_by,
+            counts: Default::default(),
         }
     }
 
@@ -195,13 +426,68 @@ impl Sessions {
     }
 
     fn sort(&mut self) {
-        self.entries.sort_by(|a, b| a.name.cmp(&b.name));
+        self.entries.sort_by(|a, b| (&a.host, &a.name).cmp(&(&b.host, &b.name)));
+    }
+
+    /// Counts every state, then keeps the entries in `only` (all, when empty)
+    /// and not in `exclude`.
+    fn filter(&mut self, only: &[ClaudeState], exclude: &[ClaudeState]) {
+        for e in &self.entries {
+            *self.counts.entry(e.state).or_default() += 1;
+        }
+        self.entries.retain(|e| (only.is_empty() || only.contains(&e.state)) && !exclude.contains(&e.state));
+    }
+
+    /// The entries under their group keys, groups in key order — states in
+    /// ClaudeState order rather than alphabetical — each keeping the sort
+    /// within it. One unnamed group when ungrouped.
+    fn groups(&self) -> Vec<(Option<String>, Vec<&SessionEntry>)> {
+        let Some(by) = self.group_by else {
+            return vec![(None, self.entries.iter().collect())];
+        };
+        let mut order: Vec<&SessionEntry> = self.entries.iter().collect();
+        match by {
+            GroupBy::State => order.sort_by_key(|e| e.state),
+            _ => order.sort_by_key(|e| e.group_key(by)),
+        }
+        let mut groups: Vec<(Option<String>, Vec<&SessionEntry>)> = Vec::new();
+        for e in order {
+            let key = e.group_key(by);
+            match groups.last_mut() {
+                Some((Some(k), members)) if *k == key => members.push(e),
+                _ => groups.push((Some(key), vec![e])),
+            }
+        }
+        groups
+    }
+
+    /// "3 active · 1 question · 5 finished", in ClaudeState order.
+    fn counts_line(&self) -> String {
+        self.counts.iter().map(|(s, n)| format!("{n} {}", s.as_str())).collect::<Vec<_>>().join(" · ")
+    }
+
+    /// `--json`: the bare entry array it's always been, or with --group-by,
+    /// `{"groups": [{"key", "entries"}], "counts": {state: n}}`.
+    fn to_json(&self) -> serde_json::Value {
+        if self.group_by.is_none() {
+            return serde_json::json!(self.entries);
+        }
+        let groups: Vec<_> = self
+            .groups()
+            .into_iter()
+            .map(|(key, entries)| serde_json::json!({ "key": key, "entries": entries }))
+            .collect();
+        serde_json::json!({ "groups": groups, "counts": self.counts })
     }
 }
 
 impl fmt::Display for Sessions {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.entries.is_empty() {
+            // Filtered down to nothing still says what there was.
+            if !self.compact && !self.counts.is_empty() {
+                write!(f, "{}", self.counts_line())?;
+            }
             return Ok(());
         }
 
@@ -227,6 +513,14 @@ impl fmt::Display for Sessions {
             .max()
             .unwrap_or(0);
 
+        // Host column width; 0 (no column) when everything is local.
+        let max_host_len = self
+            .entries
+            .iter()
+            .filter_map(|e| e.host.as_ref().map(|h| h.len()))
+            .max()
+            .unwrap_or(0);
+
         let max_state_len = self
             .entries
             .iter()
@@ -255,150 +549,262 @@ impl fmt::Display for Sessions {
                 .unwrap_or(0)
         };
 
-        for (i, entry) in self.entries.iter().enumerate() {
-            if i > 0 {
-                writeln!(f)?;
+        // Columns are sized over every group, so they line up across headers.
+        let indent = if self.group_by.is_some() { "  " } else { "" };
+        let mut first = true;
+        for (key, members) in self.groups() {
+            if let Some(key) = key {
+                if !first {
+                    writeln!(f)?;
+                }
+                first = false;
+                if self.markup {
+                    write!(f, "<b>{}</b> ({})", pango_escape(&key), members.len())?;
+                } else {
+                    write!(f, "{} ({})", key.bold(), members.len())?;
+                }
             }
-
-            // Build the name with window index (non-compact) and optional summary
-            let display_name = if self.compact {
-                entry.name.clone()
-            } else {
-                let name_with_index = format!("{}:{}", entry.name, entry.window_index);
-                match &entry.summary {
-                    Some(summary) => format!("{} <{}>", name_with_index, summary),
-                    None => name_with_index,
+            for entry in members {
+                if !first {
+                    writeln!(f)?;
                 }
-            };
-
-            // Pad state string manually since colored strings mess up format width.
-            // Pad to VISIBLE width first; coloring (ANSI or Pango span) is applied
-            // after, so it never participates in width math.
-            let state_str = entry.state.as_str();
-            let padded_state = format!("{:width$}", state_str, width = max_state_len);
-
-            // Model column (full view only). Padded to the widest model so the
-            // trailing todo/draft/question stays aligned; empty when no session
-            // reports a model at all.
-            let padded_model = (max_model_len > 0).then(|| {
-                format!("{:width$}", entry.model.as_deref().unwrap_or(""), width = max_model_len)
-            });
+                first = false;
+                write!(f, "{indent}")?;
 
-            // Right-aligned so the magnitudes line up ("12k" under "180k").
-            let padded_context = (max_context_len > 0).then(|| {
-                let cell = entry.context.map(format_tokens).unwrap_or_default();
-                format!("{:>width$}", cell, width = max_context_len)
-            });
+                // Build the name with window index (non-compact) and optional summary
+                let display_name = if self.compact {
+                    entry.name.clone()
+                } else {
+                    let name_with_index = format!("{}:{}", entry.name, entry.window_index);
+                    match &entry.summary {
+                        Some(summary) => format!("{} <{}>", name_with_index, summary),
+                        None => name_with_index,
+                    }
+                };
 
-            // The name column is likewise padded to visible width before any
-            // escaping — Pango escaping changes byte length but not glyph count,
-            // so escaping after padding keeps the columns aligned.
-            let padded_name = format!("{:width$}", display_name, width = max_name_len);
+                // Pad state string manually since colored strings mess up format width.
+                // Pad to VISIBLE width first; coloring (ANSI or Pango span) is applied
+                // after, so it never participates in width math.
+                let state_str = entry.state.as_str();
+                let padded_state = format!("{:width$}", state_str, width = max_state_len);
+
+                // Model column (full view only). Padded to the widest model so the
+                // trailing todo/draft/question stays aligned; empty when no session
+                // reports a model at all.
+                let padded_model = (max_model_len > 0).then(|| {
+                    format!("{:width$}", entry.model.as_deref().unwrap_or(""), width = max_model_len)
+                });
+
+                // Right-aligned so the magnitudes line up ("12k" under "180k"),
+                // then how it got there.
+                let padded_context = (max_context_len > 0).then(|| {
+                    let cell = entry.context.map(format_tokens).unwrap_or_default();
+                    format!("{:>width$} {}", cell, sparkline(&entry.growth), width = max_context_len)
+                });
+
+                // The name column is likewise padded to visible width before any
+                // escaping — Pango escaping changes byte length but not glyph count,
+                // so escaping after padding keeps the columns aligned.
+                let padded_name = format!("{:width$}", display_name, width = max_name_len);
+                let padded_host = if max_host_len > 0 {
+                    format!("{:width$}  ", entry.host.as_deref().unwrap_or(""), width = max_host_len)
+                } else {
+                    String::new()
+                };
 
-            // Trailing per-state content (todo / draft / question), if any.
-            let trailing = if self.compact {
-                None
-            } else {
-                match entry.state {
-                    ClaudeState::Active | ClaudeState::Planning => Some(match &entry.active_todo {
-                        Some(todo) => format!("[{}]", todo),
-                        None => "[]".to_string(),
-                    }),
-                    ClaudeState::Draft => Some(match &entry.draft_content {
-                        Some(draft) => format!("> {}", draft),
-                        None => "> ".to_string(),
-                    }),
-                    ClaudeState::Question => Some(match &entry.question_content {
-                        Some(q) => format!("? {}", q),
-                        None => "?".to_string(),
-                    }),
-                    _ => None,
-                }
-            };
+                // Trailing per-state content (todo / draft / question), if any.
+                let trailing = if self.compact {
+                    None
+                } else {
+                    match entry.state {
+                        ClaudeState::Active | ClaudeState::Planning => Some(match &entry.active_todo {
+                            Some(todo) => format!("[{}]", todo),
+                            None => "[]".to_string(),
+                        }),
+                        ClaudeState::Draft => Some(match &entry.draft_content {
+                            Some(draft) => format!("> {}", draft),
+                            None => "> ".to_string(),
+                        }),
+                        ClaudeState::Question => Some(match &entry.question_content {
+                            Some(q) => format!("? {}", q),
+                            None => "?".to_string(),
+                        }),
+                        _ => None,
+                    }
+                };
 
-            if self.markup {
-                // eww/GTK path: escape every literal segment for Pango, then wrap
-                // the state cell in a <span> only for the states that warrant
-                // grabbing my eye. Attention priority, NOT prettiness:
-                //   question -> COLOR_ERROR: a session is BLOCKED on me, nothing
-                //               moves until I act — highest visual urgency. stuck
-                //               rides along: same demand, it just phrased it in
-                //               prose instead of a selector.
-                //   error    -> COLOR_WARN: real, but errors here mostly surface
-                //               during hands-on interaction, so I'm already looking
-                //               — deliberately ranked below question. partial and
-                //               ongoing ride along: work left on the table, not
-                //               blocked mid-flight.
-                //   active/planning -> COLOR_INFO_ACTIVE: healthy "it's working" signal,
-                //               informational, lowest of the three.
-                //   limit    -> COLOR_INFO_NEGATIVE: wedged on the usage clock — nothing
-                //               to act on, but worth seeing at a glance.
-                //   finished -> COLOR_INFO_GOOD.
-                // Every other state stays uncolored — no span, no noise.
-                let state_cell = match entry.state {
-                    ClaudeState::Question | ClaudeState::Stuck => {
-                        format!("<span foreground=\"{COLOR_ERROR}\">{}</span>", pango_escape(&padded_state))
+                if self.markup {
+                    // eww/GTK path: escape every literal segment for Pango, then wrap
+                    // the state cell in a <span> only for the states that warrant
+                    // grabbing my eye. Attention priority, NOT prettiness:
+                    //   question -> COLOR_ERROR: a session is BLOCKED on me, nothing
+                    //               moves until I act — highest visual urgency. stuck
+                    //               rides along: same demand, it just phrased it in
+                    //               prose instead of a selector.
+                    //   error    -> COLOR_WARN: real, but errors here mostly surface
+                    //               during hands-on interaction, so I'm already looking
+                    //               — deliberately ranked below question. partial and
+                    //               ongoing ride along: work left on the table, not
+                    //               blocked mid-flight.
+                    //   active/planning -> COLOR_INFO_ACTIVE: healthy "it's working" signal,
+                    //               informational, lowest of the three.
+                    //   limit    -> COLOR_INFO_NEGATIVE: wedged on the usage clock — nothing
+                    //               to act on, but worth seeing at a glance.
+                    //   finished -> COLOR_INFO_GOOD.
+                    // Every other state stays uncolored — no span, no noise.
+                    let state_cell = match entry.state {
+                        ClaudeState::Question | ClaudeState::Stuck => {
+                            format!("<span foreground=\"{COLOR_ERROR}\">{}</span>", pango_escape(&padded_state))
+                        }
+                        ClaudeState::Limit | ClaudeState::Input => {
+                            format!("<span foreground=\"{COLOR_INFO_NEGATIVE}\">{}</span>", pango_escape(&padded_state))
+                        }
+                        ClaudeState::Error | ClaudeState::Partial | ClaudeState::Ongoing => {
+                            format!("<span foreground=\"{COLOR_WARN}\">{}</span>", pango_escape(&padded_state))
+                        }
+                        ClaudeState::Active | ClaudeState::Planning => {
+                            format!("<span foreground=\"{COLOR_INFO_ACTIVE}\">{}</span>", pango_escape(&padded_state))
+                        }
+                        ClaudeState::Finished => {
+                            format!("<span foreground=\"{COLOR_INFO_GOOD}\">{}</span>", pango_escape(&padded_state))
+                        }
+                        ClaudeState::Done => {
+                            format!("<span foreground=\"{COLOR_TRACE}\">{}</span>", pango_escape(&padded_state))
+                        }
+                        _ => pango_escape(&padded_state),
+                    };
+                    write!(f, "{}{}  {}", pango_escape(&padded_host), pango_escape(&padded_name), state_cell)?;
+                    if let Some(c) = &padded_context {
+                        write!(f, "  <span foreground=\"{}\">{}</span>", context_color(entry.context), pango_escape(c))?;
                     }
-                    ClaudeState::Limit | ClaudeState::Input => {
-                        format!("<span foreground=\"{COLOR_INFO_NEGATIVE}\">{}</span>", pango_escape(&padded_state))
+                    if let Some(m) = &padded_model {
+                        write!(f, "  {}", pango_escape(m))?;
                     }
-                    ClaudeState::Error | ClaudeState::Partial | ClaudeState::Ongoing => {
-                        format!("<span foreground=\"{COLOR_WARN}\">{}</span>", pango_escape(&padded_state))
+                    if let Some(t) = trailing {
+                        write!(f, "  {}", pango_escape(&t))?;
                     }
-                    ClaudeState::Active | ClaudeState::Planning => {
-                        format!("<span foreground=\"{COLOR_INFO_ACTIVE}\">{}</span>", pango_escape(&padded_state))
+                } else {
+                    // Terminal path: ANSI colors via `colored`, unchanged.
+                    let colored_state = match entry.state {
+                        ClaudeState::Active | ClaudeState::Planning => padded_state.blue(),
+                        ClaudeState::Finished => padded_state.green(),
+                        ClaudeState::Done => padded_state.bright_black(),
+                        ClaudeState::Empty => padded_state.yellow(),
+                        ClaudeState::Draft => padded_state.cyan(),
+                        ClaudeState::Question | ClaudeState::Stuck => padded_state.magenta(),
+                        ClaudeState::Error | ClaudeState::Partial | ClaudeState::Ongoing => padded_state.red(),
+                        ClaudeState::Limit | ClaudeState::Input => padded_state.white(),
+                        ClaudeState::Interrupted => padded_state.normal(),
+                    };
+                    write!(f, "{}{}  {}", padded_host.dimmed(), padded_name, colored_state)?;
+                    if let Some(c) = &padded_context {
+                        write!(f, "  {}", c.dimmed())?;
                     }
-                    ClaudeState::Finished => {
-                        format!("<span foreground=\"{COLOR_INFO_GOOD}\">{}</span>", pango_escape(&padded_state))
+                    if let Some(m) = &padded_model {
+                        write!(f, "  {}", m.dimmed())?;
                     }
-                    ClaudeState::Done => {
-                        format!("<span foreground=\"{COLOR_TRACE}\">{}</span>", pango_escape(&padded_state))
+                    if let Some(t) = trailing {
+                        write!(f, "  {}", t)?;
                     }
-                    _ => pango_escape(&padded_state),
-                };
-                write!(f, "{}  {}", pango_escape(&padded_name), state_cell)?;
-                if let Some(c) = &padded_context {
-                    write!(f, "  <span foreground=\"{}\">{}</span>", context_color(entry.context), pango_escape(c))?;
-                }
-                if let Some(m) = &padded_model {
-                    write!(f, "  {}", pango_escape(m))?;
-                }
-                if let Some(t) = trailing {
-                    write!(f, "  {}", pango_escape(&t))?;
-                }
-            } else {
-                // Terminal path: ANSI colors via `colored`, unchanged.
-                let colored_state = match entry.state {
-                    ClaudeState::Active | ClaudeState::Planning => padded_state.blue(),
-                    ClaudeState::Finished => padded_state.green(),
-                    ClaudeState::Done => padded_state.bright_black(),
-                    ClaudeState::Empty => padded_state.yellow(),
-                    ClaudeState::Draft => padded_state.cyan(),
-                    ClaudeState::Question | ClaudeState::Stuck => padded_state.magenta(),
-                    ClaudeState::Error | ClaudeState::Partial | ClaudeState::Ongoing => padded_state.red(),
-                    ClaudeState::Limit | ClaudeState::Input => padded_state.white(),
-                    ClaudeState::Interrupted => padded_state.normal(),
-                };
-                write!(f, "{}  {}", padded_name, colored_state)?;
-                if let Some(c) = &padded_context {
-                    write!(f, "  {}", c.dimmed())?;
-                }
-                if let Some(m) = &padded_model {
-                    write!(f, "  {}", m.dimmed())?;
-                }
-                if let Some(t) = trailing {
-                    write!(f, "  {}", t)?;
                 }
             }
         }
+        if !self.compact {
+            let counts = self.counts_line();
+            if !counts.is_empty() {
+                write!(f, "\n{}", if self.markup { pango_escape(&counts) } else { counts.dimmed().to_string() })?;
+            }
+        }
         Ok(())
     }
 }
 
+#[cfg(test)]
+mod sessions_tests {
+    use super::*;
+
+    fn entry(name: &str, state: ClaudeState, project: &str) -> SessionEntry {
+        SessionEntry {
+            host: None,
+            name: name.to_string(),
+            window_index: 1,
+            state,
+            active_todo: None,
+            draft_content: None,
+            question_content: None,
+            summary: None,
+            model: None,
+            context: None,
+            growth: Vec::new(),
+            project: Some(project.to_string()),
+            session_id: None,
+        }
+    }
+
+    fn sessions(group_by: Option<GroupBy>) -> Sessions {
+        let mut s = Sessions::new(false, false, group_by);
+        for e in [
+            entry("a", ClaudeState::Finished, "-home-v-nix"),
+            entry("b", ClaudeState::Question, "-home-v-site"),
+            entry("c", ClaudeState::Active, "-home-v-nix"),
+            entry("d", ClaudeState::Finished, "-home-v-site"),
+        ] {
+            s.add(e);
+        }
+        s.sort();
+        s
+    }
+
+    fn names(group: &[&SessionEntry]) -> Vec<String> {
+        group.iter().map(|e| e.name.clone()).collect()
+    }
+
+    #[test]
+    fn states_group_in_state_order_and_keep_the_name_sort_within() {
+        let s = sessions(Some(GroupBy::State));
+        let groups: Vec<_> = s.groups().into_iter().map(|(k, g)| (k.unwrap(), names(&g))).collect();
+        assert_eq!(
+            groups,
+            [("active".into(), vec!["c".to_string()]), ("finished".into(), vec!["a".into(), "d".into()]), ("question".into(), vec!["b".into()])]
+        );
+    }
+
+    #[test]
+    fn a_filtered_view_still_counts_what_it_hides() {
+        let mut s = sessions(Some(GroupBy::Project));
+        s.filter(&[], &[ClaudeState::Finished]);
+        let groups: Vec<_> = s.groups().into_iter().map(|(k, g)| (k.unwrap(), names(&g))).collect();
+        assert_eq!(groups, [("-home-v-nix".into(), vec!["c".to_string()]), ("-home-v-site".into(), vec!["b".into()])]);
+        assert_eq!(s.counts_line(), "1 active · 2 finished · 1 question");
+        assert_eq!(s.to_json()["counts"]["finished"], 2);
+
+        let mut flat = sessions(None);
+        flat.filter(&[ClaudeState::Question], &[]);
+        assert_eq!(flat.to_json().as_array().map(Vec::len), Some(1), "ungrouped --json stays a bare array");
+    }
+}
+
 /// Auto-compact fires at `autoCompactWindow` in claude/settings.json.
 /// ponytail: hardcoded, one grep away from the setting.
 const COMPACT_WINDOW: u64 = 500_000;
 
+/// Turns drawn in the context sparkline.
+const SPARK_TURNS: usize = 12;
+
+/// The last SPARK_TURNS context sizes as block glyphs, scaled against
+/// COMPACT_WINDOW rather than the series' own max — bar height reads as
+/// distance to auto-compact, and a /compact shows as the drop it is. Always
+/// SPARK_TURNS wide, so the columns after it stay aligned.
+fn sparkline(series: &[u64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let tail = &series[series.len().saturating_sub(SPARK_TURNS)..];
+    let bars: String = tail
+        .iter()
+        .map(|&t| BARS[((t.min(COMPACT_WINDOW) * (BARS.len() as u64 - 1)) / COMPACT_WINDOW) as usize])
+        .collect();
+    format!("{:>width$}", bars, width = SPARK_TURNS)
+}
+
 fn format_tokens(t: u64) -> String {
     if t >= 1000 { format!("{}k", t / 1000) } else { t.to_string() }
 }
@@ -543,19 +949,33 @@ fn read_active_todo(path: &Path) -> TodoResult {
     TodoResult { has_active_todos, display_todo }
 }
 
-// Track if the summariser backend failed (for warning message)
-static OLLAMA_UNAVAILABLE: std::sync::atomic::AtomicBool =
-    std::sync::atomic::AtomicBool::new(false);
-
-// Only use ollama when --llm-summaries is passed
+// Only ask for summaries when --llm-summaries is passed
 static USE_LLM_SUMMARIES: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
 
-/// Generate a short summary via the local ollama tier.
+const SUMMARY_PROMPT: llm::Template = llm::Template {
+    system: "Output ONLY a 3-5 word label. No explanations, no markdown, no extra text.",
+    examples: &[
+        ("add dark mode to settings", "dark mode settings"),
+        ("fix the memory leak in worker", "fix worker memory leak"),
+    ],
+    max_tokens: 32,
+};
+
+/// Generate a short summary through the configured summary backend.
 fn generate_summary_with_llm(first_message: &str) -> Option<String> {
     // Only run if --llm-summaries was passed
     if !USE_LLM_SUMMARIES.load(std::sync::atomic::Ordering::Relaxed) {
         return None;
     }
+    summarize(llm::active(), first_message)
+}
+
+/// Cached against the message itself — a session's first message never
+/// changes, so its label is asked for once.
+fn summarize(llms: &llm::Llms, first_message: &str) -> Option<String> {
+    if !llms.enabled(llm::Task::Summary) {
+        return None;
+    }
 
     // Get first line and truncate if too long
     let first_line = first_message.lines().next().unwrap_or(first_message).trim();
@@ -571,29 +991,19 @@ fn generate_summary_with_llm(first_message: &str) -> Option<String> {
         first_line
     };
 
-    let mut conv = ask_llm::Conversation::new_with_system(
-        "Output ONLY a 3-5 word label. No explanations, no markdown, no extra text.",
-    );
-    conv.add_exchange("add dark mode to settings", "dark mode settings");
-    conv.add_exchange("fix the memory leak in worker", "fix worker memory leak");
-    conv.add(ask_llm::Role::User, truncated.replace('\n', " "));
-
-    let client = ask_llm::Client::default().model(ask_llm::Model::Cheap).max_tokens(32);
-    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().ok()?;
-    let response = match runtime.block_on(async { client.conversation(&conv).await }) {
-        Ok(r) => r,
-        Err(_) => {
-            OLLAMA_UNAVAILABLE.store(true, std::sync::atomic::Ordering::Relaxed);
-            return None;
-        }
+    let key = {
+        use std::hash::{Hash as _, Hasher as _};
+        let mut h = std::collections::hash_map::DefaultHasher::new();
+        truncated.hash(&mut h);
+        format!("{:016x}", h.finish())
     };
+    let response = llms.ask(llm::Task::Summary, &key, 0, &SUMMARY_PROMPT, || Some(truncated.replace('\n', " ")))?;
 
     // Clean up: get first line, strip markdown/code blocks
     let summary = response
-        .text
         .lines()
         .next()
-        .unwrap_or(&response.text)
+        .unwrap_or(&response)
         .trim()
         .trim_start_matches(['`', '#', '*', '-'])
         .trim()
@@ -763,193 +1173,671 @@ fn context_tokens(session_file: &Path) -> Option<u64> {
     None
 }
 
-/// A settled session says how it went in its closing report — the last assistant
-/// turn. "Finished" on the pane only means Claude stopped talking; whether the
-/// work actually landed is a judgement no pattern match can make, so it's read
-/// by an LLM and cached against the transcript's mtime (one call per settle, not
-/// per status-line refresh).
-mod report {
-    use std::{
-        collections::HashMap,
-        fs,
-        io::{Read as _, Seek as _, SeekFrom},
-        path::{Path, PathBuf},
-        time::Duration,
-    };
-
-    use serde::{Deserialize, Serialize};
+/// `$XDG_CONFIG_HOME/claude-sessions/config.toml` (or `--config`): one section
+/// per part of the script that takes settings. Everything has a default, so a
+/// missing default file is simply all defaults; a missing `--config` is an error.
+mod config {
+    use super::*;
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-    #[serde(rename_all = "lowercase")]
-    pub enum Verdict {
-        Finished,
-        Stuck,
-        Partial,
-        Ongoing,
+    #[derive(Deserialize, Default)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct Config {
+        pub llm: llm::RawConfig,
+        pub cost: cost::RawConfig,
+        /// Notifications are off without this section.
+        pub notify: Option<notify::RawConfig>,
     }
 
-    const SYSTEM: &str = "You read the closing report a coding agent left at the end of its session and judge how the session ended.
+    pub fn default_path() -> PathBuf {
+        std::env::var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|_| std::env::var("HOME").map(|h| PathBuf::from(h).join(".config")))
+            .unwrap_or_default()
+            .join("claude-sessions/config.toml")
+    }
 
-finished — everything the user asked for is done
-stuck — the agent could not complete the work: blocked, failed, out of ideas, or handing it back for the user to do
-partial — the agent did some of the work but skipped, deferred, or declined a part of it
-ongoing — the agent is waiting on the user: it asks a question, or names a next step it wants a go-ahead for before carrying on
+    pub fn load(explicit: Option<&Path>) -> Result<Config, String> {
+        let path = explicit.map(Path::to_path_buf).unwrap_or_else(default_path);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if explicit.is_none() && e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(format!("{}: {e}", path.display())),
+        };
+        toml::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+}
 
-A report that closes on work still ahead of it — a \"Next step\" / \"TODO\" / \"Remaining\" section, an offer to carry on, or a request for go-ahead — is ongoing when the report waits on an answer from the user, and partial when it simply leaves that work undone. Notes about things deliberately left alone that were never asked for are neither.
+/// Every LLM call the script makes — session summaries and closing-report
+/// verdicts — goes through one `Llms`: which provider answers each task, how
+/// long it may take, and a shared on-disk cache so a settled answer costs one
+/// call, not one per status-line refresh.
+///
+/// ```toml
+/// [llm.summary]            # --llm-summaries only
+/// provider = "openai"      # ollama | openai | anthropic | heuristic
+/// url = "http://localhost:8080/v1"
+/// model = "qwen2.5-7b-instruct"
+/// api_key_env = "LOCAL_LLM_KEY"   # optional: env var holding a bearer token
+/// timeout_secs = 10
+///
+/// [llm.verdict]
+/// provider = "anthropic"
+/// ```
+///
+/// `ollama` and `anthropic` are `ask_llm`'s local and hosted tiers (anthropic
+/// needs `CLAUDE_TOKEN`, and is off without it); `openai` is any server with
+/// an OpenAI-style `/chat/completions`, ollama's own `/v1` included;
+/// `heuristic` answers from keywords with no model at all.
+mod llm {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::OnceLock;
+    use std::time::Duration;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Task {
+        Summary,
+        Verdict,
+    }
 
-Judge the implementation, nothing else. Only a piece of the asked-for code left unwritten makes a report partial. A test not written, a check not run, a build or verification left for the user — none of that counts against a report whose implementation is complete; that is finished.
+    impl Task {
+        fn as_str(self) -> &'static str {
+            match self {
+                Task::Summary => "summary",
+                Task::Verdict => "verdict",
+            }
+        }
+    }
 
-partial is asked-for code the agent could have written and chose not to. A part it reports as impossible, done differently, or dropped because the codebase would not take it — with the reason given — revises the ask instead of falling short of it, however plainly the report says the ask was not met. A known limitation left under a TODO is the same. If the implementation the agent settled on is in the tree, that is finished.
+    /// The fixed part of a task's prompt — what the cache fingerprints. The
+    /// text being judged comes alongside it.
+    #[derive(Clone, Copy)]
+    pub struct Template {
+        pub system: &'static str,
+        pub examples: &'static [(&'static str, &'static str)],
+        pub max_tokens: u32,
+    }
 
-Answer with exactly one word: finished, stuck, partial, or ongoing.";
+    pub trait Backend: Send + Sync {
+        /// Provider and model — part of the cache fingerprint, so switching
+        /// either redraws every answer.
+        fn id(&self) -> String;
+        /// An unconfigured provider is off, not failing.
+        fn available(&self) -> bool {
+            true
+        }
+        fn complete(&self, task: Task, template: &Template, user: &str, timeout: Duration) -> Result<String, String>;
+    }
 
-    /// Long reports are all preamble; the verdict lives in the closing lines.
-    const MAX_REPORT: usize = 8000;
-    const TIMEOUT: Duration = Duration::from_secs(20);
+    /// ask_llm's tiers: `Cheap` is the local ollama one, `Fast` is hosted.
+    struct AskLlm {
+        name: &'static str,
+        model: ask_llm::Model,
+        /// The hosted tier bills CLAUDE_TOKEN; without it the task is off.
+        needs_token: bool,
+    }
 
-    pub fn classify(session_file: &Path) -> Option<Verdict> {
-        // No key means the feature is off, not that anything is wrong.
-        if std::env::var("CLAUDE_TOKEN").is_err() {
-            return None;
+    impl Backend for AskLlm {
+        fn id(&self) -> String {
+            self.name.to_string()
         }
 
-        let mtime = fs::metadata(session_file)
-            .and_then(|m| m.modified())
-            .ok()?
-            .duration_since(std::time::UNIX_EPOCH)
-            .ok()?
-            .as_secs();
-        let key = session_file.file_stem()?.to_str()?.to_string();
-
-        let mut cache = load();
-        if let Some(hit) = cache.get(&key).filter(|e| e.mtime == mtime && e.prompt == prompt_id()) {
-            return hit.verdict;
+        fn available(&self) -> bool {
+            !self.needs_token || std::env::var("CLAUDE_TOKEN").is_ok()
         }
 
-        // A miss is cached too, or an unreachable model would earn a doomed HTTP
-        // call on every status-line refresh. The retry comes with the next turn
-        // in that session, which is when a verdict starts mattering again.
-        let verdict = last_report(session_file).and_then(|r| ask(&r));
-        cache.insert(key, Entry { mtime, prompt: prompt_id(), verdict });
-        save(&cache);
-        verdict
+        fn complete(&self, _: Task, template: &Template, user: &str, timeout: Duration) -> Result<String, String> {
+            let mut conv = ask_llm::Conversation::new_with_system(template.system);
+            for (ask, answer) in template.examples {
+                conv.add_exchange(*ask, *answer);
+            }
+            conv.add(ask_llm::Role::User, user);
+            let client = ask_llm::Client::default().model(self.model).max_tokens(template.max_tokens);
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().map_err(|e| e.to_string())?;
+            runtime
+                .block_on(async { tokio::time::timeout(timeout, client.conversation(&conv)).await })
+                .map_err(|_| format!("{} timed out", self.name))?
+                .map(|r| r.text)
+                .map_err(|e| e.to_string())
+        }
     }
 
-    /// Editing SYSTEM has to invalidate every stored verdict — they were drawn
-    /// by a different judge. Cross-version hash instability only costs one
-    /// re-classification round.
-    fn prompt_id() -> u64 {
-        use std::hash::{Hash as _, Hasher as _};
-        let mut h = std::collections::hash_map::DefaultHasher::new();
-        SYSTEM.hash(&mut h);
-        h.finish()
+    /// Any server speaking OpenAI's chat-completions, over curl like the rest
+    /// of this script's HTTP.
+    struct OpenAi {
+        url: String,
+        model: String,
+        api_key_env: Option<String>,
     }
 
-    /// Text of the final assistant turn. Read from the tail for the same reason
-    /// transcript_working does: transcripts reach tens of MB.
-    fn last_report(session_file: &Path) -> Option<String> {
-        const TAIL_BYTES: u64 = 256 * 1024;
-        let mut file = fs::File::open(session_file).ok()?;
-        let len = file.metadata().ok()?.len();
-        let start = len.saturating_sub(TAIL_BYTES);
-        file.seek(SeekFrom::Start(start)).ok()?;
-        let mut bytes = Vec::new();
-        file.read_to_end(&mut bytes).ok()?;
-        let buf = String::from_utf8_lossy(&bytes);
-
-        let mut lines = buf.lines();
-        if start > 0 {
-            lines.next(); // likely-partial first record
+    impl Backend for OpenAi {
+        fn id(&self) -> String {
+            format!("openai {} {}", self.url, self.model)
         }
-        extract_last_text(&lines.collect::<Vec<_>>())
-    }
 
-    /// Pure half of last_report, over whole transcript lines. A closing turn can
-    /// be split across records — the thinking block lands in its own entry — so
-    /// only text blocks count.
-    fn extract_last_text(lines: &[&str]) -> Option<String> {
-        for line in lines.iter().rev() {
-            let v: serde_json::Value = match serde_json::from_str(line) {
-                Ok(v) => v,
-                Err(_) => continue,
-            };
-            if v.get("type").and_then(|x| x.as_str()) != Some("assistant") {
-                continue;
+        fn complete(&self, _: Task, template: &Template, user: &str, timeout: Duration) -> Result<String, String> {
+            let mut messages = vec![serde_json::json!({ "role": "system", "content": template.system })];
+            for (ask, answer) in template.examples {
+                messages.push(serde_json::json!({ "role": "user", "content": ask }));
+                messages.push(serde_json::json!({ "role": "assistant", "content": answer }));
             }
-            let text = v
-                .get("message")?
-                .get("content")?
-                .as_array()?
-                .iter()
-                .filter(|b| b.get("type").and_then(|x| x.as_str()) == Some("text"))
-                .filter_map(|b| b.get("text")?.as_str())
-                .collect::<Vec<_>>()
-                .join("\n");
-            if !text.trim().is_empty() {
-                let tail = text.get(text.len().saturating_sub(MAX_REPORT)..).unwrap_or(&text);
-                return Some(tail.to_string());
+            messages.push(serde_json::json!({ "role": "user", "content": user }));
+            let body = serde_json::json!({ "model": self.model, "messages": messages, "max_tokens": template.max_tokens });
+
+            let mut cmd = Command::new("curl");
+            cmd.args(["-sS", "-f", "-m", &timeout.as_secs().max(1).to_string()])
+                .args(["-H", "Content-Type: application/json", "--data-binary", "@-"]);
+            if let Some(key) = self.api_key_env.as_deref().and_then(|v| std::env::var(v).ok()) {
+                cmd.args(["-H", &format!("Authorization: Bearer {key}")]);
             }
+            cmd.arg(format!("{}/chat/completions", self.url.trim_end_matches('/')));
+            let output = pipe(cmd, &body.to_string())?;
+            let reply: serde_json::Value = serde_json::from_slice(&output).map_err(|e| format!("{}: {e}", self.url))?;
+            reply["choices"][0]["message"]["content"]
+                .as_str()
+                .map(String::from)
+                .ok_or_else(|| format!("{}: no choices[0].message.content in reply", self.url))
         }
-        None
     }
 
-    fn ask(report: &str) -> Option<Verdict> {
-        // Cheapest tier that reliably returns a single-word verdict; the local
-        // ollama models don't hold the format well enough at 4 tokens.
-        let client = ask_llm::Client::default().model(ask_llm::Model::Fast).max_tokens(4);
-        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().ok()?;
-        let response = runtime
-            .block_on(async { tokio::time::timeout(TIMEOUT, client.ask(format!("{SYSTEM}\n\n---\n\n{report}"))).await })
-            .ok()?
-            .ok()?;
-        parse(&response.text)
+    fn pipe(mut cmd: Command, stdin: &str) -> Result<Vec<u8>, String> {
+        use std::io::Write as _;
+        use std::process::Stdio;
+        let mut child = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("curl: {e}"))?;
+        child.stdin.take().unwrap().write_all(stdin.as_bytes()).map_err(|e| format!("curl: {e}"))?;
+        let out = child.wait_with_output().map_err(|e| format!("curl: {e}"))?;
+        if out.status.success() {
+            Ok(out.stdout)
+        } else {
+            Err(format!("curl: {}", String::from_utf8_lossy(&out.stderr).trim()))
+        }
     }
 
-    /// First word only — the verdict leads, and the model sometimes tacks on a
-    /// justification the 4-token budget then cuts mid-sentence.
-    fn parse(answer: &str) -> Option<Verdict> {
-        match answer.trim().split_whitespace().next()?.trim_matches(['"', '.', '`', '*']).to_lowercase().as_str() {
-            "finished" => Some(Verdict::Finished),
-            "stuck" => Some(Verdict::Stuck),
-            "partial" => Some(Verdict::Partial),
-            "ongoing" => Some(Verdict::Ongoing),
-            _ => None,
+    /// No model: keywords for verdicts, the gist of the first line for
+    /// summaries. Never unavailable, never slow — the floor under the others.
+    struct Heuristic;
+
+    impl Backend for Heuristic {
+        fn id(&self) -> String {
+            "heuristic".to_string()
+        }
+
+        fn complete(&self, task: Task, _: &Template, user: &str, _: Duration) -> Result<String, String> {
+            Ok(match task {
+                Task::Summary => heuristic_label(user),
+                Task::Verdict => heuristic_verdict(user).to_string(),
+            })
         }
     }
 
-    #[derive(Serialize, Deserialize)]
-    struct Entry {
-        /// Transcript mtime the verdict was drawn from; a later turn invalidates it.
-        mtime: u64,
-        prompt: u64,
-        verdict: Option<Verdict>,
+    const FILLER: &[&str] = &["please", "can", "could", "would", "you", "i", "we", "want", "need", "to", "let's", "lets", "now", "ok", "so"];
+
+    pub fn heuristic_label(message: &str) -> String {
+        message
+            .split_whitespace()
+            .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+            .skip_while(|w| FILLER.contains(&w.as_str()))
+            .filter(|w| !w.is_empty())
+            .take(5)
+            .collect::<Vec<_>>()
+            .join(" ")
     }
 
-    fn cache_path() -> Option<PathBuf> {
-        std::env::var("HOME")
-            .ok()
-            .map(|h| PathBuf::from(h).join(".cache/claude-session-reports.json"))
+    /// Judged on the closing paragraph, where a report states how it ended.
+    pub fn heuristic_verdict(report: &str) -> &'static str {
+        let closing = report.trim_end().rsplit("\n\n").next().unwrap_or(report).to_lowercase();
+        let any = |words: &[&str]| words.iter().any(|w| closing.contains(w));
+        if closing.ends_with('?') || any(&["want me to", "shall i", "should i", "let me know if", "go ahead"]) {
+            "ongoing"
+        } else if any(&["couldn't", "could not", "unable to", "blocked", "failed to", "can't"]) {
+            "stuck"
+        } else if any(&["todo", "not yet", "remaining", "skipped", "left for"]) {
+            "partial"
+        } else {
+            "finished"
+        }
     }
 
-    fn load() -> HashMap<String, Entry> {
-        let Some(p) = cache_path() else { return HashMap::new() };
-        let Ok(s) = fs::read_to_string(&p) else { return HashMap::new() };
-        serde_json::from_str(&s).unwrap_or_default()
+    /// Scripted replies for offline tests; every call is recorded.
+    #[cfg(test)]
+    pub struct Mock {
+        pub replies: std::sync::Mutex<std::collections::VecDeque<Result<String, String>>>,
+        pub calls: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
     }
 
-    fn save(cache: &HashMap<String, Entry>) {
-        let Some(p) = cache_path() else { return };
-        if let Some(parent) = p.parent() {
-            let _ = fs::create_dir_all(parent);
+    #[cfg(test)]
+    impl Mock {
+        pub fn new(replies: &[Result<&str, &str>]) -> Mock {
+            Mock {
+                replies: std::sync::Mutex::new(replies.iter().map(|r| r.map(String::from).map_err(String::from)).collect()),
+                calls: Default::default(),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    impl Backend for Mock {
+        fn id(&self) -> String {
+            "mock".to_string()
+        }
+
+        fn complete(&self, _: Task, _: &Template, user: &str, _: Duration) -> Result<String, String> {
+            self.calls.lock().unwrap().push(user.to_string());
+            self.replies.lock().unwrap().pop_front().unwrap_or_else(|| Err("mock: out of replies".to_string()))
+        }
+    }
+
+    pub struct Route {
+        backend: Box<dyn Backend>,
+        timeout: Duration,
+        failed: AtomicBool,
+    }
+
+    impl Route {
+        pub fn new(backend: Box<dyn Backend>, timeout: Duration) -> Route {
+            Route { backend, timeout, failed: AtomicBool::new(false) }
+        }
+    }
+
+    pub struct Llms {
+        summary: Route,
+        verdict: Route,
+        /// None: no caching (tests that don't exercise it).
+        cache: Option<PathBuf>,
+    }
+
+    /// A miss — the provider failed — is cached like an answer, or an
+    /// unreachable model would earn a doomed call on every refresh. It's
+    /// retried once its subject changes, or after this long.
+    const MISS_RETRY_SECS: i64 = 600;
+    /// Answers drawn longer ago than this are dropped on the next save — a hit
+    /// doesn't renew one, that would cost a write per poll.
+    const CACHE_TTL_SECS: i64 = 30 * 24 * 3600;
+
+    #[derive(Serialize, Deserialize)]
+    struct Entry {
+        /// Version of the subject the answer was drawn from (a transcript
+        /// mtime); a different one invalidates it.
+        stamp: u64,
+        /// Provider + fixed prompt it was drawn with (see fingerprint).
+        fingerprint: u64,
+        text: Option<String>,
+        at: i64,
+    }
+
+    impl Llms {
+        pub fn new(summary: Route, verdict: Route, cache: Option<PathBuf>) -> Llms {
+            Llms { summary, verdict, cache }
+        }
+
+        pub fn from_config(raw: &RawConfig) -> Result<Llms, String> {
+            let route = |raw: &Option<RawRoute>, default: Provider, secs| match raw {
+                Some(r) => r.build(secs),
+                None => RawRoute { provider: default, url: None, model: None, api_key_env: None, timeout_secs: None }.build(secs),
+            };
+            Ok(Llms::new(
+                route(&raw.summary, Provider::Ollama, 10).map_err(|e| format!("[llm.summary]: {e}"))?,
+                route(&raw.verdict, Provider::Anthropic, 20).map_err(|e| format!("[llm.verdict]: {e}"))?,
+                cache_path(),
+            ))
+        }
+
+        fn route(&self, task: Task) -> &Route {
+            match task {
+                Task::Summary => &self.summary,
+                Task::Verdict => &self.verdict,
+            }
+        }
+
+        pub fn enabled(&self, task: Task) -> bool {
+            self.route(task).backend.available()
+        }
+
+        /// The task's provider, if a call to it failed during this run.
+        pub fn unavailable(&self, task: Task) -> Option<String> {
+            let route = self.route(task);
+            route.failed.load(Ordering::Relaxed).then(|| route.backend.id())
+        }
+
+        /// One uncached call.
+        pub fn complete(&self, task: Task, template: &Template, user: &str) -> Option<String> {
+            let route = self.route(task);
+            match route.backend.complete(task, template, user, route.timeout) {
+                Ok(text) => Some(text),
+                Err(_) => {
+                    route.failed.store(true, Ordering::Relaxed);
+                    None
+                }
+            }
+        }
+
+        /// A cached call about `key` as of `stamp`. `user` is only built on a
+        /// miss — the subject is often a transcript read — and a subject with
+        /// nothing to ask about (None) is remembered as a miss too. The call
+        /// itself runs unlocked; the entry is merged into the cache as it is
+        /// by then, under `lock_beside`, so concurrent pollers keep each other's.
+        pub fn ask(&self, task: Task, key: &str, stamp: u64, template: &Template, user: impl FnOnce() -> Option<String>) -> Option<String> {
+            let fingerprint = fingerprint(&self.route(task).backend.id(), template);
+            let slot = format!("{}/{key}", task.as_str());
+            let now = now_epoch();
+            if let Some(hit) = self.load().remove(&slot).filter(|e| e.stamp == stamp && e.fingerprint == fingerprint)
+                && (hit.text.is_some() || now - hit.at < MISS_RETRY_SECS)
+            {
+                return hit.text;
+            }
+            let text = user().and_then(|u| self.complete(task, template, &u));
+            let _lock = self.cache.as_deref().and_then(lock_beside);
+            let mut cache = self.load();
+            cache.insert(slot, Entry { stamp, fingerprint, text: text.clone(), at: now });
+            cache.retain(|_, e| now - e.at < CACHE_TTL_SECS);
+            self.save(&cache);
+            text
+        }
+
+        fn load(&self) -> HashMap<String, Entry> {
+            self.cache
+                .as_ref()
+                .and_then(|p| fs::read_to_string(p).ok())
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default()
+        }
+
+        fn save(&self, cache: &HashMap<String, Entry>) {
+            let Some(p) = &self.cache else { return };
+            if let Some(parent) = p.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            if let Ok(s) = serde_json::to_string(cache) {
+                let _ = fs::write(p, s);
+            }
+        }
+    }
+
+    /// Editing a prompt or switching provider has to invalidate every stored
+    /// answer — they were drawn by a different judge. Cross-version hash
+    /// instability only costs one re-ask round.
+    fn fingerprint(backend: &str, template: &Template) -> u64 {
+        use std::hash::{Hash as _, Hasher as _};
+        let mut h = std::collections::hash_map::DefaultHasher::new();
+        (backend, template.system, template.examples, template.max_tokens).hash(&mut h);
+        h.finish()
+    }
+
+    fn cache_path() -> Option<PathBuf> {
+        std::env::var("HOME").ok().map(|h| PathBuf::from(h).join(".cache/claude-sessions-llm.json"))
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(deny_unknown_fields)]
+    pub struct RawConfig {
+        summary: Option<RawRoute>,
+        verdict: Option<RawRoute>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct RawRoute {
+        provider: Provider,
+        url: Option<String>,
+        model: Option<String>,
+        api_key_env: Option<String>,
+        timeout_secs: Option<u64>,
+    }
+
+    #[derive(Deserialize, Clone, Copy)]
+    #[serde(rename_all = "lowercase")]
+    enum Provider {
+        Ollama,
+        Openai,
+        Anthropic,
+        Heuristic,
+    }
+
+    impl RawRoute {
+        /// `default_secs`: the task's own timeout, when the section sets none.
+        fn build(&self, default_secs: u64) -> Result<Route, String> {
+            if let (Provider::Ollama | Provider::Anthropic | Provider::Heuristic, Some(key)) = (
+                self.provider,
+                [("url", &self.url), ("model", &self.model), ("api_key_env", &self.api_key_env)]
+                    .into_iter()
+                    .find_map(|(key, v)| v.is_some().then_some(key)),
+            ) {
+                // ask_llm picks those itself; a key that's quietly ignored reads as honoured.
+                return Err(format!("{key} is only for provider \"openai\""));
+            }
+            let backend: Box<dyn Backend> = match self.provider {
+                Provider::Ollama => Box::new(AskLlm { name: "ollama", model: ask_llm::Model::Cheap, needs_token: false }),
+                Provider::Anthropic => Box::new(AskLlm { name: "anthropic", model: ask_llm::Model::Fast, needs_token: true }),
+                Provider::Heuristic => Box::new(Heuristic),
+                Provider::Openai => Box::new(OpenAi {
+                    url: self.url.clone().ok_or("provider \"openai\" needs a url")?,
+                    model: self.model.clone().ok_or("provider \"openai\" needs a model")?,
+                    api_key_env: self.api_key_env.clone(),
+                }),
+            };
+            Ok(Route::new(backend, Duration::from_secs(self.timeout_secs.unwrap_or(default_secs))))
+        }
+    }
+
+    static ACTIVE: OnceLock<Llms> = OnceLock::new();
+
+    /// The configured backends; the defaults until main installs the config's.
+    pub fn active() -> &'static Llms {
+        ACTIVE.get_or_init(|| Llms::from_config(&RawConfig::default()).expect("default llm config is valid"))
+    }
+
+    pub fn install(llms: Llms) {
+        let _ = ACTIVE.set(llms);
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const T: Template = Template { system: "judge", examples: &[], max_tokens: 4 };
+
+        fn mock(replies: &[Result<&str, &str>], cache: Option<PathBuf>) -> (Llms, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+            let m = Mock::new(replies);
+            let calls = m.calls.clone();
+            let route = |b: Box<dyn Backend>| Route::new(b, Duration::from_secs(1));
+            (Llms::new(route(Box::new(Heuristic)), route(Box::new(m)), cache), calls)
+        }
+
+        #[test]
+        fn an_answer_is_asked_once_per_subject_version() {
+            let cache = std::env::temp_dir().join(format!("claude-sessions-llm-test-{}.json", std::process::id()));
+            let _ = fs::remove_file(&cache);
+            let (llms, calls) = mock(&[Ok("stuck"), Err("down"), Ok("finished")], Some(cache.clone()));
+
+            assert_eq!(llms.ask(Task::Verdict, "s", 1, &T, || Some("r1".into())).as_deref(), Some("stuck"));
+            assert_eq!(llms.ask(Task::Verdict, "s", 1, &T, || panic!("a hit builds no prompt")).as_deref(), Some("stuck"));
+            // A failure is remembered for the same version, and flags the provider.
+            assert_eq!(llms.ask(Task::Verdict, "s", 2, &T, || Some("r2".into())), None);
+            assert_eq!(llms.ask(Task::Verdict, "s", 2, &T, || Some("r2".into())), None);
+            assert_eq!(llms.unavailable(Task::Verdict).as_deref(), Some("mock"));
+            assert_eq!(llms.ask(Task::Verdict, "s", 3, &T, || Some("r3".into())).as_deref(), Some("finished"));
+            assert_eq!(*calls.lock().unwrap(), ["r1", "r2", "r3"]);
+
+            let _ = fs::remove_file(&cache);
+        }
+
+        #[test]
+        fn the_heuristic_reads_the_closing_paragraph() {
+            assert_eq!(heuristic_verdict("Did X.\n\nAll tests pass."), "finished");
+            assert_eq!(heuristic_verdict("Did X.\n\nWant me to push it?"), "ongoing");
+            assert_eq!(heuristic_verdict("Tried X.\n\nI couldn't get the build to link."), "stuck");
+            assert_eq!(heuristic_verdict("Did X.\n\nThe docs are not yet updated."), "partial");
+            assert_eq!(heuristic_label("Can you please add dark mode to settings"), "add dark mode to settings");
+        }
+
+        #[test]
+        fn config_sections_pick_each_tasks_provider() {
+            let raw: RawConfig = toml::from_str("[verdict]\nprovider = \"heuristic\"\ntimeout_secs = 3\n").unwrap();
+            let llms = Llms::from_config(&raw).unwrap();
+            assert_eq!(llms.verdict.backend.id(), "heuristic");
+            assert_eq!(llms.verdict.timeout, Duration::from_secs(3));
+            assert_eq!(llms.summary.backend.id(), "ollama");
+
+            let raw: RawConfig = toml::from_str("[summary]\nprovider = \"openai\"\n").unwrap();
+            assert!(Llms::from_config(&raw).err().unwrap().contains("[llm.summary]"));
+
+            let raw: RawConfig = toml::from_str("[summary]\nprovider = \"ollama\"\nmodel = \"llama3\"\n").unwrap();
+            assert_eq!(Llms::from_config(&raw).err().unwrap(), "[llm.summary]: model is only for provider \"openai\"");
+
+            // A section without a timeout keeps its task's default.
+            let raw: RawConfig = toml::from_str("[summary]\nprovider = \"heuristic\"\n[verdict]\nprovider = \"heuristic\"\n").unwrap();
+            let llms = Llms::from_config(&raw).unwrap();
+            assert_eq!((llms.summary.timeout, llms.verdict.timeout), (Duration::from_secs(10), Duration::from_secs(20)));
+        }
+    }
+}
+
+/// A settled session says how it went in its closing report — the last assistant
+/// turn. "Finished" on the pane only means Claude stopped talking; whether the
+/// work actually landed is a judgement no pattern match can make, so it's read
+/// by an LLM and cached against the transcript's mtime (one call per settle, not
+/// per status-line refresh).
+mod report {
+    use std::{
+        fs,
+        io::{Read as _, Seek as _, SeekFrom},
+        path::Path,
+    };
+
+    use serde::{Deserialize, Serialize};
+
+    use super::llm;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum Verdict {
+        Finished,
+        Stuck,
+        Partial,
+        Ongoing,
+    }
+
+    const SYSTEM: &str = "You read the closing report a coding agent left at the end of its session and judge how the session ended.
+
+finished — everything the user asked for is done
+stuck — the agent could not complete the work: blocked, failed, out of ideas, or handing it back for the user to do
+partial — the agent did some of the work but skipped, deferred, or declined a part of it
+ongoing — the agent is waiting on the user: it asks a question, or names a next step it wants a go-ahead for before carrying on
+
+A report that closes on work still ahead of it — a \"Next step\" / \"TODO\" / \"Remaining\" section, an offer to carry on, or a request for go-ahead — is ongoing when the report waits on an answer from the user, and partial when it simply leaves that work undone. Notes about things deliberately left alone that were never asked for are neither.
+
+Judge the implementation, nothing else. Only a piece of the asked-for code left unwritten makes a report partial. A test not written, a check not run, a build or verification left for the user — none of that counts against a report whose implementation is complete; that is finished.
+
+partial is asked-for code the agent could have written and chose not to. A part it reports as impossible, done differently, or dropped because the codebase would not take it — with the reason given — revises the ask instead of falling short of it, however plainly the report says the ask was not met. A known limitation left under a TODO is the same. If the implementation the agent settled on is in the tree, that is finished.
+
+Answer with exactly one word: finished, stuck, partial, or ongoing.";
+
+    /// Long reports are all preamble; the verdict lives in the closing lines.
+    const MAX_REPORT: usize = 8000;
+
+    // The verdict leads, and a 4-token budget keeps the model from saying more.
+    const PROMPT: llm::Template = llm::Template { system: SYSTEM, examples: &[], max_tokens: 4 };
+
+    pub fn classify(session_file: &Path) -> Option<Verdict> {
+        classify_with(llm::active(), session_file)
+    }
+
+    pub fn classify_with(llms: &llm::Llms, session_file: &Path) -> Option<Verdict> {
+        // An unconfigured provider (no CLAUDE_TOKEN for the default one) means
+        // the feature is off, not that anything is wrong.
+        if !llms.enabled(llm::Task::Verdict) {
+            return None;
+        }
+
+        let mtime = fs::metadata(session_file)
+            .and_then(|m| m.modified())
+            .ok()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?
+            .as_secs();
+        let key = session_file.file_stem()?.to_str()?;
+
+        // Keyed to the transcript's mtime: the next turn in that session is
+        // when a verdict starts mattering again, and when a miss gets retried.
+        llms.ask(llm::Task::Verdict, key, mtime, &PROMPT, || last_report(session_file)).and_then(|t| parse(&t))
+    }
+
+    /// Text of the final assistant turn. Read from the tail for the same reason
+    /// transcript_working does: transcripts reach tens of MB.
+    fn last_report(session_file: &Path) -> Option<String> {
+        const TAIL_BYTES: u64 = 256 * 1024;
+        let mut file = fs::File::open(session_file).ok()?;
+        let len = file.metadata().ok()?.len();
+        let start = len.saturating_sub(TAIL_BYTES);
+        file.seek(SeekFrom::Start(start)).ok()?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).ok()?;
+        let buf = String::from_utf8_lossy(&bytes);
+
+        let mut lines = buf.lines();
+        if start > 0 {
+            lines.next(); // likely-partial first record
+        }
+        extract_last_text(&lines.collect::<Vec<_>>())
+    }
+
+    /// Pure half of last_report, over whole transcript lines. A closing turn can
+    /// be split across records — the thinking block lands in its own entry — so
+    /// only text blocks count.
+    fn extract_last_text(lines: &[&str]) -> Option<String> {
+        for line in lines.iter().rev() {
+            let v: serde_json::Value = match serde_json::from_str(line) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            if v.get("type").and_then(|x| x.as_str()) != Some("assistant") {
+                continue;
+            }
+            let text = v
+                .get("message")?
+                .get("content")?
+                .as_array()?
+                .iter()
+                .filter(|b| b.get("type").and_then(|x| x.as_str()) == Some("text"))
+                .filter_map(|b| b.get("text")?.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+            if !text.trim().is_empty() {
+                let tail = text.get(text.len().saturating_sub(MAX_REPORT)..).unwrap_or(&text);
+                return Some(tail.to_string());
+            }
         }
-        if let Ok(s) = serde_json::to_string(cache) {
-            let _ = fs::write(&p, s);
+        None
+    }
+
+    /// First word only — the verdict leads, and the model sometimes tacks on a
+    /// justification the 4-token budget then cuts mid-sentence.
+    fn parse(answer: &str) -> Option<Verdict> {
+        match answer.trim().split_whitespace().next()?.trim_matches(['"', '.', '`', '*']).to_lowercase().as_str() {
+            "finished" => Some(Verdict::Finished),
+            "stuck" => Some(Verdict::Stuck),
+            "partial" => Some(Verdict::Partial),
+            "ongoing" => Some(Verdict::Ongoing),
+            _ => None,
         }
     }
 
     #[cfg(test)]
     mod tests {
         use super::*;
+        use std::{path::PathBuf, time::Duration};
+
+        /// One uncached verdict from the configured backend. The default is
+        /// the hosted tier: the cheapest that reliably returns a single word —
+        /// the local ollama models don't hold the format well enough at 4 tokens.
+        fn ask(report: &str) -> Option<Verdict> {
+            llm::active().complete(llm::Task::Verdict, &PROMPT, report).and_then(|t| parse(&t))
+        }
 
         #[test]
         fn closing_text_is_read_past_a_thinking_only_turn() {
@@ -994,6 +1882,23 @@ Answer with exactly one word: finished, stuck, partial, or ongoing.";
             }
         }
 
+        /// The whole verdict path — report extraction, the cached call, the
+        /// parse — over a scripted backend.
+        #[test]
+        fn a_settled_transcript_gets_the_backends_verdict() {
+            let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+            let mock = llm::Mock::new(&[Ok("Partial.")]);
+            let calls = mock.calls.clone();
+            let llms = llm::Llms::new(
+                llm::Route::new(Box::new(llm::Mock::new(&[])), Duration::from_secs(1)),
+                llm::Route::new(Box::new(mock), Duration::from_secs(1)),
+                None,
+            );
+            let verdict = classify_with(&llms, &dir.join("finished__idle_pane_own_transcript.jsonl"));
+            assert_eq!(verdict, Some(Verdict::Partial));
+            assert!(calls.lock().unwrap()[0].starts_with("Done, all green."));
+        }
+
         #[test]
         fn verdicts_parse_off_a_bare_word() {
             assert_eq!(parse("stuck"), Some(Verdict::Stuck));
@@ -1332,6 +2237,8 @@ struct SessionMetadata {
     model: Option<String>,
     /// Context size of the last turn (see context_tokens)
     context: Option<u64>,
+    /// Context size per turn, oldest first (see cost::growth)
+    growth: Vec<u64>,
     /// Transcript verdict on whether work is in flight (see transcript_working)
     transcript_working: Option<bool>,
     /// How long the transcript has sat untouched; the Finished→Done clock.
@@ -1358,16 +2265,17 @@ fn get_session_info_for_pane(shell_pid: u32, deep: &str) -> Option<SessionMetada
         .map(|s| s.to_string())?;
 
     let todo_result = get_active_todo_from_session(&session_id);
-    let summary = get_session_summary(&session_file);
+    let readings = read_transcript(&session_file);
 
     Some(SessionMetadata {
         has_active_todos: todo_result.has_active_todos,
         file: session_file.clone(),
         display_todo: todo_result.display_todo,
-        summary,
-        model: latest_model(&session_file),
-        context: context_tokens(&session_file),
-        transcript_working: transcript_working(&session_file),
+        summary: readings.summary,
+        model: readings.model,
+        context: readings.context,
+        growth: readings.growth,
+        transcript_working: readings.transcript_working,
         idle_for: fs::metadata(&session_file)
             .and_then(|m| m.modified())
             .ok()
@@ -1375,6 +2283,50 @@ fn get_session_info_for_pane(shell_pid: u32, deep: &str) -> Option<SessionMetada
     })
 }
 
+/// Everything read off a transcript's bytes (as opposed to its mtime, which
+/// keeps ticking into idle_for).
+#[derive(Clone, Default)]
+struct TranscriptReadings {
+    summary: Option<String>,
+    model: Option<String>,
+    context: Option<u64>,
+    growth: Vec<u64>,
+    transcript_working: Option<bool>,
+}
+
+/// (len, mtime) a reading was taken at. Transcripts are append-only, so an
+/// unchanged pair means unchanged content.
+type TranscriptStamp = (u64, std::time::SystemTime);
+
+/// A one-shot run starts cold and gains nothing from this; the daemon keeps it
+/// across ticks, so a transcript is re-read only after it was actually written
+/// to — summary reads walk the whole file, and an idle session's file doesn't
+/// move for hours.
+static TRANSCRIPT_MEMO: std::sync::LazyLock<std::sync::Mutex<HashMap<PathBuf, (TranscriptStamp, TranscriptReadings)>>> =
+    std::sync::LazyLock::new(Default::default);
+
+fn read_transcript(file: &Path) -> TranscriptReadings {
+    let stamp = fs::metadata(file).ok().and_then(|m| Some((m.len(), m.modified().ok()?)));
+    if let Some(stamp) = stamp
+        && let Some((at, hit)) = TRANSCRIPT_MEMO.lock().unwrap().get(file)
+        && *at == stamp
+    {
+        return hit.clone();
+    }
+
+    let readings = TranscriptReadings {
+        summary: get_session_summary(file),
+        model: latest_model(file),
+        context: context_tokens(file),
+        growth: cost::growth(file),
+        transcript_working: transcript_working(file),
+    };
+    if let Some(stamp) = stamp {
+        TRANSCRIPT_MEMO.lock().unwrap().insert(file.to_path_buf(), (stamp, readings.clone()));
+    }
+    readings
+}
+
 /// Every capture a pane needs, fetched in one batched round trip. A separate
 /// `tmux capture-pane` child per kind per pane was ~20 forks a second — the
 /// dominant cost of this script's poll.
@@ -1394,7 +2346,7 @@ const CAPTURE_KINDS: [(&str, &[&str]); 3] = [
     ("deep", &["-p", "-S", "-500"]),
 ];
 
-fn capture_panes(targets: &[String]) -> HashMap<String, PaneCapture> {
+fn capture_panes(host: &Host, targets: &[String]) -> HashMap<String, PaneCapture> {
     // Unique per run: these panes routinely display this script's own source, so
     // a fixed literal could be sitting in a pane at capture time and be read as
     // a separator. This one doesn't exist until the run that emits it.
@@ -1413,7 +2365,7 @@ fn capture_panes(targets: &[String]) -> HashMap<String, PaneCapture> {
     let mut pending: Vec<String> = targets.to_vec();
     let mut out = HashMap::new();
     while !pending.is_empty() {
-        let got = run_batch(&sentinel, &pending);
+        let got = run_batch(host, &sentinel, &pending);
         if got.is_empty() {
             pending.remove(0);
             continue;
@@ -1424,7 +2376,7 @@ fn capture_panes(targets: &[String]) -> HashMap<String, PaneCapture> {
     out
 }
 
-fn run_batch(sentinel: &str, targets: &[String]) -> HashMap<String, PaneCapture> {
+fn run_batch(host: &Host, sentinel: &str, targets: &[String]) -> HashMap<String, PaneCapture> {
     let mut args: Vec<String> = Vec::new();
     for target in targets {
         for (kind, flags) in CAPTURE_KINDS {
@@ -1447,9 +2399,10 @@ fn run_batch(sentinel: &str, targets: &[String]) -> HashMap<String, PaneCapture>
     }
 
     // Not gated on exit status: an aborted chain still wrote every command
-    // before the failing one.
-    let output = Command::new("tmux").args(&args).output().expect("Failed to execute tmux");
-    parse_batch(sentinel, targets, &String::from_utf8_lossy(&output.stdout))
+    // before the failing one. A host that can't be reached at all yields
+    // nothing, and capture_panes walks the rest off as unreachable.
+    let stdout = host.tmux(&args).output().map(|o| o.stdout).unwrap_or_default();
+    parse_batch(sentinel, targets, &String::from_utf8_lossy(&stdout))
 }
 
 /// Split a batched capture stream back into per-target triples, keyed by the
@@ -1553,38 +2506,169 @@ mod batch_tests {
         assert_eq!(got.len(), 1);
         assert_eq!(got["a:1"].plain, format!("{impostor}body of a:1 plain\n"));
     }
-}
 
-struct Pane {
-    session: String,
-    window_index: u32,
-    /// A claude process is live in this pane (vs. a claude-NAMED window at a shell).
-    is_claude_pane: bool,
-    pane_pid: u32,
-    target: String,
+    #[test]
+    fn host_specs_name_a_server_by_shape() {
+        assert_eq!(Host::parse("local", "ssh"), Host::Local);
+        assert_eq!(Host::parse("/tmp/tmux-1000/work", "ssh"), Host::Socket("/tmp/tmux-1000/work".into()));
+        assert_eq!(
+            Host::parse("build-box", "ssh -o BatchMode=yes"),
+            Host::Ssh { dest: "build-box".into(), via: vec!["ssh".into(), "-o".into(), "BatchMode=yes".into()] }
+        );
+        assert_eq!(Host::parse("/tmp/tmux-1000/work", "ssh").label().as_deref(), Some("/tmp/tmux-1000/work"));
+    }
+
+    /// The remote shell re-splits whatever ssh hands it. A stand-in for ssh
+    /// that runs the line through a local `sh`, in front of a stand-in tmux
+    /// that echoes its argv, shows what a real remote tmux would receive.
+    #[test]
+    fn a_remote_tmux_receives_its_arguments_verbatim() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("claude-sessions-ssh-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = |name: &str, body: &str| {
+            let path = dir.join(name);
+            fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            path
+        };
+        script("tmux", r#"printf '%s\n' "$@""#);
+        let ssh = script("fake-ssh", &format!(r#"shift; PATH={}:$PATH exec sh -c "$1""#, dir.display()));
+
+        let host = Host::Ssh { dest: "box".into(), via: vec![ssh.to_string_lossy().into_owned()] };
+        let args = ["capture-pane", "-t", "it's:1", ";", "display-message", "-p", "@@nrc@@ #{session_name} $HOME `x`"];
+        let out = host.tmux(&args).output().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        assert_eq!(String::from_utf8_lossy(&out.stdout).lines().collect::<Vec<_>>(), args);
+    }
 }
 
-fn get_claude_windows() -> Vec<ClaudeWindow> {
-    let output = Command::new("tmux")
-        .args([
-            "list-panes",
-            "-a",
-            "-F",
-            "#{session_name}\t#{window_index}\t#{window_name}\t#{pane_current_command}\t#{pane_pid}",
-        ])
-        .output()
-        .expect("Failed to execute tmux");
+/// A tmux server to poll. Everything about a poll — list-panes, the batched
+/// capture chain — is tmux commands, so one on another box is the same poll
+/// with `ssh <dest>` in front. What isn't tmux (/proc, the transcripts under
+/// ~/.claude) stays on the machine it's on: remote windows are classified from
+/// their pane text alone.
+#[derive(Debug, Clone, PartialEq)]
+enum Host {
+    /// The default server — what a bare `tmux` talks to.
+    Local,
+    /// Another server on this machine (`tmux -S <socket>`).
+    Socket(PathBuf),
+    /// A server on another machine. `via` is the ssh invocation, split on
+    /// whitespace, that the destination and the command line are appended to.
+    Ssh { dest: String, via: Vec<String> },
+}
 
-    if !output.status.success() {
-        return Vec::new();
+impl Host {
+    fn parse(spec: &str, ssh: &str) -> Host {
+        if spec == "local" {
+            Host::Local
+        } else if spec.contains('/') {
+            Host::Socket(PathBuf::from(spec))
+        } else {
+            Host::Ssh { dest: spec.to_string(), via: ssh.split_whitespace().map(String::from).collect() }
+        }
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    /// What the host column shows; None for the local server, which has never
+    /// needed one. Window keys are built from it, so a socket is named by its
+    /// whole path — `/tmp/a/work` and `/tmp/b/work` are two servers.
+    fn label(&self) -> Option<String> {
+        match self {
+            Host::Local => None,
+            Host::Socket(path) => Some(path.to_string_lossy().into_owned()),
+            Host::Ssh { dest, .. } => Some(dest.clone()),
+        }
+    }
+
+    /// /proc and ~/.claude are readable for this server's panes.
+    fn is_local(&self) -> bool {
+        !matches!(self, Host::Ssh { .. })
+    }
+
+    fn tmux(&self, args: &[impl AsRef<str>]) -> Command {
+        let args = args.iter().map(|a| a.as_ref());
+        match self {
+            Host::Local => {
+                let mut cmd = Command::new("tmux");
+                cmd.args(args);
+                cmd
+            }
+            Host::Socket(path) => {
+                let mut cmd = Command::new("tmux");
+                cmd.arg("-S").arg(path).args(args);
+                cmd
+            }
+            Host::Ssh { dest, via } => {
+                // ssh joins its argv into one string for the remote shell, so
+                // every argument is quoted here — the capture chain's `;`
+                // separators and `#{…}` formats must reach tmux verbatim.
+                let (program, opts) = via.split_first().map(|(p, o)| (p.as_str(), o)).unwrap_or(("ssh", &[]));
+                let line = std::iter::once("tmux").chain(args).map(shell_quote).collect::<Vec<_>>().join(" ");
+                let mut cmd = Command::new(program);
+                cmd.args(opts).arg(dest).arg(line);
+                cmd
+            }
+        }
+    }
+}
+
+/// POSIX single-quoting: the one form in which no character is special.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Poll every host at once — a remote round trip is ssh-handshake slow, and
+/// one dead box must not hold up the rest — and merge them into one list.
+fn poll_hosts(hosts: &[Host]) -> Vec<ClaudeWindow> {
+    std::thread::scope(|scope| {
+        let polls: Vec<_> = hosts.iter().map(|host| scope.spawn(move || get_claude_windows(host))).collect();
+        polls.into_iter().flat_map(|p| p.join().unwrap_or_default()).collect()
+    })
+}
+
+struct Pane {
+    session: String,
+    window_index: u32,
+    /// A claude process is live in this pane (vs. a claude-NAMED window at a shell).
+    is_claude_pane: bool,
+    pane_pid: u32,
+    target: String,
+    cwd: PathBuf,
+}
+
+fn get_claude_windows(host: &Host) -> Vec<ClaudeWindow> {
+    let output = host
+        .tmux(&[
+            "list-panes",
+            "-a",
+            "-F",
+            "#{session_name}\t#{window_index}\t#{window_name}\t#{pane_current_command}\t#{pane_pid}\t#{pane_current_path}",
+        ])
+        .output();
+    let output = match (output, host) {
+        (Ok(output), _) if output.status.success() => output,
+        // No local server is just nothing running; a remote that won't answer
+        // is worth a word, or its sessions silently vanish from the table.
+        (Ok(output), Host::Ssh { dest, .. }) => {
+            eprintln!("{}", format!("warn: {dest}: {}", String::from_utf8_lossy(&output.stderr).trim()).yellow());
+            return Vec::new();
+        }
+        (Err(e), Host::Ssh { dest, .. }) => {
+            eprintln!("{}", format!("warn: {dest}: {e}").yellow());
+            return Vec::new();
+        }
+        _ => return Vec::new(),
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
     let panes: Vec<Pane> = stdout
         .lines()
         .filter_map(|line| {
             let parts: Vec<&str> = line.split('\t').collect();
-            if parts.len() < 5 {
+            if parts.len() < 6 {
                 return None;
             }
 
@@ -1593,6 +2677,7 @@ fn get_claude_windows() -> Vec<ClaudeWindow> {
             let window_name = parts[2];
             let pane_command = parts[3];
             let pane_pid: u32 = parts[4].parse().ok()?;
+            let cwd = PathBuf::from(parts[5]);
 
             // Window must have "claude" in its name OR the pane command must be "claude"
             let is_claude_window = window_name.contains("claude");
@@ -1607,6 +2692,7 @@ fn get_claude_windows() -> Vec<ClaudeWindow> {
                 is_claude_pane,
                 pane_pid,
                 target: format!("{}:{}", session, window_index),
+                cwd,
             })
         })
         .collect();
@@ -1615,7 +2701,7 @@ fn get_claude_windows() -> Vec<ClaudeWindow> {
     targets.sort();
     targets.dedup(); // split windows list one pane each, but capture is per window
 
-    let captures = capture_panes(&targets);
+    let captures = capture_panes(host, &targets);
     // A pane that vanished mid-poll gets no entry; empty captures reach exactly
     // the readings a failed capture produced before — Finished live, Empty dead.
     let missing = PaneCapture::default();
@@ -1626,7 +2712,7 @@ fn get_claude_windows() -> Vec<ClaudeWindow> {
         let session = pane.session.as_str();
         let caps = captures.get(&pane.target).unwrap_or(&missing);
 
-        let (state, active_todo, draft_content, question_content, summary, model, context) = if is_claude_pane {
+        let (state, active_todo, draft_content, question_content, summary, model, context, growth, resume, session_id) = if is_claude_pane {
             // Terminal parsing decides the blocking states (Question/Draft/Error)
             // and the working state, but active↔finished flip-flops between tool
             // calls when no spinner is captured. For that one reading we defer to
@@ -1639,12 +2725,14 @@ fn get_claude_windows() -> Vec<ClaudeWindow> {
             // message), so a metadata lookup could only mis-attribute a
             // neighbour's transcript to it.
             if activity.state == ClaudeState::Empty {
-                (ClaudeState::Empty, None, None, None, None, None, None)
+                (ClaudeState::Empty, None, None, None, None, None, None, Vec::new(), None, None)
             } else {
-                let metadata = get_session_info_for_pane(pane_pid, &caps.deep);
+                let metadata = if host.is_local() { get_session_info_for_pane(pane_pid, &caps.deep) } else { None };
                 let summary = metadata.as_ref().and_then(|m| m.summary.clone());
                 let model = metadata.as_ref().and_then(|m| m.model.clone());
                 let context = metadata.as_ref().and_then(|m| m.context);
+                let growth = metadata.as_ref().map(|m| m.growth.clone()).unwrap_or_default();
+                let session_id = metadata.as_ref().and_then(|m| Some(m.file.file_stem()?.to_string_lossy().into_owned()));
 
                 match activity.state {
                     ClaudeState::Finished => {
@@ -1655,7 +2743,7 @@ fn get_claude_windows() -> Vec<ClaudeWindow> {
                         );
                         if refined != ClaudeState::Finished {
                             let todo = metadata.as_ref().and_then(|m| m.display_todo.clone());
-                            (refined, todo, None, None, summary, model, context)
+                            (refined, todo, None, None, summary, model, context, growth, None, session_id)
                         } else {
                             let stale = matches!(&metadata, Some(m) if m.idle_for.is_some_and(|d| d >= DONE_AFTER));
                             // Done is a decayed signal — I've had 45 minutes to see
@@ -1670,7 +2758,7 @@ fn get_claude_windows() -> Vec<ClaudeWindow> {
                                     Some(report::Verdict::Finished) | None => ClaudeState::Finished,
                                 }
                             };
-                            (state, None, None, None, summary, model, context)
+                            (state, None, None, None, summary, model, context, growth, None, session_id)
                         }
                     }
                     _ => (
@@ -1681,6 +2769,9 @@ fn get_claude_windows() -> Vec<ClaudeWindow> {
                         summary,
                         model,
                         context,
+                        growth,
+                        None,
+                        session_id,
                     ),
                 }
             }
@@ -1691,29 +2782,24 @@ fn get_claude_windows() -> Vec<ClaudeWindow> {
             // conversation and names its transcript, so the session keeps its
             // summary after death. No transcript refinement for the dead:
             // nothing can be in flight there.
-            let tail = caps
-                .plain
-                .lines()
-                .rev()
-                .filter(|l| !l.trim().is_empty())
-                .take(15)
-                .collect::<Vec<_>>()
-                .join("\n");
+            let tail = shell_tail(&caps.plain);
 
             match dead_claude_resume_id(&tail) {
                 Some(id) => {
-                    let file = get_process_cwd(pane_pid).and_then(|cwd| {
+                    let cwd = if host.is_local() { get_process_cwd(pane_pid) } else { Some(pane.cwd.clone()) };
+                    let file = host.is_local().then_some(cwd.as_ref()).flatten().and_then(|cwd| {
                         let home = std::env::var("HOME").ok()?;
                         Some(
                             PathBuf::from(home)
                                 .join(".claude/projects")
-                                .join(path_to_project_name(&cwd))
+                                .join(path_to_project_name(cwd))
                                 .join(format!("{id}.jsonl")),
                         )
                     });
-                    let summary = file.as_deref().and_then(get_session_summary);
-                    let model = file.as_deref().and_then(latest_model);
-                    let context = file.as_deref().and_then(context_tokens);
+                    let readings = file.as_deref().map(read_transcript).unwrap_or_default();
+                    let TranscriptReadings { summary, model, context, growth, transcript_working } = readings;
+                    let session_id = Some(id.clone());
+                    let resume = cwd.map(|cwd| Resume { id, cwd, mid_turn: transcript_working == Some(true) });
                     // Killed mid-turn (esc, then Ctrl-C) exits with the
                     // "⎿ Interrupted" row still at the bottom — that's the last
                     // real state, not a clean Finished.
@@ -1722,13 +2808,14 @@ fn get_claude_windows() -> Vec<ClaudeWindow> {
                     } else {
                         ClaudeState::Finished
                     };
-                    (state, None, None, None, summary, model, context)
+                    (state, None, None, None, summary, model, context, growth, resume, session_id)
                 }
-                None => (ClaudeState::Empty, None, None, None, None, None, None),
+                None => (ClaudeState::Empty, None, None, None, None, None, None, Vec::new(), None, None),
             }
         };
 
         claude_windows.push(ClaudeWindow {
+            host: host.label(),
             session: session.to_string(),
             window_index,
             state,
@@ -1739,6 +2826,10 @@ fn get_claude_windows() -> Vec<ClaudeWindow> {
             summary,
             model,
             context,
+            growth,
+            cwd: pane.cwd.clone(),
+            resume,
+            session_id,
         });
     }
 
@@ -1756,6 +2847,17 @@ struct ActivityResult {
     /// Carried out of the classifier because the transcript-arbitrated
     /// Finished→Active upgrade in get_claude_windows needs it too.
     plan_mode: bool,
+    /// The draft is a prompt open in an external editor (a `prompt_file`
+    /// rule): Enter there edits the file rather than submitting it.
+    in_editor: bool,
+}
+
+/// The last 15 non-empty lines of a shell pane, bottom-most last — where a
+/// dead claude's exit chrome sits.
+fn shell_tail(plain: &str) -> String {
+    let mut lines: Vec<&str> = plain.lines().rev().filter(|l| !l.trim().is_empty()).take(15).collect();
+    lines.reverse();
+    lines.join("\n")
 }
 
 /// Session id from Claude Code's exit chrome ("Resume this session with:" /
@@ -1817,114 +2919,385 @@ fn determine_claude_activity(caps: &PaneCapture) -> ActivityResult {
 ///
 /// Kept free of tmux/process/fs I/O specifically so it can be exercised by
 /// snapshot fixtures — see the `tests` module. Add a new captured pane dump
-/// under `tests/fixtures/` and it's covered here with no mocking.
+/// under `tests/fixtures/` and it's covered here with no mocking. WHAT it looks
+/// for lives in the rules (see `mod rules`), not here.
 fn classify_activity(
     content: &str,
     capture_escaped: impl FnOnce() -> Option<String>,
 ) -> ActivityResult {
-    // Look at the last portion for various indicators
-    // Filter empty lines first, then take last 15 non-empty lines
-    let last_portion: String = content
-        .lines()
-        .rev()
-        .filter(|l| !l.trim().is_empty())
-        .take(15)
-        .collect::<Vec<_>>()
-        .join("\n");
-
-    // Plan mode swaps the permissions footer for "⏸ plan mode on (shift+tab to
-    // cycle)". A working pane under it is Planning rather than Active; every
-    // other state is unaffected.
-    let plan_mode = last_portion.contains("plan mode on");
-    let working = if plan_mode { ClaudeState::Planning } else { ClaudeState::Active };
-
-    // Match spinner pattern: a status phrase ending in the ellipsis glyph "…".
-    // Single-word labels render as "Running…", "Cogitating…" — but Claude Code
-    // also emits MULTI-WORD labels whose "…" trails a lowercase tail word, e.g.
-    // "Building and verifying static musl binary…", "Baking the response…". The
-    // old `[A-Z][a-z]+…` required the capitalized word to sit IMMEDIATELY before
-    // "…", so every multi-word spinner was misread as Finished — an active
-    // session looking idle. But bare "<letters>…" is TOO loose the other way:
-    // the welcome box truncates long model names ("claude-fable-5 with high
-    // effo… ·"), reading an idle session as Active forever. The timer suffix
-    // disambiguates — a live spinner always renders "… (<elapsed>…", truncated
-    // prose never grows a paren — so we require it. The word before "…" can end
-    // in a digit too ("Wiring Postgres backups to R2…"), hence \p{N}.
-    // A live spinner is unambiguous: Claude is working THIS frame. We do NOT gate
-    // it on has_prompt_at_end — the TUI always renders its input box ("❯ ") at the
-    // bottom of the pane even while the spinner runs above it, so that guard would
-    // veto Active on essentially every frame. The genuine "stopped, waiting on me"
-    // cases (selector / AskUserQuestion) are matched later and their selectors
-    // never coexist with a live spinner, so they still fire when appropriate.
-    // Checked FIRST — before the limit branch — because after a usage-limit hit
-    // the session can resume working while the "⎿ You've hit your … limit" chrome
-    // still sits in scrollback; a live spinner means it's working NOW, and Active
-    // must win over that stale limit row.
-    // Anchored to a line-leading spinner glyph: sessions QUOTE spinner lines in
-    // prose (e.g. a recap discussing "…R2… (3m 56s…"), and an unanchored match
-    // read those idle panes as Active. Real spinners always render as their own
-    // line — glyph, space, phrase, "… (<elapsed>" — while quoted ones sit mid-line
-    // or behind list/chrome prefixes ("- ", "⎿  "). [^…]* keeps the match inside
-    // one spinner phrase so a lazy dot can't bridge from a literal "…" in prose to
-    // a later "… (". If the glyph alphabet ever grows past this set, the
-    // transcript override in get_claude_windows still catches the missed Active.
-    let spinner_pattern = Regex::new(r"(?m)^\s*[·✢✳✶✻✽∗*]\s+[^…]*[\p{L}\p{N}]… \(").unwrap();
-    if spinner_pattern.is_match(&last_portion) {
-        return ActivityResult { state: working, draft_content: None, question_content: None, plan_mode };
-    }
-
-    // Hitting the session/usage limit renders as a result row
-    // "⎿  You've hit your session limit · resets ..." usually followed by the
-    // /rate-limit-options selector ("❯ 1. Stop and wait for limit to reset").
-    // That selector would be claimed by the Question branch below — but this
-    // isn't a question in any meaningful sense: no answer I pick unblocks the
-    // session before the reset clock does. So it gets its own state, checked
-    // before everything else. Anchored the same way as the API-error chrome:
-    // trimmed line STARTS with "⎿" with the limit text as its immediate body,
-    // so narration that merely QUOTES the chrome doesn't fire.
-    let limit_pattern = Regex::new(r"(?m)^\s*⎿\s+You['’]ve hit your (session )?limit").unwrap();
-    if limit_pattern.is_match(&last_portion) {
-        return ActivityResult { state: ClaudeState::Limit, draft_content: None, question_content: None, plan_mode };
-    }
-
-    // Check for question state FIRST: a blocking selection prompt is the
-    // highest-priority signal — it means Claude has stopped and is waiting on me,
-    // and it must win over leftover spinner text or active todos. Two distinct
-    // renderings both mean "waiting for me to pick an option":
-    //   1. Permission/selector menu: "❯ 1. Option text" (the ❯ marks the cursor)
-    //   2. AskUserQuestion widget: a boxed question whose footer reads
-    //      "Enter to select · ↑/↓ to navigate". This footer is unique to the
-    //      widget — normal output never prints it — so it's the reliable marker.
-    // The widget can be tall, so we match against the full capture, not just the
-    // last 15 lines (the question header may have scrolled above last_portion).
-    // The live AskUserQuestion footer is left-aligned widget chrome: trimmed, the
-    // line STARTS with "Enter to select". Anchoring on the start (not a substring
-    // match) rejects scrollback that merely quotes the footer mid-line — e.g. a
-    // session debugging this very script, or one that captured another's pane,
-    // where the text is always preceded by line numbers, prose, or code.
-    // Typed text in the live input box, if any. The box always renders its mode
-    // footer ("⏵⏵ … (shift+tab to cycle)"); real selectors (permission /
-    // AskUserQuestion / limit) carry their own footer instead, never this one. So
-    // a "❯ 1. …" line under this footer is the user typing a numbered list — NOT
-    // a selector — and must suppress the question branch below.
-    // Search `content` (top-down order), NOT `last_portion`: last_portion is
-    // built bottom-up, so input_box_text's own rev() would cancel out and pick
-    // the TOPMOST ">"-looking line — e.g. quoted npm output ("> playwright …")
-    // in a tool result — instead of the live input box at the bottom.
-    let typed_input = last_portion
-        .contains("shift+tab to cycle")
-        .then(|| input_box_text(content))
-        .flatten();
-
-    let question_selector_pattern = Regex::new(r"(?m)^\s*❯\s*\d+\.\s+.+$").unwrap();
-    let is_selector = question_selector_pattern.is_match(&last_portion);
-    let is_askquestion = last_portion
-        .lines()
-        .any(|l| l.trim_start().starts_with("Enter to select") && l.contains("↑/↓ to navigate"));
-    if (is_selector || is_askquestion) && typed_input.is_none() {
-        // Extract the question text - the nearest line ending with "?" searching
-        // upward from the bottom (skip prompt lines and option rows).
-        let question_text = content
+    rules::active().classify(content, capture_escaped).0
+}
+
+/// Every pattern `classify_activity` matches a pane against, as data: Claude's
+/// UI changes shape often, and a changed spinner glyph or limit string shouldn't
+/// need a recompile. `DEFAULT` below is the built-in set; `--rules <file>`
+/// swaps in another one written in the same TOML shape, and `check-rules`
+/// replays a set against the fixtures.
+///
+/// Rules are tried top to bottom and the first one whose conditions all hold
+/// decides the state — order IS precedence. A rule's conditions:
+///   any / all / none — regexes; at least one / every one / none of them match
+///   window           — "tail" (last `tail_lines` non-empty lines, or `lines`)
+///                      or "full" (the whole capture); a pattern can override it
+///                      with `{ re = '…', window = "full" }`
+///   typed_input      — true/false: text is / isn't sitting in the live input box
+///   extract          — a named hook that pulls the displayed content out, and
+///                      for `escaped_draft` also decides whether the rule holds
+/// `state = "working"` resolves to active, or planning under plan mode.
+mod rules {
+    use super::*;
+    use std::sync::OnceLock;
+
+    pub const DEFAULT: &str = r#####"
+# Shared windows. The tail is the last N non-empty lines: chrome that matters
+# sits at the bottom of the pane, and scrollback above it is history.
+tail_lines = 15
+
+# Plan mode swaps the permissions footer for "⏸ plan mode on (shift+tab to
+# cycle)". Doesn't change WHETHER Claude works, only what the work is: it turns
+# `working` into planning and nothing else.
+plan_mode = 'plan mode on'
+
+# The input box always renders its mode footer ("⏵⏵ … (shift+tab to cycle)");
+# real selectors (permission / AskUserQuestion / limit) carry their own footer
+# instead, never this one. Under it, a non-empty prompt line is typed input.
+input_footer = 'shift\+tab to cycle'
+
+fallback = "finished"
+
+# A live spinner: a status phrase ending in "…" followed by its elapsed timer,
+# "… (<elapsed>". Multi-word labels end on a lowercase word, or a digit
+# ("Wiring Postgres backups to R2…"). The timer is required: the welcome box
+# truncates long model names with "…" too, and truncated prose never grows a
+# paren. Anchored to a line-leading glyph, since sessions QUOTE spinner lines in
+# prose; [^…]* keeps the match inside one phrase. Not gated on the prompt — the
+# TUI renders "❯ " under a running spinner every frame — and first, because a
+# session resumed after a limit hit still has that limit row in scrollback.
+[[rule]]
+name = "spinner"
+state = "working"
+any = ['(?m)^\s*[·✢✳✶✻✽∗*]\s+[^…]*[\p{L}\p{N}]… \(']
+
+# "⎿  You've hit your session limit · resets …", usually followed by the
+# /rate-limit-options selector. No answer unblocks it before the reset clock
+# does, so it's not a question. Anchored like the API-error row below.
+[[rule]]
+name = "limit"
+state = "limit"
+any = ['''(?m)^\s*⎿\s+You['’]ve hit your (session )?limit''']
+
+# Claude stopped and waits on me to pick an option: a permission/selector menu
+# ("❯ 1. Option", ❯ marks the cursor), or the AskUserQuestion widget, whose
+# left-aligned footer normal output never prints. A "❯ 1. …" line under the
+# input footer is me typing a numbered list, hence typed_input = false.
+[[rule]]
+name = "question"
+state = "question"
+typed_input = false
+any = ['(?m)^\s*❯\s*\d+\.\s+.+$', '(?m)^\s*Enter to select.*↑/↓ to navigate']
+extract = "question"
+
+# The prompt is open in an external editor on /tmp/claude-prompt-<uuid>.md.
+# Matched at line start (the nvim title bar) so prose naming the file doesn't.
+[[rule]]
+name = "editor_draft"
+state = "draft"
+window = "full"
+any = ['(?m)^[/ ]*t.*/claude-prompt-([a-f0-9-]+)\.md']
+extract = "prompt_file"
+
+# Typed-but-unsent text on the bypass-permissions prompt. Only the escaped
+# capture tells real input from a dim ghost suggestion; the hook decides.
+# Before the welcome check: I may be typing on the welcome screen.
+[[rule]]
+name = "escaped_draft"
+state = "draft"
+all = ['bypass permissions']
+extract = "escaped_draft"
+
+# "⎿  Interrupted · What should Claude do instead?" — an esc-aborted turn
+# dropped back to a live prompt. Before the welcome check (a `claude -c`
+# continuation still shows the welcome box) and the prompt gate (Finished would
+# bury it); but once I start typing the reply, it's input.
+[[rule]]
+name = "interrupted"
+state = "interrupted"
+typed_input = false
+any = ['(?m)^\s*⎿\s+Interrupted']
+
+# "⎿  API Error: 529 Overloaded." aborts the turn back to a live prompt, so it
+# must beat the prompt gate. Anchored so the trimmed line STARTS with ⎿ and
+# "API Error:" is its immediate body: narration quoting the chrome nests a
+# second glyph or wraps it in prose and doesn't match.
+[[rule]]
+name = "api_error"
+state = "error"
+any = ['(?m)^\s*⎿\s+API Error:']
+
+# Typed into the box but not sent. Loose on purpose (see README) — but before
+# the welcome check: typing on the welcome screen is input.
+[[rule]]
+name = "input"
+state = "input"
+typed_input = true
+
+# A fresh session's welcome screen: the v2 banner logo, or the older texts (a
+# RELAUNCHED claude's "Welcome back" box still has "Tips"). A relaunch paints
+# its welcome under the previous conversation, so any "●" row vetoes it.
+[[rule]]
+name = "welcome"
+state = "empty"
+window = "full"
+any = ['▐▛███▜▌', 'No recent activity', 'Tips for getting started']
+none = ['●']
+
+# A dead claude: exit chrome "Resume this session with:" / bare
+# `claude --resume <uuid>`. Tail only: an exit hint deep in scrollback under a
+# relaunched claude must not shadow the live session.
+[[rule]]
+name = "dead_claude"
+state = "finished"
+any = ['(?m)^claude --resume ([0-9a-f-]{36})\s*$']
+
+# Claude tailing a background shell (the footer's "N shell") leaves a live
+# prompt with no spinner, but it's blocked on the shell, not done. Only known
+# wait-y commands count — a dev server outlives the turn.
+[[rule]]
+name = "watched_shell"
+state = "working"
+all = ['\d+ shells?\b', { re = 'gh run watch', window = "full" }]
+
+# A prompt line ("❯ ", or "> " on older builds) means Claude waits on me.
+[[rule]]
+name = "prompt"
+state = "finished"
+window = "full"
+any = ['(?m)^\s*(> |❯[ \x{00A0}])']
+
+# No prompt captured and the verbatim limit strings Claude prints when wedged —
+# never prose that merely says "error".
+[[rule]]
+name = "usage_error"
+state = "error"
+any = ['(?i)(usage limit reached|approaching usage limit|5-hour limit|rate limit exceeded|too many requests)']
+"#####;
+
+    static ACTIVE: OnceLock<Rules> = OnceLock::new();
+
+    /// The set `classify_activity` runs: `--rules` if main installed one, else DEFAULT.
+    pub fn active() -> &'static Rules {
+        ACTIVE.get_or_init(|| Rules::parse(DEFAULT).expect("built-in rules parse"))
+    }
+
+    /// Must run before the first classification; later calls lose to whatever is installed.
+    pub fn install(rules: Rules) {
+        let _ = ACTIVE.set(rules);
+    }
+
+    pub fn load(path: &Path) -> Result<Rules, String> {
+        let raw = fs::read_to_string(path).map_err(|e| format!("read {}: {e}", path.display()))?;
+        Rules::parse(&raw).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    #[derive(Deserialize)]
+    struct RawRules {
+        tail_lines: usize,
+        plan_mode: String,
+        input_footer: String,
+        fallback: ClaudeState,
+        rule: Vec<RawRule>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct RawRule {
+        name: String,
+        state: Target,
+        #[serde(default)]
+        window: Window,
+        lines: Option<usize>,
+        #[serde(default)]
+        any: Vec<RawPattern>,
+        #[serde(default)]
+        all: Vec<RawPattern>,
+        #[serde(default)]
+        none: Vec<RawPattern>,
+        typed_input: Option<bool>,
+        extract: Option<Extract>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawPattern {
+        Bare(String),
+        Windowed { re: String, window: Window },
+    }
+
+    #[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Window {
+        #[default]
+        Tail,
+        Full,
+    }
+
+    #[derive(Deserialize, Clone, Copy)]
+    #[serde(untagged)]
+    enum Target {
+        /// "working": active, or planning under plan mode
+        Working(WorkingTag),
+        State(ClaudeState),
+    }
+
+    #[derive(Deserialize, Clone, Copy)]
+    #[serde(rename_all = "lowercase")]
+    enum WorkingTag {
+        Working,
+    }
+
+    #[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    enum Extract {
+        /// The nearest question line above the options.
+        Question,
+        /// First line of /tmp/claude-prompt-<uuid>.md; the uuid is capture group 1.
+        PromptFile,
+        /// Typed text on the prompt line of the escaped capture; no text (or only
+        /// a dim ghost suggestion) means the rule doesn't hold.
+        EscapedDraft,
+    }
+
+    struct Pattern {
+        re: Regex,
+        /// None: the rule's own window.
+        window: Option<Window>,
+    }
+
+    pub struct Rule {
+        pub name: String,
+        state: Target,
+        window: Window,
+        lines: Option<usize>,
+        any: Vec<Pattern>,
+        all: Vec<Pattern>,
+        none: Vec<Pattern>,
+        typed_input: Option<bool>,
+        extract: Option<Extract>,
+    }
+
+    pub struct Rules {
+        tail_lines: usize,
+        plan_mode: Regex,
+        input_footer: Regex,
+        fallback: ClaudeState,
+        pub rules: Vec<Rule>,
+    }
+
+    fn compile(re: &str) -> Result<Regex, String> {
+        Regex::new(re).map_err(|e| format!("bad regex {re:?}: {e}"))
+    }
+
+    impl Rules {
+        pub fn parse(raw: &str) -> Result<Rules, String> {
+            let raw: RawRules = toml::from_str(raw).map_err(|e| e.to_string())?;
+            let patterns = |ps: Vec<RawPattern>| -> Result<Vec<Pattern>, String> {
+                ps.into_iter()
+                    .map(|p| match p {
+                        RawPattern::Bare(re) => Ok(Pattern { re: compile(&re)?, window: None }),
+                        RawPattern::Windowed { re, window } => Ok(Pattern { re: compile(&re)?, window: Some(window) }),
+                    })
+                    .collect()
+            };
+            let rules = raw
+                .rule
+                .into_iter()
+                .map(|r| {
+                    let err = |e: String| format!("rule {:?}: {e}", r.name);
+                    Ok(Rule {
+                        any: patterns(r.any).map_err(err)?,
+                        all: patterns(r.all).map_err(err)?,
+                        none: patterns(r.none).map_err(err)?,
+                        name: r.name,
+                        state: r.state,
+                        window: r.window,
+                        lines: r.lines,
+                        typed_input: r.typed_input,
+                        extract: r.extract,
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok(Rules {
+                tail_lines: raw.tail_lines,
+                plan_mode: compile(&raw.plan_mode)?,
+                input_footer: compile(&raw.input_footer)?,
+                fallback: raw.fallback,
+                rules,
+            })
+        }
+
+        /// The state, plus the name of the rule that decided it (None: fallback).
+        pub fn classify(&self, content: &str, capture_escaped: impl FnOnce() -> Option<String>) -> (ActivityResult, Option<&str>) {
+            let tail = |n: usize| {
+                let mut lines: Vec<&str> = content.lines().rev().filter(|l| !l.trim().is_empty()).take(n).collect();
+                lines.reverse();
+                lines.join("\n")
+            };
+            let default_tail = tail(self.tail_lines);
+
+            let plan_mode = self.plan_mode.is_match(&default_tail);
+            // Searched in `content` top-down, so input_box_text lands on the
+            // bottom-most prompt line — the live box, not a quoted "> " above it.
+            let typed_input = self.input_footer.is_match(&default_tail).then(|| input_box_text(content)).flatten();
+            let mut capture_escaped = Some(capture_escaped);
+
+            for rule in &self.rules {
+                let rule_tail = rule.lines.map(tail);
+                let text = |w: Option<Window>| match w.unwrap_or(rule.window) {
+                    Window::Full => content,
+                    Window::Tail => rule_tail.as_deref().unwrap_or(&default_tail),
+                };
+                if rule.typed_input.is_some_and(|want| want != typed_input.is_some())
+                    || !rule.all.iter().all(|p| p.re.is_match(text(p.window)))
+                    || rule.none.iter().any(|p| p.re.is_match(text(p.window)))
+                {
+                    continue;
+                }
+                let hit = if rule.any.is_empty() {
+                    Some(None)
+                } else {
+                    rule.any.iter().find_map(|p| p.re.captures(text(p.window)).map(|c| c.get(1).map(|g| g.as_str())))
+                };
+                let Some(group) = hit else { continue };
+
+                let (mut draft_content, mut question_content) = (None, None);
+                match rule.extract {
+                    None => {}
+                    Some(Extract::Question) => question_content = question_text(content),
+                    Some(Extract::PromptFile) => draft_content = group.and_then(prompt_file_draft),
+                    Some(Extract::EscapedDraft) => {
+                        let escaped = capture_escaped.take().and_then(|f| f());
+                        match escaped.as_deref().and_then(escaped_draft) {
+                            Some(draft) => draft_content = (!draft.is_empty()).then_some(draft),
+                            None => continue,
+                        }
+                    }
+                }
+                let state = match rule.state {
+                    Target::Working(_) if plan_mode => ClaudeState::Planning,
+                    Target::Working(_) => ClaudeState::Active,
+                    Target::State(s) => s,
+                };
+                let in_editor = rule.extract == Some(Extract::PromptFile);
+                return (ActivityResult { state, draft_content, question_content, plan_mode, in_editor }, Some(&rule.name));
+            }
+            (ActivityResult { state: self.fallback, draft_content: None, question_content: None, plan_mode, in_editor: false }, None)
+        }
+    }
+
+    /// The question text: the nearest line ending with "?" searching upward
+    /// from the bottom, skipping prompt lines and option rows.
+    fn question_text(content: &str) -> Option<String> {
+        content
             .lines()
             .rev()
             .take(30)
@@ -1939,203 +3312,177 @@ fn classify_activity(
                 } else {
                     trimmed
                 }
-            });
-
-        return ActivityResult {
-            state: ClaudeState::Question,
-            draft_content: None,
-            question_content: question_text,
-            plan_mode,
-        };
+            })
     }
 
-    // Check for external editor with claude-prompt temp file
-    // When user opens external editor (nvim, vim, etc), Claude creates a temp file
-    // like /tmp/claude-prompt-*.md - if we see this in the pane, it's a draft
-    // Match only at start of line (nvim title bar) to avoid false positives from text mentioning the file
-    let claude_prompt_pattern = Regex::new(r"(?m)^[/ ]*t.*/claude-prompt-([a-f0-9-]+)\.md").unwrap();
-    if let Some(caps) = claude_prompt_pattern.captures(&content) {
-        let uuid = &caps[1];
-        let full_path = format!("/tmp/claude-prompt-{}.md", uuid);
-        // Read the temp file contents for draft display
-        let draft_content = fs::read_to_string(&full_path)
+    /// First line of the prompt Claude handed the external editor.
+    fn prompt_file_draft(uuid: &str) -> Option<String> {
+        fs::read_to_string(format!("/tmp/claude-prompt-{uuid}.md"))
             .ok()
             .map(|s| s.trim().to_string())
             .filter(|s| !s.is_empty())
             .map(|s| {
-                // Get first line and truncate if needed
                 let first_line = s.lines().next().unwrap_or(&s);
                 if first_line.len() > 50 {
                     format!("{}...", &first_line[..50])
                 } else {
                     first_line.to_string()
                 }
-            });
-        return ActivityResult {
-            state: ClaudeState::Draft,
-            draft_content,
-            question_content: None,
-            plan_mode,
-        };
+            })
     }
 
-    // Check for draft state: user has typed something after "> "
-    // The bypass permissions prompt is visible and there's actual text after "> "
-    // Note: We need to distinguish real input from grey suggestions (dim text)
-    // This must come BEFORE the fresh session check, as user may type on welcome screen
-    if last_portion.contains("bypass permissions") {
-        // Re-capture with escape codes to detect dim text (suggestions)
-        if let Some(content_esc) = capture_escaped() {
-            // Find the current prompt line - it has "[0m>" pattern (reset then prompt)
-            // The prompt may be followed by regular space or NBSP
-            if let Some(prompt_line) = content_esc.lines().find(|l| l.contains("\x1b[0m>"))
-            {
-                if let Some(pos) = prompt_line.find("\x1b[0m>") {
-                    let after_gt = &prompt_line[pos + 5..]; // 5 = len of "\x1b[0m>"
-                    // Check if this is a suggestion vs real user input
-                    // Suggestions have dim text (\x1b[...2m), possibly with cursor highlight (\x1b[7m) on first char
-                    // Real input has regular text without dim escapes
-                    // Note: dim can be "\x1b[2m" or combined like "\x1b[0;2m"
-                    let dim_pattern = Regex::new(r"\x1b\[[0-9;]*2m").unwrap();
-                    let reverse_pattern = Regex::new(r"\x1b\[7m").unwrap();
-                    // It's a suggestion if: has dim text OR (has reverse video followed by dim)
-                    // which indicates cursor is highlighting a suggestion character
-                    let has_dim = dim_pattern.is_match(after_gt);
-                    let has_reverse_then_dim = reverse_pattern.is_match(after_gt) && has_dim;
-                    let is_suggestion = has_dim || has_reverse_then_dim;
-                    let has_content = after_gt.chars().any(|c| c.is_alphanumeric());
-                    if has_content && !is_suggestion {
-                        // Extract the actual draft text (strip escape codes)
-                        let escape_pattern = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
-                        let clean_text = escape_pattern.replace_all(after_gt, "");
-                        // Also strip NBSP and trim
-                        let draft_text = clean_text.replace('\u{00A0}', " ").trim().to_string();
-                        // Truncate if too long
-                        let draft_display = if draft_text.len() > 50 {
-                            format!("{}...", &draft_text[..50])
-                        } else {
-                            draft_text
-                        };
-                        return ActivityResult {
-                            state: ClaudeState::Draft,
-                            draft_content: if draft_display.is_empty() { None } else { Some(draft_display) },
-                            question_content: None,
-                            plan_mode,
-                        };
-                    }
-                }
-            }
+    /// Typed text after the prompt of an escape-coded capture. The current
+    /// prompt line has a "[0m>" (reset, then prompt), followed by a space or
+    /// NBSP. Suggestions render dim (`\x1b[…2m`, maybe under a reverse-video
+    /// cursor `\x1b[7m`); real input has no dim escapes. None: no real input.
+    fn escaped_draft(content_esc: &str) -> Option<String> {
+        let prompt_line = content_esc.lines().find(|l| l.contains("\x1b[0m>"))?;
+        let pos = prompt_line.find("\x1b[0m>")?;
+        let after_gt = &prompt_line[pos + 5..]; // 5 = len of "\x1b[0m>"
+        let dim_pattern = Regex::new(r"\x1b\[[0-9;]*2m").unwrap();
+        let is_suggestion = dim_pattern.is_match(after_gt);
+        let has_content = after_gt.chars().any(|c| c.is_alphanumeric());
+        if !has_content || is_suggestion {
+            return None;
         }
+        let escape_pattern = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+        let clean_text = escape_pattern.replace_all(after_gt, "");
+        let draft_text = clean_text.replace('\u{00A0}', " ").trim().to_string();
+        Some(if draft_text.len() > 50 { format!("{}...", &draft_text[..50]) } else { draft_text })
     }
 
-    // User-aborted turn: esc mid-tool-call renders result-row chrome
-    // "⎿  Interrupted · What should Claude do instead?" and drops back to a live
-    // prompt — same shape as the API-error row, same anchoring. Must precede
-    // BOTH the fresh-session check (a `claude -c` continuation still shows the
-    // welcome box, which reads as Empty on full-content match) and the
-    // prompt-line gate (Finished would bury it — and todos usually still have
-    // pending items here, so the Finished→Active upgrade in get_claude_windows
-    // would then mislabel the session as working). But typed input wins: once I
-    // start composing the "what to do instead" reply, the pane is Input, not a
-    // bare interruption — same suppression the question branch uses.
-    if shows_interruption(&last_portion) && typed_input.is_none() {
-        return ActivityResult { state: ClaudeState::Interrupted, draft_content: None, question_content: None, plan_mode };
-    }
-
-    // API errors render as Claude Code's own tool-result chrome: a result row
-    // led by the "⎿" glyph whose body is "API Error: <code> <reason>" (e.g.
-    // "⎿  API Error: 529 Overloaded.", "⎿  API Error: Connection error.").
-    // Unlike usage-limit wedges, these abort the turn and DROP BACK to a live
-    // prompt — so this MUST be checked before the prompt-line gate below, which
-    // would otherwise mis-read the idle "❯ " as Finished and bury the failure.
-    //
-    // Two guards keep this from firing on Claude's own narration that QUOTES the
-    // chrome (e.g. a session — like this one — discussing an API error it saw):
-    //   1. A live spinner already returned Active above, so a working session
-    //      that mentions the error never reaches here.
-    //   2. The row must be GENUINE chrome, not quoted: the line, once trimmed,
-    //      STARTS with "⎿" and "API Error:" is its IMMEDIATE body — only
-    //      whitespace between them. Quoted narration nests a second glyph
-    //      ("⎿  ⎿  API Error:") or wraps it in prose, so "API Error:" is not the
-    //      row's own first token and the anchored match rejects it.
-    let api_error_pattern = Regex::new(r"(?m)^\s*⎿\s+API Error:").unwrap();
-    if api_error_pattern.is_match(&last_portion) {
-        return ActivityResult { state: ClaudeState::Error, draft_content: None, question_content: None, plan_mode };
-    }
-
-
-    // User has typed into the input box but not sent yet. We don't distinguish
-    // empty/finished/input precisely (see README) — a non-empty input line under
-    // the mode footer is enough. Comes after the spinner/draft/error branches so
-    // a genuinely working or errored session still wins — but BEFORE the fresh
-    // welcome check: typing on the welcome screen is input, not an empty pane.
-    if typed_input.is_some() {
-        return ActivityResult { state: ClaudeState::Input, draft_content: None, question_content: None, plan_mode };
-    }
-
-    // Check for fresh session (welcome screen) - after the draft/typed branches.
-    // v2.1.15x dropped the old standalone "Tips for getting started"/"No recent
-    // activity" text in favor of the banner logo, but the "Welcome back" box of
-    // a RELAUNCHED claude still carries the "Tips" string. Every marker is gated
-    // on the absence of conversation bullets ("●"): a relaunch/resume paints its
-    // welcome UNDER the previous session's rows, and a pane that still shows a
-    // conversation is that conversation's state (usually Finished via the prompt
-    // gate below), not an empty one.
-    if !content.contains('●')
-        && (content.contains("▐▛███▜▌")
-            || content.contains("No recent activity")
-            || content.contains("Tips for getting started"))
-    {
-        return ActivityResult { state: ClaudeState::Empty, draft_content: None, question_content: None, plan_mode };
+    /// One fixture's replay: its named state, what the rules made of it, and
+    /// which rule decided. Shared by `check-rules` and the fixture test.
+    pub struct FixtureRun {
+        pub stem: String,
+        pub expected: Option<ClaudeState>,
+        pub result: ActivityResult,
+        pub rule: Option<String>,
+        /// transcript_working of the `.jsonl` companion, if there is one
+        pub verdict: Option<bool>,
+        pub has_transcript: bool,
+        /// After the transcript deliberation — what the prefix names.
+        pub final_state: ClaudeState,
     }
 
-    // A dead claude: on exit the TUI prints "Resume this session with:" and the
-    // bare `claude --resume <uuid>` command, then hands the pane back to the
-    // shell. The conversation is over — Finished. Restricted to the recent tail:
-    // an exit hint deep in scrollback under a RELAUNCHED claude must not shadow
-    // the live session's state.
-    if dead_claude_resume_id(&last_portion).is_some() {
-        return ActivityResult { state: ClaudeState::Finished, draft_content: None, question_content: None, plan_mode };
+    pub fn fixture_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+        let mut txts: Vec<PathBuf> = fs::read_dir(dir)
+            .map_err(|e| format!("read {}: {e}", dir.display()))?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|x| x == "txt"))
+            .collect();
+        // Deterministic order so the snapshot review list is stable run-to-run.
+        txts.sort();
+        Ok(txts)
     }
 
-    // A background shell Claude is actively tailing (e.g. `gh run watch` on a CI
-    // build) leaves the pane on a live "❯ " prompt with no spinner, so the gate
-    // below would read it as Finished — but Claude isn't done, it's blocked on
-    // the shell's result. The footer's "N shell" (and the "N shell still
-    // running" recap line) marks a live background shell. Not every lingering
-    // shell counts — a dev server outlives the turn — so we only upgrade to
-    // Active for known wait-y commands. Expand the list as more turn up.
-    const WATCH_SHELL_PATTERNS: &[&str] = &["gh run watch"];
-    let has_bg_shell = Regex::new(r"\d+ shells?\b").unwrap().is_match(&last_portion);
-    if has_bg_shell && WATCH_SHELL_PATTERNS.iter().any(|p| content.contains(p)) {
-        return ActivityResult { state: working, draft_content: None, question_content: None, plan_mode };
+    /// Replays a `<state>__<desc>.txt` fixture the way production reads the
+    /// pane: the optional `.esc` companion feeds the draft path, the optional
+    /// `.jsonl` goes through the same refine_finished the Finished arm uses
+    /// (todos pinned to false — fixtures carry no todo files).
+    pub fn run_fixture(rules: &Rules, txt: &Path) -> Result<FixtureRun, String> {
+        let stem = txt.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let plain = fs::read_to_string(txt).map_err(|e| format!("read {}: {e}", txt.display()))?;
+        let esc = fs::read_to_string(txt.with_extension("esc")).ok();
+        let (result, rule) = rules.classify(&plain, || esc.clone());
+        let rule = rule.map(str::to_string);
+
+        let jsonl = txt.with_extension("jsonl");
+        let has_transcript = jsonl.exists();
+        let (final_state, verdict) = if has_transcript && result.state == ClaudeState::Finished {
+            let verdict = transcript_working(&jsonl);
+            (ClaudeState::refine_finished(verdict, false, result.plan_mode), verdict)
+        } else {
+            (result.state, None)
+        };
+        let expected = stem.split("__").next().and_then(ClaudeState::parse);
+        Ok(FixtureRun { stem, expected, result, rule, verdict, has_transcript, final_state })
     }
 
-    // Check if there's an input prompt line - indicates Claude is waiting for
-    // input (task done). Modern Claude Code renders the prompt as "❯ "; older
-    // builds used "> ". Either one means Finished.
-    let has_prompt = content.lines().any(is_prompt_line);
-    if has_prompt {
-        return ActivityResult { state: ClaudeState::Finished, draft_content: None, question_content: None, plan_mode };
+    /// `check-rules`: replay every fixture under `dir` and report the ones whose
+    /// named state the rules no longer produce. Err iff anything mismatched.
+    pub fn check(rules: &Rules, dir: &Path, json: bool) -> Result<(), String> {
+        let runs = fixture_files(dir)?.iter().map(|t| run_fixture(rules, t)).collect::<Result<Vec<_>, _>>()?;
+        let bad: Vec<&FixtureRun> = runs.iter().filter(|r| r.expected != Some(r.final_state)).collect();
+
+        if json {
+            let rows: Vec<_> = runs
+                .iter()
+                .map(|r| {
+                    serde_json::json!({
+                        "fixture": r.stem,
+                        "expected": r.expected,
+                        "got": r.final_state,
+                        "pane": r.result.state,
+                        "transcript_working": r.verdict,
+                        "rule": r.rule,
+                        "ok": r.expected == Some(r.final_state),
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::Value::Array(rows));
+        } else {
+            let width = runs.iter().map(|r| r.stem.len()).max().unwrap_or(0);
+            for r in &runs {
+                let mut rule = r.rule.as_deref().unwrap_or("fallback").to_string();
+                if r.has_transcript {
+                    rule += &format!(", pane {} + transcript {:?}", r.result.state.as_str(), r.verdict);
+                }
+                match r.expected {
+                    Some(e) if e == r.final_state => println!("{} {:width$}  {} ({rule})", "ok".green(), r.stem, e.as_str()),
+                    Some(e) => println!(
+                        "{} {:width$}  expected {}, got {} ({rule})",
+                        "!!".red(),
+                        r.stem,
+                        e.as_str(),
+                        r.final_state.as_str()
+                    ),
+                    None => println!("{} {:width$}  no <state>__ prefix; got {} ({rule})", "!!".red(), r.stem, r.final_state.as_str()),
+                }
+            }
+            println!("{}/{} fixtures match", runs.len() - bad.len(), runs.len());
+        }
+        if bad.is_empty() { Ok(()) } else { Err(format!("{} fixture(s) mismatch", bad.len())) }
     }
 
-    // Error state is reserved for Claude actually being WEDGED — chiefly running
-    // out of the usage allowance. We get here only when no prompt line was
-    // captured (a live prompt always means Finished, even if earlier output
-    // mentioned an error). The pattern is intentionally narrow: it matches the
-    // verbatim strings Claude Code prints when blocked on limits, NOT prose that
-    // happens to contain the word "error" — Claude narrating a build failure in
-    // its recap ("Error: No repository field…") is a finished, healthy session,
-    // not an errored one.
-    let error_pattern =
-        Regex::new(r"(?i)(usage limit reached|approaching usage limit|5-hour limit|rate limit exceeded|too many requests)")
-            .unwrap();
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const PANE: &str = "● did a thing\n✻ Cogitating… (3s · esc to interrupt)\n❯ \n";
 
-    if error_pattern.is_match(&last_portion) {
-        return ActivityResult { state: ClaudeState::Error, draft_content: None, question_content: None, plan_mode };
-    }
+        /// Precedence is file order: the same pane reads differently once a
+        /// rule file puts the prompt gate ahead of the spinner.
+        #[test]
+        fn the_first_matching_rule_in_file_order_decides() {
+            assert_eq!(active().classify(PANE, || None).1, Some("spinner"));
+
+            let reordered = r#"
+                tail_lines = 15
+                plan_mode = 'plan mode on'
+                input_footer = 'shift\+tab to cycle'
+                fallback = "empty"
+                [[rule]]
+                name = "prompt"
+                state = "finished"
+                any = ['(?m)^\s*❯ ']
+                [[rule]]
+                name = "spinner"
+                state = "working"
+                any = ['… \(']
+            "#;
+            let rules = Rules::parse(reordered).unwrap();
+            let (result, rule) = rules.classify(PANE, || None);
+            assert_eq!((result.state, rule), (ClaudeState::Finished, Some("prompt")));
+            assert_eq!(rules.classify("nothing here", || None).0.state, ClaudeState::Empty);
+        }
 
-    ActivityResult { state: ClaudeState::Finished, draft_content: None, question_content: None, plan_mode }
+        #[test]
+        fn a_bad_regex_names_its_rule() {
+            let err = Rules::parse(&DEFAULT.replace("'▐▛███▜▌'", "'(unclosed'")).err().unwrap();
+            assert!(err.contains("\"welcome\""), "got {err}");
+        }
+    }
 }
 
 // ----- 5-hour usage % from Claude Code's OAuth-authenticated /api/oauth/usage -----
@@ -2181,6 +3528,12 @@ struct CacheState {
     /// Unix epoch of last fetch attempt (success or fail). Throttles retries
     /// so we don't hammer the endpoint when it's per-minute rate-limited.
     last_fetch_attempt_at: Option<i64>,
+    /// Unix epoch of the poll that wrote `window_states` — how stale they are
+    /// decides whether the history can bridge to this poll (see history::diff).
+    polled_at: Option<i64>,
+    /// Every successful fetch, for the burn rate (see mod forecast).
+    #[serde(default)]
+    samples: Vec<forecast::Sample>,
 }
 
 #[derive(Deserialize)]
@@ -2217,6 +3570,21 @@ fn save_cache(cache: &CacheState) {
     }
 }
 
+/// An exclusive lock on `path`'s read-modify-write, held until the file is
+/// dropped. Pollers run side by side (the bar's two defpolls, the daemon), and
+/// two that both read the same old contents would each write their own update
+/// over the other's. None when there's nowhere to put the lock — the caller
+/// then goes ahead unlocked.
+fn lock_beside(path: &Path) -> Option<fs::File> {
+    let p = path.with_extension("lock");
+    if let Some(parent) = p.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let file = fs::OpenOptions::new().create(true).truncate(false).write(true).open(p).ok()?;
+    file.lock().ok()?;
+    Some(file)
+}
+
 /// Utilization windows from Claude Code's OAuth usage endpoint. `utilization`
 /// and `percent` are already 0..100; `resets_at` is RFC3339 (always UTC).
 #[derive(Deserialize)]
@@ -2338,11 +3706,15 @@ fn format_duration_until(target: i64) -> String {
 }
 
 /// One usage limit window. `compact` renders just "<time_left> · <pct_left>";
-/// `full` prefixes the limit's name so a lone value can't be misread.
+/// `full` prefixes the limit's name so a lone value can't be misread. Either
+/// gains "→<time>" when the forecast has it running out before it resets.
+#[derive(Serialize)]
 struct LimitView {
+    #[serde(skip)]
     name: &'static str,
     used_pct: Option<f64>,
     resets_at: Option<i64>,
+    runs_out_at: Option<i64>,
 }
 
 impl LimitView {
@@ -2362,7 +3734,11 @@ impl LimitView {
             Some(t) => format_duration_until(t),
             None => "?".to_string(),
         };
-        format!("{time_left} · {pct_left}")
+        match self.runs_out_at {
+            Some(t) if t <= now_epoch() => format!("{time_left} · {pct_left} →out"),
+            Some(t) => format!("{time_left} · {pct_left} →{}", format_duration_until(t)),
+            None => format!("{time_left} · {pct_left}"),
+        }
     }
 
     fn full(&self) -> String {
@@ -2370,16 +3746,20 @@ impl LimitView {
     }
 }
 
-fn format_usage_header(u: &UsageInfo, compact: bool) -> String {
-    let mut limits = vec![LimitView { name: "total", used_pct: u.five_hour_used_pct, resets_at: u.five_hour_resets_at }];
-    // Compact is a two-cell bar; the account-wide weekly only earns a slot once
-    // the cells are labelled and there's room to read them.
-    if !compact {
-        limits.push(LimitView { name: "weekly", used_pct: u.weekly_all_used_pct, resets_at: u.weekly_all_resets_at });
-    }
-    limits.push(LimitView { name: "fable", used_pct: u.weekly_used_pct, resets_at: u.weekly_resets_at });
-    limits
+fn limit_views(u: &UsageInfo, f: &forecast::Forecast) -> [LimitView; 3] {
+    [
+        LimitView { name: "total", used_pct: u.five_hour_used_pct, resets_at: u.five_hour_resets_at, runs_out_at: f.five_hour },
+        LimitView { name: "weekly", used_pct: u.weekly_all_used_pct, resets_at: u.weekly_all_resets_at, runs_out_at: f.weekly_all },
+        LimitView { name: "fable", used_pct: u.weekly_used_pct, resets_at: u.weekly_resets_at, runs_out_at: f.weekly },
+    ]
+}
+
+fn format_usage_header(u: &UsageInfo, f: &forecast::Forecast, compact: bool) -> String {
+    limit_views(u, f)
         .iter()
+        // Compact is a two-cell bar; the account-wide weekly only earns a slot
+        // once the cells are labelled and there's room to read them.
+        .filter(|l| !compact || l.name != "weekly")
         .map(|l| if compact { l.compact() } else { l.full() })
         .collect::<Vec<_>>()
         .join(" | ")
@@ -2388,7 +3768,7 @@ fn format_usage_header(u: &UsageInfo, compact: bool) -> String {
 fn current_state_map(windows: &[ClaudeWindow]) -> HashMap<String, String> {
     windows
         .iter()
-        .map(|w| (format!("{}:{}", w.session, w.window_index), w.state.as_str().to_string()))
+        .map(|w| (w.key(), w.state.as_str().to_string()))
         .collect()
 }
 
@@ -2424,7 +3804,7 @@ fn should_recompute(prev: &CacheState, windows: &[ClaudeWindow]) -> bool {
         _ => {}
     }
     for w in windows {
-        let key = format!("{}:{}", w.session, w.window_index);
+        let key = w.key();
         let new_state = w.state.as_str();
         let old_state = prev.window_states.get(&key).map(|s| s.as_str()).unwrap_or("");
         if old_state != new_state
@@ -2445,21 +3825,15 @@ fn should_recompute(prev: &CacheState, windows: &[ClaudeWindow]) -> bool {
     false
 }
 
-fn main() {
-    let args = Args::parse();
-
-    // Set LLM summaries flag
-    if args.llm_summaries {
-        USE_LLM_SUMMARIES.store(true, std::sync::atomic::Ordering::Relaxed);
-    }
-
-    let windows = get_claude_windows();
-
-    // Group windows by session to find sessions with non-empty windows
-    let mut session_windows: HashMap<String, Vec<&ClaudeWindow>> = HashMap::new();
-    for window in &windows {
+/// The table main() prints: claude-named shell windows deduped away, sorted.
+/// Shared with the daemon so a served table is the exact one a direct run builds.
+fn session_entries(windows: &[ClaudeWindow]) -> Vec<SessionEntry> {
+    // Group windows by session to find sessions with non-empty windows. Same-
+    // named sessions on different hosts are different sessions.
+    let mut session_windows: HashMap<(Option<String>, String), Vec<&ClaudeWindow>> = HashMap::new();
+    for window in windows {
         session_windows
-            .entry(window.session.clone())
+            .entry((window.host.clone(), window.session.clone()))
             .or_default()
             .push(window);
     }
@@ -2467,7 +3841,7 @@ fn main() {
     // Find sessions that have at least one real session slot: a non-empty
     // window OR a live (if fresh) claude. Used below to hide shell-only
     // claude-named windows in sessions where actual claudes exist.
-    let sessions_with_non_empty: HashSet<String> = session_windows
+    let sessions_with_non_empty: HashSet<(Option<String>, String)> = session_windows
         .iter()
         .filter(|(_, wins)| {
             wins.iter()
@@ -2482,32 +3856,33 @@ fn main() {
     //   - shell windows in sessions that have real claudes are hidden entirely
     //   - all-shell sessions collapse to a single empty row
     let mut results: Vec<&ClaudeWindow> = Vec::new();
-    let mut seen_empty_session: HashSet<&str> = HashSet::new();
+    let mut seen_empty_session: HashSet<(Option<String>, String)> = HashSet::new();
 
-    for window in &windows {
+    for window in windows {
         if window.state == ClaudeState::Empty && !window.claude_running {
-            if sessions_with_non_empty.contains(&window.session) {
+            let session = (window.host.clone(), window.session.clone());
+            if sessions_with_non_empty.contains(&session) {
                 continue;
             }
-            if seen_empty_session.contains(window.session.as_str()) {
+            if !seen_empty_session.insert(session) {
                 continue;
             }
-            seen_empty_session.insert(&window.session);
         }
         results.push(window);
     }
 
-    // Sort by session name, then window index
+    // Sort by host (local first), session name, then window index
     results.sort_by(|a, b| {
-        a.session
-            .cmp(&b.session)
+        a.host
+            .cmp(&b.host)
+            .then(a.session.cmp(&b.session))
             .then(a.window_index.cmp(&b.window_index))
     });
 
-    // Build Sessions struct
-    let mut sessions = Sessions::new(args.compact, args.markup);
-    for window in results {
-        sessions.add(SessionEntry {
+    results
+        .into_iter()
+        .map(|window| SessionEntry {
+            host: window.host.clone(),
             name: window.session.clone(),
             window_index: window.window_index,
             state: window.state,
@@ -2517,173 +3892,2630 @@ fn main() {
             summary: window.summary.clone(),
             model: window.model.clone(),
             context: window.context,
-        });
-    }
-    sessions.sort();
+            growth: window.growth.clone(),
+            project: Some(path_to_project_name(&window.cwd)),
+            session_id: window.session_id.clone(),
+        })
+        .collect()
+}
 
-    // Show warning if ollama was unavailable (only in non-compact mode with summaries)
-    if !args.compact && !args.json && OLLAMA_UNAVAILABLE.load(std::sync::atomic::Ordering::Relaxed)
-    {
-        eprintln!(
-            "{}",
-            "warn: ollama unavailable, using fallback summaries".yellow()
-        );
+/// Everything a poll writes back. Appends the state flips since the previous
+/// poll to the history, then refetches 5h utilization on state flip, on cache
+/// time-staleness, or when prior usage is unknown (throttled; otherwise reuses
+/// cache). Persists this poll's window states either way — they're what the
+/// next call diffs against — a fetch that landed as a forecast sample, and the
+/// cost ledger when the poll's transcript reads counted new turns. One poll at
+/// a time: a second waits on the cache's lock and then diffs against what the
+/// first wrote, rather than appending the same flips to the history again.
+fn persist_poll(windows: &[ClaudeWindow]) -> (UsageInfo, forecast::Forecast) {
+    cost::save();
+    let _lock = cache_path().and_then(|p| lock_beside(&p));
+    let mut cache = load_cache();
+    history::append(&history::diff(&cache.window_states, cache.polled_at, windows, now_epoch()));
+    let did_attempt = should_recompute(&cache, windows);
+    let fresh = if did_attempt { fetch_usage() } else { None };
+    let usage = fresh.map(|u| u.overlay(cache.usage)).unwrap_or(cache.usage);
+    if fresh.is_some() {
+        forecast::record(&mut cache.samples, usage, now_epoch());
     }
-
-    // Refetch 5h utilization on state flip, on cache time-staleness,
-    // or when prior usage is unknown. Throttled. Otherwise reuse cache.
-    let cache = load_cache();
-    let did_attempt = should_recompute(&cache, &windows);
-    let usage = if did_attempt {
-        fetch_usage().map(|u| u.overlay(cache.usage)).unwrap_or(cache.usage)
-    } else {
-        cache.usage
-    };
+    let forecast = forecast::forecast(&cache.samples, now_epoch());
     save_cache(&CacheState {
-        window_states: current_state_map(&windows),
+        window_states: current_state_map(windows),
         usage,
         last_fetch_attempt_at: if did_attempt {
             Some(now_epoch())
         } else {
             cache.last_fetch_attempt_at
         },
+        polled_at: Some(now_epoch()),
+        samples: cache.samples,
     });
+    (usage, forecast)
+}
 
-    if args.json {
-        println!("{}", serde_json::to_string(&sessions.entries).unwrap());
-    } else if args.markup {
-        // Header is purely informational; left uncolored so it inherits the
-        // widget's default text color (the eww label's own styling).
-        println!("{}", pango_escape(&format_usage_header(&usage, args.compact)));
-        println!("{}", sessions);
-    } else {
-        println!("{}", format_usage_header(&usage, args.compact).dimmed());
-        println!("{}", sessions);
+/// When each usage limit runs out at the current pace. Every successful fetch
+/// is kept as a sample in the state cache; the climb between the samples of a
+/// window that's still open is the burn rate.
+mod forecast {
+    use super::*;
+
+    /// No window still open reaches back further than this.
+    const KEEP_SECS: i64 = 8 * 86400;
+    /// A pace read off less than this is noise: a fetch right after a burst
+    /// extrapolates to "out in four minutes".
+    const MIN_SPAN_SECS: i64 = 15 * 60;
+    /// Two fetches of one window can disagree on its reset by a rounding.
+    const SAME_WINDOW_SECS: i64 = 5 * 60;
+    /// How far back "the current pace" looks. The 5h window is read whole; a
+    /// weekly one by its last day — Monday's burn says little about Friday.
+    const FIVE_HOUR_PACE_SECS: i64 = 5 * 3600;
+    const WEEKLY_PACE_SECS: i64 = 86400;
+
+    #[derive(Clone, Copy, Serialize, Deserialize)]
+    pub struct Sample {
+        pub at: i64,
+        pub usage: UsageInfo,
     }
-}
 
-#[cfg(test)]
-mod tests {
-    //! Snapshot tests for the terminal-state classifier.
-    //!
-    //! Every regression this script has ever had lived in `classify_activity`:
-    //! a real pane that got read as the wrong `ClaudeState`. These tests pin that
-    //! function against REAL captured pane dumps — no tmux, no /proc, no mocks.
-    //!
-    //! ## Fixture layout (`tests/fixtures/`)
-    //! - `<state>__<name>.txt`  — plain `tmux capture-pane -p` output. The
-    //!   `<state>` prefix (before `__`) is the EXPECTED state and is asserted.
-    //! - `<state>__<name>.esc`  — OPTIONAL companion: escape-coded
-    //!   `tmux capture-pane -p -e` output. Only the draft path consults it; if
-    //!   absent the classifier is told the escaped capture is unavailable.
-    //! - `<state>__<name>.jsonl` — OPTIONAL companion: tail of the session's
-    //!   transcript. States now come from pane text AND the transcript (the
-    //!   active↔finished deliberation), so fixtures for that path persist both.
-    //!   The prefix names the FINAL state after `refine_finished`, letting one
-    //!   pane dump pin both verdicts (same .txt, different .jsonl).
-    //!
-    //! ## Adding a case (the whole point — trivial, no code edit)
-    //! Capture a live pane in the state you want to lock in:
-    //!     tmux capture-pane -t <sess>:<win> -p -S -50   > tests/fixtures/question__askwidget.txt
-    //!     # only for draft cases, also grab the escaped capture:
-    //!     tmux capture-pane -t <sess>:<win> -p -e -S -10 > tests/fixtures/draft__typed.esc
-    //!     # for active/finished deliberation cases, persist the transcript tail:
-    //!     tail -n 15 ~/.claude/projects/<proj>/<session>.jsonl > tests/fixtures/finished__idle.jsonl
-    //! Then `cargo insta accept` to record its full ActivityResult snapshot.
-    //! The filename-prefix assertion runs automatically.
+    /// Unix epoch seconds at which each limit runs out at the current pace —
+    /// in the past when it already has. None: no pace yet, nothing burning,
+    /// or the window resets first.
+    #[derive(Default, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Forecast {
+        pub five_hour: Option<i64>,
+        /// The fable weekly limit (UsageInfo's `weekly_*`).
+        pub weekly: Option<i64>,
+        pub weekly_all: Option<i64>,
+    }
 
-    use super::*;
-    use std::fs;
-    use std::path::{Path, PathBuf};
+    impl Forecast {
+        /// Whether any limit runs out within `minutes` of `now`.
+        pub fn warns(&self, minutes: u64, now: i64) -> bool {
+            [self.five_hour, self.weekly, self.weekly_all]
+                .into_iter()
+                .flatten()
+                .any(|at| at - now <= minutes as i64 * 60)
+        }
+    }
 
-    fn fixtures_dir() -> PathBuf {
-        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+    pub fn record(samples: &mut Vec<Sample>, usage: UsageInfo, now: i64) {
+        samples.retain(|s| now - s.at < KEEP_SECS);
+        samples.push(Sample { at: now, usage });
     }
 
-    /// Parse the expected state out of a fixture filename's `<state>__` prefix.
-    fn expected_state_from_name(stem: &str) -> ClaudeState {
-        let prefix = stem.split("__").next().expect("fixture name has a prefix");
-        match prefix {
-            "empty" => ClaudeState::Empty,
-            "active" => ClaudeState::Active,
-            "planning" => ClaudeState::Planning,
-            "finished" => ClaudeState::Finished,
-            "draft" => ClaudeState::Draft,
-            "question" => ClaudeState::Question,
-            "input" => ClaudeState::Input,
-            "error" => ClaudeState::Error,
-            "limit" => ClaudeState::Limit,
-            "interrupted" => ClaudeState::Interrupted,
-            other => panic!(
-                "fixture {stem:?} has unknown state prefix {other:?}; \
-                 name it <state>__<desc>.txt"
-            ),
+    pub fn forecast(samples: &[Sample], now: i64) -> Forecast {
+        let limit = |f: fn(&UsageInfo) -> (Option<f64>, Option<i64>), pace| {
+            let points = samples.iter().filter_map(|s| match f(&s.usage) {
+                (Some(pct), Some(reset)) => Some((s.at, pct, reset)),
+                _ => None,
+            });
+            runs_out(&points.collect::<Vec<_>>(), pace, now)
+        };
+        Forecast {
+            five_hour: limit(|u| (u.five_hour_used_pct, u.five_hour_resets_at), FIVE_HOUR_PACE_SECS),
+            weekly: limit(|u| (u.weekly_used_pct, u.weekly_resets_at), WEEKLY_PACE_SECS),
+            weekly_all: limit(|u| (u.weekly_all_used_pct, u.weekly_all_resets_at), WEEKLY_PACE_SECS),
         }
     }
 
-    /// Walk every `*.txt` fixture, classify it, and assert two things:
-    ///   1. the classified state equals the filename's `<state>__` prefix, and
-    ///   2. the full ActivityResult matches its recorded insta snapshot.
-    /// Drop a new correctly-named `.txt` in and it's covered with zero code edits.
-    #[test]
-    fn fixtures_classify_to_their_named_state() {
-        let dir = fixtures_dir();
-        let mut txts: Vec<PathBuf> = fs::read_dir(&dir)
-            .unwrap_or_else(|e| panic!("read {dir:?}: {e}"))
-            .filter_map(|e| e.ok().map(|e| e.path()))
-            .filter(|p| p.extension().is_some_and(|x| x == "txt"))
-            .collect();
-        // Deterministic order so the snapshot review list is stable run-to-run.
-        txts.sort();
+    /// `(at, used_pct, resets_at)`, oldest first. Linear from the first to the
+    /// last reading of the current window within `pace` — usage comes in
+    /// bursts, and the endpoints are what a burst-and-pause pattern averages to.
+    fn runs_out(points: &[(i64, f64, i64)], pace: i64, now: i64) -> Option<i64> {
+        let &(last_at, last_pct, reset) = points.last()?;
+        if reset <= now {
+            return None; // rolled over since; the next fetch starts it afresh
+        }
+        if last_pct >= 100.0 {
+            return Some(last_at);
+        }
+        let &(first_at, first_pct, _) = points
+            .iter()
+            .rev()
+            .take_while(|(at, _, r)| (r - reset).abs() < SAME_WINDOW_SECS && last_at - at <= pace)
+            .last()?;
+        if last_at - first_at < MIN_SPAN_SECS || last_pct <= first_pct {
+            return None;
+        }
+        let per_sec = (last_pct - first_pct) / (last_at - first_at) as f64;
+        let at = last_at + ((100.0 - last_pct) / per_sec) as i64;
+        (at < reset).then_some(at)
+    }
 
-        assert!(
-            !txts.is_empty(),
-            "no *.txt fixtures in {dir:?} — capture one with `tmux capture-pane -p`"
-        );
+    #[cfg(test)]
+    mod tests {
+        use super::*;
 
-        for txt in txts {
-            let stem = txt.file_stem().unwrap().to_string_lossy().to_string();
-            let plain = fs::read_to_string(&txt).unwrap_or_else(|e| panic!("read {txt:?}: {e}"));
-
-            // Companion escaped capture, only present for draft fixtures.
-            let esc = fs::read_to_string(txt.with_extension("esc")).ok();
-            let result = classify_activity(&plain, || esc.clone());
-
-            // Companion transcript tail, only present for fixtures pinning the
-            // active↔finished deliberation. Replayed through the same
-            // refine_finished the production Finished arm uses (todos-fallback
-            // pinned to false — fixtures carry no todo files).
-            let jsonl = txt.with_extension("jsonl");
-            let (final_state, verdict) = if jsonl.exists() {
-                assert_eq!(
-                    result.state,
-                    ClaudeState::Finished,
-                    "fixture {stem:?} has a .jsonl companion but the pane classified as \
-                     {:?} — the transcript is only consulted for Finished panes",
-                    result.state
-                );
-                let verdict = transcript_working(&jsonl);
-                (
-                    ClaudeState::refine_finished(verdict, false, result.plan_mode),
-                    verdict,
-                )
-            } else {
-                (result.state, None)
-            };
+        fn sample(at: i64, pct: f64, reset: i64) -> Sample {
+            Sample { at, usage: UsageInfo { five_hour_used_pct: Some(pct), five_hour_resets_at: Some(reset), ..Default::default() } }
+        }
+
+        /// 10% an hour from 40% is six hours to go — past a reset four hours
+        /// out, so no forecast; twice the pace runs out an hour before it.
+        #[test]
+        fn the_pace_within_the_open_window_says_whether_it_runs_out_first() {
+            let reset = 20_000;
+            let steady = [sample(0, 20.0, reset), sample(3600, 30.0, reset), sample(7200, 40.0, reset)];
+            assert_eq!(forecast(&steady, 7200).five_hour, None);
+
+            let fast = [sample(0, 0.0, reset), sample(3600, 20.0, reset), sample(7200, 40.0, reset)];
+            assert_eq!(forecast(&fast, 7200).five_hour, Some(7200 + 3 * 3600));
+            assert!(forecast(&fast, 7200).warns(180, 7200));
+            assert!(!forecast(&fast, 7200).warns(179, 7200));
+        }
 
-            let expected = expected_state_from_name(&stem);
+        /// The previous window's climb isn't this one's pace, and one reading
+        /// (or two minutes apart) isn't a pace at all.
+        #[test]
+        fn only_the_current_window_with_enough_span_counts() {
+            let before = [sample(0, 10.0, 5000), sample(3600, 90.0, 5000)];
+            let after = [sample(6000, 5.0, 24_000), sample(6120, 6.0, 24_000)];
+            let samples: Vec<Sample> = before.into_iter().chain(after).collect();
+            assert_eq!(forecast(&samples, 6120).five_hour, None);
+            assert_eq!(forecast(&[sample(0, 100.0, 9000)], 60).five_hour, Some(0), "already out");
+            assert_eq!(forecast(&[sample(0, 100.0, 9000)], 9000).five_hour, None, "and reset since");
+        }
+    }
+}
+
+/// Token and dollar accounting over whole transcripts. Every assistant turn's
+/// usage is summed per day and model into a ledger keyed by transcript, which
+/// remembers the byte offset it got to — transcripts are append-only, so each
+/// read picks up where the last stopped instead of re-walking tens of MB. The
+/// same pass keeps each session's context size per turn for the table's
+/// growth sparkline.
+///
+/// Prices are USD per million tokens, matched to a model by the longest key
+/// contained in its id; `[cost.prices.<key>]` in the config file overrides or
+/// adds to the built-in list prices.
+mod cost {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::sync::{LazyLock, Mutex};
+
+    /// Context points kept per session — more than any sparkline draws.
+    const SERIES_LEN: usize = 64;
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+    pub struct Tokens {
+        pub input: u64,
+        pub output: u64,
+        pub cache_read: u64,
+        pub cache_write: u64,
+    }
+
+    impl Tokens {
+        fn add(&mut self, o: &Tokens) {
+            self.input += o.input;
+            self.output += o.output;
+            self.cache_read += o.cache_read;
+            self.cache_write += o.cache_write;
+        }
+
+        fn sub(&mut self, o: &Tokens) {
+            self.input -= o.input;
+            self.output -= o.output;
+            self.cache_read -= o.cache_read;
+            self.cache_write -= o.cache_write;
+        }
+
+        /// The context the turn ran in: what context_tokens reads for the last one.
+        fn context(&self) -> u64 {
+            self.input + self.output + self.cache_read + self.cache_write
+        }
+    }
+
+    /// The last turn counted: its records can repeat (see ingest).
+    #[derive(Serialize, Deserialize, Clone)]
+    struct Last {
+        id: String,
+        day: String,
+        model: String,
+        tokens: Tokens,
+        sidechain: bool,
+    }
+
+    #[derive(Serialize, Deserialize, Default)]
+    pub struct FileLedger {
+        /// Bytes consumed — always at a line boundary.
+        offset: u64,
+        last: Option<Last>,
+        /// UTC day (YYYY-MM-DD) -> model -> tokens
+        pub days: BTreeMap<String, BTreeMap<String, Tokens>>,
+        /// Context size after each main-thread turn, oldest first.
+        pub context: Vec<u64>,
+    }
+
+    impl FileLedger {
+        /// Fold complete transcript lines in. A turn with several content
+        /// blocks is written as one record per block, each repeating the turn's
+        /// message id and usage; a repeat replaces the turn's earlier reading
+        /// rather than counting it again. False when no line was a turn.
+        pub fn ingest(&mut self, lines: &str) -> bool {
+            let mut counted = false;
+            for line in lines.lines() {
+                let Ok(v) = serde_json::from_str::<serde_json::Value>(line) else { continue };
+                if v.get("type").and_then(|t| t.as_str()) != Some("assistant") {
+                    continue;
+                }
+                let Some(msg) = v.get("message") else { continue };
+                let (Some(usage), Some(model)) = (msg.get("usage"), msg.get("model").and_then(|m| m.as_str())) else {
+                    continue;
+                };
+                if model == "<synthetic>" {
+                    continue;
+                }
+                let field = |k: &str| usage.get(k).and_then(|x| x.as_u64()).unwrap_or(0);
+                let tokens = Tokens {
+                    input: field("input_tokens"),
+                    output: field("output_tokens"),
+                    cache_read: field("cache_read_input_tokens"),
+                    cache_write: field("cache_creation_input_tokens"),
+                };
+                let id = msg.get("id").and_then(|x| x.as_str()).unwrap_or_default().to_string();
+                let day = v.get("timestamp").and_then(|t| t.as_str()).and_then(|t| t.get(..10)).unwrap_or("unknown").to_string();
+                let model = model.strip_prefix("claude-").unwrap_or(model).to_string();
+                let sidechain = v.get("isSidechain").and_then(|x| x.as_bool()) == Some(true);
+
+                if let Some(last) = self.last.take_if(|l| !id.is_empty() && l.id == id) {
+                    if let Some(t) = self.days.get_mut(&last.day).and_then(|d| d.get_mut(&last.model)) {
+                        t.sub(&last.tokens);
+                    }
+                    if !last.sidechain {
+                        self.context.pop();
+                    }
+                }
+                self.days.entry(day.clone()).or_default().entry(model.clone()).or_default().add(&tokens);
+                // Subagents run in their own window; only the main thread's is the session's.
+                if !sidechain {
+                    self.context.push(tokens.context());
+                    if self.context.len() > SERIES_LEN {
+                        self.context.remove(0);
+                    }
+                }
+                self.last = Some(Last { id, day, model, tokens, sidechain });
+                counted = true;
+            }
+            counted
+        }
+
+        /// Catch up with the file on disk. False when no new turn was read —
+        /// the offset may still have moved past other records.
+        fn catch_up(&mut self, path: &Path) -> bool {
+            let Ok(mut file) = fs::File::open(path) else { return false };
+            let Ok(len) = file.metadata().map(|m| m.len()) else { return false };
+            if len < self.offset {
+                // Shrunk: not the file that was counted. Start over.
+                *self = FileLedger::default();
+            }
+            if len == self.offset || file.seek(SeekFrom::Start(self.offset)).is_err() {
+                return false;
+            }
+            let mut bytes = Vec::new();
+            if file.read_to_end(&mut bytes).is_err() {
+                return false;
+            }
+            // A record still being written stays for next time.
+            let Some(end) = bytes.iter().rposition(|b| *b == b'\n') else { return false };
+            let counted = self.ingest(&String::from_utf8_lossy(&bytes[..=end]));
+            self.offset += end as u64 + 1;
+            counted
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Default)]
+    struct Ledger {
+        files: HashMap<PathBuf, FileLedger>,
+        /// Counted something since it was loaded or last saved.
+        #[serde(skip)]
+        dirty: bool,
+    }
+
+    fn ledger_path() -> Option<PathBuf> {
+        std::env::var("HOME").ok().map(|h| PathBuf::from(h).join(".cache/claude-sessions-cost.json"))
+    }
+
+    /// Loaded once per process; the daemon keeps it warm across ticks.
+    static LEDGER: LazyLock<Mutex<Option<Ledger>>> = LazyLock::new(Default::default);
+
+    fn with_ledger<T>(f: impl FnOnce(&mut Ledger) -> (bool, T)) -> T {
+        let mut guard = LEDGER.lock().unwrap();
+        let ledger = guard.get_or_insert_with(|| {
+            ledger_path()
+                .and_then(|p| fs::read_to_string(p).ok())
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default()
+        });
+        let (changed, out) = f(ledger);
+        ledger.dirty |= changed;
+        out
+    }
+
+    /// Writes the ledger back if a turn was counted since the last save. Once
+    /// per poll, not per transcript read: it holds every transcript ever seen.
+    pub fn save() {
+        let mut guard = LEDGER.lock().unwrap();
+        let Some(ledger) = guard.as_mut().filter(|l| l.dirty) else { return };
+        if let Some(p) = ledger_path()
+            && let Ok(s) = serde_json::to_string(ledger)
+        {
+            let _ = fs::write(p, s);
+        }
+        ledger.dirty = false;
+    }
+
+    /// Context size per turn for one transcript, brought up to date in memory
+    /// (see save).
+    pub fn growth(file: &Path) -> Vec<u64> {
+        with_ledger(|l| {
+            let entry = l.files.entry(file.to_path_buf()).or_default();
+            (entry.catch_up(file), entry.context.clone())
+        })
+    }
+
+    #[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+    #[serde(deny_unknown_fields)]
+    pub struct Price {
+        pub input: f64,
+        pub output: f64,
+        pub cache_read: f64,
+        pub cache_write: f64,
+    }
+
+    impl Price {
+        fn of(&self, t: &Tokens) -> f64 {
+            (t.input as f64 * self.input
+                + t.output as f64 * self.output
+                + t.cache_read as f64 * self.cache_read
+                + t.cache_write as f64 * self.cache_write)
+                / 1e6
+        }
+    }
+
+    /// List prices at the time of writing; fable bills like opus.
+    const DEFAULT_PRICES: [(&str, Price); 4] = [
+        ("opus", Price { input: 15.0, output: 75.0, cache_read: 1.5, cache_write: 18.75 }),
+        ("fable", Price { input: 15.0, output: 75.0, cache_read: 1.5, cache_write: 18.75 }),
+        ("sonnet", Price { input: 3.0, output: 15.0, cache_read: 0.3, cache_write: 3.75 }),
+        ("haiku", Price { input: 1.0, output: 5.0, cache_read: 0.1, cache_write: 1.25 }),
+    ];
+
+    #[derive(Deserialize, Default)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct RawConfig {
+        prices: BTreeMap<String, Price>,
+    }
+
+    pub struct Prices(BTreeMap<String, Price>);
+
+    impl Prices {
+        pub fn new(raw: &RawConfig) -> Prices {
+            let mut table: BTreeMap<String, Price> = DEFAULT_PRICES.iter().map(|(k, p)| (k.to_string(), *p)).collect();
+            table.extend(raw.prices.iter().map(|(k, p)| (k.clone(), *p)));
+            Prices(table)
+        }
+
+        /// Longest key contained in the model id — "sonnet-4" outranks "sonnet".
+        fn of(&self, model: &str) -> Option<&Price> {
+            self.0.iter().filter(|(k, _)| model.contains(k.as_str())).max_by_key(|(k, _)| k.len()).map(|(_, p)| p)
+        }
+    }
+
+    #[derive(Serialize, Default, Clone, Copy)]
+    pub struct Total {
+        pub tokens: Tokens,
+        pub usd: f64,
+        /// Tokens from models with no price — counted, but not in usd.
+        pub unpriced: u64,
+    }
+
+    impl Total {
+        fn add(&mut self, tokens: &Tokens, price: Option<&Price>) {
+            self.tokens.add(tokens);
+            match price {
+                Some(p) => self.usd += p.of(tokens),
+                None => self.unpriced += tokens.context(),
+            }
+        }
+    }
+
+    #[derive(Serialize, Default)]
+    pub struct Report {
+        pub since: String,
+        pub total: Total,
+        pub days: BTreeMap<String, Total>,
+        pub projects: BTreeMap<String, Total>,
+        pub models: BTreeMap<String, Total>,
+        /// Most expensive first, cut to --top.
+        pub sessions: Vec<(String, String, Total)>,
+    }
+
+    /// Sum every ledger day on or after `since` (a YYYY-MM-DD string).
+    pub fn report<'a>(
+        files: impl IntoIterator<Item = (&'a Path, &'a FileLedger)>,
+        prices: &Prices,
+        since: &str,
+        top: usize,
+    ) -> Report {
+        let mut r = Report { since: since.to_string(), ..Default::default() };
+        for (path, ledger) in files {
+            let session = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+            let project = path
+                .parent()
+                .and_then(|p| p.file_name())
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let mut session_total = Total::default();
+            for (day, models) in ledger.days.range(since.to_string()..) {
+                for (model, tokens) in models {
+                    let price = prices.of(model);
+                    for total in [
+                        &mut r.total,
+                        &mut session_total,
+                        r.days.entry(day.clone()).or_default(),
+                        r.projects.entry(project.clone()).or_default(),
+                        r.models.entry(model.clone()).or_default(),
+                    ] {
+                        total.add(tokens, price);
+                    }
+                }
+            }
+            if session_total.tokens != Tokens::default() {
+                r.sessions.push((session, project, session_total));
+            }
+        }
+        r.sessions.sort_by(|a, b| b.2.usd.total_cmp(&a.2.usd));
+        r.sessions.truncate(top);
+        r
+    }
+
+    fn count(n: u64) -> String {
+        if n >= 1_000_000 { format!("{:.1}M", n as f64 / 1e6) } else { format_tokens(n) }
+    }
+
+    fn row(label: &str, t: &Total) -> String {
+        let unpriced = if t.unpriced > 0 { format!("  (+{} unpriced)", count(t.unpriced)) } else { String::new() };
+        format!(
+            "  {label:<28} {:>9}   in {:>6}  out {:>6}  cache r {:>6}  w {:>6}{unpriced}",
+            format!("${:.2}", t.usd),
+            count(t.tokens.input),
+            count(t.tokens.output),
+            count(t.tokens.cache_read),
+            count(t.tokens.cache_write),
+        )
+    }
+
+    pub fn table(r: &Report) -> String {
+        let mut out = vec![row(&format!("total since {}", r.since), &r.total)];
+        for (title, rows) in [("day (UTC)", &r.days), ("project", &r.projects), ("model", &r.models)] {
+            out.push(format!("\nby {title}"));
+            out.extend(rows.iter().map(|(k, t)| row(k, t)));
+        }
+        out.push("\ntop sessions".to_string());
+        out.extend(r.sessions.iter().map(|(s, p, t)| row(&format!("{} {}", &s[..s.len().min(8)], p), t)));
+        out.join("\n")
+    }
+
+    /// `claude_sessions cost`: bring every transcript under ~/.claude/projects
+    /// up to date in the ledger, then total the last `days` days.
+    pub fn run(raw: &RawConfig, days: u64, top: usize, json: bool) {
+        let Ok(home) = std::env::var("HOME") else { return };
+        let files: Vec<PathBuf> = fs::read_dir(PathBuf::from(home).join(".claude/projects"))
+            .into_iter()
+            .flatten()
+            .filter_map(|e| fs::read_dir(e.ok()?.path()).ok())
+            .flatten()
+            .filter_map(|e| Some(e.ok()?.path()))
+            .filter(|p| p.extension().is_some_and(|x| x == "jsonl"))
+            .collect();
+
+        let since = chrono::DateTime::from_timestamp(now_epoch() - days.saturating_sub(1) as i64 * 86400, 0)
+            .map(|d| d.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        let report = with_ledger(|l| {
+            // Forget transcripts that were deleted.
+            let before = l.files.len();
+            l.files.retain(|p, _| files.contains(p));
+            let mut changed = l.files.len() != before;
+            for f in &files {
+                changed |= l.files.entry(f.clone()).or_default().catch_up(f);
+            }
+            (changed, report(l.files.iter().map(|(p, f)| (p.as_path(), f)), &Prices::new(raw), &since, top))
+        });
+        save();
+
+        if json {
+            println!("{}", serde_json::to_string(&report).unwrap());
+        } else {
+            println!("{}", table(&report));
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn turn(id: &str, day: &str, model: &str, input: u64, output: u64, sidechain: bool) -> String {
+            serde_json::json!({
+                "type": "assistant",
+                "isSidechain": sidechain,
+                "timestamp": format!("{day}T10:00:00.000Z"),
+                "message": { "id": id, "model": model, "usage": {
+                    "input_tokens": input, "output_tokens": output,
+                    "cache_read_input_tokens": 1000, "cache_creation_input_tokens": 0,
+                }},
+            })
+            .to_string()
+                + "\n"
+        }
+
+        #[test]
+        fn a_turns_repeated_records_count_once_at_their_latest_reading() {
+            let mut l = FileLedger::default();
+            l.ingest(&(turn("m1", "2026-10-01", "claude-opus-4", 10, 5, false) + &turn("m1", "2026-10-01", "claude-opus-4", 10, 50, false)));
+            l.ingest(&turn("m2", "2026-10-02", "claude-opus-4", 20, 5, true));
+            l.ingest(&turn("m3", "2026-10-02", "claude-haiku-4", 30, 5, false));
+            assert_eq!(l.days["2026-10-01"]["opus-4"], Tokens { input: 10, output: 50, cache_read: 1000, cache_write: 0 });
+            assert_eq!(l.days["2026-10-02"]["opus-4"].input, 20);
+            // The subagent's turn is billed but isn't the session's context.
+            assert_eq!(l.context, [1060, 1035]);
+        }
+
+        /// Offsets only advance past whole lines, so a record half-written at
+        /// read time is read whole on the next catch-up.
+        #[test]
+        fn catching_up_reads_only_what_was_appended() {
+            let path = std::env::temp_dir().join(format!("claude-sessions-cost-{}.jsonl", std::process::id()));
+            let full = turn("m1", "2026-10-01", "claude-opus-4", 10, 5, false) + &turn("m2", "2026-10-01", "claude-opus-4", 10, 5, false);
+            let cut = full.len() - 20;
+            fs::write(&path, &full[..cut]).unwrap();
+            let mut l = FileLedger::default();
+            assert!(l.catch_up(&path));
+            assert_eq!(l.context.len(), 1);
+            fs::write(&path, &full).unwrap();
+            assert!(l.catch_up(&path));
+            assert!(!l.catch_up(&path));
+            assert_eq!(l.days["2026-10-01"]["opus-4"].input, 20);
+
+            // A user message moves the offset but counts nothing worth a save.
+            let user = serde_json::json!({ "type": "user", "message": { "content": "go on" } }).to_string() + "\n";
+            fs::write(&path, full.clone() + &user).unwrap();
+            assert!(!l.catch_up(&path));
+            assert_eq!(l.offset, (full + &user).len() as u64);
+            let _ = fs::remove_file(&path);
+        }
+
+        #[test]
+        fn totals_split_by_day_project_and_model_with_the_longest_price_key() {
+            let mut l = FileLedger::default();
+            l.ingest(&turn("m1", "2026-09-30", "claude-opus-4", 1_000_000, 0, false));
+            l.ingest(&turn("m2", "2026-10-01", "claude-sonnet-4", 1_000_000, 0, false));
+            l.ingest(&turn("m3", "2026-10-01", "mystery", 1_000_000, 0, false));
+            let raw: RawConfig =
+                toml::from_str("[prices.sonnet-4]\ninput = 2.0\noutput = 0.0\ncache_read = 0.0\ncache_write = 0.0\n").unwrap();
+            let path = PathBuf::from("/h/.claude/projects/-home-v-nix/abcdef12-0000.jsonl");
+            let r = report([(path.as_path(), &l)], &Prices::new(&raw), "2026-10-01", 10);
+            assert_eq!(r.days.keys().collect::<Vec<_>>(), ["2026-10-01"]);
+            assert_eq!(r.total.usd, 2.0);
+            assert_eq!(r.total.unpriced, 1_001_000);
+            assert_eq!(r.projects["-home-v-nix"].tokens.input, 2_000_000);
+            assert_eq!(r.sessions[0].0, "abcdef12-0000");
+        }
+    }
+}
+
+/// Append-only record of every window state flip, so "how long has this sat in
+/// Question" and "how often did it hit Limit today" have an answer at all — the
+/// cache only ever holds the previous poll. One JSON line per flip under
+/// `$XDG_STATE_HOME/claude-sessions/`; `claude_sessions history` folds it back
+/// into time-in-state.
+mod history {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::io::{BufRead as _, BufReader, Write as _};
+
+    /// Further apart than this, two polls don't bridge: the time between them
+    /// (bar hidden, machine off) is unknown, not time spent in the last state seen.
+    const MAX_GAP: i64 = 10 * 60;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct Record {
+        /// "session:window_index"
+        pub key: String,
+        /// path_to_project_name of the pane's cwd. Only opening records carry
+        /// it — a closed window's cwd is gone with it.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub project: Option<String>,
+        /// None: the window wasn't being watched before (new, or after a gap).
+        pub from: Option<ClaudeState>,
+        /// None: the window closed, or watching stopped (a gap).
+        pub to: Option<ClaudeState>,
+        pub at: i64,
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        let state_dir = std::env::var("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .or_else(|_| std::env::var("HOME").map(|h| PathBuf::from(h).join(".local/state")))
+            .ok()?;
+        Some(state_dir.join("claude-sessions/history.jsonl"))
+    }
+
+    /// Flips between the previous poll (`prev`, written at `prev_at`) and this one.
+    pub fn diff(prev: &HashMap<String, String>, prev_at: Option<i64>, windows: &[ClaudeWindow], now: i64) -> Vec<Record> {
+        let gap = prev_at.is_none_or(|t| now - t > MAX_GAP);
+        let prev: BTreeMap<&String, Option<ClaudeState>> = prev.iter().map(|(k, s)| (k, ClaudeState::parse(s))).collect();
+        let mut out = Vec::new();
+
+        // Whatever was open when watching stopped ends where watching stopped.
+        // Without a prev_at (a cache from before history existed) there's no
+        // telling when that was, so those spans are dropped, not invented.
+        if let (true, Some(prev_at)) = (gap, prev_at) {
+            for (key, state) in &prev {
+                out.push(Record { key: (*key).clone(), project: None, from: *state, to: None, at: prev_at });
+            }
+        }
+        let live = if gap { BTreeMap::new() } else { prev };
+
+        // Split windows list one pane each; the last pane wins, same as current_state_map.
+        let next: BTreeMap<String, &ClaudeWindow> =
+            windows.iter().map(|w| (w.key(), w)).collect();
+        for (key, w) in &next {
+            let from = live.get(key).copied().flatten();
+            if from != Some(w.state) {
+                out.push(Record {
+                    key: key.clone(),
+                    project: Some(path_to_project_name(&w.cwd)),
+                    from,
+                    to: Some(w.state),
+                    at: now,
+                });
+            }
+        }
+        for (key, state) in live.iter().filter(|(k, _)| !next.contains_key(**k)) {
+            out.push(Record { key: (*key).clone(), project: None, from: *state, to: None, at: now });
+        }
+        out
+    }
+
+    pub fn append(records: &[Record]) {
+        if records.is_empty() {
+            return;
+        }
+        let Some(p) = path() else { return };
+        if let Some(parent) = p.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let Ok(mut file) = fs::OpenOptions::new().create(true).append(true).open(&p) else { return };
+        // One write per poll, so a batch lands whole even for a reader
+        // tailing the file; pollers take turns in `persist_poll`.
+        let batch: String = records.iter().filter_map(|r| serde_json::to_string(r).ok()).map(|l| l + "\n").collect();
+        let _ = file.write_all(batch.as_bytes());
+    }
+
+    pub fn load() -> Vec<Record> {
+        let Some(file) = path().and_then(|p| fs::File::open(p).ok()) else { return Vec::new() };
+        BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|l| serde_json::from_str(&l).ok())
+            .collect()
+    }
+
+    /// One uninterrupted stretch of a window in one state.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Span {
+        pub key: String,
+        pub project: Option<String>,
+        pub state: ClaudeState,
+        pub start: i64,
+        pub end: i64,
+        /// Still in this state as of the last poll.
+        pub open: bool,
+    }
+
+    pub fn spans(records: &[Record], now: i64) -> Vec<Span> {
+        let mut records: Vec<&Record> = records.iter().collect();
+        records.sort_by_key(|r| r.at); // stable: same-poll records keep their order
+
+        let mut open: BTreeMap<&str, Span> = BTreeMap::new();
+        // A window's project outlives its spans: a flip record carries it, and
+        // the closing record after it doesn't.
+        let mut projects: HashMap<&str, &str> = HashMap::new();
+        let mut out = Vec::new();
+        for r in records {
+            if let Some(p) = &r.project {
+                projects.insert(&r.key, p);
+            }
+            if let Some(mut span) = open.remove(r.key.as_str()) {
+                span.end = r.at;
+                span.open = false;
+                out.push(span);
+            }
+            if let Some(state) = r.to {
+                open.insert(
+                    &r.key,
+                    Span {
+                        key: r.key.clone(),
+                        project: projects.get(r.key.as_str()).map(|p| p.to_string()),
+                        state,
+                        start: r.at,
+                        end: now,
+                        open: true,
+                    },
+                );
+            }
+        }
+        out.extend(open.into_values());
+        out
+    }
+
+    /// Seconds per state bucket, the columns `history` reports.
+    #[derive(Debug, Default, Clone, PartialEq, Serialize)]
+    pub struct Totals {
+        pub active: i64,
+        pub finished: i64,
+        pub question: i64,
+        pub stuck: i64,
+        /// Times the window went INTO Limit, not time spent there.
+        pub limits: u32,
+    }
+
+    impl Totals {
+        fn add(&mut self, span: &Span, secs: i64, entered: bool) {
+            match span.state {
+                ClaudeState::Active | ClaudeState::Planning => self.active += secs,
+                ClaudeState::Finished | ClaudeState::Done => self.finished += secs,
+                ClaudeState::Question => self.question += secs,
+                ClaudeState::Stuck => self.stuck += secs,
+                ClaudeState::Limit if entered => self.limits += 1,
+                _ => {}
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    pub struct Wait {
+        pub key: String,
+        pub project: Option<String>,
+        pub state: ClaudeState,
+        pub start: i64,
+        pub secs: i64,
+        pub ongoing: bool,
+    }
+
+    #[derive(Debug, Default, Serialize)]
+    pub struct Report {
+        pub sessions: BTreeMap<String, Totals>,
+        pub projects: BTreeMap<String, Totals>,
+        /// Longest stretches blocked on me, longest first.
+        pub waits: Vec<Wait>,
+    }
+
+    /// States in which nothing moves until a human looks.
+    fn is_unattended(state: ClaudeState) -> bool {
+        matches!(state, ClaudeState::Question | ClaudeState::Stuck | ClaudeState::Ongoing)
+    }
+
+    /// Fold spans into the report, clipped to [since, now].
+    pub fn report(spans: &[Span], since: i64, now: i64, top: usize) -> Report {
+        let mut report = Report::default();
+        for span in spans {
+            let (start, end) = (span.start.max(since), span.end.min(now));
+            if end <= start && span.start < since {
+                continue;
+            }
+            let secs = (end - start).max(0);
+            let entered = span.start >= since;
+            report.sessions.entry(span.key.clone()).or_default().add(span, secs, entered);
+            let project = span.project.clone().unwrap_or_else(|| "?".to_string());
+            report.projects.entry(project).or_default().add(span, secs, entered);
+            if is_unattended(span.state) && secs > 0 {
+                report.waits.push(Wait {
+                    key: span.key.clone(),
+                    project: span.project.clone(),
+                    state: span.state,
+                    start: span.start,
+                    secs,
+                    ongoing: span.open,
+                });
+            }
+        }
+        report.waits.sort_by_key(|w| std::cmp::Reverse(w.secs));
+        report.waits.truncate(top);
+        report
+    }
+
+    fn format_secs(secs: i64) -> String {
+        let (hours, mins) = (secs / 3600, (secs % 3600) / 60);
+        if hours > 0 { format!("{hours}h{mins:02}m") } else { format!("{mins}m") }
+    }
+
+    fn table(title: &str, rows: &BTreeMap<String, Totals>) -> String {
+        let width = rows.keys().map(|k| k.len()).chain([title.len()]).max().unwrap_or(0);
+        let mut out = format!(
+            "{:width$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>6}\n",
+            title, "active", "finished", "question", "stuck", "limits"
+        );
+        for (name, t) in rows {
+            out += &format!(
+                "{:width$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>6}\n",
+                name,
+                format_secs(t.active),
+                format_secs(t.finished),
+                format_secs(t.question),
+                format_secs(t.stuck),
+                t.limits
+            );
+        }
+        out
+    }
+
+    pub fn run(hours: u64, top: usize, json: bool) {
+        let now = now_epoch();
+        // Nothing has polled for a while, so open spans end at the last poll —
+        // the same rule diff applies once polling resumes.
+        let until = match load_cache().polled_at {
+            Some(t) if now - t > MAX_GAP => t,
+            _ => now,
+        };
+        let report = report(&spans(&load(), until), now - hours as i64 * 3600, until, top);
+        if json {
+            println!("{}", serde_json::to_string(&report).unwrap());
+            return;
+        }
+        if report.sessions.is_empty() {
+            println!("no state history in the last {hours}h");
+            return;
+        }
+        println!("{}", table("session", &report.sessions));
+        println!("{}", table("project", &report.projects));
+        println!("{}", "longest unattended waits".bold());
+        for w in &report.waits {
+            let tail = if w.ongoing && until == now { "still waiting".to_string() } else { format!("from {} ago", format_secs(now - w.start)) };
+            println!("  {:8}  {:>8}  {}  ({tail})", w.state.as_str(), format_secs(w.secs), w.key);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn window(key: &str, state: ClaudeState) -> ClaudeWindow {
+            let (session, index) = key.split_once(':').unwrap();
+            ClaudeWindow {
+                host: None,
+                session: session.to_string(),
+                window_index: index.parse().unwrap(),
+                state,
+                claude_running: true,
+                active_todo: None,
+                draft_content: None,
+                question_content: None,
+                summary: None,
+                model: None,
+                context: None,
+                growth: Vec::new(),
+                cwd: PathBuf::from("/home/v/nix"),
+                resume: None,
+                session_id: None,
+            }
+        }
+
+        fn prev(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+            pairs.iter().map(|(k, s)| (k.to_string(), s.to_string())).collect()
+        }
+
+        #[test]
+        fn a_poll_records_flips_openings_and_closings() {
+            let got = diff(
+                &prev(&[("a:1", "active"), ("b:1", "finished"), ("c:1", "active")]),
+                Some(100),
+                &[window("a:1", ClaudeState::Question), window("b:1", ClaudeState::Finished), window("d:1", ClaudeState::Empty)],
+                160,
+            );
+            let summary: Vec<_> = got.iter().map(|r| (r.key.as_str(), r.from, r.to, r.at)).collect();
+            assert_eq!(
+                summary,
+                vec![
+                    ("a:1", Some(ClaudeState::Active), Some(ClaudeState::Question), 160),
+                    ("d:1", None, Some(ClaudeState::Empty), 160),
+                    ("c:1", Some(ClaudeState::Active), None, 160),
+                ]
+            );
+            assert_eq!(got[0].project.as_deref(), Some("-home-v-nix"));
+        }
+
+        /// Hours with nothing polling are unknown time: the old spans end at
+        /// the last poll and every window reopens at this one.
+        #[test]
+        fn a_polling_gap_is_not_time_in_state() {
+            let got = diff(&prev(&[("a:1", "question")]), Some(100), &[window("a:1", ClaudeState::Question)], 100 + 3 * 3600);
+            let summary: Vec<_> = got.iter().map(|r| (r.from, r.to, r.at)).collect();
+            assert_eq!(
+                summary,
+                vec![(Some(ClaudeState::Question), None, 100), (None, Some(ClaudeState::Question), 100 + 3 * 3600)]
+            );
+        }
+
+        #[test]
+        fn spans_fold_into_time_in_state_and_longest_waits() {
+            let rec = |key: &str, to: Option<ClaudeState>, at| Record {
+                key: key.to_string(),
+                project: to.map(|_| "-p".to_string()),
+                from: None,
+                to,
+                at,
+            };
+            let records = vec![
+                rec("a:1", Some(ClaudeState::Active), 0),
+                rec("a:1", Some(ClaudeState::Question), 600),
+                rec("a:1", Some(ClaudeState::Limit), 4200),
+                rec("b:1", Some(ClaudeState::Stuck), 1000),
+                rec("b:1", None, 1600),
+            ];
+            let report = report(&spans(&records, 5000), 0, 5000, 10);
+            let a = &report.sessions["a:1"];
+            assert_eq!((a.active, a.question, a.limits), (600, 3600, 1));
+            assert_eq!(report.projects["-p"].stuck, 600);
+            let waits: Vec<_> = report.waits.iter().map(|w| (w.key.as_str(), w.secs, w.ongoing)).collect();
+            assert_eq!(waits, vec![("a:1", 3600, false), ("b:1", 600, false)]);
+        }
+    }
+}
+
+/// Desktop notifications on the transitions worth looking up for — a window
+/// arriving in Question, Limit, Error, Stuck or Finished. Fired by the daemon,
+/// the one process that sees every tick. On when the config has a `[notify]`
+/// section:
+///
+/// ```toml
+/// [notify]
+/// states = ["question", "limit", "error", "stuck", "finished"]
+/// command = "~/bin/on-claude"   # optional: gets the notice as JSON on stdin, instead of notify-send
+/// cooldown_secs = 600           # same window, same state: at most once per this
+/// quiet_hours = "23:00-08:00"   # local time; notices due inside are dropped
+///
+/// [notify.hold_secs]            # how long a state must stick before it counts
+/// finished = 20
+/// ```
+///
+/// A window that's the active window of an attached tmux client is being
+/// looked at already, so it isn't notified about.
+mod notify {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn default_states() -> Vec<ClaudeState> {
+        vec![ClaudeState::Question, ClaudeState::Limit, ClaudeState::Error, ClaudeState::Stuck, ClaudeState::Finished]
+    }
+
+    /// Finished flickers between tool calls when no spinner is captured; a
+    /// few ticks' hold keeps that from buzzing.
+    fn default_hold() -> BTreeMap<String, u64> {
+        BTreeMap::from([("finished".to_string(), 20)])
+    }
+
+    #[derive(Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct RawConfig {
+        states: Vec<ClaudeState>,
+        command: Option<String>,
+        cooldown_secs: i64,
+        quiet_hours: Option<String>,
+        hold_secs: BTreeMap<String, u64>,
+    }
+
+    impl Default for RawConfig {
+        fn default() -> Self {
+            RawConfig { states: default_states(), command: None, cooldown_secs: 600, quiet_hours: None, hold_secs: default_hold() }
+        }
+    }
+
+    /// What a hook gets on stdin.
+    #[derive(Serialize, Debug, PartialEq)]
+    pub struct Notice {
+        pub key: String,
+        pub from: Option<ClaudeState>,
+        pub to: ClaudeState,
+        pub project: Option<String>,
+        pub summary: Option<String>,
+        pub question: Option<String>,
+        pub at: i64,
+    }
+
+    pub struct Notifier {
+        states: Vec<ClaudeState>,
+        command: Option<String>,
+        cooldown: i64,
+        /// Minutes after local midnight, [start, end); may wrap past midnight.
+        quiet: Option<(u32, u32)>,
+        hold: HashMap<ClaudeState, i64>,
+        /// Windows that arrived in a notified state and haven't held it long enough yet.
+        pending: HashMap<String, (Option<ClaudeState>, ClaudeState, i64)>,
+        sent: HashMap<(String, ClaudeState), i64>,
+    }
+
+    fn minutes(hhmm: &str) -> Option<u32> {
+        let (h, m) = hhmm.trim().split_once(':')?;
+        let (h, m): (u32, u32) = (h.parse().ok()?, m.parse().ok()?);
+        (h < 24 && m < 60).then_some(h * 60 + m)
+    }
+
+    impl Notifier {
+        pub fn new(raw: &RawConfig) -> Result<Notifier, String> {
+            let quiet = match &raw.quiet_hours {
+                None => None,
+                Some(q) => Some(
+                    q.split_once('-')
+                        .and_then(|(a, b)| Some((minutes(a)?, minutes(b)?)))
+                        .ok_or_else(|| format!("[notify] quiet_hours {q:?} is not HH:MM-HH:MM"))?,
+                ),
+            };
+            let hold = raw
+                .hold_secs
+                .iter()
+                .map(|(state, secs)| {
+                    ClaudeState::parse(state)
+                        .map(|s| (s, *secs as i64))
+                        .ok_or_else(|| format!("[notify.hold_secs] unknown state {state:?}"))
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(Notifier {
+                states: raw.states.clone(),
+                command: raw.command.clone(),
+                cooldown: raw.cooldown_secs,
+                quiet,
+                hold,
+                pending: HashMap::new(),
+                sent: HashMap::new(),
+            })
+        }
+
+        fn in_quiet_hours(&self, minute: impl FnOnce() -> Option<u32>) -> bool {
+            let Some((start, end)) = self.quiet else { return false };
+            let Some(now) = minute() else { return false };
+            if start <= end { (start..end).contains(&now) } else { now >= start || now < end }
+        }
+
+        /// Feed one tick's transitions; returns the (key, from, to) now due.
+        /// `minute` (local time) and `focused` (windows on screen in a tmux
+        /// client) are only asked for when something is due — each is a fork.
+        pub fn tick(
+            &mut self,
+            transitions: &[daemon::Transition],
+            now: i64,
+            minute: impl FnOnce() -> Option<u32>,
+            focused: impl FnOnce() -> HashSet<String>,
+        ) -> Vec<(String, Option<ClaudeState>, ClaudeState)> {
+            for t in transitions {
+                match t.to {
+                    Some(to) if self.states.contains(&to) => {
+                        self.pending.insert(t.key.clone(), (t.from, to, t.at));
+                    }
+                    // Moved on before its hold ran out: it never settled there.
+                    _ => {
+                        self.pending.remove(&t.key);
+                    }
+                }
+            }
+
+            let matured: Vec<String> = self
+                .pending
+                .iter()
+                .filter(|(_, (_, to, since))| now - since >= self.hold.get(to).copied().unwrap_or(0))
+                .map(|(key, _)| key.clone())
+                .collect();
+            if matured.is_empty() {
+                return Vec::new();
+            }
+            let mut due: Vec<_> = matured
+                .into_iter()
+                .filter_map(|key| {
+                    let (from, to, _) = self.pending.remove(&key)?;
+                    let last = self.sent.get(&(key.clone(), to));
+                    last.is_none_or(|at| now - at >= self.cooldown).then_some((key, from, to))
+                })
+                .collect();
+            if due.is_empty() || self.in_quiet_hours(minute) {
+                return Vec::new();
+            }
+            let focused = focused();
+            due.retain(|(key, _, _)| !focused.contains(key));
+            due.sort();
+            for (key, _, to) in &due {
+                self.sent.insert((key.clone(), *to), now);
+            }
+            due
+        }
+
+        pub fn send(&self, notice: &Notice) {
+            let mut cmd = match &self.command {
+                Some(hook) => {
+                    let mut cmd = Command::new("sh");
+                    cmd.arg("-c").arg(hook);
+                    cmd
+                }
+                None => {
+                    let urgency = match notice.to {
+                        ClaudeState::Question | ClaudeState::Stuck => "critical",
+                        _ => "normal",
+                    };
+                    let body = notice.question.as_deref().or(notice.summary.as_deref()).unwrap_or_default();
+                    let mut cmd = Command::new("notify-send");
+                    cmd.args(["-a", "claude-sessions", "-u", urgency])
+                        .arg(format!("{} — {}", notice.key, notice.to.as_str()))
+                        .arg(body);
+                    cmd
+                }
+            };
+            let payload = self.command.is_some().then(|| serde_json::to_string(notice).unwrap_or_default());
+            // Off the poll thread: a slow hook must not stall the table.
+            std::thread::spawn(move || {
+                use std::io::Write as _;
+                use std::process::Stdio;
+                let Ok(mut child) = cmd.stdin(Stdio::piped()).stdout(Stdio::null()).spawn() else { return };
+                if let (Some(payload), Some(mut stdin)) = (payload, child.stdin.take()) {
+                    let _ = stdin.write_all(payload.as_bytes());
+                }
+                let _ = child.wait();
+            });
+        }
+    }
+
+    /// Local minutes after midnight. chrono here has no timezone database;
+    /// `date` has the system's.
+    pub fn local_minute() -> Option<u32> {
+        let out = Command::new("date").arg("+%H:%M").output().ok()?;
+        minutes(&String::from_utf8_lossy(&out.stdout))
+    }
+
+    /// Every window some attached client is showing, as local window keys.
+    pub fn focused_windows() -> HashSet<String> {
+        Host::Local
+            .tmux(&["list-clients", "-F", "#{session_name}:#{window_index}"])
+            .output()
+            .map(|o| String::from_utf8_lossy(&o.stdout).lines().map(String::from).collect())
+            .unwrap_or_default()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn t(key: &str, to: ClaudeState, at: i64) -> daemon::Transition {
+            daemon::Transition { key: key.into(), from: Some(ClaudeState::Active), to: Some(to), at }
+        }
+
+        fn notifier(toml_src: &str) -> Notifier {
+            Notifier::new(&toml::from_str(toml_src).unwrap()).unwrap()
+        }
+
+        #[test]
+        fn a_state_notifies_once_it_holds_and_not_again_inside_the_cooldown() {
+            let mut n = notifier("cooldown_secs = 100\n[hold_secs]\nfinished = 20\n");
+            let quiet = || None;
+            let none = HashSet::new;
+            assert_eq!(n.tick(&[t("a:1", ClaudeState::Question, 0)], 0, quiet, none).len(), 1);
+            // Finished flickers back to Active within its hold: nothing.
+            assert!(n.tick(&[t("b:1", ClaudeState::Finished, 0)], 0, quiet, none).is_empty());
+            let back = daemon::Transition { key: "b:1".into(), from: Some(ClaudeState::Finished), to: Some(ClaudeState::Active), at: 10 };
+            assert!(n.tick(&[back], 10, quiet, none).is_empty());
+            assert!(n.tick(&[t("b:1", ClaudeState::Finished, 30)], 30, quiet, none).is_empty());
+            assert_eq!(n.tick(&[], 50, quiet, none), [("b:1".to_string(), Some(ClaudeState::Active), ClaudeState::Finished)]);
+            // Same window back in Question inside the cooldown: suppressed.
+            assert!(n.tick(&[t("a:1", ClaudeState::Question, 60)], 60, quiet, none).is_empty());
+            assert_eq!(n.tick(&[t("a:1", ClaudeState::Question, 200)], 200, quiet, none).len(), 1);
+            // Active isn't a notified state.
+            assert!(n.tick(&[t("c:1", ClaudeState::Active, 300)], 300, quiet, none).is_empty());
+        }
+
+        #[test]
+        fn quiet_hours_and_the_focused_window_drop_the_notice() {
+            let mut n = notifier("quiet_hours = \"23:00-08:00\"\n");
+            assert!(n.tick(&[t("a:1", ClaudeState::Question, 0)], 0, || Some(23 * 60 + 30), HashSet::new).is_empty());
+            assert!(n.tick(&[t("a:2", ClaudeState::Question, 0)], 0, || Some(7 * 60), HashSet::new).is_empty());
+            assert_eq!(n.tick(&[t("a:3", ClaudeState::Question, 0)], 0, || Some(9 * 60), HashSet::new).len(), 1);
+            let focused = || HashSet::from(["a:4".to_string()]);
+            assert!(n.tick(&[t("a:4", ClaudeState::Question, 0)], 0, || Some(9 * 60), focused).is_empty());
+            assert!(Notifier::new(&toml::from_str("quiet_hours = \"late\"").unwrap()).is_err());
+        }
+    }
+}
+
+/// `--daemon`: one resident poller instead of a fresh process per eww tick and
+/// per supervise_sessions read. Each tick is still one batched tmux capture, but
+/// transcripts are only re-read once they've grown (TRANSCRIPT_MEMO), and the
+/// resulting table is served over a Unix socket.
+///
+/// Protocol — one request line per connection:
+///   `snapshot`  — answered with one JSON line (`Snapshot`), then closed
+///   `subscribe` — held open; every state flip is pushed as a JSON `Transition` line
+mod daemon {
+    use super::*;
+    use std::io::{BufRead as _, BufReader, Write as _};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Snapshot {
+        pub entries: Vec<SessionEntry>,
+        pub usage: UsageInfo,
+        #[serde(default)]
+        pub forecast: forecast::Forecast,
+    }
+
+    /// One state flip of one window. `from` is None for a window that just
+    /// appeared, `to` None for one that closed.
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Transition {
+        /// "session:window_index"
+        pub key: String,
+        pub from: Option<ClaudeState>,
+        pub to: Option<ClaudeState>,
+        /// Unix epoch seconds of the tick that saw it.
+        pub at: i64,
+    }
+
+    /// What the listener shares with the poll loop.
+    #[derive(Default)]
+    pub struct Shared {
+        /// Serialized `Snapshot` line; None until the first tick lands, and a
+        /// `snapshot` request before then gets a bare close (the client polls itself).
+        pub snapshot: Mutex<Option<String>>,
+        pub subscribers: Mutex<Vec<UnixStream>>,
+    }
+
+    /// A client gives up on a wedged daemon after this and polls by itself.
+    const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+
+    pub fn default_socket() -> PathBuf {
+        std::env::var("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir())
+            .join("claude-sessions.sock")
+    }
+
+    pub fn state_map(windows: &[ClaudeWindow]) -> HashMap<String, ClaudeState> {
+        windows.iter().map(|w| (w.key(), w.state)).collect()
+    }
+
+    /// Every key whose state differs between two ticks, in key order.
+    pub fn transitions(prev: &HashMap<String, ClaudeState>, next: &HashMap<String, ClaudeState>, at: i64) -> Vec<Transition> {
+        let keys: std::collections::BTreeSet<&String> = prev.keys().chain(next.keys()).collect();
+        keys.into_iter()
+            .filter_map(|key| {
+                let (from, to) = (prev.get(key).copied(), next.get(key).copied());
+                (from != to).then(|| Transition { key: key.clone(), from, to, at })
+            })
+            .collect()
+    }
+
+    pub fn serve(
+        socket: &Path,
+        interval: Duration,
+        hosts: &[Host],
+        mut notifier: Option<notify::Notifier>,
+        auto_revive: bool,
+    ) -> Result<(), String> {
+        if UnixStream::connect(socket).is_ok() {
+            return Err(format!("a daemon is already serving {}", socket.display()));
+        }
+        // Nothing answered, so whatever sits at the path is a dead daemon's leftover.
+        let _ = fs::remove_file(socket);
+        let listener = UnixListener::bind(socket).map_err(|e| format!("bind {}: {e}", socket.display()))?;
+
+        let shared = Arc::new(Shared::default());
+        spawn_listener(listener, Arc::clone(&shared));
+
+        let mut prev: Option<HashMap<String, ClaudeState>> = None;
+        loop {
+            let windows = poll_hosts(hosts);
+            let (usage, forecast) = persist_poll(&windows);
+            let snapshot = Snapshot { entries: session_entries(&windows), usage, forecast };
+            *shared.snapshot.lock().unwrap() = serde_json::to_string(&snapshot).ok();
+
+            let next = state_map(&windows);
+            let now = now_epoch();
+            // The first tick has nothing to diff against — reporting every window
+            // as freshly appeared would fire each subscriber's handlers on restart.
+            let flips = prev.as_ref().map(|prev| transitions(prev, &next, now)).unwrap_or_default();
+            for t in &flips {
+                broadcast(&shared, t);
+            }
+            prev = Some(next);
+
+            if let Some(notifier) = &mut notifier {
+                for (key, from, to) in notifier.tick(&flips, now, notify::local_minute, notify::focused_windows) {
+                    let w = windows.iter().find(|w| w.key() == key);
+                    notifier.send(&notify::Notice {
+                        key,
+                        from,
+                        to,
+                        project: w.map(|w| path_to_project_name(&w.cwd)),
+                        summary: w.and_then(|w| w.summary.clone()),
+                        question: w.and_then(|w| w.question_content.clone()),
+                        at: now,
+                    });
+                }
+            }
+
+            if auto_revive {
+                for w in windows.iter().filter(|w| w.resume.is_some()) {
+                    let Some(host) = hosts.iter().find(|h| h.label() == w.host) else { continue };
+                    match revive::revive(host, w, true, now) {
+                        Ok(revive::Outcome::Revived(done)) => eprintln!("{done}"),
+                        Ok(revive::Outcome::Skipped(_)) => {}
+                        Err(e) => eprintln!("{}", format!("revive {}: {e}", w.key()).yellow()),
+                    }
+                }
+            }
+
+            std::thread::sleep(interval);
+        }
+    }
+
+    pub fn spawn_listener(listener: UnixListener, shared: Arc<Shared>) {
+        std::thread::spawn(move || {
+            for stream in listener.incoming().filter_map(Result::ok) {
+                let shared = Arc::clone(&shared);
+                // Per connection, so a client that never sends its request line
+                // can't stall everyone behind it.
+                std::thread::spawn(move || handle(stream, &shared));
+            }
+        });
+    }
+
+    fn handle(stream: UnixStream, shared: &Shared) {
+        let _ = stream.set_read_timeout(Some(CLIENT_TIMEOUT));
+        // A subscriber that stops reading fills its socket buffer; without this
+        // the next broadcast blocks the poll loop, and every snapshot with it.
+        let _ = stream.set_write_timeout(Some(CLIENT_TIMEOUT));
+        let mut request = String::new();
+        let Ok(reader) = stream.try_clone() else { return };
+        if BufReader::new(reader).read_line(&mut request).is_err() {
+            return;
+        }
+        let mut stream = stream;
+        match request.trim() {
+            "snapshot" => {
+                if let Some(line) = shared.snapshot.lock().unwrap().as_deref() {
+                    let _ = writeln!(stream, "{line}");
+                }
+            }
+            "subscribe" => shared.subscribers.lock().unwrap().push(stream),
+            other => {
+                let _ = writeln!(stream, "{}", serde_json::json!({ "error": format!("unknown request {other:?}") }));
+            }
+        }
+    }
+
+    /// Subscribers that hung up, or stopped reading for CLIENT_TIMEOUT, are
+    /// dropped on the first write that fails.
+    pub fn broadcast(shared: &Shared, t: &Transition) {
+        let Ok(line) = serde_json::to_string(t) else { return };
+        shared.subscribers.lock().unwrap().retain_mut(|s| writeln!(s, "{line}").is_ok());
+    }
+
+    /// None on anything short of a full answer — no daemon, a wedged one, or one
+    /// that hasn't finished its first tick; the caller then polls by itself.
+    pub fn query(socket: &Path) -> Option<Snapshot> {
+        let mut stream = UnixStream::connect(socket).ok()?;
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT)).ok()?;
+        writeln!(stream, "snapshot").ok()?;
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).ok()?;
+        serde_json::from_str(&line).ok()
+    }
+
+    /// Copy the transition stream to stdout until the daemon goes away.
+    pub fn follow(socket: &Path) -> Result<(), String> {
+        let mut stream = UnixStream::connect(socket).map_err(|e| format!("no daemon at {}: {e}", socket.display()))?;
+        writeln!(stream, "subscribe").map_err(|e| e.to_string())?;
+        let mut stdout = std::io::stdout();
+        for line in BufReader::new(stream).lines() {
+            let line = line.map_err(|e| e.to_string())?;
+            // eww's deflisten reads line by line; an unflushed pipe would hold
+            // transitions back until the buffer fills.
+            writeln!(stdout, "{line}").and_then(|_| stdout.flush()).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn map(pairs: &[(&str, ClaudeState)]) -> HashMap<String, ClaudeState> {
+            pairs.iter().map(|(k, s)| (k.to_string(), *s)).collect()
+        }
+
+        #[test]
+        fn only_flipped_appeared_and_vanished_windows_transition() {
+            let prev = map(&[("a:1", ClaudeState::Active), ("b:1", ClaudeState::Finished), ("c:1", ClaudeState::Active)]);
+            let next = map(&[("a:1", ClaudeState::Question), ("b:1", ClaudeState::Finished), ("d:1", ClaudeState::Empty)]);
+            let got = transitions(&prev, &next, 7);
+            assert_eq!(
+                got,
+                vec![
+                    Transition { key: "a:1".into(), from: Some(ClaudeState::Active), to: Some(ClaudeState::Question), at: 7 },
+                    Transition { key: "c:1".into(), from: Some(ClaudeState::Active), to: None, at: 7 },
+                    Transition { key: "d:1".into(), from: None, to: Some(ClaudeState::Empty), at: 7 },
+                ]
+            );
+        }
+
+        #[test]
+        fn snapshot_and_subscription_round_trip_over_the_socket() {
+            let socket = std::env::temp_dir().join(format!("claude-sessions-test-{}.sock", std::process::id()));
+            let _ = fs::remove_file(&socket);
+            let shared = Arc::new(Shared::default());
+            spawn_listener(UnixListener::bind(&socket).unwrap(), Arc::clone(&shared));
+
+            // Before the first tick there is nothing to serve.
+            assert!(query(&socket).is_none());
+
+            let snapshot = Snapshot { entries: Vec::new(), usage: UsageInfo { five_hour_used_pct: Some(12.0), ..Default::default() },
+                forecast: Default::default(),
+            };
+            *shared.snapshot.lock().unwrap() = serde_json::to_string(&snapshot).ok();
+            assert_eq!(query(&socket).unwrap().usage.five_hour_used_pct, Some(12.0));
+
+            let mut sub = UnixStream::connect(&socket).unwrap();
+            writeln!(sub, "subscribe").unwrap();
+            while shared.subscribers.lock().unwrap().is_empty() {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            let t = Transition { key: "a:1".into(), from: Some(ClaudeState::Active), to: Some(ClaudeState::Finished), at: 1 };
+            broadcast(&shared, &t);
+            let mut line = String::new();
+            BufReader::new(sub).read_line(&mut line).unwrap();
+            assert_eq!(serde_json::from_str::<Transition>(&line).unwrap(), t);
+
+            let _ = fs::remove_file(&socket);
+        }
+
+        #[test]
+        fn a_subscriber_that_stops_reading_is_dropped_not_waited_on() {
+            let socket = std::env::temp_dir().join(format!("claude-sessions-stall-{}.sock", std::process::id()));
+            let _ = fs::remove_file(&socket);
+            let shared = Arc::new(Shared::default());
+            spawn_listener(UnixListener::bind(&socket).unwrap(), Arc::clone(&shared));
+
+            let mut stalled = UnixStream::connect(&socket).unwrap();
+            writeln!(stalled, "subscribe").unwrap();
+            while shared.subscribers.lock().unwrap().is_empty() {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            // Enough to fill any socket buffer, never read.
+            let t = Transition { key: "a:1".into(), from: Some(ClaudeState::Active), to: Some(ClaudeState::Finished), at: 1 };
+            for _ in 0..100_000 {
+                broadcast(&shared, &t);
+                if shared.subscribers.lock().unwrap().is_empty() {
+                    break;
+                }
+            }
+            assert!(shared.subscribers.lock().unwrap().is_empty());
+
+            drop(stalled);
+            let _ = fs::remove_file(&socket);
+        }
+    }
+}
+
+/// `answer` / `send`: unblock a session without attaching to it — from the eww
+/// bar, or a phone over ssh. Both re-capture the pane and re-classify it right
+/// before typing anything: the table the caller acted on may be seconds old,
+/// and keystrokes meant for a selector land as prompt text (or vice versa) if
+/// the pane moved on in between.
+mod act {
+    use super::*;
+
+    /// `[host/]session:window`, where host is spelled the way `--host` takes it.
+    /// Split at the last `/`, so a socket path keeps its own.
+    pub struct Target {
+        pub host: Host,
+        /// `session:window`, as tmux's `-t` takes it.
+        pub pane: String,
+    }
+
+    impl Target {
+        pub fn parse(spec: &str, ssh: &str) -> Result<Target, String> {
+            let (host, pane) = match spec.rsplit_once('/') {
+                Some((host, pane)) => (Host::parse(host, ssh), pane),
+                None => (Host::Local, spec),
+            };
+            match pane.rsplit_once(':') {
+                Some((session, index)) if !session.is_empty() && index.parse::<u32>().is_ok() => {
+                    Ok(Target { host, pane: pane.to_string() })
+                }
+                _ => Err(format!("{spec:?} is not a [host/]session:window target")),
+            }
+        }
+
+        /// What the pane reads as this instant — pane text only, the same
+        /// reading the transcript refinement starts from.
+        pub fn capture(&self) -> Result<(ActivityResult, PaneCapture), String> {
+            let mut caps = capture_panes(&self.host, std::slice::from_ref(&self.pane));
+            let caps = caps.remove(&self.pane).ok_or_else(|| format!("{}: no such window", self.pane))?;
+            Ok((determine_claude_activity(&caps), caps))
+        }
+
+        /// Several tmux commands as one `;` chain — one round trip, and nothing
+        /// else can reach the pane between them.
+        pub fn run(&self, commands: &[&[&str]]) -> Result<(), String> {
+            let args: Vec<&str> = commands.join(&";");
+            let out = self.host.tmux(&args).output().map_err(|e| format!("tmux: {e}"))?;
+            if out.status.success() {
+                Ok(())
+            } else {
+                Err(format!("tmux: {}", String::from_utf8_lossy(&out.stderr).trim()))
+            }
+        }
+    }
+
+    /// The numbered options of the selector on screen: the block of `N. label`
+    /// lines around the `❯` cursor. Numbered lists further up — Claude's own
+    /// prose — are cut off by the question line between them and the block.
+    pub fn options(content: &str) -> Vec<(u32, String)> {
+        let option = Regex::new(r"^\s*(?:❯\s*)?(\d+)\.\s+(.+?)\s*$").unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        let Some(cursor) = lines.iter().rposition(|l| l.trim_start().starts_with('❯') && option.is_match(l)) else {
+            return Vec::new();
+        };
+        // Blank lines and deeper-indented description rows sit inside a block.
+        let in_block = |l: &str| option.is_match(l) || l.trim().is_empty() || l.starts_with("      ");
+        let above = lines[..cursor].iter().rev().take_while(|l| in_block(l)).count();
+        let below = lines[cursor..].iter().take_while(|l| in_block(l)).count();
+        lines[cursor - above..cursor + below]
+            .iter()
+            .filter_map(|l| option.captures(l))
+            .filter_map(|c| Some((c[1].parse().ok()?, c[2].to_string())))
+            .collect()
+    }
+
+    /// An option by number, or by a case-insensitive piece of its label that
+    /// names exactly one of them.
+    pub fn pick(options: &[(u32, String)], choice: &str) -> Result<u32, String> {
+        if let Ok(n) = choice.parse::<u32>() {
+            return options
+                .iter()
+                .any(|(i, _)| *i == n)
+                .then_some(n)
+                .ok_or_else(|| format!("no option {n}; the question offers 1-{}", options.len()));
+        }
+        let needle = choice.to_lowercase();
+        let hits: Vec<&(u32, String)> = options.iter().filter(|(_, l)| l.to_lowercase().contains(&needle)).collect();
+        match hits.as_slice() {
+            [(n, _)] => Ok(*n),
+            [] => Err(format!("no option reads {choice:?}")),
+            _ => Err(format!(
+                "{choice:?} matches options {}",
+                hits.iter().map(|(n, _)| n.to_string()).collect::<Vec<_>>().join(", ")
+            )),
+        }
+    }
+
+    pub fn answer(target: &Target, choice: &str) -> Result<String, String> {
+        let (activity, caps) = target.capture()?;
+        if activity.state != ClaudeState::Question {
+            return Err(format!("{} reads as {}, not question — nothing sent", target.pane, activity.state.as_str()));
+        }
+        let options = options(&caps.plain);
+        if options.is_empty() {
+            return Err(format!("{}: no numbered options on screen — nothing sent", target.pane));
+        }
+        let n = pick(&options, choice)?;
+        // The selectors take an option's digit as select-and-confirm.
+        target.run(&[&["send-keys", "-t", &target.pane, &n.to_string()]])?;
+        let label = &options.iter().find(|(i, _)| *i == n).unwrap().1;
+        Ok(format!("{}: answered {n}. {label}", target.pane))
+    }
+
+    /// The pane reading a table state was refined from: the transcript and
+    /// report verdicts only ever refine a Finished pane, plan mode an Active one.
+    fn pane_reading(state: ClaudeState) -> ClaudeState {
+        match state {
+            ClaudeState::Done | ClaudeState::Stuck | ClaudeState::Partial | ClaudeState::Ongoing => ClaudeState::Finished,
+            ClaudeState::Planning => ClaudeState::Active,
+            s => s,
+        }
+    }
+
+    /// `--expect`: the window still reads as what the caller saw. Compared as
+    /// pane readings — `send` reads the pane alone, the table it was called
+    /// from read the transcripts too.
+    fn still_reads_as(pane: &str, now: ClaudeState, expect: Option<ClaudeState>) -> Result<(), String> {
+        match expect {
+            Some(seen) if pane_reading(seen) != pane_reading(now) => {
+                Err(format!("{pane} reads as {} now, not {} — nothing sent", now.as_str(), seen.as_str()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// A prompt text can be typed at: Claude's turn is over and nothing is
+    /// typed or asked. Limit is among them — its selector closes with Escape.
+    fn idle(state: ClaudeState) -> bool {
+        matches!(
+            state,
+            ClaudeState::Finished
+                | ClaudeState::Done
+                | ClaudeState::Stuck
+                | ClaudeState::Partial
+                | ClaudeState::Ongoing
+                | ClaudeState::Interrupted
+                | ClaudeState::Limit
+        )
+    }
+
+    /// An idle prompt with no selector open. The limit notice stays above the
+    /// prompt once its selector is closed, so Limit alone doesn't tell.
+    fn at_prompt(state: ClaudeState, plain: &str) -> bool {
+        idle(state) && options(plain).is_empty()
+    }
+
+    /// After the Escape meant for a limit pane's wait-or-upgrade selector:
+    /// wait for the pane to redraw at the prompt. Until then a paste would
+    /// land in the menu and its Enter pick an option.
+    fn dismissed(target: &Target) -> Result<(), String> {
+        let mut state = ClaudeState::Limit;
+        for _ in 0..10 {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            let (activity, caps) = target.capture()?;
+            state = activity.state;
+            if at_prompt(state, &caps.plain) {
+                return Ok(());
+            }
+        }
+        Err(format!("{} reads as {} after Escape, not an idle prompt — nothing sent", target.pane, state.as_str()))
+    }
+
+    /// With text: type it at an idle prompt and submit. Without: submit what's
+    /// already typed into the box. Text is never typed over a draft — the two
+    /// would run together into one message, and clearing it would lose what
+    /// was typed by hand — so a draft has to be submitted or cleared in the
+    /// pane first. A prompt open in an external editor isn't submitted either:
+    /// Enter would only land in the file. A limit pane counts as idle: its wait-or-upgrade selector
+    /// is dismissed first, and the text goes in once the pane reads as a bare
+    /// prompt again — what a nudge after the usage reset needs.
+    pub fn send(target: &Target, text: Option<&str>, expect: Option<ClaudeState>) -> Result<String, String> {
+        let (activity, _) = target.capture()?;
+        let state = activity.state;
+        still_reads_as(&target.pane, state, expect)?;
+        match (text, state) {
+            (Some(text), state) if idle(state) => {
+                if state == ClaudeState::Limit {
+                    target.run(&[&["send-keys", "-t", &target.pane, "Escape"]])?;
+                    dismissed(target)?;
+                }
+                // A bracketed paste, so newlines stay inside the message
+                // instead of each submitting what came before it.
+                let buffer = format!("claude-sessions-{}", std::process::id());
+                target.run(&[
+                    &["set-buffer", "-b", &buffer, "--", text],
+                    &["paste-buffer", "-p", "-d", "-b", &buffer, "-t", &target.pane],
+                    &["send-keys", "-t", &target.pane, "Enter"],
+                ])?;
+                Ok(format!("{}: sent {} chars", target.pane, text.chars().count()))
+            }
+            (None, ClaudeState::Draft) if activity.in_editor => {
+                Err(format!("{}: the prompt is open in an editor — save and quit it there; nothing sent", target.pane))
+            }
+            (None, ClaudeState::Input | ClaudeState::Draft) => {
+                target.run(&[&["send-keys", "-t", &target.pane, "Enter"]])?;
+                Ok(format!("{}: submitted the typed draft", target.pane))
+            }
+            (Some(_), ClaudeState::Input | ClaudeState::Draft) => Err(format!(
+                "{} already holds a typed draft — send without text submits it, or clear it in the pane first; nothing sent",
+                target.pane
+            )),
+            (None, _) => Err(format!("{} reads as {}, not input — nothing to submit", target.pane, state.as_str())),
+            (Some(_), _) => Err(format!("{} reads as {}, not an idle prompt — nothing sent", target.pane, state.as_str())),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn fixture(name: &str) -> String {
+            fs::read_to_string(format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"))).unwrap()
+        }
+
+        #[test]
+        fn a_limit_pane_is_at_the_prompt_only_once_its_selector_is_gone() {
+            let selector = fixture("limit__session_limit_selector.txt");
+            assert!(!at_prompt(ClaudeState::Limit, &selector));
+            let closed = selector.split("\nWhat do you want to do?").next().unwrap();
+            assert!(at_prompt(ClaudeState::Limit, closed));
+            assert!(!at_prompt(ClaudeState::Question, closed));
+        }
+
+        #[test]
+        fn a_prompt_open_in_an_editor_is_a_draft_enter_cant_submit() {
+            let editor = classify_activity("/tmp/claude-prompt-0f3a-77b1.md [+]\nrun the tests again\n~\n~\n", || None);
+            assert_eq!((editor.state, editor.in_editor), (ClaudeState::Draft, true));
+            let typed = fixture("draft__typed_input.txt");
+            let typed = classify_activity(&typed, || Some(fixture("draft__typed_input.esc")));
+            assert_eq!((typed.state, typed.in_editor), (ClaudeState::Draft, false));
+        }
+
+        #[test]
+        fn the_options_are_the_block_under_the_cursor() {
+            let labels = |name| options(&fixture(name)).into_iter().map(|(_, l)| l).collect::<Vec<_>>();
+            assert_eq!(labels("question__askwidget.txt"), ["chrono", "time", "jiff"]);
+            assert_eq!(
+                labels("question__permission_selector.txt"),
+                ["Yes, allow this command", "Yes, and don't ask again for bash commands", "No, and tell Claude what to do differently"]
+            );
+            assert!(options("1. a\n2. b").is_empty(), "no cursor, no selector");
+        }
+
+        #[test]
+        fn a_choice_names_one_option_or_nothing_is_sent() {
+            let opts = options(&fixture("question__permission_selector.txt"));
+            assert_eq!(pick(&opts, "2"), Ok(2));
+            assert_eq!(pick(&opts, "tell claude"), Ok(3));
+            assert!(pick(&opts, "4").is_err());
+            assert!(pick(&opts, "yes").unwrap_err().contains("1, 2"));
+        }
+
+        #[test]
+        fn targets_split_their_host_at_the_last_slash() {
+            let t = Target::parse("/tmp/tmux-1000/work/nix:2", "ssh").unwrap();
+            assert_eq!((t.host, t.pane.as_str()), (Host::Socket("/tmp/tmux-1000/work".into()), "nix:2"));
+            assert_eq!(Target::parse("nix:2", "ssh").unwrap().host, Host::Local);
+            assert!(Target::parse("nix", "ssh").is_err());
+        }
+
+        #[test]
+        fn expect_compares_the_pane_reading_the_table_state_came_from() {
+            use ClaudeState::*;
+            assert!(still_reads_as("nix:2", Finished, None).is_ok());
+            assert!(still_reads_as("nix:2", Finished, Some(Stuck)).is_ok());
+            assert!(still_reads_as("nix:2", Active, Some(Planning)).is_ok());
+            assert!(still_reads_as("nix:2", Limit, Some(Limit)).is_ok());
+            let moved = still_reads_as("nix:2", Input, Some(Done)).unwrap_err();
+            assert_eq!(moved, "nix:2 reads as input now, not done — nothing sent");
+            assert!(still_reads_as("nix:2", Question, Some(Finished)).is_err());
+        }
+    }
+}
+
+/// Live panes turned into regression material. `record` snapshots a window the
+/// way the poll reads it — into a `<state>__<desc>` fixture, or with `--frames`
+/// into a numbered timeline — and `replay` runs the classifier back over a
+/// timeline to show where its reading flipped.
+mod fixture {
+    use super::*;
+
+    /// The transcript lines a `.jsonl` companion keeps: enough to hold the last
+    /// message, which is all the active/finished deliberation reads.
+    const TRANSCRIPT_TAIL: usize = 15;
+
+    /// A timeline's per-frame record, next to the frames themselves.
+    const INDEX: &str = "frames.jsonl";
+
+    /// What a capture-pane dump doesn't carry.
+    #[derive(Serialize, Deserialize)]
+    pub struct Cursor {
+        pub x: u32,
+        pub y: u32,
+        pub width: u32,
+        pub height: u32,
+    }
+
+    /// One line of a timeline's `frames.jsonl`: what the pane read as when it
+    /// was recorded, so a replay under other rules can say what changed.
+    #[derive(Serialize, Deserialize)]
+    struct Frame {
+        frame: String,
+        at: f64,
+        state: ClaudeState,
+        rule: Option<String>,
+        cursor: Cursor,
+    }
+
+    /// The cursor and geometry, plus the pane's shell pid for the transcript
+    /// lookup — one display-message.
+    fn pane_info(target: &act::Target) -> Result<(Cursor, u32), String> {
+        let format = "#{cursor_x} #{cursor_y} #{pane_width} #{pane_height} #{pane_pid}";
+        let out = target
+            .host
+            .tmux(&["display-message", "-p", "-t", target.pane.as_str(), format])
+            .output()
+            .map_err(|e| format!("tmux: {e}"))?;
+        if !out.status.success() {
+            return Err(format!("tmux: {}", String::from_utf8_lossy(&out.stderr).trim()));
+        }
+        let n: Vec<u32> = String::from_utf8_lossy(&out.stdout).split_whitespace().filter_map(|w| w.parse().ok()).collect();
+        match n[..] {
+            [x, y, width, height, pid] => Ok((Cursor { x, y, width, height }, pid)),
+            _ => Err(format!("{}: unreadable pane geometry", target.pane)),
+        }
+    }
+
+    fn now() -> f64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or(0.0)
+    }
+
+    /// Raw dumps hold whatever the session saw — env dumps and tool output
+    /// included (see tests/fixtures/README.md). Names the files to look over.
+    fn secrets_warning(files: &[PathBuf]) -> Option<String> {
+        let shape = Regex::new(r"(?i)sk-ant|token|secret").unwrap();
+        let hits: Vec<String> = files
+            .iter()
+            .filter(|f| fs::read_to_string(f).is_ok_and(|s| shape.is_match(&s)))
+            .map(|f| f.display().to_string())
+            .collect();
+        (!hits.is_empty()).then(|| format!("mentions a token or secret — look it over before committing: {}", hits.join(", ")))
+    }
+
+    fn check_name(name: &str) -> Result<(), String> {
+        if name.is_empty() || name.contains("__") || name.contains('/') {
+            return Err(format!("{name:?} can't name a fixture: it's the <desc> after <state>__"));
+        }
+        Ok(())
+    }
+
+    /// One capture into `<dir>/<state>__<name>.txt` plus its `.esc` and
+    /// `.cursor`, and — when the pane alone reads finished and its transcript
+    /// is at hand — the `.jsonl` tail that arbitrates. `<state>` is what the
+    /// window reads as now, or `expect` when it's being recorded because it
+    /// reads wrong.
+    pub fn record(target: &act::Target, name: &str, expect: Option<ClaudeState>, dir: &Path) -> Result<String, String> {
+        check_name(name)?;
+        let (_, caps) = target.capture()?;
+        let (cursor, pid) = pane_info(target)?;
+        let (pane, _) = rules::active().classify(&caps.plain, || Some(caps.escaped.clone()));
+
+        // Remote transcripts live on the other machine, as they do for the poll.
+        let transcript = (pane.state == ClaudeState::Finished && target.host.is_local())
+            .then(|| get_session_info_for_pane(pid, &caps.deep))
+            .flatten()
+            .and_then(|m| fs::read_to_string(m.file).ok())
+            .map(|s| {
+                let lines: Vec<&str> = s.lines().collect();
+                lines[lines.len().saturating_sub(TRANSCRIPT_TAIL)..].join("\n") + "\n"
+            });
+
+        // Named after the transcript is in place: the prefix is the final verdict.
+        fs::create_dir_all(dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+        let taken: Vec<_> = fs::read_dir(dir)
+            .map_err(|e| format!("read {}: {e}", dir.display()))?
+            .filter_map(|e| e.ok()?.file_name().into_string().ok())
+            .filter(|f| f.split_once("__").is_some_and(|(_, rest)| Path::new(rest).file_stem() == Some(name.as_ref())))
+            .collect();
+        if let Some(f) = taken.first() {
+            return Err(format!("{} already holds {f}", dir.display()));
+        }
+        let cursor = serde_json::to_string(&cursor).map_err(|e| e.to_string())? + "\n";
+        let mut bodies = vec![("txt", &caps.plain), ("esc", &caps.escaped), ("cursor", &cursor)];
+        if let Some(t) = &transcript {
+            bodies.push(("jsonl", t));
+        }
+        // Drafted in a side directory: an unprefixed `<name>.txt` left in `dir`
+        // by a failed run is a fixture with no state, and fails the test.
+        let staging = dir.join(format!(".record-{}", std::process::id()));
+        let result = draft(&staging, name, &bodies).and_then(|(got, files)| {
+            let state = expect.unwrap_or(got.final_state);
+            let named: Vec<PathBuf> =
+                files.iter().map(|f| dir.join(format!("{}__{}", state.as_str(), f.file_name().unwrap().to_string_lossy()))).collect();
+            for (i, (from, to)) in files.iter().zip(&named).enumerate() {
+                if let Err(e) = fs::rename(from, to) {
+                    for f in &named[..i] {
+                        let _ = fs::remove_file(f);
+                    }
+                    return Err(format!("rename {}: {e}", from.display()));
+                }
+            }
+            Ok((got, state, named))
+        });
+        let _ = fs::remove_dir_all(&staging);
+        let (got, state, named) = result?;
+
+        let exts: Vec<_> = named.iter().filter_map(|f| f.extension()?.to_str()).collect();
+        let rule = got.rule.as_deref().unwrap_or("fallback");
+        let mut out = format!("wrote {}.{{{}}}", named[0].with_extension("").display(), exts.join(","));
+        if state == got.final_state {
+            out += &format!(" — reads as {} ({rule})", state.as_str());
+        } else {
+            out += &format!(
+                " — reads as {} ({rule}), so check-rules lists it until the rules say {}",
+                got.final_state.as_str(),
+                state.as_str()
+            );
+        }
+        out += "\nrecord its snapshot: INSTA_UPDATE=always claude_sessions_test";
+        if let Some(w) = secrets_warning(&named) {
+            out += &format!("\n{}", w.yellow());
+        }
+        Ok(out)
+    }
+
+    /// Writes `<name>.<ext>` for each body into `staging` and classifies them
+    /// as a fixture would be.
+    fn draft(staging: &Path, name: &str, bodies: &[(&str, &String)]) -> Result<(rules::FixtureRun, Vec<PathBuf>), String> {
+        fs::create_dir_all(staging).map_err(|e| format!("create {}: {e}", staging.display()))?;
+        let mut files = Vec::new();
+        for (ext, body) in bodies {
+            let path = staging.join(format!("{name}.{ext}"));
+            fs::write(&path, body).map_err(|e| format!("write {}: {e}", path.display()))?;
+            files.push(path);
+        }
+        Ok((rules::run_fixture(rules::active(), &files[0])?, files))
+    }
+
+    /// `frames` captures `every` apart into `<dir>/<name>/`: `NNNN.txt` and
+    /// `.esc` per frame, and what each read as — with the cursor — in
+    /// `frames.jsonl`. Flips are printed as they're seen. A window that closes
+    /// ends the timeline early, keeping what was captured.
+    pub fn record_timeline(
+        target: &act::Target,
+        name: &str,
+        frames: usize,
+        every: std::time::Duration,
+        dir: &Path,
+    ) -> Result<String, String> {
+        check_name(name)?;
+        let dir = dir.join(name);
+        if dir.exists() {
+            return Err(format!("{} already exists", dir.display()));
+        }
+        fs::create_dir_all(&dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+
+        let mut index = String::new();
+        let mut files = Vec::new();
+        let mut last = None;
+        let mut taken = 0;
+        for n in 0..frames {
+            if n > 0 {
+                std::thread::sleep(every);
+            }
+            let (caps, cursor) = match target.capture().and_then(|(_, caps)| Ok((caps, pane_info(target)?.0))) {
+                Ok(got) => got,
+                Err(e) if n > 0 => {
+                    println!("{}", format!("stopped after {n} frames: {e}").yellow());
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+            let (result, rule) = rules::active().classify(&caps.plain, || Some(caps.escaped.clone()));
+            let frame = Frame { frame: format!("{n:04}"), at: now(), state: result.state, rule: rule.map(str::to_string), cursor };
+
+            let txt = dir.join(format!("{}.txt", frame.frame));
+            for (path, body) in [(txt.clone(), &caps.plain), (txt.with_extension("esc"), &caps.escaped)] {
+                fs::write(&path, body).map_err(|e| format!("write {}: {e}", path.display()))?;
+                files.push(path);
+            }
+            if last != Some(frame.state) {
+                println!("{}  {} ({})", frame.frame, frame.state.as_str(), frame.rule.as_deref().unwrap_or("fallback"));
+                last = Some(frame.state);
+            }
+            index += &(serde_json::to_string(&frame).map_err(|e| e.to_string())? + "\n");
+            taken += 1;
+        }
+        let path = dir.join(INDEX);
+        fs::write(&path, index).map_err(|e| format!("write {}: {e}", path.display()))?;
+
+        let mut out = format!("wrote {taken} frames to {}", dir.display());
+        if let Some(w) = secrets_warning(&files) {
+            out += &format!("\n{}", w.yellow());
+        }
+        Ok(out)
+    }
+
+    /// A frame as replayed, beside what it read as when recorded.
+    #[derive(Debug, Serialize)]
+    pub struct Reading {
+        pub frame: String,
+        /// Seconds since the first frame, when the timeline was recorded with times.
+        pub at: Option<f64>,
+        pub state: ClaudeState,
+        pub rule: Option<String>,
+        pub recorded: Option<ClaudeState>,
+    }
+
+    /// The first reading and every one whose state differs from the one before.
+    pub fn flips(readings: &[Reading]) -> Vec<&Reading> {
+        readings
+            .iter()
+            .enumerate()
+            .filter(|(i, r)| *i == 0 || readings[i - 1].state != r.state)
+            .map(|(_, r)| r)
+            .collect()
+    }
+
+    /// Classifies every `*.txt` under `dir` in name order, pane text only —
+    /// the same reading each frame got when it was recorded.
+    pub fn replay_dir(rules: &rules::Rules, dir: &Path) -> Result<Vec<Reading>, String> {
+        let recorded: HashMap<String, Frame> = fs::read_to_string(dir.join(INDEX))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|l| serde_json::from_str::<Frame>(l).ok())
+            .map(|f| (f.frame.clone(), f))
+            .collect();
+        let mut start = None;
+        rules::fixture_files(dir)?
+            .iter()
+            .map(|txt| {
+                let frame = txt.file_stem().unwrap_or_default().to_string_lossy().to_string();
+                let plain = fs::read_to_string(txt).map_err(|e| format!("read {}: {e}", txt.display()))?;
+                let esc = fs::read_to_string(txt.with_extension("esc")).ok();
+                let (result, rule) = rules.classify(&plain, || esc);
+                let then = recorded.get(&frame);
+                let at = then.map(|f| f.at - *start.get_or_insert(f.at));
+                Ok(Reading { at, state: result.state, rule: rule.map(str::to_string), recorded: then.map(|f| f.state), frame })
+            })
+            .collect()
+    }
+
+    /// `replay`: where the classification flipped across a timeline, and which
+    /// frames the rules now read differently than when they were recorded.
+    pub fn replay(rules: &rules::Rules, dir: &Path, json: bool) -> Result<(), String> {
+        let readings = replay_dir(rules, dir)?;
+        if readings.is_empty() {
+            return Err(format!("{}: no frames", dir.display()));
+        }
+        let flips = flips(&readings);
+        let changed: Vec<&Reading> = readings.iter().filter(|r| r.recorded.is_some_and(|s| s != r.state)).collect();
+
+        if json {
+            let report = serde_json::json!({ "frames": readings.len(), "flips": flips, "changed": changed });
+            println!("{report}");
+            return Ok(());
+        }
+        let mut prev: Option<ClaudeState> = None;
+        for r in &flips {
+            let at = r.at.map(|s| format!("{s:>7.1}s")).unwrap_or_default();
+            let to = match prev {
+                Some(p) => format!("{} → {}", p.as_str(), r.state.as_str()),
+                None => r.state.as_str().to_string(),
+            };
+            println!("{}{at}  {to} ({})", r.frame, r.rule.as_deref().unwrap_or("fallback"));
+            prev = Some(r.state);
+        }
+        for r in &changed {
+            println!(
+                "{} {}  recorded {}, now {} ({})",
+                "!!".red(),
+                r.frame,
+                r.recorded.unwrap().as_str(),
+                r.state.as_str(),
+                r.rule.as_deref().unwrap_or("fallback")
+            );
+        }
+        println!("{} frames, {} flips, {} read differently than recorded", readings.len(), flips.len() - 1, changed.len());
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// Frames written the way record_timeline writes them, from fixtures.
+        fn timeline(frames: &[(&str, ClaudeState)]) -> PathBuf {
+            let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+            let dir = std::env::temp_dir().join(format!("claude-sessions-timeline-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let mut index = String::new();
+            for (n, (fixture, recorded)) in frames.iter().enumerate() {
+                fs::copy(fixtures.join(fixture), dir.join(format!("{n:04}.txt"))).unwrap();
+                let cursor = Cursor { x: 0, y: 0, width: 80, height: 24 };
+                let frame = Frame { frame: format!("{n:04}"), at: 100.0 + n as f64, state: *recorded, rule: None, cursor };
+                index += &(serde_json::to_string(&frame).unwrap() + "\n");
+            }
+            fs::write(dir.join(INDEX), index).unwrap();
+            dir
+        }
+
+        #[test]
+        fn a_timeline_reports_each_flip_and_what_reads_differently_now() {
+            use ClaudeState::*;
+            let dir = timeline(&[
+                ("active__spinner_building.txt", Active),
+                ("active__spinner_canoodling.txt", Active),
+                ("finished__recap_with_prompt.txt", Active),
+                ("finished__quoted_npm_prompt.txt", Finished),
+                ("question__askwidget.txt", Question),
+            ]);
+            let readings = replay_dir(rules::active(), &dir).unwrap();
+            let _ = fs::remove_dir_all(&dir);
+
+            let flips: Vec<_> = flips(&readings).iter().map(|r| (r.frame.as_str(), r.state, r.at)).collect();
+            assert_eq!(
+                flips,
+                [("0000", Active, Some(0.0)), ("0002", Finished, Some(2.0)), ("0004", Question, Some(4.0))]
+            );
+            let changed: Vec<_> = readings.iter().filter(|r| r.recorded != Some(r.state)).map(|r| r.frame.as_str()).collect();
+            assert_eq!(changed, ["0002"]);
+        }
+
+        #[test]
+        fn every_takes_seconds_not_a_negative_or_nan() {
+            assert_eq!(parse_seconds("0.5"), Ok(std::time::Duration::from_millis(500)));
+            for bad in ["-1", "NaN", "inf", "soon"] {
+                assert!(parse_seconds(bad).is_err(), "{bad}");
+            }
+        }
+    }
+}
+
+/// Bringing back a claude that exited in its pane: the exit chrome's
+/// `claude --resume <id>` typed back in at the pane's shell, in the directory
+/// its transcript is filed under. Every revival is appended to
+/// `$XDG_STATE_HOME/claude-sessions/revivals.jsonl` with why — and that log is
+/// also what keeps a second run, or the daemon's next tick, from typing it again.
+mod revive {
+    use super::*;
+    use std::io::Write as _;
+
+    /// A session revived this recently isn't retyped: the new claude takes a
+    /// few seconds to replace the shell, and until it does the pane still
+    /// shows the exit chrome that asked for it.
+    const COOLDOWN_SECS: i64 = 10 * 60;
+    /// Auto-revival gives up on a session that keeps dying — a crash loop
+    /// wants a human, not a fourth restart.
+    const AUTO_MAX_PER_HOUR: usize = 3;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct Record {
+        pub at: i64,
+        /// ClaudeWindow::key of the pane it was typed into
+        pub key: String,
+        pub id: String,
+        pub cwd: PathBuf,
+        pub reason: String,
+        /// By the daemon's --auto-revive, not a `revive` run.
+        pub auto: bool,
+    }
+
+    pub enum Outcome {
+        Revived(String),
+        Skipped(String),
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        history::path().map(|p| p.with_file_name("revivals.jsonl"))
+    }
+
+    fn read_log() -> Vec<Record> {
+        let Some(text) = path().and_then(|p| fs::read_to_string(p).ok()) else { return Vec::new() };
+        text.lines().filter_map(|l| serde_json::from_str(l).ok()).collect()
+    }
+
+    fn append(record: &Record) -> Result<(), String> {
+        let path = path().ok_or("no state directory for the revival log")?;
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut f| writeln!(f, "{line}"))
+            .map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// Whether the log lets this exit be revived now, and the reason to record.
+    /// Auto-revival only picks up a claude that died mid-turn: one quit at an
+    /// idle prompt was quit on purpose.
+    pub fn decide(resume: &Resume, log: &[Record], auto: bool, now: i64) -> Result<String, String> {
+        if let Some(last) = log.iter().rev().find(|r| r.id == resume.id)
+            && now - last.at < COOLDOWN_SECS
+        {
+            return Err(format!("already revived {}s ago", now - last.at));
+        }
+        if auto {
+            if !resume.mid_turn {
+                return Err("quit at an idle prompt; left alone".to_string());
+            }
+            let recent = log.iter().filter(|r| r.id == resume.id && r.auto && now - r.at < 3600).count();
+            if recent >= AUTO_MAX_PER_HOUR {
+                return Err(format!("revived {recent} times in the last hour; giving up"));
+            }
+        }
+        Ok(if resume.mid_turn { "died mid-turn" } else { "exited" }.to_string())
+    }
+
+    /// A local process already running this session — resumed by hand in
+    /// another pane, say.
+    fn running(id: &str) -> Option<u32> {
+        fs::read_dir("/proc").ok()?.filter_map(|e| e.ok()).find_map(|e| {
+            let pid: u32 = e.file_name().to_str()?.parse().ok()?;
+            let cmdline = fs::read(e.path().join("cmdline")).ok()?;
+            cmdline.split(|b| *b == 0).any(|arg| arg == id.as_bytes()).then_some(pid)
+        })
+    }
+
+    /// `revive`: the exited claude in `target`, or every one on `hosts`. Err
+    /// when a named target wasn't revived.
+    pub fn run(hosts: &[Host], target: Option<&str>, ssh: &str) -> Result<(), String> {
+        let (hosts, pane) = match target {
+            Some(spec) => {
+                let t = act::Target::parse(spec, ssh)?;
+                (vec![t.host], Some(t.pane))
+            }
+            None => (hosts.to_vec(), None),
+        };
+        let now = now_epoch();
+        let mut seen = false;
+        for host in &hosts {
+            for w in get_claude_windows(host) {
+                let here = format!("{}:{}", w.session, w.window_index);
+                let wanted = match &pane {
+                    Some(p) => *p == here,
+                    None => w.resume.is_some(),
+                };
+                if !wanted {
+                    continue;
+                }
+                seen = true;
+                match revive(host, &w, false, now)? {
+                    Outcome::Revived(done) => println!("{done}"),
+                    Outcome::Skipped(why) if pane.is_some() => return Err(why),
+                    Outcome::Skipped(why) => println!("{}", why.dimmed()),
+                }
+            }
+        }
+        match (seen, pane) {
+            (false, Some(p)) => Err(format!("{p}: no claude window there")),
+            (false, None) => {
+                println!("no exited claude to revive");
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn revive(host: &Host, window: &ClaudeWindow, auto: bool, now: i64) -> Result<Outcome, String> {
+        let key = window.key();
+        let Some(resume) = &window.resume else {
+            return Ok(Outcome::Skipped(format!("{key}: no exited claude here")));
+        };
+        let reason = match decide(resume, &read_log(), auto, now) {
+            Ok(reason) => reason,
+            Err(why) => return Ok(Outcome::Skipped(format!("{key}: {why}"))),
+        };
+        if host.is_local()
+            && let Some(pid) = running(&resume.id)
+        {
+            return Ok(Outcome::Skipped(format!("{key}: {} is already running as pid {pid}", resume.id)));
+        }
+
+        // Re-read right before typing: whatever the poll saw, the pane must
+        // still be that same dead claude's shell.
+        let target = act::Target { host: host.clone(), pane: format!("{}:{}", window.session, window.window_index) };
+        let (_, caps) = target.capture()?;
+        if dead_claude_resume_id(&shell_tail(&caps.plain)).as_deref() != Some(resume.id.as_str()) {
+            return Ok(Outcome::Skipped(format!("{key}: the pane changed since the poll")));
+        }
+        let command = format!("cd {} && claude --resume {}", shell_quote(&resume.cwd.to_string_lossy()), resume.id);
+        target.run(&[
+            &["send-keys", "-t", &target.pane, "-l", &command],
+            &["send-keys", "-t", &target.pane, "Enter"],
+        ])?;
+        append(&Record { at: now, key: key.clone(), id: resume.id.clone(), cwd: resume.cwd.clone(), reason: reason.clone(), auto })?;
+        Ok(Outcome::Revived(format!("{key}: resumed {} in {} ({reason})", resume.id, resume.cwd.display())))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn record(at: i64, auto: bool) -> Record {
+            Record { at, key: "nix:2".into(), id: "abc".into(), cwd: "/home/v/nix".into(), reason: String::new(), auto }
+        }
+
+        #[test]
+        fn a_revival_waits_out_its_cooldown_and_auto_only_takes_mid_turn_deaths() {
+            let died = Resume { id: "abc".into(), cwd: "/home/v/nix".into(), mid_turn: true };
+            let quit = Resume { mid_turn: false, ..died.clone() };
+
+            assert_eq!(decide(&died, &[], true, 10_000), Ok("died mid-turn".to_string()));
+            assert!(decide(&quit, &[], true, 10_000).is_err());
+            assert_eq!(decide(&quit, &[], false, 10_000), Ok("exited".to_string()));
+
+            let log = [record(10_000 - 60, false)];
+            assert!(decide(&died, &log, false, 10_000).unwrap_err().contains("60s ago"));
+            assert!(decide(&died, &log, false, 10_000 + COOLDOWN_SECS).is_ok());
+        }
+
+        #[test]
+        fn auto_revival_gives_up_on_a_crash_loop() {
+            let died = Resume { id: "abc".into(), cwd: "/home/v/nix".into(), mid_turn: true };
+            let log: Vec<Record> = (0..3).map(|i| record(1000 + i * 700, true)).collect();
+            let now = 1000 + 2 * 700 + COOLDOWN_SECS;
+            assert!(decide(&died, &log, true, now).unwrap_err().contains("giving up"));
+            assert!(decide(&died, &log, false, now).is_ok(), "a manual revive still goes through");
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    // Set LLM summaries flag
+    if args.llm_summaries {
+        USE_LLM_SUMMARIES.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    let config = match config::load(args.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", format!("error: {e}").red());
+            std::process::exit(1);
+        }
+    };
+    match llm::Llms::from_config(&config.llm) {
+        Ok(llms) => llm::install(llms),
+        Err(e) => {
+            eprintln!("{}", format!("error: {e}").red());
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(path) = &args.rules {
+        match rules::load(path) {
+            Ok(r) => rules::install(r),
+            Err(e) => {
+                eprintln!("{}", format!("error: {e}").red());
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let hosts: Vec<Host> = if args.hosts.is_empty() {
+        vec![Host::Local]
+    } else {
+        args.hosts.iter().map(|h| Host::parse(h, &args.ssh)).collect()
+    };
+
+    match &args.command {
+        Some(Cmd::History { hours, top }) => {
+            history::run(*hours, *top, args.json);
+            return;
+        }
+        Some(Cmd::Cost { days, top }) => {
+            cost::run(&config.cost, *days, *top, args.json);
+            return;
+        }
+        Some(Cmd::Usage) => {
+            let cache = load_cache();
+            let forecast = forecast::forecast(&cache.samples, now_epoch());
+            let warn = args.warn_at.is_some_and(|m| forecast.warns(m, now_epoch()));
+            if args.json {
+                let limits: serde_json::Map<String, serde_json::Value> = limit_views(&cache.usage, &forecast)
+                    .iter()
+                    .map(|l| (l.name.to_string(), serde_json::json!(l)))
+                    .collect();
+                println!("{}", serde_json::json!({ "limits": limits, "warn": warn }));
+            } else {
+                let header = format_usage_header(&cache.usage, &forecast, false);
+                println!("{}", if warn { header.red() } else { header.normal() });
+            }
+            return;
+        }
+        Some(Cmd::CheckRules { dir }) => {
+            if let Err(e) = rules::check(rules::active(), dir, args.json) {
+                eprintln!("{}", format!("error: {e}").red());
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Cmd::Revive { target }) => {
+            if let Err(e) = revive::run(&hosts, target.as_deref(), &args.ssh) {
+                eprintln!("{}", format!("error: {e}").red());
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Cmd::Record { target, name, expect, frames, every, dir }) => {
+            let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+            let result = act::Target::parse(target, &args.ssh).and_then(|t| {
+                if *frames > 1 {
+                    let dir = dir.clone().unwrap_or_else(|| manifest.join("tests/timelines"));
+                    fixture::record_timeline(&t, name, *frames, *every, &dir)
+                } else {
+                    let dir = dir.clone().unwrap_or_else(|| manifest.join("tests/fixtures"));
+                    fixture::record(&t, name, *expect, &dir)
+                }
+            });
+            match result {
+                Ok(done) => println!("{done}"),
+                Err(e) => {
+                    eprintln!("{}", format!("error: {e}").red());
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(Cmd::Replay { dir }) => {
+            if let Err(e) = fixture::replay(rules::active(), dir, args.json) {
+                eprintln!("{}", format!("error: {e}").red());
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Cmd::Answer { target, .. } | Cmd::Send { target, .. }) => {
+            let result = act::Target::parse(target, &args.ssh).and_then(|t| match &args.command {
+                Some(Cmd::Answer { option, .. }) => act::answer(&t, option),
+                Some(Cmd::Send { text, expect, .. }) => act::send(&t, text.as_deref(), *expect),
+                _ => unreachable!(),
+            });
+            match result {
+                Ok(done) => println!("{done}"),
+                Err(e) => {
+                    eprintln!("{}", format!("error: {e}").red());
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        None => {}
+    }
+
+    let socket = args.socket.clone().unwrap_or_else(daemon::default_socket);
+    if args.daemon || args.events {
+        let result = if args.daemon {
+            match config.notify.as_ref().map(notify::Notifier::new).transpose() {
+                Ok(notifier) => daemon::serve(&socket, std::time::Duration::from_secs(args.interval), &hosts, notifier, args.auto_revive),
+                Err(e) => Err(e),
+            }
+        } else {
+            daemon::follow(&socket)
+        };
+        if let Err(e) = result {
+            eprintln!("{}", format!("error: {e}").red());
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // A live daemon has already done this poll; ask it before doing our own.
+    // An explicit --host list is a question about those servers, which the
+    // daemon may not be watching, and --llm-summaries asks for summaries its
+    // poll may not have made.
+    let served = if args.hosts.is_empty() && !args.llm_summaries { daemon::query(&socket) } else { None };
+    let daemon::Snapshot { entries, usage, forecast } = served.unwrap_or_else(|| {
+        let windows = poll_hosts(&hosts);
+        let (usage, forecast) = if args.hosts.is_empty() {
+            persist_poll(&windows)
+        } else {
+            // Not the set of windows the cache holds: writing these back would
+            // log every other window as closed, then reopened on the next poll.
+            let cache = load_cache();
+            (cache.usage, forecast::forecast(&cache.samples, now_epoch()))
+        };
+        daemon::Snapshot { entries: session_entries(&windows), usage, forecast }
+    });
+    let header = format_usage_header(&usage, &forecast, args.compact);
+    let warn = args.warn_at.is_some_and(|m| forecast.warns(m, now_epoch()));
+
+    let mut sessions = Sessions::new(args.compact, args.markup, args.group_by);
+    for entry in entries {
+        sessions.add(entry);
+    }
+    sessions.sort();
+    sessions.filter(&args.only, &args.exclude);
+
+    // Show warning if the summary backend was unavailable (only in non-compact mode with summaries)
+    if !args.compact && !args.json && let Some(backend) = llm::active().unavailable(llm::Task::Summary) {
+        eprintln!(
+            "{}",
+            format!("warn: {backend} unavailable, using fallback summaries").yellow()
+        );
+    }
+
+    if args.json {
+        println!("{}", sessions.to_json());
+    } else if args.markup {
+        // Header is informational; left uncolored so it inherits the widget's
+        // default text color (the eww label's own styling) — unless --warn-at trips.
+        if warn {
+            println!("<span foreground=\"{COLOR_ERROR}\">{}</span>", pango_escape(&header));
+        } else {
+            println!("{}", pango_escape(&header));
+        }
+        println!("{}", sessions);
+    } else {
+        println!("{}", if warn { header.red() } else { header.dimmed() });
+        println!("{}", sessions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! Snapshot tests for the terminal-state classifier.
+    //!
+    //! Every regression this script has ever had lived in `classify_activity`:
+    //! a real pane that got read as the wrong `ClaudeState`. These tests pin that
+    //! function against REAL captured pane dumps — no tmux, no /proc, no mocks.
+    //!
+    //! ## Fixture layout (`tests/fixtures/`)
+    //! - `<state>__<name>.txt`  — plain `tmux capture-pane -p` output. The
+    //!   `<state>` prefix (before `__`) is the EXPECTED state and is asserted.
+    //! - `<state>__<name>.esc`  — OPTIONAL companion: escape-coded
+    //!   `tmux capture-pane -p -e` output. Only the draft path consults it; if
+    //!   absent the classifier is told the escaped capture is unavailable.
+    //! - `<state>__<name>.jsonl` — OPTIONAL companion: tail of the session's
+    //!   transcript. States now come from pane text AND the transcript (the
+    //!   active↔finished deliberation), so fixtures for that path persist both.
+    //!   The prefix names the FINAL state after `refine_finished`, letting one
+    //!   pane dump pin both verdicts (same .txt, different .jsonl).
+    //! - `<state>__<name>.cursor` — OPTIONAL companion: the cursor and pane
+    //!   size `record` saw, as JSON. Nothing classifies by it yet.
+    //!
+    //! ## Adding a case (the whole point — trivial, no code edit)
+    //! Capture a live pane in the state you want to lock in:
+    //!     tmux capture-pane -t <sess>:<win> -p -S -50   > tests/fixtures/question__askwidget.txt
+    //!     # only for draft cases, also grab the escaped capture:
+    //!     tmux capture-pane -t <sess>:<win> -p -e -S -10 > tests/fixtures/draft__typed.esc
+    //!     # for active/finished deliberation cases, persist the transcript tail:
+    //!     tail -n 15 ~/.claude/projects/<proj>/<session>.jsonl > tests/fixtures/finished__idle.jsonl
+    //! Then `cargo insta accept` to record its full ActivityResult snapshot.
+    //! The filename-prefix assertion runs automatically.
+
+    use super::*;
+    use std::path::{Path, PathBuf};
+
+    fn fixtures_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+    }
+
+    /// Walk every `*.txt` fixture, classify it, and assert two things:
+    ///   1. the classified state equals the filename's `<state>__` prefix, and
+    ///   2. the full ActivityResult matches its recorded insta snapshot.
+    /// Drop a new correctly-named `.txt` in and it's covered with zero code edits.
+    #[test]
+    fn fixtures_classify_to_their_named_state() {
+        // The replay itself is `rules::run_fixture`, the same one `check-rules` runs.
+        let dir = fixtures_dir();
+        let txts = rules::fixture_files(&dir).unwrap();
+
+        assert!(
+            !txts.is_empty(),
+            "no *.txt fixtures in {dir:?} — capture one with `tmux capture-pane -p`"
+        );
+
+        for txt in txts {
+            let run = rules::run_fixture(rules::active(), &txt).unwrap();
+            let stem = run.stem;
+
+            // The transcript is only consulted for Finished panes.
+            if run.has_transcript {
+                assert_eq!(
+                    run.result.state,
+                    ClaudeState::Finished,
+                    "fixture {stem:?} has a .jsonl companion but the pane classified as \
+                     {:?} — the transcript is only consulted for Finished panes",
+                    run.result.state
+                );
+            }
+
+            let expected = run.expected.unwrap_or_else(|| {
+                panic!("fixture {stem:?} has an unknown state prefix; name it <state>__<desc>.txt")
+            });
             assert_eq!(
-                final_state, expected,
-                "fixture {stem:?} resolved to {final_state:?} (pane: {:?}, transcript: {verdict:?}), \
+                run.final_state, expected,
+                "fixture {stem:?} resolved to {:?} (pane: {:?} via rule {:?}, transcript: {:?}), \
                  expected {expected:?}",
-                result.state
+                run.final_state, run.result.state, run.rule, run.verdict
             );
 
             // Full-result snapshot catches subtler drift (wrong extracted
             // question text, wrong truncation) the state check alone can't see.
             // Transcript-arbitrated fixtures also pin the verdict itself.
-            if jsonl.exists() {
-                insta::assert_debug_snapshot!(stem.clone(), (result, verdict));
+            let result = run.result;
+            if run.has_transcript {
+                insta::assert_debug_snapshot!(stem.clone(), (result, run.verdict));
             } else {
                 insta::assert_debug_snapshot!(stem.clone(), result);
             }
@@ -22,7 +22,7 @@ alias gn="$__fish_scripts_dir/git_scripts.rs publish"
 alias kbd="$__fish_scripts_dir/kbd.rs"
 alias optimize_for="sudo -E $__fish_scripts_dir/optimize_for.rs"
 alias smart_shutdown="$__fish_scripts_dir/smart_shutdown.rs"
-# plain `shutdown now` skips the claude inventory write, silently breaking restore_sessions
+# plain `shutdown now` skips the workspace snapshot, silently breaking restore_sessions
 function shutdown
 	if contains -- -r $argv; or contains -- --reboot $argv
 		command shutdown $argv
@@ -61,6 +61,7 @@ alias gp="$__fish_scripts_dir/git_scripts.rs push"
 alias gpf="$__fish_scripts_dir/git_scripts.rs push --force"
 alias gpl="$__fish_scripts_dir/git_scripts.rs push --force-with-lease"
 alias gbd="$__fish_scripts_dir/git_scripts.rs delete"
+alias gstack="$__fish_scripts_dir/git_scripts.rs stack"
 
 function gc
 	if contains -- -c $argv
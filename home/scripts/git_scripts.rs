@@ -7,12 +7,17 @@ edition = "2024"
 [dependencies]
 clap = { version = "4.5.49", features = ["derive"] }
 gix = { version = "0.78", features = ["merge", "blocking-network-client", "blocking-http-transport-reqwest-rust-tls", "worktree-mutation"] }
+serde = { version = "1", features = ["derive"] }
 serde_json = "1"
+toml = "0.9"
+base64 = "0.22"
+crypto_box = { version = "0.9", features = ["seal"] }
 ---
 
 use clap::{Parser, Subcommand};
 use gix::merge::tree::TreatAsUnresolved;
 use std::env;
+use std::path::PathBuf;
 use std::process::{Command, Stdio};
 
 #[derive(Parser, Debug)]
@@ -50,6 +55,13 @@ enum Commands {
         /// Print decision and final args without invoking git push
         #[arg(long)]
         dry_run: bool,
+        /// Instead of pushing, report which remote commits would be dropped and whether each
+        /// is provably preserved locally, with diffstats. Exits 2 if any are at risk
+        #[arg(long)]
+        explain: bool,
+        /// --explain, as JSON
+        #[arg(long)]
+        json: bool,
         /// Additional arguments to pass to git push
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
@@ -61,19 +73,53 @@ enum Commands {
     },
     /// Reword a commit message by hash without interactive rebase
     Reword {
-        /// Commit hash to reword (any ancestor of HEAD)
+        /// Commit to reword (on HEAD's first-parent line)
         commit: String,
         /// New commit message
         message: String,
     },
-    /// Extract a commit from history, leaving it as stashed uncommitted changes
+    /// Remove commits from history, replaying what came after them
+    Drop {
+        /// Commits to drop
+        #[arg(required = true)]
+        commits: Vec<String>,
+    },
+    /// Meld a commit into another one (its parent by default), keeping the target's message
+    Squash {
+        /// Commit to fold away
+        commit: String,
+        /// Commit to fold it into
+        #[arg(long)]
+        into: Option<String>,
+        /// Message for the combined commit
+        #[arg(short, long)]
+        message: Option<String>,
+    },
+    /// Move a commit to another place in history
+    #[command(group(clap::ArgGroup::new("place").required(true).args(["after", "before"])))]
+    Reorder {
+        /// Commit to move
+        commit: String,
+        /// Put it right on top of this commit
+        #[arg(long)]
+        after: Option<String>,
+        /// Put it right below this commit
+        #[arg(long)]
+        before: Option<String>,
+    },
+    /// Take a commit out of history, leaving its changes staged for splitting up
     Extract {
         /// Commit hash to extract
         commit: String,
+        /// Stash the changes instead of leaving them staged
+        #[arg(long)]
+        stash: bool,
     },
-    /// Create a new GitHub repository with standard labels and milestones
+    /// Restore the branch to where the last reword/drop/squash/reorder/extract found it
+    Undo,
+    /// Create a new GitHub repository set up from the publish template (labels, milestones, ...)
     Publish {
-        /// Repository name (defaults to current directory name)
+        /// Repository name (defaults to current directory name); with --sync, OWNER/NAME (defaults to origin)
         repo_name: Option<String>,
         /// Create a private repository
         #[arg(long, conflicts_with = "public")]
@@ -84,7 +130,54 @@ enum Commands {
         /// Commit all changes first with this message
         #[arg(short, long)]
         commit: Option<String>,
+        /// Template file [default: $XDG_CONFIG_HOME/git_scripts/publish.toml, if it exists;
+        /// else the built-in template::DEFAULT]
+        #[arg(long)]
+        template: Option<PathBuf>,
+        /// Bring an existing repo in line with the template, reporting each difference
+        #[arg(long, conflicts_with_all = ["private", "public", "commit"])]
+        sync: bool,
+        /// With --sync: only report the differences; exits 2 if there are any
+        #[arg(long, requires = "sync")]
+        dry_run: bool,
+    },
+    /// Stacked branches: each layer builds on the one below and gets its own PR
+    Stack {
+        #[command(subcommand)]
+        cmd: StackCmd,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum StackCmd {
+    /// Create BRANCH on top of the current one and record it as the new top layer
+    New { branch: String },
+    /// Record existing BRANCHES as a stack, bottom first
+    Set {
+        #[arg(required = true)]
+        branches: Vec<String>,
+        /// What the bottom layer builds on (default: the repo's default branch)
+        #[arg(long)]
+        onto: Option<String>,
+    },
+    /// List the current stack's layers, bottom first
+    Show,
+    /// Rebuild each layer on its parent's current tip (after amending a lower layer)
+    Restack,
+    /// Push every layer, with the same force-safety rules as `push`
+    Push {
+        /// Print the push commands without running them
+        #[arg(long)]
+        dry_run: bool,
     },
+    /// Sync and push the stack, then open one PR per layer against the layer below, retargeting existing ones
+    Pr {
+        /// Create new PRs as drafts
+        #[arg(long)]
+        draft: bool,
+    },
+    /// Drop merged layers, moving the next one onto the default branch (restacked and retargeted)
+    Sync,
 }
 
 /// Run command with inherited stdio (user sees output)
@@ -118,6 +211,797 @@ fn run_cmd_status(cmd: &str, args: &[&str]) -> bool {
         .unwrap_or(false)
 }
 
+/// GitHub's REST API (plus the one GraphQL mutation REST lacks), spoken through `curl`.
+/// The API root is `GITHUB_API_URL` (default https://api.github.com), so tests can point
+/// it at a local mock; the token is `GITHUB_KEY`, else whatever `gh auth token` has.
+mod github {
+    use base64::Engine;
+    use serde::Deserialize;
+    use serde::de::DeserializeOwned;
+    use serde_json::{Value, json};
+    use std::fmt;
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    #[derive(Debug)]
+    pub enum Error {
+        /// curl itself failed (DNS, connection refused, TLS, ...).
+        Curl { code: Option<i32>, stderr: String },
+        /// The API answered with an error status.
+        Http { status: u16, method: &'static str, path: String, message: String },
+        /// The API answered, but not with what we expected.
+        Decode { path: String, error: String },
+    }
+
+    impl Error {
+        pub fn is_not_found(&self) -> bool {
+            matches!(self, Error::Http { status: 404, .. })
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Error::Curl { code: Some(code), stderr } => write!(f, "curl exited {code}: {}", stderr.trim()),
+                Error::Curl { code: None, stderr } => write!(f, "curl: {}", stderr.trim()),
+                Error::Http { status, method, path, message } => write!(f, "{method} {path}: HTTP {status}: {message}"),
+                Error::Decode { path, error } => write!(f, "unexpected response from {path}: {error}"),
+            }
+        }
+    }
+
+    impl From<Error> for String {
+        fn from(e: Error) -> String {
+            e.to_string()
+        }
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub struct Repo {
+        pub full_name: String,
+        pub clone_url: String,
+        pub default_branch: String,
+    }
+
+    /// One side of a PR.
+    #[derive(Deserialize, Debug)]
+    pub struct PullRef {
+        #[serde(rename = "ref")]
+        pub branch: String,
+        /// None once the head repo has been deleted.
+        pub repo: Option<Repo>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub struct Pull {
+        pub number: u64,
+        pub node_id: String,
+        pub state: String,
+        #[serde(default)]
+        pub draft: bool,
+        pub merged_at: Option<String>,
+        pub html_url: String,
+        pub base: PullRef,
+        pub head: PullRef,
+    }
+
+    impl Pull {
+        pub fn is_open(&self) -> bool {
+            self.state == "open"
+        }
+
+        pub fn is_merged(&self) -> bool {
+            self.merged_at.is_some()
+        }
+    }
+
+    pub struct NewPull<'a> {
+        pub title: &'a str,
+        pub body: &'a str,
+        /// `branch`, or `owner:branch` for a PR from a fork.
+        pub head: &'a str,
+        pub base: &'a str,
+        pub draft: bool,
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub struct Milestone {
+        pub number: u64,
+        pub title: String,
+        pub description: Option<String>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub struct Label {
+        pub name: String,
+        pub color: String,
+        pub description: Option<String>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub struct File {
+        pub sha: String,
+        /// Base64, wrapped at 60 columns.
+        content: String,
+    }
+
+    impl File {
+        pub fn text(&self) -> Option<String> {
+            let packed: String = self.content.split_whitespace().collect();
+            let bytes = base64::engine::general_purpose::STANDARD.decode(packed).ok()?;
+            String::from_utf8(bytes).ok()
+        }
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub struct Enabled {
+        pub enabled: bool,
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub struct StatusChecks {
+        pub strict: bool,
+        pub contexts: Vec<String>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub struct Reviews {
+        #[serde(default)]
+        pub required_approving_review_count: u8,
+    }
+
+    /// GET /branches/{branch}/protection, the parts `publish` templates.
+    #[derive(Deserialize, Debug)]
+    pub struct Protection {
+        pub required_status_checks: Option<StatusChecks>,
+        pub enforce_admins: Option<Enabled>,
+        pub required_pull_request_reviews: Option<Reviews>,
+        pub allow_force_pushes: Option<Enabled>,
+    }
+
+    #[derive(Deserialize)]
+    struct Secret {
+        name: String,
+    }
+
+    #[derive(Deserialize)]
+    struct Secrets {
+        secrets: Vec<Secret>,
+    }
+
+    #[derive(Deserialize)]
+    struct SecretsKey {
+        key_id: String,
+        key: String,
+    }
+
+    /// Percent-encode `s` for a URL path segment (or query value).
+    pub fn escape(s: &str) -> String {
+        s.bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+                _ => format!("%{b:02X}"),
+            })
+            .collect()
+    }
+
+    /// Like `escape`, but keeping `/` for multi-segment paths (files, branch names).
+    pub fn escape_path(s: &str) -> String {
+        s.split('/').map(escape).collect::<Vec<_>>().join("/")
+    }
+
+    /// Quote `s` for a curl config file.
+    fn quote(s: &str) -> String {
+        let escaped = s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\r', "\\r").replace('\t', "\\t");
+        format!("\"{escaped}\"")
+    }
+
+    pub struct Client {
+        api: String,
+        token: String,
+    }
+
+    impl Client {
+        pub fn from_env() -> Result<Client, String> {
+            let api = std::env::var("GITHUB_API_URL").unwrap_or_else(|_| "https://api.github.com".to_string());
+            let token = match std::env::var("GITHUB_KEY") {
+                Ok(t) if !t.is_empty() => t,
+                _ => Command::new("gh")
+                    .args(["auth", "token"])
+                    .stderr(Stdio::null())
+                    .output()
+                    .ok()
+                    .filter(|o| o.status.success())
+                    .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .ok_or("no GitHub token: set GITHUB_KEY (or log in with `gh auth login`)")?,
+            };
+            Ok(Client { api: api.trim_end_matches('/').to_string(), token })
+        }
+
+        /// `method` `path` (relative to the API root) with an optional JSON body. An empty
+        /// response (204) decodes as JSON `null`, i.e. as `()`.
+        fn request<T: DeserializeOwned>(&self, method: &'static str, path: &str, body: Option<&Value>) -> Result<T, Error> {
+            // Token and body go through a config on stdin, so neither shows up in `ps`.
+            let mut config = format!(
+                "header = {}\nheader = \"Accept: application/vnd.github+json\"\nheader = \"X-GitHub-Api-Version: 2022-11-28\"\n",
+                quote(&format!("Authorization: Bearer {}", self.token))
+            );
+            if let Some(body) = body {
+                config.push_str("header = \"Content-Type: application/json\"\n");
+                config.push_str(&format!("data-binary = {}\n", quote(&body.to_string())));
+            }
+            let url = format!("{}{path}", self.api);
+            let mut child = Command::new("curl")
+                .args(["-sS", "-L", "-X", method, "--config", "-", "-w", "\n%{http_code}", &url])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .map_err(|e| Error::Curl { code: None, stderr: e.to_string() })?;
+            let _ = child.stdin.take().expect("piped stdin").write_all(config.as_bytes());
+            let out = child.wait_with_output().map_err(|e| Error::Curl { code: None, stderr: e.to_string() })?;
+            if !out.status.success() {
+                let stderr = String::from_utf8_lossy(&out.stderr).to_string();
+                return Err(Error::Curl { code: out.status.code(), stderr });
+            }
+
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            let (text, status) = stdout.rsplit_once('\n').unwrap_or(("", &stdout));
+            let status: u16 = status.trim().parse().unwrap_or(0);
+            let decode = |e: serde_json::Error| Error::Decode { path: path.to_string(), error: e.to_string() };
+            if !(200..300).contains(&status) {
+                // {"message": ..., "errors": [{"message": ...} | {"resource", "field", "code"}]}, or
+                // from a proxy or an outage, not JSON at all: then the text is the message.
+                let value: Value = serde_json::from_str(text).unwrap_or(Value::Null);
+                let mut message = value["message"].as_str().unwrap_or(text.trim()).to_string();
+                for e in value["errors"].as_array().into_iter().flatten() {
+                    let detail = e["message"].as_str().map(str::to_string).unwrap_or_else(|| {
+                        format!("{} {} {}", e["resource"].as_str().unwrap_or(""), e["field"].as_str().unwrap_or(""), e["code"].as_str().unwrap_or(""))
+                    });
+                    message.push_str(&format!("; {}", detail.trim()));
+                }
+                return Err(Error::Http { status, method, path: path.to_string(), message });
+            }
+            let value: Value = if text.trim().is_empty() { Value::Null } else { serde_json::from_str(text).map_err(decode)? };
+            serde_json::from_value(value).map_err(decode)
+        }
+
+        /// Every page of a GET list, 100 at a time until a short page; `items` takes a
+        /// page apart where the list is wrapped in an object.
+        fn all<P: DeserializeOwned, T>(&self, path: &str, items: impl Fn(P) -> Vec<T>) -> Result<Vec<T>, Error> {
+            let sep = if path.contains('?') { '&' } else { '?' };
+            let mut all = Vec::new();
+            for page in 1.. {
+                let batch = items(self.request("GET", &format!("{path}{sep}per_page=100&page={page}"), None)?);
+                let short = batch.len() < 100;
+                all.extend(batch);
+                if short {
+                    break;
+                }
+            }
+            Ok(all)
+        }
+
+        pub fn repo(&self, slug: &str) -> Result<Repo, Error> {
+            self.request("GET", &format!("/repos/{slug}"), None)
+        }
+
+        /// Fork `slug` into the authenticated account. GitHub creates forks asynchronously,
+        /// so this waits until the fork answers; an existing fork is simply returned.
+        pub fn fork(&self, slug: &str) -> Result<Repo, Error> {
+            let fork: Repo = self.request("POST", &format!("/repos/{slug}/forks"), Some(&json!({})))?;
+            let mut tries = 0;
+            loop {
+                match self.repo(&fork.full_name) {
+                    Err(e) if e.is_not_found() && tries < 30 => {
+                        tries += 1;
+                        std::thread::sleep(std::time::Duration::from_secs(1));
+                    }
+                    other => return other,
+                }
+            }
+        }
+
+        pub fn create_repo(&self, name: &str, private: bool) -> Result<Repo, Error> {
+            self.request("POST", "/user/repos", Some(&json!({ "name": name, "private": private })))
+        }
+
+        /// PRs from `head` (`owner:branch`), newest first.
+        pub fn pulls_from(&self, slug: &str, head: &str) -> Result<Vec<Pull>, Error> {
+            self.all(&format!("/repos/{slug}/pulls?state=all&head={}", escape(head)), |page: Vec<Pull>| page)
+        }
+
+        pub fn create_pull(&self, slug: &str, pull: &NewPull) -> Result<Pull, Error> {
+            let body = json!({ "title": pull.title, "body": pull.body, "head": pull.head, "base": pull.base, "draft": pull.draft });
+            self.request("POST", &format!("/repos/{slug}/pulls"), Some(&body))
+        }
+
+        pub fn set_pull_base(&self, slug: &str, number: u64, base: &str) -> Result<Pull, Error> {
+            self.request("PATCH", &format!("/repos/{slug}/pulls/{number}"), Some(&json!({ "base": base })))
+        }
+
+        /// Drafts can only be undrafted through GraphQL.
+        pub fn mark_ready(&self, pull: &Pull) -> Result<(), Error> {
+            let query = "mutation($id: ID!) { markPullRequestReadyForReview(input: {pullRequestId: $id}) { clientMutationId } }";
+            let response: Value = self.request("POST", "/graphql", Some(&json!({ "query": query, "variables": { "id": pull.node_id } })))?;
+            match response["errors"].as_array().and_then(|e| e.first()) {
+                Some(e) => Err(Error::Http {
+                    status: 200,
+                    method: "POST",
+                    path: "/graphql".to_string(),
+                    message: e["message"].as_str().unwrap_or("GraphQL error").to_string(),
+                }),
+                None => Ok(()),
+            }
+        }
+
+        /// Merge with a merge commit.
+        pub fn merge_pull(&self, slug: &str, number: u64) -> Result<(), Error> {
+            let _: Value = self.request("PUT", &format!("/repos/{slug}/pulls/{number}/merge"), Some(&json!({ "merge_method": "merge" })))?;
+            Ok(())
+        }
+
+        pub fn delete_branch(&self, slug: &str, branch: &str) -> Result<(), Error> {
+            self.request("DELETE", &format!("/repos/{slug}/git/refs/heads/{}", escape_path(branch)), None)
+        }
+
+        pub fn create_milestone(&self, slug: &str, title: &str, description: &str) -> Result<Milestone, Error> {
+            let body = json!({ "title": title, "state": "open", "description": description });
+            self.request("POST", &format!("/repos/{slug}/milestones"), Some(&body))
+        }
+
+        /// Actions secrets have to be sealed with the repo's public key before upload.
+        pub fn set_secret(&self, slug: &str, name: &str, value: &str) -> Result<(), Error> {
+            let key: SecretsKey = self.request("GET", &format!("/repos/{slug}/actions/secrets/public-key"), None)?;
+            let path = format!("/repos/{slug}/actions/secrets/{name}");
+            let bad_key = |error: String| Error::Decode { path: path.clone(), error };
+            let b64 = base64::engine::general_purpose::STANDARD;
+            let key_bytes = b64.decode(&key.key).map_err(|e| bad_key(format!("public key: {e}")))?;
+            let public = crypto_box::PublicKey::from_slice(&key_bytes).map_err(|e| bad_key(format!("public key: {e}")))?;
+            let sealed = public
+                .seal(&mut crypto_box::aead::OsRng, value.as_bytes())
+                .map_err(|e| bad_key(format!("sealing: {e}")))?;
+            let body = json!({ "encrypted_value": b64.encode(sealed), "key_id": key.key_id });
+            let _: Value = self.request("PUT", &path, Some(&body))?;
+            Ok(())
+        }
+        pub fn labels(&self, slug: &str) -> Result<Vec<Label>, Error> {
+            self.all(&format!("/repos/{slug}/labels"), |page: Vec<Label>| page)
+        }
+
+        pub fn create_label(&self, slug: &str, name: &str, color: &str, description: &str) -> Result<(), Error> {
+            let body = json!({ "name": name, "color": color, "description": description });
+            let _: Value = self.request("POST", &format!("/repos/{slug}/labels"), Some(&body))?;
+            Ok(())
+        }
+
+        /// Rename (e.g. to fix its case), recolor and redescribe the label now called `current`.
+        pub fn update_label(&self, slug: &str, current: &str, name: &str, color: &str, description: &str) -> Result<(), Error> {
+            let body = json!({ "new_name": name, "color": color, "description": description });
+            let _: Value = self.request("PATCH", &format!("/repos/{slug}/labels/{}", escape(current)), Some(&body))?;
+            Ok(())
+        }
+
+        pub fn delete_label(&self, slug: &str, name: &str) -> Result<(), Error> {
+            self.request("DELETE", &format!("/repos/{slug}/labels/{}", escape(name)), None)
+        }
+
+        /// Open and closed.
+        pub fn milestones(&self, slug: &str) -> Result<Vec<Milestone>, Error> {
+            self.all(&format!("/repos/{slug}/milestones?state=all"), |page: Vec<Milestone>| page)
+        }
+
+        pub fn set_milestone_description(&self, slug: &str, number: u64, description: &str) -> Result<(), Error> {
+            let body = json!({ "description": description });
+            let _: Value = self.request("PATCH", &format!("/repos/{slug}/milestones/{number}"), Some(&body))?;
+            Ok(())
+        }
+
+        /// A file on the default branch; None if there is none.
+        pub fn file(&self, slug: &str, path: &str) -> Result<Option<File>, Error> {
+            match self.request::<File>("GET", &format!("/repos/{slug}/contents/{}", escape_path(path)), None) {
+                Ok(file) => Ok(Some(file)),
+                Err(e) if e.is_not_found() => Ok(None),
+                Err(e) => Err(e),
+            }
+        }
+
+        /// Commit `content` as `path` on the default branch; `sha` is the blob it replaces.
+        pub fn put_file(&self, slug: &str, path: &str, content: &str, sha: Option<&str>, message: &str) -> Result<(), Error> {
+            let mut body = json!({ "message": message, "content": base64::engine::general_purpose::STANDARD.encode(content) });
+            if let Some(sha) = sha {
+                body["sha"] = json!(sha);
+            }
+            let _: Value = self.request("PUT", &format!("/repos/{slug}/contents/{}", escape_path(path)), Some(&body))?;
+            Ok(())
+        }
+
+        /// None when the branch isn't protected.
+        pub fn protection(&self, slug: &str, branch: &str) -> Result<Option<Protection>, Error> {
+            match self.request("GET", &format!("/repos/{slug}/branches/{}/protection", escape_path(branch)), None) {
+                Ok(p) => Ok(Some(p)),
+                Err(e) if e.is_not_found() => Ok(None),
+                Err(e) => Err(e),
+            }
+        }
+
+        pub fn protect(&self, slug: &str, branch: &str, body: &Value) -> Result<(), Error> {
+            let _: Value = self.request("PUT", &format!("/repos/{slug}/branches/{}/protection", escape_path(branch)), Some(body))?;
+            Ok(())
+        }
+
+        pub fn secret_names(&self, slug: &str) -> Result<Vec<String>, Error> {
+            self.all(&format!("/repos/{slug}/actions/secrets"), |page: Secrets| page.secrets.into_iter().map(|s| s.name).collect())
+        }
+
+        /// Also makes it the default branch if `from` was, and retargets open PRs.
+        pub fn rename_branch(&self, slug: &str, from: &str, to: &str) -> Result<(), Error> {
+            let body = json!({ "new_name": to });
+            let _: Value = self.request("POST", &format!("/repos/{slug}/branches/{}/rename", escape_path(from)), Some(&body))?;
+            Ok(())
+        }
+    }
+}
+
+/// `owner/name` from a GitHub remote URL, https or ssh.
+fn github_slug(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("github.com")?;
+    let slug = rest.trim_start_matches([':', '/']).trim_end_matches('/').trim_end_matches(".git");
+    (slug.split('/').count() == 2).then(|| slug.to_string())
+}
+
+fn remote_url(repo: &gix::Repository, name: &str) -> Option<String> {
+    repo.find_remote(name)
+        .ok()?
+        .url(gix::remote::Direction::Push)
+        .map(|u| u.to_bstring().to_string())
+}
+
+/// The GitHub side of the checkout: PRs go to `upstream` when this is a fork (as gh picks
+/// it), else to `origin`; they come from branches on `origin`, where we push.
+struct Hub {
+    api: github::Client,
+    base: String,
+    base_remote: &'static str,
+    head_owner: String,
+}
+
+impl Hub {
+    fn open(repo: &gix::Repository) -> Result<Hub, String> {
+        let origin = remote_url(repo, "origin")
+            .and_then(|u| github_slug(&u))
+            .ok_or("origin is not a GitHub repository")?;
+        let (base_remote, base) = match remote_url(repo, "upstream").and_then(|u| github_slug(&u)) {
+            Some(upstream) => ("upstream", upstream),
+            None => ("origin", origin.clone()),
+        };
+        let head_owner = origin.split('/').next().unwrap_or_default().to_string();
+        Ok(Hub { api: github::Client::from_env()?, base, base_remote, head_owner })
+    }
+
+    fn head(&self, branch: &str) -> String {
+        format!("{}:{branch}", self.head_owner)
+    }
+
+    /// The newest PR from `branch`, open or not.
+    fn pull(&self, branch: &str) -> Result<Option<github::Pull>, String> {
+        Ok(self.api.pulls_from(&self.base, &self.head(branch))?.into_iter().next())
+    }
+
+    fn default_branch(&self) -> Result<String, String> {
+        Ok(self.api.repo(&self.base)?.default_branch)
+    }
+
+    /// Title and body the way `gh pr create --fill` makes them: a lone commit's subject
+    /// and body, else the branch name over a list of the commits.
+    fn fill(&self, branch: &str, base: &str) -> (String, String) {
+        let range = format!("{}/{base}..{branch}", self.base_remote);
+        let log = run_cmd_output("git", &["log", "--reverse", "--format=%s%x1f%b%x1e", &range]).unwrap_or_default();
+        let commits: Vec<(&str, &str)> = log
+            .split('\x1e')
+            .filter_map(|c| c.trim().split_once('\x1f'))
+            .collect();
+        match commits.as_slice() {
+            [(subject, body)] => (subject.to_string(), body.trim().to_string()),
+            _ => (branch.to_string(), commits.iter().map(|(s, _)| format!("- {s}\n")).collect()),
+        }
+    }
+}
+
+fn open_hub(repo: &gix::Repository) -> Hub {
+    Hub::open(repo).unwrap_or_else(|e| {
+        eprintln!("ERROR: {e}");
+        std::process::exit(1);
+    })
+}
+
+/// What `publish` sets a repo up with and `publish --sync` holds it to: DEFAULT, with the
+/// keys of `$XDG_CONFIG_HOME/git_scripts/publish.toml` (or `--template`) read over it.
+mod template {
+    use serde::Deserialize;
+    use serde_json::{Value, json};
+    use std::collections::BTreeMap;
+    use std::path::{Path, PathBuf};
+
+    pub const DEFAULT: &str = r#"
+# Name of the default branch: publish pushes under it, --sync renames the repo's to it.
+# Unset = whatever the repo has.
+# default_branch = "master"
+
+# Labels, matched by name (case-insensitively); color is 6 hex digits. Labels the repo
+# has beyond these are reported, and deleted only with prune_labels = true. The set below
+# is what GitHub gives every new repo, plus `breaking`.
+prune_labels = false
+
+[[label]]
+name = "bug"
+color = "d73a4a"
+description = "Something isn't working"
+
+[[label]]
+name = "enhancement"
+color = "a2eeef"
+description = "New feature or request"
+
+[[label]]
+name = "documentation"
+color = "0075ca"
+description = "Improvements or additions to documentation"
+
+[[label]]
+name = "question"
+color = "d876e3"
+description = "Further information is requested"
+
+[[label]]
+name = "duplicate"
+color = "cfd3d7"
+description = "This issue or pull request already exists"
+
+[[label]]
+name = "good first issue"
+color = "7057ff"
+description = "Good for newcomers"
+
+[[label]]
+name = "help wanted"
+color = "008672"
+description = "Extra attention is needed"
+
+[[label]]
+name = "invalid"
+color = "e4e669"
+description = "This doesn't seem right"
+
+[[label]]
+name = "wontfix"
+color = "ffffff"
+description = "This will not be worked on"
+
+[[label]]
+name = "breaking"
+color = "b60205"
+description = "Changes public behavior"
+
+[[milestone]]
+title = "1.0"
+description = "Minimum viable product"
+
+[[milestone]]
+title = "2.0"
+description = "Fix bugs, rewrite hacks"
+
+[[milestone]]
+title = "3.0"
+description = "More and better"
+
+# Issue templates, kept at .github/ISSUE_TEMPLATE/<file>.md on the default branch.
+#
+# [[issue_template]]
+# file = "bug_report"
+# name = "Bug report"
+# about = "Something doesn't work"
+# labels = ["bug"]
+# body = """
+# **What happened, and what did you expect?**
+# """
+
+# Protection of the default branch. Unset = leave it as it is.
+#
+# [protection]
+# required_status_checks = ["ci"]  # checks that must pass before merging
+# strict = true                    # ...on a branch that is up to date
+# required_approvals = 1           # unset = no reviews needed
+# enforce_admins = false
+# allow_force_pushes = false
+
+# Actions secrets: secret name = environment variable with its value. GitHub never shows
+# secret values back, so --sync only sets the ones the repo is missing.
+[secrets]
+loc_gist_token = "GITHUB_LOC_GIST" # LoC badge generation
+"#;
+
+    #[derive(Deserialize, Default)]
+    #[serde(deny_unknown_fields)]
+    struct Raw {
+        default_branch: Option<String>,
+        prune_labels: Option<bool>,
+        label: Option<Vec<Label>>,
+        milestone: Option<Vec<Milestone>>,
+        issue_template: Option<Vec<IssueTemplate>>,
+        protection: Option<Protection>,
+        secrets: Option<BTreeMap<String, String>>,
+    }
+
+    #[derive(Deserialize, Clone, Debug)]
+    #[serde(deny_unknown_fields)]
+    pub struct Label {
+        pub name: String,
+        pub color: String,
+        #[serde(default)]
+        pub description: String,
+    }
+
+    #[derive(Deserialize, Clone, Debug)]
+    #[serde(deny_unknown_fields)]
+    pub struct Milestone {
+        pub title: String,
+        #[serde(default)]
+        pub description: String,
+    }
+
+    #[derive(Deserialize, Clone, Debug)]
+    #[serde(deny_unknown_fields)]
+    pub struct IssueTemplate {
+        pub file: String,
+        pub name: String,
+        pub about: String,
+        #[serde(default)]
+        pub title: String,
+        #[serde(default)]
+        pub labels: Vec<String>,
+        #[serde(default)]
+        pub body: String,
+    }
+
+    #[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+    #[serde(deny_unknown_fields)]
+    pub struct Protection {
+        #[serde(default)]
+        pub required_status_checks: Vec<String>,
+        #[serde(default)]
+        pub strict: bool,
+        pub required_approvals: Option<u8>,
+        #[serde(default)]
+        pub enforce_admins: bool,
+        #[serde(default)]
+        pub allow_force_pushes: bool,
+    }
+
+    #[derive(Debug)]
+    pub struct Template {
+        pub default_branch: Option<String>,
+        pub prune_labels: bool,
+        pub labels: Vec<Label>,
+        pub milestones: Vec<Milestone>,
+        pub issue_templates: Vec<IssueTemplate>,
+        pub protection: Option<Protection>,
+        /// (secret name, environment variable)
+        pub secrets: Vec<(String, String)>,
+    }
+
+    impl IssueTemplate {
+        pub fn path(&self) -> String {
+            format!(".github/ISSUE_TEMPLATE/{}.md", self.file)
+        }
+
+        /// The file as GitHub reads it: front matter, then the body.
+        pub fn render(&self) -> String {
+            let quoted = |s: &str| format!("'{}'", s.replace('\'', "''"));
+            format!(
+                "---\nname: {}\nabout: {}\ntitle: {}\nlabels: {}\n---\n{}",
+                quoted(&self.name),
+                quoted(&self.about),
+                quoted(&self.title),
+                quoted(&self.labels.join(", ")),
+                self.body.trim_start_matches('\n')
+            )
+        }
+    }
+
+    impl Protection {
+        /// The PUT /branches/{branch}/protection payload.
+        pub fn body(&self) -> Value {
+            let checks = (!self.required_status_checks.is_empty() || self.strict)
+                .then(|| json!({ "strict": self.strict, "contexts": self.required_status_checks }));
+            let reviews = self.required_approvals.map(|n| json!({ "required_approving_review_count": n }));
+            json!({
+                "required_status_checks": checks,
+                "enforce_admins": self.enforce_admins,
+                "required_pull_request_reviews": reviews,
+                "restrictions": null,
+                "allow_force_pushes": self.allow_force_pushes,
+            })
+        }
+
+        pub fn describe(&self) -> String {
+            let mut parts = Vec::new();
+            if !self.required_status_checks.is_empty() {
+                let strict = if self.strict { ", up to date" } else { "" };
+                parts.push(format!("checks [{}]{strict}", self.required_status_checks.join(", ")));
+            }
+            if let Some(n) = self.required_approvals {
+                parts.push(format!("{n} approval(s)"));
+            }
+            if self.enforce_admins {
+                parts.push("admins included".to_string());
+            }
+            parts.push(if self.allow_force_pushes { "force pushes allowed" } else { "no force pushes" }.to_string());
+            parts.join(", ")
+        }
+    }
+
+    fn default_path() -> PathBuf {
+        std::env::var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|_| std::env::var("HOME").map(|h| PathBuf::from(h).join(".config")))
+            .unwrap_or_default()
+            .join("git_scripts/publish.toml")
+    }
+
+    fn color(label: &Label) -> Result<String, String> {
+        let c = label.color.trim_start_matches('#').to_ascii_lowercase();
+        if c.len() == 6 && c.chars().all(|ch| ch.is_ascii_hexdigit()) {
+            Ok(c)
+        } else {
+            Err(format!("label {:?}: color {:?} is not 6 hex digits", label.name, label.color))
+        }
+    }
+
+    impl Template {
+        /// DEFAULT, with the file's keys read over it. An explicit path must exist; the
+        /// default one may not.
+        pub fn load(explicit: Option<&Path>) -> Result<Template, String> {
+            let path = explicit.map(Path::to_path_buf).unwrap_or_else(default_path);
+            match std::fs::read_to_string(&path) {
+                Ok(text) => Template::parse(&text).map_err(|e| format!("{}: {e}", path.display())),
+                Err(e) if explicit.is_none() && e.kind() == std::io::ErrorKind::NotFound => Template::parse(""),
+                Err(e) => Err(format!("{}: {e}", path.display())),
+            }
+        }
+
+        /// DEFAULT, with `text`'s keys read over it.
+        pub fn parse(text: &str) -> Result<Template, String> {
+            let user: Raw = toml::from_str(text).map_err(|e| e.to_string())?;
+            let base: Raw = toml::from_str(DEFAULT).expect("template::DEFAULT parses");
+            let labels = user
+                .label
+                .or(base.label)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|l| Ok(Label { color: color(&l)?, ..l }))
+                .collect::<Result<_, String>>()?;
+            let issue_templates: Vec<IssueTemplate> = user.issue_template.or(base.issue_template).unwrap_or_default();
+            if let Some(bad) = issue_templates.iter().find(|t| t.file.is_empty() || t.file.contains('/')) {
+                return Err(format!("issue_template {:?}: file must be a plain file name", bad.name));
+            }
+            Ok(Template {
+                default_branch: user.default_branch.or(base.default_branch),
+                prune_labels: user.prune_labels.or(base.prune_labels).unwrap_or(false),
+                labels,
+                milestones: user.milestone.or(base.milestone).unwrap_or_default(),
+                issue_templates,
+                protection: user.protection.or(base.protection).map(|mut p| {
+                    p.required_status_checks.sort();
+                    p
+                }),
+                secrets: user.secrets.or(base.secrets).unwrap_or_default().into_iter().collect(),
+            })
+        }
+    }
+}
+
 /// Equivalent to `gg` fish function: git add -A && git commit -am "msg" && git push
 fn run_gg(message: &[String]) {
     let msg = if message.is_empty() {
@@ -217,12 +1101,7 @@ fn fork(message: Vec<String>) {
         }
     };
 
-    let origin_url = match repo.find_remote("origin") {
-        Ok(remote) => remote.url(gix::remote::Direction::Push).map(|u| u.to_bstring().to_string()),
-        Err(_) => None,
-    };
-
-    let origin_url = match origin_url {
+    let origin_url = match remote_url(&repo, "origin") {
         Some(url) => url,
         None => {
             eprintln!("ERROR: No origin remote found");
@@ -246,27 +1125,22 @@ fn fork(message: Vec<String>) {
         return;
     }
 
-    // Extract repo name: strip .git suffix, take everything after last /
-    let repo_name = origin_url
-        .trim_end_matches(".git")
-        .rsplit('/')
-        .next()
-        .unwrap_or("");
-
-    if repo_name.is_empty() {
-        eprintln!("ERROR: Could not extract repo name from origin URL: {origin_url}");
+    let Some(slug) = github_slug(&origin_url) else {
+        eprintln!("ERROR: origin is not a GitHub repository: {origin_url}");
         std::process::exit(1);
-    }
+    };
 
     println!("Forking repository...");
-    // gh repo fork creates fork if needed, is idempotent
-    let _ = Command::new("gh")
-        .args(["repo", "fork", "--remote=false"])
-        .stdout(Stdio::null())
-        .status();
+    // Idempotent: an existing fork is returned as is
+    let fork_url = match open_hub(&repo).api.fork(&slug) {
+        Ok(fork) => fork.clone_url,
+        Err(e) => {
+            eprintln!("ERROR: Failed to fork {slug}: {e}");
+            std::process::exit(1);
+        }
+    };
 
     // Setup remotes: upstream = original, origin = fork
-    let fork_url = format!("https://github.com/{github_name}/{repo_name}.git");
 
     // Check if upstream already exists
     let has_upstream = remote_exists(&repo, "upstream");
@@ -295,21 +1169,6 @@ fn fork(message: Vec<String>) {
     run_gg(&message);
 }
 
-fn get_default_branch() -> Option<String> {
-    // Try to get the default branch from GitHub via gh CLI
-    run_cmd_output(
-        "gh",
-        &[
-            "repo",
-            "view",
-            "--json",
-            "defaultBranchRef",
-            "-q",
-            ".defaultBranchRef.name",
-        ],
-    )
-}
-
 fn pr(target_branch: Option<String>, draft: bool) {
     let repo = open_repo();
 
@@ -321,44 +1180,41 @@ fn pr(target_branch: Option<String>, draft: bool) {
         }
     };
 
+    let hub = open_hub(&repo);
+    let fail = |what: &str, e: String| -> ! {
+        eprintln!("ERROR: {what}: {e}");
+        std::process::exit(1);
+    };
+
     // Resolve target branch: use provided value, or detect default branch
     let target_branch = match target_branch {
         Some(b) => b,
-        None => match get_default_branch() {
-            Some(b) => {
+        None => match hub.default_branch() {
+            Ok(b) => {
                 println!("Using default branch: {b}");
                 b
             }
-            None => {
-                eprintln!("ERROR: Could not detect default branch. Please specify target branch.");
-                std::process::exit(1);
-            }
+            Err(e) => fail("Could not detect default branch (specify the target branch)", e),
         },
     };
 
+    let existing = hub
+        .pull(&current_branch)
+        .unwrap_or_else(|e| fail("Could not look up PRs", e))
+        .filter(|p| p.is_open());
+
     // Draft mode: just create a draft PR and exit
     if draft {
-        let args = vec![
-            "pr",
-            "create",
-            "--draft",
-            "--fill",
-            "--head",
-            &current_branch,
-            "-B",
-            &target_branch,
-        ];
+        if let Some(existing) = existing {
+            println!("Draft PR already exists: {}", existing.html_url);
+            return;
+        }
         println!("Creating draft PR for branch '{current_branch}' -> '{target_branch}'");
-        let output = Command::new("gh").args(args).output();
-        match output {
-            Ok(o) if o.status.success() => {}
-            Ok(o) if String::from_utf8_lossy(&o.stderr).contains("already exists") => {
-                println!("Draft PR already exists");
-            }
-            _ => {
-                eprintln!("ERROR: Failed to create draft PR");
-                std::process::exit(1);
-            }
+        let (title, body) = hub.fill(&current_branch, &target_branch);
+        let new = github::NewPull { title: &title, body: &body, head: &hub.head(&current_branch), base: &target_branch, draft: true };
+        match hub.api.create_pull(&hub.base, &new) {
+            Ok(created) => println!("{}", created.html_url),
+            Err(e) => fail("Failed to create draft PR", e.into()),
         }
         return;
     }
@@ -368,100 +1224,35 @@ fn pr(target_branch: Option<String>, draft: bool) {
         std::process::exit(1);
     }
 
-    // Check if a PR already exists for this branch
-    let existing_pr = Command::new("gh")
-        .args(["pr", "view", "--json", "number,isDraft,baseRefName"])
-        .output();
-
-    let (pr_exists, is_draft, existing_base) = match &existing_pr {
-        Ok(o) if o.status.success() => {
-            let json_str = String::from_utf8_lossy(&o.stdout);
-            let is_draft = json_str.contains("\"isDraft\":true");
-            let base = json_str
-                .split("\"baseRefName\":\"")
-                .nth(1)
-                .and_then(|s| s.split('"').next())
-                .map(|s| s.to_string());
-            (true, is_draft, base)
-        }
-        _ => (false, false, None),
-    };
-
-    if pr_exists {
-        // Check if existing PR targets same branch
-        if existing_base.as_deref() != Some(&target_branch) {
-            eprintln!(
-                "ERROR: Existing PR targets '{}', but you specified '{target_branch}'",
-                existing_base.as_deref().unwrap_or("unknown")
-            );
-            std::process::exit(1);
-        }
-
-        if is_draft {
-            println!("Found existing draft PR, marking ready for review...");
-            if !run_cmd("gh", &["pr", "ready"]) {
-                eprintln!("ERROR: Failed to mark PR as ready");
+    let pr = match existing {
+        Some(existing) => {
+            // Check if existing PR targets same branch
+            if existing.base.branch != target_branch {
+                eprintln!(
+                    "ERROR: Existing PR targets '{}', but you specified '{target_branch}'",
+                    existing.base.branch
+                );
                 std::process::exit(1);
             }
-        } else {
-            println!("PR already exists, proceeding to merge...");
-        }
-    } else {
-        println!("Creating PR: {current_branch} -> {target_branch}");
-
-        // Try to create the PR (use --head to avoid push detection issues)
-        let pr_create_output = Command::new("gh")
-            .args([
-                "pr",
-                "create",
-                "-B",
-                &target_branch,
-                "-f",
-                "-t",
-                &current_branch,
-                "--head",
-                &current_branch,
-            ])
-            .stdin(Stdio::null())
-            .output();
-
-        if !pr_create_output
-            .map(|o| o.status.success())
-            .unwrap_or(false)
-        {
-            eprintln!("ERROR: Failed to create PR");
-            std::process::exit(1);
+            if existing.draft {
+                println!("Found existing draft PR, marking ready for review...");
+                if let Err(e) = hub.api.mark_ready(&existing) {
+                    fail("Failed to mark PR as ready", e.into());
+                }
+            } else {
+                println!("PR already exists, proceeding to merge...");
+            }
+            existing
         }
-    }
-
-    // Get PR number for the current branch (matches by head branch, not title)
-    let pr_view_output = Command::new("gh")
-        .args(["pr", "view", "--json", "number"])
-        .output();
-
-    let pr_number = match pr_view_output {
-        Ok(output) if output.status.success() => {
-            let json_str = String::from_utf8_lossy(&output.stdout);
-            // Format: {"number":123}
-            json_str
-                .split("\"number\":")
-                .nth(1)
-                .and_then(|s| s.split(|c: char| !c.is_ascii_digit()).next())
-                .filter(|s| !s.is_empty())
-                .map(|s| s.to_string())
-        }
-        _ => None,
-    };
-
-    let pr_number = match pr_number {
-        Some(n) => n,
         None => {
-            eprintln!("ERROR: Could not find PR number for '{current_branch}'");
-            std::process::exit(1);
+            println!("Creating PR: {current_branch} -> {target_branch}");
+            let (_, body) = hub.fill(&current_branch, &target_branch);
+            let new = github::NewPull { title: &current_branch, body: &body, head: &hub.head(&current_branch), base: &target_branch, draft: false };
+            hub.api.create_pull(&hub.base, &new).unwrap_or_else(|e| fail("Failed to create PR", e.into()))
         }
     };
 
-    println!("Merging PR #{pr_number}");
+    println!("Merging PR #{}", pr.number);
 
     // Checkout target branch
     if !run_cmd("git", &["checkout", &target_branch]) {
@@ -469,16 +1260,17 @@ fn pr(target_branch: Option<String>, draft: bool) {
         std::process::exit(1);
     }
 
-    // Merge the PR with delete and merge commit
-    let merge_status = Command::new("gh")
-        .args(["pr", "merge", "-dm", &pr_number])
-        .stdin(Stdio::null())
-        .status();
-
-    if !merge_status.map(|s| s.success()).unwrap_or(false) {
-        eprintln!("ERROR: Failed to merge PR");
-        std::process::exit(1);
+    // Merge with a merge commit, then delete the branch on both ends
+    if let Err(e) = hub.api.merge_pull(&hub.base, pr.number) {
+        fail("Failed to merge PR", e.into());
+    }
+    if let Some(head_repo) = &pr.head.repo
+        && let Err(e) = hub.api.delete_branch(&head_repo.full_name, &current_branch)
+    {
+        eprintln!("WARNING: Failed to delete remote branch {current_branch}: {e}");
     }
+    run_cmd_status("git", &["branch", "-D", &current_branch]);
+    run_cmd_status("git", &["branch", "-dr", &format!("origin/{current_branch}")]);
 
     // Pull to get the merge commit locally
     run_cmd("git", &["pull"]);
@@ -723,91 +1515,71 @@ fn remote_tree_in_local_history(
     false
 }
 
-fn push(force_with_lease: bool, force: bool, dry_run: bool, extra_args: Vec<String>) {
-    let repo = open_repo();
-
-    let branch = match repo.head_name() {
-        Ok(Some(name)) => name.shorten().to_string(),
-        _ => {
-            eprintln!("ERROR: Could not get current branch (detached HEAD?)");
-            std::process::exit(1);
-        }
-    };
-
-    // Fetch the remote branch to ensure we have up-to-date refs for comparison
+/// Fetch origin's copy of `branch`; None if origin doesn't have it yet.
+fn fetch_remote_tip(branch: &str) -> Result<Option<gix::ObjectId>, String> {
     let fetch_refspec = format!("{branch}:refs/remotes/origin/{branch}");
-    if let Err(e) = gix_fetch(&repo, &fetch_refspec) {
-        eprintln!("ERROR: Failed to fetch origin/{branch}: {e}");
-        std::process::exit(1);
-    }
-
+    gix_fetch(&open_repo(), &fetch_refspec).map_err(|e| format!("Failed to fetch origin/{branch}: {e}"))?;
     // Re-open repo to get fresh refs after fetch
-    let repo = open_repo();
-
-    // Get local HEAD commit
-    let local_commit = match repo.head_commit() {
-        Ok(c) => c.id,
-        Err(e) => {
-            eprintln!("ERROR: Could not get HEAD commit: {e}");
-            std::process::exit(1);
-        }
-    };
+    Ok(ref_tip(&open_repo(), &format!("refs/remotes/origin/{branch}")))
+}
 
-    // Get remote commit
-    let remote_ref_name = format!("refs/remotes/origin/{branch}");
-    let remote_commit = repo
-        .find_reference(&remote_ref_name)
+fn ref_tip(repo: &gix::Repository, name: &str) -> Option<gix::ObjectId> {
+    repo.find_reference(name)
         .ok()
         .and_then(|mut r| r.peel_to_id().ok())
-        .map(|id| id.detach());
-
-    let remote_commit = match remote_commit {
-        Some(c) => c,
-        None => {
-            // No remote branch yet, just push normally
-            let extra_refs: Vec<&str> = extra_args.iter().map(|s| s.as_str()).collect();
-            let mut args = vec!["push", "--follow-tags"];
-            args.extend(extra_refs);
-            if dry_run {
-                println!("DRY-RUN: git {}", args.join(" "));
-                return;
-            }
-            if !run_cmd("git", &args) {
-                std::process::exit(1);
-            }
-            return;
-        }
-    };
-
-    let use_force = force;
-    let mut use_force_with_lease = force_with_lease;
-    let explicit_force = force_with_lease || force;
+        .map(|id| id.detach())
+}
 
-    // Get tree IDs
-    let local_tree = repo
-        .head_commit()
-        .ok()
-        .and_then(|c| c.tree_id().ok())
-        .map(|id| id.detach());
-    let remote_tree = repo
-        .find_object(remote_commit)
+fn commit_tree(repo: &gix::Repository, commit: gix::ObjectId) -> Option<gix::ObjectId> {
+    repo.find_object(commit)
         .ok()
         .and_then(|o| o.peel_to_commit().ok())
         .and_then(|c| c.tree_id().ok())
-        .map(|id| id.detach());
-
-    // Check ancestor relationships
-    let remote_is_ancestor = is_ancestor(&repo, remote_commit, local_commit);
-    let local_is_ancestor = is_ancestor(&repo, local_commit, remote_commit);
+        .map(|id| id.detach())
+}
 
-    // Determine if force push would be safe
-    let trees_match = matches!((&local_tree, &remote_tree), (Some(l), Some(r)) if l == r);
+/// The force-push safety checks for local vs. origin's copy, kept separately so
+/// `push --explain` can show which of them held.
+struct Safety {
+    remote_is_ancestor: bool,
+    local_is_ancestor: bool,
+    trees_match: bool,
+    /// Checks 1-5, only run when histories diverged.
+    tree_in_history: bool,
+    merge_would_be_clean: bool,
+    no_content_diff: bool,
+    fixup_squash: bool,
+    subjects_match: bool,
+}
 
-    // Check if we actually need a force push (histories diverged)
-    let needs_force = !local_is_ancestor && !remote_is_ancestor;
+impl Safety {
+    fn check(repo: &gix::Repository, local_commit: gix::ObjectId, remote_commit: gix::ObjectId) -> Safety {
+        // Get tree IDs
+        let local_tree = commit_tree(repo, local_commit);
+        let remote_tree = commit_tree(repo, remote_commit);
+
+        // Check ancestor relationships
+        let remote_is_ancestor = is_ancestor(repo, remote_commit, local_commit);
+        let local_is_ancestor = is_ancestor(repo, local_commit, remote_commit);
+
+        // Determine if force push would be safe
+        let trees_match = matches!((&local_tree, &remote_tree), (Some(l), Some(r)) if l == r);
+
+        let mut safety = Safety {
+            remote_is_ancestor,
+            local_is_ancestor,
+            trees_match,
+            tree_in_history: false,
+            merge_would_be_clean: false,
+            no_content_diff: false,
+            fixup_squash: false,
+            subjects_match: false,
+        };
+        if !safety.needs_force() {
+            return safety;
+        }
 
-    // Check if local contains remote's content
-    let local_contains_remote_content = needs_force && {
+        // Check if local contains remote's content
         let (local_tree, remote_tree) = match (&local_tree, &remote_tree) {
             (Some(l), Some(r)) => (*l, *r),
             _ => {
@@ -817,34 +1589,25 @@ fn push(force_with_lease: bool, force: bool, dry_run: bool, extra_args: Vec<Stri
         };
 
         // Check 1: exact tree match in history
-        let tree_in_history = remote_tree_in_local_history(&repo, local_commit, remote_tree);
+        safety.tree_in_history = remote_tree_in_local_history(repo, local_commit, remote_tree);
 
         // Check 2: merging remote into local would be conflict-free
         // Find merge-base to use as ancestor for three-way merge simulation
         let merge_base: Option<gix::ObjectId> = repo.merge_base(local_commit, remote_commit).ok().map(|id| id.detach());
-        let merge_would_be_clean = merge_base
-            .map(|base| {
-                let base_tree = repo
-                    .find_object(base)
-                    .ok()
-                    .and_then(|o| o.peel_to_commit().ok())
-                    .and_then(|c| c.tree_id().ok())
-                    .map(|id| id.detach());
-
-                match base_tree {
-                    Some(base_tree) => !merge_would_conflict(&repo, base_tree, local_tree, remote_tree),
-                    None => false,
-                }
+        safety.merge_would_be_clean = merge_base
+            .map(|base| match commit_tree(repo, base) {
+                Some(base_tree) => !merge_would_conflict(repo, base_tree, local_tree, remote_tree),
+                None => false,
             })
             .unwrap_or(false);
 
         // Check 3: no actual content difference (just history rewrite)
-        let no_content_diff = trees_have_same_content(&repo, local_tree, remote_tree);
+        safety.no_content_diff = trees_have_same_content(repo, local_tree, remote_tree);
 
         // Check 4: fixup-squash — every change remote made (vs merge base) is already
         // present in local's tree, meaning local squashed remote's work plus more on top.
-        let fixup_squash = merge_base
-            .map(|base| remote_changes_subsumed_by_local(&repo, base, local_tree, remote_tree))
+        safety.fixup_squash = merge_base
+            .map(|base| remote_changes_subsumed_by_local(repo, base, local_tree, remote_tree))
             .unwrap_or(false);
 
         // Check 5: every remote commit subject (since merge-base) has a local
@@ -852,40 +1615,324 @@ fn push(force_with_lease: bool, force: bool, dry_run: bool, extra_args: Vec<Stri
         // Catches the gups case where a fixup overwrote the same lines as its
         // target — blob OIDs differ so check 4 fails, and 3-way merge conflicts
         // so check 2 fails, but autosquash preserved the subject.
-        let subjects_match = merge_base
-            .map(|base| subjects_subsume(&repo, base, local_commit, remote_commit))
+        safety.subjects_match = merge_base
+            .map(|base| subjects_subsume(repo, base, local_commit, remote_commit))
             .unwrap_or(false);
 
-        tree_in_history || merge_would_be_clean || no_content_diff || fixup_squash || subjects_match
+        safety
+    }
+
+    /// Histories diverged, so a plain push can't go through.
+    fn needs_force(&self) -> bool {
+        !self.local_is_ancestor && !self.remote_is_ancestor
+    }
+
+    fn local_contains_remote_content(&self) -> bool {
+        self.needs_force()
+            && (self.tree_in_history || self.merge_would_be_clean || self.no_content_diff || self.fixup_squash || self.subjects_match)
+    }
+
+    /// The force flag a push gets, with the line to tell the user about it, or why
+    /// it's refused. Explicit force on main branches is only let through when harmless;
+    /// without one, diverged histories get --force-with-lease when local provably
+    /// contains everything remote has.
+    fn decide(
+        &self,
+        branch: &str,
+        remote_commit: gix::ObjectId,
+        force_with_lease: bool,
+        force: bool,
+    ) -> Result<(Option<String>, Option<String>), String> {
+        let mut use_force_with_lease = force_with_lease;
+        let explicit_force = force_with_lease || force;
+        let mut note = None;
+
+        if explicit_force && is_main_branch(branch) {
+            // User explicitly requested force - check if it's safe on main branches
+            if self.trees_match || self.remote_is_ancestor || self.local_contains_remote_content() {
+                note = Some(format!("Safe force push on {branch}: local contains all remote content."));
+            } else {
+                return Err(format!("Refusing to force push {branch} (would lose remote content not in local)"));
+            }
+        } else if !explicit_force && self.needs_force() {
+            // No explicit force flag, but histories diverged - auto-force if safe
+            if self.trees_match || self.local_contains_remote_content() {
+                note = Some("Local contains all remote content - auto-enabling force-with-lease.".to_string());
+                use_force_with_lease = true;
+            }
+            // If not safe, let git push fail naturally with its error message
+        }
+
+        let arg = if force {
+            Some("--force".to_string())
+        } else if use_force_with_lease {
+            // Use explicit expected value to avoid "stale info" error after fetch
+            Some(format!("--force-with-lease=refs/heads/{branch}:{remote_commit}"))
+        } else {
+            None
+        };
+        Ok((arg, note))
+    }
+}
+
+/// The force flag (if any) a push of `branch` at `local_commit` over origin's
+/// `remote_commit` gets, or why it's refused; see [`Safety::decide`].
+fn force_arg(
+    repo: &gix::Repository,
+    branch: &str,
+    local_commit: gix::ObjectId,
+    remote_commit: gix::ObjectId,
+    force_with_lease: bool,
+    force: bool,
+) -> Result<Option<String>, String> {
+    let (arg, note) = Safety::check(repo, local_commit, remote_commit).decide(branch, remote_commit, force_with_lease, force)?;
+    if let Some(note) = note {
+        println!("{note}");
+    }
+    Ok(arg)
+}
+
+/// A remote commit that pushing over origin's copy would drop from its history.
+struct LostCommit {
+    id: gix::ObjectId,
+    subject: String,
+    /// Why it's provably preserved locally; None means it's at risk.
+    preserved_by: Option<String>,
+    /// (path, added, removed) against its first parent; counts are None for binary files.
+    files: Vec<(String, Option<u64>, Option<u64>)>,
+}
+
+/// `git diff --numstat` of `commit` against its first parent (or the empty tree).
+fn diffstat(repo: &gix::Repository, commit: gix::ObjectId) -> Vec<(String, Option<u64>, Option<u64>)> {
+    let id = commit.to_string();
+    let parent = repo
+        .find_object(commit)
+        .ok()
+        .and_then(|o| o.peel_to_commit().ok())
+        .and_then(|c| c.parent_ids().next().map(|p| p.to_string()));
+    let out = match &parent {
+        Some(p) => run_cmd_output("git", &["diff", "--numstat", p, &id]),
+        None => run_cmd_output("git", &["diff-tree", "--numstat", "-r", "--root", "--no-commit-id", &id]),
+    };
+    out.unwrap_or_default()
+        .lines()
+        .filter_map(|l| {
+            let mut parts = l.splitn(3, '\t');
+            let (added, removed, path) = (parts.next()?, parts.next()?, parts.next()?);
+            Some((path.to_string(), added.parse().ok(), removed.parse().ok()))
+        })
+        .collect()
+}
+
+/// Every commit reachable from `remote` but not `local`, newest first, each checked
+/// against the same evidence `Safety` uses: identical tree, tree somewhere in local
+/// history, or a local commit with the same subject once autosquash prefixes are gone.
+fn lost_commits(repo: &gix::Repository, local: gix::ObjectId, remote: gix::ObjectId) -> Vec<LostCommit> {
+    let local_tree = commit_tree(repo, local);
+    let base = repo.merge_base(local, remote).ok().map(|id| id.detach());
+    let subject_of = |id: gix::ObjectId| -> String {
+        repo.find_object(id)
+            .ok()
+            .and_then(|o| o.try_into_commit().ok())
+            .and_then(|c| c.message().ok().map(|m| m.summary().to_string()))
+            .unwrap_or_default()
+    };
+    let walk = |tip: gix::ObjectId, hidden: Option<gix::ObjectId>| -> Vec<gix::ObjectId> {
+        repo.rev_walk([tip])
+            .with_hidden(hidden)
+            .all()
+            .map(|w| w.filter_map(|r| r.ok()).map(|info| info.id().detach()).collect())
+            .unwrap_or_default()
     };
+    let local_subjects: Vec<(gix::ObjectId, String)> = walk(local, base).into_iter().map(|id| (id, subject_of(id))).collect();
+
+    walk(remote, Some(local))
+        .into_iter()
+        .map(|id| {
+            let subject = subject_of(id);
+            let tree = commit_tree(repo, id);
+            let preserved_by = if tree.is_some() && tree == local_tree {
+                Some("same tree as local tip".to_string())
+            } else if tree.is_some_and(|t| remote_tree_in_local_history(repo, local, t)) {
+                Some("tree appears in local history".to_string())
+            } else {
+                local_subjects
+                    .iter()
+                    .find(|(_, s)| strip_autosquash_prefix(s) == strip_autosquash_prefix(&subject))
+                    .map(|(l, _)| format!("same subject as local {}", l.to_hex_with_len(8)))
+            };
+            LostCommit { files: diffstat(repo, id), id, subject, preserved_by }
+        })
+        .collect()
+}
 
-    if explicit_force && is_main_branch(&branch) {
-        // User explicitly requested force - check if it's safe on main branches
-        if trees_match || remote_is_ancestor || local_contains_remote_content {
-            println!("Safe force push on {branch}: local contains all remote content.");
+/// `push --explain`: what a push of `branch` would do to origin's copy and why, as
+/// text or JSON. Returns the exit code: 2 when any remote commit is at risk.
+fn explain_push(
+    repo: &gix::Repository,
+    branch: &str,
+    local: gix::ObjectId,
+    remote: Option<gix::ObjectId>,
+    force_with_lease: bool,
+    force: bool,
+    json: bool,
+) -> i32 {
+    let Some(remote) = remote else {
+        if json {
+            let report = serde_json::json!({
+                "branch": branch, "local": local.to_string(), "remote": null, "relation": "new",
+                "lost": [], "at_risk": 0, "decision": { "force": null, "refused": null },
+            });
+            println!("{report}");
         } else {
-            eprintln!("Refusing to force push {branch} (would lose remote content not in local)");
+            println!("origin has no {branch} yet: plain push, nothing to lose");
+        }
+        return 0;
+    };
+
+    let safety = Safety::check(repo, local, remote);
+    let relation = match (local == remote, safety.remote_is_ancestor, safety.local_is_ancestor) {
+        (true, ..) => "same",
+        (_, true, _) => "ahead",
+        (_, _, true) => "behind",
+        _ => "diverged",
+    };
+    let lost = lost_commits(repo, local, remote);
+    let at_risk = lost.iter().filter(|c| c.preserved_by.is_none()).count();
+    let decision = safety.decide(branch, remote, force_with_lease, force);
+    let checks = [
+        ("tree_in_history", safety.tree_in_history),
+        ("merge_would_be_clean", safety.merge_would_be_clean),
+        ("no_content_diff", safety.no_content_diff),
+        ("fixup_squash", safety.fixup_squash),
+        ("subjects_match", safety.subjects_match),
+    ];
+
+    if json {
+        let lost: Vec<serde_json::Value> = lost
+            .iter()
+            .map(|c| {
+                let files: Vec<serde_json::Value> = c
+                    .files
+                    .iter()
+                    .map(|(path, added, removed)| serde_json::json!({ "path": path, "added": added, "removed": removed }))
+                    .collect();
+                serde_json::json!({
+                    "id": c.id.to_string(), "subject": c.subject,
+                    "preserved": c.preserved_by.is_some(), "preserved_by": c.preserved_by, "files": files,
+                })
+            })
+            .collect();
+        let (force_arg, refused) = match &decision {
+            Ok((arg, _)) => (arg.clone(), None),
+            Err(e) => (None, Some(e.clone())),
+        };
+        let report = serde_json::json!({
+            "branch": branch, "local": local.to_string(), "remote": remote.to_string(), "relation": relation,
+            "checks": checks.iter().map(|(k, v)| (k.to_string(), serde_json::Value::Bool(*v))).collect::<serde_json::Map<_, _>>(),
+            "lost": lost, "at_risk": at_risk,
+            "decision": { "force": force_arg, "refused": refused },
+        });
+        println!("{report}");
+    } else {
+        println!("local {} vs origin/{branch} {}: {relation}", local.to_hex_with_len(8), remote.to_hex_with_len(8));
+        if lost.is_empty() {
+            println!("No remote commits would be dropped.");
+        } else {
+            println!("{} remote commit(s) not in local, {at_risk} at risk:", lost.len());
+        }
+        for c in &lost {
+            let verdict = match &c.preserved_by {
+                Some(why) => format!("preserved ({why})"),
+                None => "AT RISK".to_string(),
+            };
+            println!("  {} {}  {verdict}", c.id.to_hex_with_len(8), c.subject);
+            for (path, added, removed) in &c.files {
+                match (added, removed) {
+                    (Some(a), Some(r)) => println!("      {path} | +{a} -{r}"),
+                    _ => println!("      {path} | binary"),
+                }
+            }
+        }
+        if safety.needs_force() {
+            let held: Vec<&str> = checks.iter().filter(|(_, v)| *v).map(|(k, _)| *k).collect();
+            println!("checks that held: {}", if held.is_empty() { "none".to_string() } else { held.join(", ") });
+        }
+        match decision {
+            Ok((Some(arg), _)) => println!("decision: push with {arg}"),
+            Ok((None, _)) if safety.needs_force() || relation == "behind" => println!("decision: plain push (git will reject it)"),
+            Ok((None, _)) => println!("decision: plain push"),
+            Err(e) => println!("decision: {e}"),
+        }
+    }
+    if at_risk > 0 { 2 } else { 0 }
+}
+
+fn push(force_with_lease: bool, force: bool, dry_run: bool, explain: bool, json: bool, extra_args: Vec<String>) {
+    let repo = open_repo();
+
+    let branch = match repo.head_name() {
+        Ok(Some(name)) => name.shorten().to_string(),
+        _ => {
+            eprintln!("ERROR: Could not get current branch (detached HEAD?)");
             std::process::exit(1);
         }
-    } else if !explicit_force && needs_force {
-        // No explicit force flag, but histories diverged - auto-force if safe
-        if trees_match || local_contains_remote_content {
-            println!("Local contains all remote content - auto-enabling force-with-lease.");
-            use_force_with_lease = true;
+    };
+
+    // Fetch the remote branch to ensure we have up-to-date refs for comparison
+    let remote_commit = match fetch_remote_tip(&branch) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("ERROR: {e}");
+            std::process::exit(1);
+        }
+    };
+    let repo = open_repo();
+
+    // Get local HEAD commit
+    let local_commit = match repo.head_commit() {
+        Ok(c) => c.id,
+        Err(e) => {
+            eprintln!("ERROR: Could not get HEAD commit: {e}");
+            std::process::exit(1);
         }
-        // If not safe, let git push fail naturally with its error message
+    };
+
+    if explain || json {
+        std::process::exit(explain_push(&repo, &branch, local_commit, remote_commit, force_with_lease, force, json));
     }
 
+    let remote_commit = match remote_commit {
+        Some(c) => c,
+        None => {
+            // No remote branch yet, just push normally
+            let extra_refs: Vec<&str> = extra_args.iter().map(|s| s.as_str()).collect();
+            let mut args = vec!["push", "--follow-tags"];
+            args.extend(extra_refs);
+            if dry_run {
+                println!("DRY-RUN: git {}", args.join(" "));
+                return;
+            }
+            if !run_cmd("git", &args) {
+                std::process::exit(1);
+            }
+            return;
+        }
+    };
+
+    let force_arg = match force_arg(&repo, &branch, local_commit, remote_commit, force_with_lease, force) {
+        Ok(arg) => arg,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
     let extra_refs: Vec<&str> = extra_args.iter().map(|s| s.as_str()).collect();
 
     let mut args = vec!["push"];
-    let force_with_lease_arg;
-    if use_force {
-        args.push("--force");
-    } else if use_force_with_lease {
-        // Use explicit expected value to avoid "stale info" error after fetch
-        force_with_lease_arg = format!("--force-with-lease=refs/heads/{branch}:{remote_commit}");
-        args.push(&force_with_lease_arg);
+    if let Some(arg) = &force_arg {
+        args.push(arg);
     }
     args.push("--follow-tags");
     args.extend(extra_refs);
@@ -901,7 +1948,7 @@ fn push(force_with_lease: bool, force: bool, dry_run: bool, extra_args: Vec<Stri
 }
 
 fn delete(branch: String) {
-    let _ = open_repo(); // verify we're in a repo
+    let repo = open_repo();
 
     if is_main_branch(&branch) {
         eprintln!("Refusing to delete {branch}");
@@ -914,91 +1961,249 @@ fn delete(branch: String) {
         std::process::exit(1);
     }
 
-    // Delete remote branch
-    if !run_cmd("git", &["push", "origin", "--delete", &branch]) {
-        eprintln!("ERROR: Failed to delete remote branch {branch}");
-        std::process::exit(1);
+    // Delete remote branch: through the API when origin is on GitHub, else with git
+    let origin = remote_url(&repo, "origin").and_then(|u| github_slug(&u));
+    match origin {
+        Some(slug) => {
+            if let Err(e) = open_hub(&repo).api.delete_branch(&slug, &branch) {
+                eprintln!("ERROR: Failed to delete remote branch {branch}: {e}");
+                std::process::exit(1);
+            }
+            run_cmd_status("git", &["branch", "-dr", &format!("origin/{branch}")]);
+        }
+        None => {
+            if !run_cmd("git", &["push", "origin", "--delete", &branch]) {
+                eprintln!("ERROR: Failed to delete remote branch {branch}");
+                std::process::exit(1);
+            }
+        }
     }
 
     println!("Deleted branch {branch} locally and on remote");
 }
 
-struct Milestone {
-    title: &'static str,
-    description: &'static str,
+/// A pass of `publish` / `publish --sync` over a repo: every difference from the template
+/// is printed, and fixed unless this is a dry run.
+struct Reconcile<'a> {
+    api: &'a github::Client,
+    slug: &'a str,
+    apply: bool,
+    drift: usize,
+    failed: usize,
+    /// Parts of the repo that couldn't be read (a 403 for protection on a free plan,
+    /// say); their checks are skipped, the rest still run.
+    unread: usize,
 }
 
-const MILESTONES: &[Milestone] = &[
-    Milestone {
-        title: "1.0",
-        description: "Minimum viable product",
-    },
-    Milestone {
-        title: "2.0",
-        description: "Fix bugs, rewrite hacks",
-    },
-    Milestone {
-        title: "3.0",
-        description: "More and better",
-    },
-];
-
-fn create_milestone(github_name: &str, github_key: &str, repo_name: &str, milestone: &Milestone) {
-    let title = milestone.title;
-    let body = serde_json::json!({
-        "title": title,
-        "state": "open",
-        "description": milestone.description
-    });
+impl Reconcile<'_> {
+    fn fix(&mut self, what: String, fix: impl FnOnce(&github::Client, &str) -> Result<(), github::Error>) {
+        self.drift += 1;
+        if !self.apply {
+            println!("  {what}");
+            return;
+        }
+        match fix(self.api, self.slug) {
+            Ok(()) => println!("  {what}: fixed"),
+            Err(e) => {
+                self.failed += 1;
+                eprintln!("  {what}: FAILED: {e}");
+            }
+        }
+    }
 
-    let output = Command::new("curl")
-        .args([
-            "-L",
-            "-X",
-            "POST",
-            "-H",
-            "Accept: application/vnd.github+json",
-            "-H",
-            &format!("Authorization: token {github_key}"),
-            "-H",
-            "X-GitHub-Api-Version: 2022-11-28",
-            &format!("https://api.github.com/repos/{github_name}/{repo_name}/milestones"),
-            "-d",
-            &body.to_string(),
-        ])
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status();
+    /// What a part's checks read first; a failure is reported and counted, and None
+    /// skips that part.
+    fn read<T>(&mut self, what: &str, read: Result<T, github::Error>) -> Option<T> {
+        match read {
+            Ok(t) => Some(t),
+            Err(e) => {
+                self.unread += 1;
+                eprintln!("  {what}: could not read, not checked: {e}");
+                None
+            }
+        }
+    }
 
-    match output {
-        Ok(s) if s.success() => println!("Created milestone '{title}'"),
-        _ => eprintln!("Failed to create milestone '{title}'"),
+    /// Drift the template has no fix for.
+    fn note(&self, what: String) {
+        println!("  {what}");
     }
 }
 
-fn publish(repo_name: Option<String>, private: bool, public: bool, commit: Option<String>) {
-    // Get environment variables
-    let github_name = match env::var("GITHUB_NAME") {
-        Ok(v) => v,
-        Err(_) => {
-            eprintln!("ERROR: GITHUB_NAME is not set");
-            std::process::exit(1);
+fn reconcile<'a>(api: &'a github::Client, slug: &'a str, template: &template::Template, apply: bool) -> Result<Reconcile<'a>, String> {
+    let mut r = Reconcile { api, slug, apply, drift: 0, failed: 0, unread: 0 };
+
+    let mut branch = api.repo(slug)?.default_branch;
+    if let Some(want) = template.default_branch.as_ref().filter(|want| **want != branch) {
+        r.fix(format!("default branch: {branch}, template: {want}"), |api, slug| api.rename_branch(slug, &branch, want));
+        if apply && r.failed == 0 {
+            branch = want.clone();
         }
-    };
+    }
 
-    let github_key = match env::var("GITHUB_KEY") {
-        Ok(v) => v,
-        Err(_) => {
-            eprintln!("ERROR: GITHUB_KEY is not set");
+    if let Some(labels) = r.read("labels", api.labels(slug)) {
+        for want in &template.labels {
+            let Some(have) = labels.iter().find(|l| l.name.eq_ignore_ascii_case(&want.name)) else {
+                r.fix(format!("label {:?}: missing", want.name), |api, slug| {
+                    api.create_label(slug, &want.name, &want.color, &want.description)
+                });
+                continue;
+            };
+            let have_description = have.description.as_deref().unwrap_or("");
+            let mut diffs = Vec::new();
+            if have.name != want.name {
+                diffs.push(format!("named {:?}", have.name));
+            }
+            if !have.color.eq_ignore_ascii_case(&want.color) {
+                diffs.push(format!("color {}, template: {}", have.color, want.color));
+            }
+            if have_description != want.description {
+                diffs.push(format!("description {have_description:?}"));
+            }
+            if !diffs.is_empty() {
+                r.fix(format!("label {:?}: {}", want.name, diffs.join(", ")), |api, slug| {
+                    api.update_label(slug, &have.name, &want.name, &want.color, &want.description)
+                });
+            }
+        }
+        for extra in labels.iter().filter(|l| !template.labels.iter().any(|w| w.name.eq_ignore_ascii_case(&l.name))) {
+            if template.prune_labels {
+                r.fix(format!("label {:?}: not in the template", extra.name), |api, slug| api.delete_label(slug, &extra.name));
+            } else {
+                r.note(format!("label {:?}: not in the template (kept; prune_labels = true deletes it)", extra.name));
+            }
+        }
+    }
+
+    if let Some(milestones) = r.read("milestones", api.milestones(slug)) {
+        for want in &template.milestones {
+            match milestones.iter().find(|m| m.title == want.title) {
+                None => r.fix(format!("milestone {:?}: missing", want.title), |api, slug| {
+                    api.create_milestone(slug, &want.title, &want.description).map(|_| ())
+                }),
+                Some(have) if have.description.as_deref().unwrap_or("") != want.description => {
+                    let what = format!("milestone {:?}: description {:?}", want.title, have.description.as_deref().unwrap_or(""));
+                    r.fix(what, |api, slug| api.set_milestone_description(slug, have.number, &want.description));
+                }
+                Some(_) => {}
+            }
+        }
+        for extra in milestones.iter().filter(|m| !template.milestones.iter().any(|w| w.title == m.title)) {
+            r.note(format!("milestone {:?}: not in the template (kept)", extra.title));
+        }
+    }
+
+    for issue_template in &template.issue_templates {
+        let path = issue_template.path();
+        let text = issue_template.render();
+        let Some(have) = r.read(&path, api.file(slug, &path)) else { continue };
+        let what = match &have {
+            None => "missing",
+            Some(file) if file.text().as_deref() != Some(text.as_str()) => "differs",
+            Some(_) => continue,
+        };
+        let sha = have.map(|f| f.sha);
+        let message = format!("publish: {} issue template {}", if sha.is_some() { "update" } else { "add" }, issue_template.file);
+        r.fix(format!("{path}: {what}"), |api, slug| api.put_file(slug, &path, &text, sha.as_deref(), &message));
+    }
+
+    if let Some(want) = &template.protection
+        && let Some(have) = r.read(&format!("protection of {branch}"), api.protection(slug, &branch))
+    {
+        let have = have.map(|p| {
+            let mut checks = p.required_status_checks.as_ref().map(|c| c.contexts.clone()).unwrap_or_default();
+            checks.sort();
+            template::Protection {
+                required_status_checks: checks,
+                strict: p.required_status_checks.is_some_and(|c| c.strict),
+                required_approvals: p.required_pull_request_reviews.map(|r| r.required_approving_review_count),
+                enforce_admins: p.enforce_admins.is_some_and(|e| e.enabled),
+                allow_force_pushes: p.allow_force_pushes.is_some_and(|e| e.enabled),
+            }
+        });
+        if have.as_ref() != Some(want) {
+            let was = have.map(|p| p.describe()).unwrap_or_else(|| "unprotected".to_string());
+            r.fix(format!("protection of {branch}: {was}; template: {}", want.describe()), |api, slug| {
+                api.protect(slug, &branch, &want.body())
+            });
+        }
+    }
+
+    // GitHub never hands values back (and uppercases names): only presence is checked.
+    if !template.secrets.is_empty()
+        && let Some(have) = r.read("secrets", api.secret_names(slug))
+    {
+        for (name, var) in &template.secrets {
+            if have.iter().any(|h| h.eq_ignore_ascii_case(name)) {
+                continue;
+            }
+            match env::var(var) {
+                Ok(value) => r.fix(format!("secret {name}: missing"), |api, slug| api.set_secret(slug, name, &value)),
+                Err(_) => r.note(format!("secret {name}: missing, and ${var} is not set to fill it")),
+            }
+        }
+    }
+
+    Ok(r)
+}
+
+/// `publish --sync`: hold an existing repo (origin's, or OWNER/NAME) to the template.
+fn publish_sync(repo_name: Option<String>, template: &template::Template, dry_run: bool) {
+    let slug = match repo_name {
+        Some(slug) if slug.contains('/') => slug,
+        Some(name) => {
+            eprintln!("ERROR: --sync takes OWNER/NAME, not {name:?}");
             std::process::exit(1);
         }
+        None => match remote_url(&open_repo(), "origin").and_then(|u| github_slug(&u)) {
+            Some(slug) => slug,
+            None => {
+                eprintln!("ERROR: origin is not a GitHub repository; pass OWNER/NAME");
+                std::process::exit(1);
+            }
+        },
     };
+    let api = github::Client::from_env().unwrap_or_else(|e| {
+        eprintln!("ERROR: {e}");
+        std::process::exit(1);
+    });
 
-    let github_loc_gist = env::var("GITHUB_LOC_GIST").ok();
-    if github_loc_gist.is_none() {
-        eprintln!("WARNING: GITHUB_LOC_GIST is not set, loc_gist_token secret will not be created // in my setup it's used for LoC badge generation");
+    println!("{slug}:");
+    let r = reconcile(&api, &slug, template, !dry_run).unwrap_or_else(|e| {
+        eprintln!("ERROR: {e}");
+        std::process::exit(1);
+    });
+    match (r.drift, dry_run) {
+        (0, _) if r.unread > 0 => println!("No differences in what could be read."),
+        (0, _) => println!("Matches the template."),
+        (n, true) => println!("{n} difference(s) from the template."),
+        (n, false) => println!("{} of {n} difference(s) fixed.", n - r.failed),
+    }
+    if r.unread > 0 {
+        eprintln!("{} part(s) of the repo could not be read and were not checked", r.unread);
+    }
+    if r.failed > 0 || r.unread > 0 {
+        std::process::exit(1);
+    }
+    if dry_run && r.drift > 0 {
+        std::process::exit(2);
+    }
+}
+
+fn publish(repo_name: Option<String>, private: bool, public: bool, commit: Option<String>, template: Option<PathBuf>, sync: bool, dry_run: bool) {
+    let template = template::Template::load(template.as_deref()).unwrap_or_else(|e| {
+        eprintln!("ERROR: template: {e}");
+        std::process::exit(1);
+    });
+    if sync {
+        return publish_sync(repo_name, &template, dry_run);
     }
 
+    let api = github::Client::from_env().unwrap_or_else(|e| {
+        eprintln!("ERROR: {e}");
+        std::process::exit(1);
+    });
+
     // Determine repo name
     let repo_name = repo_name.unwrap_or_else(|| {
         env::current_dir()
@@ -1010,14 +2215,8 @@ fn publish(repo_name: Option<String>, private: bool, public: bool, commit: Optio
             })
     });
 
-    // Determine visibility flag
-    let visibility = if private {
-        "--private"
-    } else if public {
-        "--public"
-    } else {
-        "--private" // default to private
-    };
+    // Private unless --public
+    let private = private || !public;
 
     println!("Creating repository: {repo_name}");
 
@@ -1056,14 +2255,14 @@ fn publish(repo_name: Option<String>, private: bool, public: bool, commit: Optio
         std::process::exit(1);
     }
 
-    // gh repo create
-    if !run_cmd("gh", &["repo", "create", &repo_name, visibility, "--source=."]) {
-        eprintln!("ERROR: gh repo create failed");
+    let created = api.create_repo(&repo_name, private).unwrap_or_else(|e| {
+        eprintln!("ERROR: Failed to create repository: {e}");
         std::process::exit(1);
-    }
+    });
+    let slug = created.full_name;
 
     // git remote add origin
-    let remote_url = format!("https://github.com/{github_name}/{repo_name}.git");
+    let remote_url = created.clone_url;
     // Remove existing origin if any, then add
     run_cmd_status("git", &["remote", "remove", "origin"]);
     if !run_cmd("git", &["remote", "add", "origin", &remote_url]) {
@@ -1071,209 +2270,850 @@ fn publish(repo_name: Option<String>, private: bool, public: bool, commit: Optio
         std::process::exit(1);
     }
 
-    // git push — push the actual current branch (whatever init.defaultBranch produced)
-    let branch = current_branch(&open_repo()).unwrap_or_else(|| {
+    // git push — the template's default branch, else whatever init.defaultBranch produced
+    let mut branch = current_branch(&open_repo()).unwrap_or_else(|| {
         eprintln!("ERROR: Could not determine current branch after commit");
         std::process::exit(1);
     });
+    if let Some(want) = template.default_branch.as_ref().filter(|want| **want != branch) {
+        if !run_cmd("git", &["branch", "-M", want]) {
+            eprintln!("ERROR: Failed to rename {branch} to {want}");
+            std::process::exit(1);
+        }
+        branch = want.clone();
+    }
     if !run_cmd("git", &["push", "-u", "origin", &branch]) {
         eprintln!("ERROR: git push failed");
         std::process::exit(1);
     }
 
-    // Create milestones
-    println!("\nCreating milestones...");
-    for milestone in MILESTONES {
-        create_milestone(&github_name, &github_key, &repo_name, milestone);
+    println!("\nApplying the template...");
+    let r = reconcile(&api, &slug, &template, true).unwrap_or_else(|e| {
+        eprintln!("ERROR: {e}");
+        std::process::exit(1);
+    });
+    // Issue templates were committed on GitHub's side
+    if !template.issue_templates.is_empty() {
+        run_cmd("git", &["pull", "-q", "--ff-only"]);
     }
-
-    // Set loc_gist_token secret if available
-    if let Some(loc_gist) = github_loc_gist {
-        println!("\nSetting loc_gist_token secret...");
-        let full_repo = format!("{github_name}/{repo_name}");
-        if !run_cmd(
-            "gh",
-            &[
-                "secret",
-                "set",
-                "loc_gist_token",
-                "--repo",
-                &full_repo,
-                "--body",
-                &loc_gist,
-            ],
-        ) {
-            eprintln!("WARNING: Failed to set loc_gist_token secret");
-        }
+    if r.failed + r.unread > 0 {
+        eprintln!("\nRepository {repo_name} created, but {} template step(s) failed", r.failed + r.unread);
+        std::process::exit(1);
     }
 
     println!("\nRepository {repo_name} created successfully!");
 }
 
-fn reword(commit_str: String, new_message: String) {
-    let repo = open_repo();
+/// Write a copy of commit `orig` with its parents mapped through `parents`, and its tree
+/// and message replaced when given. Author, committer and extra headers are kept as-is,
+/// except a signature: it covers the old tree, parents and message, so a copy that
+/// changes any of them goes out unsigned rather than with a signature that won't verify.
+/// A copy that changes nothing is `orig` itself.
+fn rewrite_commit(
+    repo: &gix::Repository,
+    orig: gix::ObjectId,
+    tree: Option<gix::ObjectId>,
+    parents: impl FnOnce(Vec<gix::ObjectId>) -> Vec<gix::ObjectId>,
+    message: Option<&[u8]>,
+) -> Result<gix::ObjectId, String> {
+    let obj = repo.find_object(orig).map_err(|e| format!("find_object {orig}: {e}"))?;
+    let commit = obj.peel_to_commit().map_err(|e| format!("peel_to_commit {orig}: {e}"))?;
+    let raw = commit.decode().map_err(|e| format!("decode commit {orig}: {e}"))?;
+
+    let old_parents: Vec<gix::ObjectId> = raw.parents().collect();
+    let new_parents = parents(old_parents.clone());
+    let tree = tree.unwrap_or_else(|| raw.tree());
+    if tree == raw.tree() && new_parents == old_parents && message.is_none_or(|m| m == raw.message.as_ref() as &[u8]) {
+        return Ok(orig);
+    }
 
-    let target_id = repo
-        .rev_parse_single(commit_str.as_str())
-        .unwrap_or_else(|e| {
-            eprintln!("ERROR: not a commit '{commit_str}': {e}");
-            std::process::exit(1);
+    let new_commit = gix::objs::Commit {
+        tree,
+        parents: new_parents.into(),
+        author: raw
+            .author()
+            .map_err(|e| format!("author parse: {e}"))?
+            .to_owned()
+            .map_err(|e| format!("author date: {e}"))?,
+        committer: raw
+            .committer()
+            .map_err(|e| format!("committer parse: {e}"))?
+            .to_owned()
+            .map_err(|e| format!("committer date: {e}"))?,
+        encoding: raw.encoding.map(|e| e.into()),
+        message: match message {
+            Some(m) => m.into(),
+            None => raw.message.into(),
+        },
+        extra_headers: raw.extra_headers
+            .iter()
+            .filter(|(k, _)| !matches!(k.as_ref() as &[u8], b"gpgsig" | b"gpgsig-sha256"))
+            .map(|(k, v)| (k.to_owned().into(), v.as_ref().to_owned().into()))
+            .collect(),
+    };
+
+    repo.write_object(&new_commit)
+        .map(|id| id.detach())
+        .map_err(|e| format!("write_object: {e}"))
+}
+
+/// Move `name` to `new`, failing if it no longer points at `old` (when given).
+fn update_ref(repo: &gix::Repository, name: gix::refs::FullName, old: Option<gix::ObjectId>, new: gix::ObjectId, message: &str) -> Result<(), String> {
+    repo.edit_reference(gix::refs::transaction::RefEdit {
+        change: gix::refs::transaction::Change::Update {
+            log: gix::refs::transaction::LogChange {
+                mode: gix::refs::transaction::RefLog::AndReference,
+                force_create_reflog: false,
+                message: message.into(),
+            },
+            expected: match old {
+                Some(old) => gix::refs::transaction::PreviousValue::MustExistAndMatch(gix::refs::Target::Object(old)),
+                None => gix::refs::transaction::PreviousValue::Any,
+            },
+            new: gix::refs::Target::Object(new),
+        },
+        name,
+        deref: false,
+    })
+    .map(|_| ())
+    .map_err(|e| format!("update-ref: {e}"))
+}
+
+/// `commit`'s changes against `old_parent` (None for a root commit), applied onto
+/// `onto_tree`: a three-way tree merge, written only if it's clean.
+fn pick(repo: &gix::Repository, commit: gix::ObjectId, old_parent: Option<gix::ObjectId>, onto_tree: gix::ObjectId) -> Result<gix::ObjectId, String> {
+    let tree_of = |c| commit_tree(repo, c).ok_or_else(|| format!("no tree for {c}"));
+    let base_tree = match old_parent {
+        Some(p) => tree_of(p)?,
+        None => gix::ObjectId::empty_tree(repo.object_hash()),
+    };
+    let their_tree = tree_of(commit)?;
+    if base_tree == onto_tree {
+        return Ok(their_tree);
+    }
+    let mut outcome = repo
+        .merge_trees(base_tree, onto_tree, their_tree, Default::default(), Default::default())
+        .map_err(|e| format!("merging {commit}: {e}"))?;
+    if outcome.has_unresolved_conflicts(TreatAsUnresolved::default()) {
+        let subject = repo
+            .find_object(commit)
+            .ok()
+            .and_then(|o| o.try_into_commit().ok())
+            .and_then(|c| c.message().ok().map(|m| m.summary().to_string()))
+            .unwrap_or_default();
+        return Err(format!("{} \"{subject}\" doesn't apply cleanly on its new base", commit.to_hex_with_len(8)));
+    }
+    outcome
+        .tree
+        .write()
+        .map(|id| id.detach())
+        .map_err(|e| format!("writing tree for {commit}: {e}"))
+}
+
+/// One history edit on HEAD's first-parent line.
+enum Edit {
+    Reword(gix::ObjectId, String),
+    Drop(gix::ObjectId),
+    /// Fold `commit` into `into`, optionally replacing the message.
+    Squash { commit: gix::ObjectId, into: gix::ObjectId, message: Option<String> },
+    /// Move `commit` to sit right on top of `after`; None moves it to the bottom.
+    Reorder { commit: gix::ObjectId, after: Option<gix::ObjectId> },
+}
+
+/// An entry of the rewrite's todo list: a commit, and what changes about it.
+struct Step {
+    commit: gix::ObjectId,
+    message: Option<String>,
+    /// Commits folded into this one, applied after it in order.
+    squashed: Vec<gix::ObjectId>,
+}
+
+fn first_parent(repo: &gix::Repository, commit: gix::ObjectId) -> Option<gix::ObjectId> {
+    repo.find_object(commit)
+        .ok()
+        .and_then(|o| o.peel_to_commit().ok())
+        .and_then(|c| c.parent_ids().next().map(|p| p.detach()))
+}
+
+fn parent_ids(repo: &gix::Repository, commit: gix::ObjectId) -> Result<Vec<gix::ObjectId>, String> {
+    Ok(repo
+        .find_object(commit)
+        .map_err(|e| format!("find_object {commit}: {e}"))?
+        .peel_to_commit()
+        .map_err(|e| format!("peel_to_commit {commit}: {e}"))?
+        .parent_ids()
+        .map(|id| id.detach())
+        .collect())
+}
+
+/// The rewrite engine behind reword/drop/squash/reorder/extract. Works like an
+/// interactive rebase of HEAD's first-parent line down to the oldest commit involved,
+/// done in memory: merge commits keep their side parents (their changes relative to
+/// the first parent are replayed; a merge squashed into another commit hands its side
+/// parents over to it), and every commit is replayed before any ref moves, so a
+/// conflict leaves nothing behind. Returns (old head, new head).
+fn rewrite_history(repo: &gix::Repository, edits: Vec<Edit>) -> Result<(gix::ObjectId, gix::ObjectId), String> {
+    current_branch(repo).ok_or("HEAD is detached")?;
+    let head = repo.head_commit().map_err(|e| format!("could not read HEAD: {e}"))?.id;
+
+    let mut wanted: Vec<gix::ObjectId> = edits
+        .iter()
+        .flat_map(|e| match e {
+            Edit::Reword(c, _) | Edit::Drop(c) => vec![*c],
+            Edit::Squash { commit, into, .. } => vec![*commit, *into],
+            Edit::Reorder { commit, after } => [Some(*commit), *after].into_iter().flatten().collect(),
         })
-        .detach();
+        .collect();
+    // Moving a commit to the bottom takes the whole line, not just the part above it.
+    let to_root = edits.iter().any(|e| matches!(e, Edit::Reorder { after: None, .. }));
+    // HEAD's first-parent line, newest first, down to the oldest commit an edit names.
+    let mut chain = Vec::new();
+    let walk = repo
+        .rev_walk([head])
+        .first_parent_only()
+        .all()
+        .map_err(|e| format!("rev_walk failed: {e}"))?;
+    for info in walk.filter_map(|r| r.ok()) {
+        if wanted.is_empty() && !to_root {
+            break;
+        }
+        let id = info.id().detach();
+        wanted.retain(|w| *w != id);
+        chain.push(id);
+    }
+    if let Some(missing) = wanted.first() {
+        return Err(format!("{} is not on HEAD's first-parent line", missing.to_hex_with_len(8)));
+    }
+    chain.reverse();
+    let base = chain.first().and_then(|c| first_parent(repo, *c));
+
+    let mut todo: Vec<Step> = chain.into_iter().map(|commit| Step { commit, message: None, squashed: Vec::new() }).collect();
+    let position = |todo: &[Step], c: gix::ObjectId| {
+        todo.iter()
+            .position(|s| s.commit == c)
+            .ok_or_else(|| format!("{} was already dropped or squashed", c.to_hex_with_len(8)))
+    };
+    for edit in edits {
+        match edit {
+            Edit::Reword(c, message) => {
+                let i = position(&todo, c)?;
+                todo[i].message = Some(message);
+            }
+            Edit::Drop(c) => {
+                todo.remove(position(&todo, c)?);
+            }
+            Edit::Squash { commit, into, message } => {
+                if commit == into {
+                    return Err("can't squash a commit into itself".to_string());
+                }
+                let step = todo.remove(position(&todo, commit)?);
+                let i = position(&todo, into)?;
+                todo[i].squashed.push(step.commit);
+                todo[i].squashed.extend(step.squashed);
+                if message.is_some() {
+                    todo[i].message = message;
+                }
+            }
+            Edit::Reorder { commit, after } => {
+                let step = todo.remove(position(&todo, commit)?);
+                let at = match after {
+                    Some(a) => position(&todo, a)? + 1,
+                    None => 0,
+                };
+                todo.insert(at, step);
+            }
+        }
+    }
+
+    let mut parent = base;
+    for step in todo {
+        let parents = parent_ids(repo, step.commit)?;
+        if parent == parents.first().copied() && step.message.is_none() && step.squashed.is_empty() {
+            // Untouched and still on the same parent: keep it as it is.
+            parent = Some(step.commit);
+            continue;
+        }
+        let onto_tree = match parent {
+            Some(p) => commit_tree(repo, p).ok_or_else(|| format!("no tree for {p}"))?,
+            None => gix::ObjectId::empty_tree(repo.object_hash()),
+        };
+        let mut tree = pick(repo, step.commit, parents.first().copied(), onto_tree)?;
+        let mut new_parents: Vec<gix::ObjectId> = parent.into_iter().chain(parents.iter().skip(1).copied()).collect();
+        for s in &step.squashed {
+            let squashed_parents = parent_ids(repo, *s)?;
+            tree = pick(repo, *s, squashed_parents.first().copied(), tree)?;
+            for side in squashed_parents.into_iter().skip(1) {
+                if !new_parents.contains(&side) {
+                    new_parents.push(side);
+                }
+            }
+        }
+        let message = step.message.as_deref().map(str::as_bytes);
+        parent = Some(rewrite_commit(repo, step.commit, Some(tree), |_| new_parents, message)?);
+    }
+    let new_head = parent.ok_or("that would leave the branch without commits")?;
+    Ok((head, new_head))
+}
+
+fn resolve_commit(repo: &gix::Repository, spec: &str) -> Result<gix::ObjectId, String> {
+    repo.rev_parse_single(spec)
+        .map_err(|e| format!("not a commit '{spec}': {e}"))?
+        .object()
+        .map_err(|e| format!("not a commit '{spec}': {e}"))?
+        .peel_to_commit()
+        .map(|c| c.id)
+        .map_err(|e| format!("not a commit '{spec}': {e}"))
+}
+
+/// Point the checked-out branch at `new`. `--keep` moves index and worktree along
+/// (refusing to clobber local changes); `--soft` leaves them, which is how `extract`
+/// ends up with the commit's changes staged.
+fn move_head(new: gix::ObjectId, mode: &str) -> Result<(), String> {
+    if run_cmd("git", &["reset", mode, "-q", &new.to_string()]) {
+        Ok(())
+    } else {
+        Err(format!("git reset {mode} failed; history is unchanged, the rewritten tip is {new}"))
+    }
+}
 
-    let head_id = repo.head_commit().unwrap_or_else(|e| {
-        eprintln!("ERROR: could not read HEAD: {e}");
+/// Parse the commit arguments, run the edits and move HEAD; exits on any error. Once
+/// HEAD has moved, the old tip is saved as `refs/git_scripts/backup/<branch>` for
+/// `undo` — not before, so a reset that refuses leaves the previous backup in place.
+fn run_rewrite(build: impl FnOnce(&gix::Repository) -> Result<Vec<Edit>, String>, reset_mode: &str) -> gix::ObjectId {
+    let repo = open_repo();
+    let result = build(&repo).and_then(|edits| rewrite_history(&repo, edits)).and_then(|(old, new)| {
+        move_head(new, reset_mode)?;
+        let branch = current_branch(&repo).ok_or("HEAD is detached")?;
+        let backup: gix::refs::FullName = format!("refs/git_scripts/backup/{branch}").try_into().map_err(|e| format!("{branch}: {e}"))?;
+        update_ref(&repo, backup, None, old, "backup before rewrite")
+            .map_err(|e| format!("{e}; the rewrite went through, but undo can't find {}", old.to_hex_with_len(8)))?;
+        println!("{} -> {}  (undo: git_scripts undo)", old.to_hex_with_len(8), new.to_hex_with_len(8));
+        Ok(new)
+    });
+    result.unwrap_or_else(|e| {
+        eprintln!("ERROR: {e}");
         std::process::exit(1);
-    }).id;
+    })
+}
+
+fn reword(commit: String, message: String) {
+    run_rewrite(|repo| Ok(vec![Edit::Reword(resolve_commit(repo, &commit)?, message)]), "--keep");
+}
+
+fn drop_commits(commits: Vec<String>) {
+    run_rewrite(|repo| commits.iter().map(|c| Ok(Edit::Drop(resolve_commit(repo, c)?))).collect(), "--keep");
+}
 
-    // Verify target is ancestor of HEAD
-    if !is_ancestor(&repo, target_id, head_id) {
-        eprintln!("ERROR: {commit_str} is not an ancestor of HEAD");
+fn squash(commit: String, into: Option<String>, message: Option<String>) {
+    run_rewrite(
+        |repo| {
+            let commit = resolve_commit(repo, &commit)?;
+            let into = match into {
+                Some(i) => resolve_commit(repo, &i)?,
+                None => first_parent(repo, commit).ok_or("a root commit has nothing to squash into")?,
+            };
+            Ok(vec![Edit::Squash { commit, into, message }])
+        },
+        "--keep",
+    );
+}
+
+fn reorder(commit: String, after: Option<String>, before: Option<String>) {
+    run_rewrite(
+        |repo| {
+            let commit = resolve_commit(repo, &commit)?;
+            let after = match (after, before) {
+                (Some(a), _) => Some(resolve_commit(repo, &a)?),
+                // Right below `before`: on top of whatever will sit under it.
+                (None, Some(b)) => {
+                    let b = resolve_commit(repo, &b)?;
+                    match first_parent(repo, b) {
+                        Some(p) if p == commit => first_parent(repo, commit),
+                        p => p,
+                    }
+                }
+                (None, None) => return Err("say where: --after or --before".to_string()),
+            };
+            Ok(vec![Edit::Reorder { commit, after }])
+        },
+        "--keep",
+    );
+}
+
+/// Drop `commit` from history but keep its changes staged on top, ready to be
+/// recommitted in pieces; with `stash`, stash them instead.
+fn extract(commit: String, stash: bool) {
+    if !run_cmd_status("git", &["diff", "--cached", "--quiet"]) {
+        eprintln!("ERROR: the index has staged changes; commit or stash them first");
         std::process::exit(1);
     }
-
-    // Collect descendants oldest-first (target excluded).
-    // rev_walk from HEAD yields newest-first; stop when we reach target.
-    let descendants: Vec<gix::ObjectId> = {
-        let walk = repo.rev_walk([head_id]).all().unwrap_or_else(|e| {
-            eprintln!("ERROR: rev_walk failed: {e}");
+    let id = resolve_commit(&open_repo(), &commit).unwrap_or_else(|e| {
+        eprintln!("ERROR: {e}");
+        std::process::exit(1);
+    });
+    run_rewrite(|_| Ok(vec![Edit::Drop(id)]), "--soft");
+    let short = id.to_hex_with_len(8);
+    if stash {
+        let stash_msg = format!("extracted {short}");
+        if !run_cmd("git", &["stash", "push", "--staged", "-m", &stash_msg]) {
+            eprintln!("ERROR: stash failed");
             std::process::exit(1);
-        });
-        let mut v: Vec<gix::ObjectId> = walk
-            .filter_map(|r| r.ok())
-            .map(|info| info.id().detach())
-            .take_while(|id| *id != target_id)
-            .collect();
-        v.reverse(); // oldest-first
-        v
-    };
+        }
+        println!("Extracted {short} into stash.");
+    } else {
+        println!("Extracted {short}: its changes are staged.");
+    }
+}
 
-    // Rewrite a single commit object, replacing its message and optionally its first parent
-    let rewrite = |orig: gix::ObjectId, new_msg: &str, new_parent: Option<gix::ObjectId>| -> gix::ObjectId {
-        let obj = repo.find_object(orig).unwrap_or_else(|e| {
-            eprintln!("ERROR: find_object {orig}: {e}");
-            std::process::exit(1);
-        });
-        let commit = obj.peel_to_commit().unwrap_or_else(|e| {
-            eprintln!("ERROR: peel_to_commit {orig}: {e}");
-            std::process::exit(1);
-        });
-        let raw = commit.decode().unwrap_or_else(|e| {
-            eprintln!("ERROR: decode commit {orig}: {e}");
-            std::process::exit(1);
-        });
+/// Put the branch back where the last rewrite found it. The tip being left becomes the
+/// new backup, so a second undo redoes.
+fn undo() {
+    let repo = open_repo();
+    let result = (|| {
+        let branch = current_branch(&repo).ok_or("HEAD is detached")?;
+        let name = format!("refs/git_scripts/backup/{branch}");
+        let backup = ref_tip(&repo, &name).ok_or_else(|| format!("No backup for {branch}"))?;
+        let head = repo.head_commit().map_err(|e| format!("could not read HEAD: {e}"))?.id;
+        move_head(backup, "--keep")?;
+        let name: gix::refs::FullName = name.try_into().map_err(|e| format!("{branch}: {e}"))?;
+        update_ref(&repo, name, Some(backup), head, "undo")?;
+        println!("{branch}: {} -> {}", head.to_hex_with_len(8), backup.to_hex_with_len(8));
+        Ok::<_, String>(())
+    })();
+    if let Err(e) = result {
+        eprintln!("ERROR: {e}");
+        std::process::exit(1);
+    }
+}
 
-        let parents: Vec<gix::ObjectId> = if let Some(p) = new_parent {
-            // Replace only first parent, keep any extra parents (merge commits)
-            let mut ps: Vec<gix::ObjectId> = raw.parents().collect();
-            if ps.is_empty() {
-                ps.push(p);
+/// Replay `from..tip` onto `onto`, cherry-pick style: each commit's changes against
+/// its first parent are merged onto the new chain, merge commits keeping their other
+/// parents. Only objects get written, so on a conflict nothing observable has changed.
+/// Returns the new tip.
+fn replay(repo: &gix::Repository, from: gix::ObjectId, tip: gix::ObjectId, onto: gix::ObjectId) -> Result<gix::ObjectId, String> {
+    let mut pending: Vec<gix::ObjectId> = repo
+        .rev_walk([tip])
+        .with_hidden([from])
+        .all()
+        .map_err(|e| format!("rev_walk failed: {e}"))?
+        .filter_map(|r| r.ok())
+        .map(|info| info.id().detach())
+        .collect();
+    pending.reverse(); // oldest-first
+
+    let mut mapped: std::collections::HashMap<gix::ObjectId, gix::ObjectId> = [(from, onto)].into();
+    // A commit is ready once every parent it has inside the range is rewritten; walk
+    // order alone doesn't promise that across merges.
+    while !pending.is_empty() {
+        let before = pending.len();
+        let mut i = 0;
+        while i < pending.len() {
+            let orig = pending[i];
+            let parents: Vec<gix::ObjectId> = repo
+                .find_object(orig)
+                .map_err(|e| format!("find_object {orig}: {e}"))?
+                .peel_to_commit()
+                .map_err(|e| format!("peel_to_commit {orig}: {e}"))?
+                .parent_ids()
+                .map(|id| id.detach())
+                .collect();
+            if parents.iter().any(|p| pending.contains(p)) {
+                i += 1;
+                continue;
+            }
+            let new_parents: Vec<gix::ObjectId> = parents.iter().map(|p| *mapped.get(p).unwrap_or(p)).collect();
+            let new = if new_parents == parents {
+                orig
             } else {
-                ps[0] = p;
+                let (Some(&old_base), Some(&new_base)) = (parents.first(), new_parents.first()) else {
+                    return Err(format!("{orig} has no parent to replay against"));
+                };
+                let onto_tree = commit_tree(repo, new_base).ok_or_else(|| format!("no tree for {new_base}"))?;
+                let tree = pick(repo, orig, Some(old_base), onto_tree)?;
+                rewrite_commit(repo, orig, Some(tree), |_| new_parents, None)?
+            };
+            mapped.insert(orig, new);
+            pending.remove(i);
+        }
+        if pending.len() == before {
+            return Err("history to replay has a cycle".to_string());
+        }
+    }
+    Ok(mapped.get(&tip).copied().unwrap_or(onto))
+}
+
+/// One branch in a stack. Stacks live in git config: `branch.<b>.stackParent` names
+/// the layer below (the default branch, for the bottom one) and `branch.<b>.stackBase`
+/// the parent commit `<b>` was last built on, which is where its own commits start.
+struct Layer {
+    branch: String,
+    parent: String,
+    base: Option<gix::ObjectId>,
+}
+
+fn stack_layers(repo: &gix::Repository) -> Vec<Layer> {
+    let config = repo.config_snapshot();
+    let Ok(refs) = repo.references() else { return Vec::new() };
+    let Ok(branches) = refs.local_branches() else { return Vec::new() };
+    branches
+        .filter_map(|r| r.ok())
+        .filter_map(|r| {
+            let branch = r.name().shorten().to_string();
+            let parent = config.string(format!("branch.{branch}.stackParent"))?.to_string();
+            let base = config
+                .string(format!("branch.{branch}.stackBase"))
+                .and_then(|b| gix::ObjectId::from_hex(&b).ok());
+            Some(Layer { branch, parent, base })
+        })
+        .collect()
+}
+
+/// The stack `branch` is part of, bottom layer first.
+fn load_stack(repo: &gix::Repository, branch: &str) -> Result<Vec<Layer>, String> {
+    let mut all = stack_layers(repo);
+    let Some(mut bottom) = all.iter().position(|l| l.branch == branch) else {
+        return Err(format!("{branch} is not part of a stack (see `stack new`, `stack set`)"));
+    };
+    for _ in 0..all.len() {
+        match all.iter().position(|l| l.branch == all[bottom].parent) {
+            Some(below) => bottom = below,
+            None => break,
+        }
+    }
+    let mut stack = vec![all.remove(bottom)];
+    loop {
+        let top = &stack[stack.len() - 1].branch;
+        let children: Vec<usize> = (0..all.len()).filter(|&i| &all[i].parent == top).collect();
+        match children[..] {
+            [] => return Ok(stack),
+            [child] => stack.push(all.remove(child)),
+            _ => {
+                let names: Vec<&str> = children.iter().map(|&i| all[i].branch.as_str()).collect();
+                return Err(format!("Stack forks at {top} ({}); only linear stacks are supported", names.join(", ")));
             }
-            ps
-        } else {
-            raw.parents().collect()
-        };
+        }
+    }
+}
 
-        let new_commit = gix::objs::Commit {
-            tree: raw.tree(),
-            parents: parents.into(),
-            author: raw
-                .author()
-                .unwrap_or_else(|e| { eprintln!("ERROR: author parse: {e}"); std::process::exit(1); })
-                .to_owned()
-                .unwrap_or_else(|e| { eprintln!("ERROR: author date: {e}"); std::process::exit(1); }),
-            committer: raw
-                .committer()
-                .unwrap_or_else(|e| { eprintln!("ERROR: committer parse: {e}"); std::process::exit(1); })
-                .to_owned()
-                .unwrap_or_else(|e| { eprintln!("ERROR: committer date: {e}"); std::process::exit(1); }),
-            encoding: raw.encoding.map(|e| e.into()),
-            message: if new_msg.is_empty() { raw.message.into() } else { new_msg.as_bytes().into() },
-            extra_headers: raw.extra_headers
-                .iter()
-                .map(|(k, v)| (k.to_owned().into(), v.as_ref().to_owned().into()))
-                .collect(),
-        };
+fn record_layer(branch: &str, parent: &str, base: gix::ObjectId) -> Result<(), String> {
+    let base = base.to_string();
+    for (key, value) in [("stackParent", parent), ("stackBase", base.as_str())] {
+        if !run_cmd_status("git", &["config", &format!("branch.{branch}.{key}"), value]) {
+            return Err(format!("Failed to record {branch} in the stack"));
+        }
+    }
+    Ok(())
+}
 
-        repo.write_object(&new_commit).unwrap_or_else(|e| {
-            eprintln!("ERROR: write_object: {e}");
-            std::process::exit(1);
-        }).detach()
+fn forget_layer(branch: &str) {
+    run_cmd_status("git", &["config", "--unset", &format!("branch.{branch}.stackParent")]);
+    run_cmd_status("git", &["config", "--unset", &format!("branch.{branch}.stackBase")]);
+}
+
+/// The default branch: from GitHub, or the first main-ish branch that exists locally.
+fn trunk_name(repo: &gix::Repository) -> Option<String> {
+    Hub::open(repo).and_then(|hub| hub.default_branch()).ok().or_else(|| {
+        ["main", "master"]
+            .into_iter()
+            .find(|b| ref_tip(repo, &format!("refs/heads/{b}")).is_some())
+            .map(|b| b.to_string())
+    })
+}
+
+/// Where a layer sitting on `parent` should be built: another layer's local tip, or for
+/// the default branch, origin's copy when we have one (that's where merged layers land).
+fn parent_tip(repo: &gix::Repository, stack: &[Layer], parent: &str) -> Option<gix::ObjectId> {
+    if stack.iter().any(|l| l.branch == parent) {
+        return ref_tip(repo, &format!("refs/heads/{parent}"));
+    }
+    ref_tip(repo, &format!("refs/remotes/origin/{parent}")).or_else(|| ref_tip(repo, &format!("refs/heads/{parent}")))
+}
+
+fn count_commits(repo: &gix::Repository, tip: gix::ObjectId, base: gix::ObjectId) -> usize {
+    repo.rev_walk([tip])
+        .with_hidden([base])
+        .all()
+        .map(|w| w.filter_map(|r| r.ok()).count())
+        .unwrap_or(0)
+}
+
+fn stack_new(branch: String) -> Result<(), String> {
+    let repo = open_repo();
+    let current = current_branch(&repo).ok_or("Could not get current branch (detached HEAD?)")?;
+    let head = repo.head_commit().map_err(|e| format!("Could not get HEAD commit: {e}"))?.id;
+    let stacked = stack_layers(&repo).iter().any(|l| l.branch == current);
+    if !stacked && !is_main_branch(&current) && trunk_name(&repo).as_deref() != Some(current.as_str()) {
+        // A plain feature branch becomes the bottom layer.
+        let trunk = trunk_name(&repo).ok_or("Could not detect the default branch")?;
+        let trunk_tip = parent_tip(&repo, &[], &trunk).ok_or_else(|| format!("No {trunk} branch"))?;
+        let base = repo.merge_base(head, trunk_tip).map_err(|e| format!("{current} has no merge base with {trunk}: {e}"))?;
+        record_layer(&current, &trunk, base.detach())?;
+    }
+    if !run_cmd("git", &["switch", "-c", &branch]) {
+        return Err(format!("Failed to create {branch}"));
+    }
+    record_layer(&branch, &current, head)?;
+    println!("{branch} stacked on {current}");
+    Ok(())
+}
+
+fn stack_set(branches: Vec<String>, onto: Option<String>) -> Result<(), String> {
+    let repo = open_repo();
+    let trunk = match onto {
+        Some(t) => t,
+        None => trunk_name(&repo).ok_or("Could not detect the default branch; pass --onto")?,
     };
+    let mut parent = trunk;
+    for branch in &branches {
+        let tip = ref_tip(&repo, &format!("refs/heads/{branch}")).ok_or_else(|| format!("No branch {branch}"))?;
+        let parent_at = ref_tip(&repo, &format!("refs/heads/{parent}"))
+            .or_else(|| ref_tip(&repo, &format!("refs/remotes/origin/{parent}")))
+            .ok_or_else(|| format!("No branch {parent}"))?;
+        let base = repo.merge_base(tip, parent_at).map_err(|e| format!("{branch} has no merge base with {parent}: {e}"))?;
+        record_layer(branch, &parent, base.detach())?;
+        parent = branch.clone();
+    }
+    println!("Recorded stack: {}", branches.join(" <- "));
+    Ok(())
+}
+
+fn stack_show() -> Result<(), String> {
+    let repo = open_repo();
+    let current = current_branch(&repo).ok_or("Could not get current branch (detached HEAD?)")?;
+    let stack = load_stack(&repo, &current)?;
+    println!("{}", stack[0].parent);
+    for layer in &stack {
+        let marker = if layer.branch == current { "*" } else { " " };
+        let tip = ref_tip(&repo, &format!("refs/heads/{}", layer.branch));
+        let on = parent_tip(&repo, &stack, &layer.parent);
+        let state = match (tip, layer.base, on) {
+            (Some(tip), Some(base), Some(on)) => {
+                let n = count_commits(&repo, tip, base);
+                let stale = base != on && !is_ancestor(&repo, on, tip);
+                format!("{n} commit{}{}", if n == 1 { "" } else { "s" }, if stale { ", needs restack" } else { "" })
+            }
+            (None, ..) => "branch missing".to_string(),
+            _ => "base unknown, needs restack".to_string(),
+        };
+        println!("{marker} {}  ({state})", layer.branch);
+    }
+    Ok(())
+}
 
-    // 1. Rewrite target with new message
-    let mut prev = rewrite(target_id, &new_message, None);
-    println!("reworded: {target_id} -> {prev}");
+/// Rebuild every layer on its parent's current tip, bottom-up. Everything is replayed
+/// in memory first; refs only move once the whole stack went through without conflicts.
+fn stack_restack(from_branch: &str) -> Result<(), String> {
+    let repo = open_repo();
+    let stack = load_stack(&repo, from_branch)?;
+    let current = current_branch(&repo);
+
+    let mut tips: std::collections::HashMap<&str, gix::ObjectId> = Default::default();
+    let mut moves = Vec::new();
+    for layer in &stack {
+        let old = ref_tip(&repo, &format!("refs/heads/{}", layer.branch)).ok_or_else(|| format!("No branch {}", layer.branch))?;
+        let onto = match tips.get(layer.parent.as_str()) {
+            Some(&t) => t,
+            None => parent_tip(&repo, &stack, &layer.parent).ok_or_else(|| format!("No branch {}", layer.parent))?,
+        };
+        let new = if is_ancestor(&repo, onto, old) {
+            old
+        } else {
+            let from = match layer.base {
+                Some(b) => b,
+                None => repo.merge_base(old, onto).map_err(|e| format!("{}: no merge base with {}: {e}", layer.branch, layer.parent))?.detach(),
+            };
+            replay(&repo, from, old, onto).map_err(|e| format!("Restacking {} onto {}: {e}", layer.branch, layer.parent))?
+        };
+        tips.insert(&layer.branch, new);
+        moves.push((layer, old, new, onto));
+    }
 
-    // 2. Replay each descendant, reparenting first parent to the new chain
-    for desc in descendants {
-        let next = rewrite(desc, "", Some(prev));
-        println!("replayed: {desc} -> {next}");
-        prev = next;
+    for (layer, old, new, onto) in moves {
+        if new != old {
+            if current.as_deref() == Some(layer.branch.as_str()) {
+                // The checked-out layer also needs its index and worktree moved; --keep
+                // refuses rather than clobber local changes.
+                if !run_cmd("git", &["reset", "--keep", &new.to_string()]) {
+                    return Err(format!("Failed to move checked-out {} to {new}", layer.branch));
+                }
+            } else {
+                let name: gix::refs::FullName = format!("refs/heads/{}", layer.branch).try_into().map_err(|e| format!("{}: {e}", layer.branch))?;
+                update_ref(&repo, name, Some(old), new, "stack restack")?;
+            }
+            println!("restacked {}: {} -> {}", layer.branch, old.to_hex_with_len(8), new.to_hex_with_len(8));
+        }
+        record_layer(&layer.branch, &layer.parent, onto)?;
     }
+    Ok(())
+}
 
-    // 3. Move branch ref to new tip
-    let branch_ref = repo.head_name().unwrap_or_else(|e| {
-        eprintln!("ERROR: head_name: {e}");
-        std::process::exit(1);
-    }).unwrap_or_else(|| {
-        eprintln!("ERROR: HEAD is detached");
-        std::process::exit(1);
-    });
+/// Push every layer bottom-up, each with the same force rules as `push`.
+fn stack_push(from_branch: &str, dry_run: bool) -> Result<(), String> {
+    let stack = load_stack(&open_repo(), from_branch)?;
+    for layer in &stack {
+        let branch = &layer.branch;
+        let remote_commit = fetch_remote_tip(branch)?;
+        let repo = open_repo();
+        let local_commit = ref_tip(&repo, &format!("refs/heads/{branch}")).ok_or_else(|| format!("No branch {branch}"))?;
+
+        let mut args = vec!["push".to_string()];
+        match remote_commit {
+            Some(remote) if remote == local_commit => {
+                println!("{branch}: up to date");
+                continue;
+            }
+            Some(remote) => args.extend(force_arg(&repo, branch, local_commit, remote, false, false)?),
+            None => args.push("-u".to_string()),
+        }
+        args.extend(["origin".to_string(), branch.clone()]);
+        let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+        if dry_run {
+            println!("DRY-RUN: git {}", args.join(" "));
+            continue;
+        }
+        if !run_cmd("git", &args) {
+            return Err(format!("Pushing {branch} failed; layers above it were not pushed"));
+        }
+    }
+    Ok(())
+}
 
-    repo.edit_reference(gix::refs::transaction::RefEdit {
-        change: gix::refs::transaction::Change::Update {
-            log: gix::refs::transaction::LogChange {
-                mode: gix::refs::transaction::RefLog::AndReference,
-                force_create_reflog: false,
-                message: "reword".into(),
-            },
-            expected: gix::refs::transaction::PreviousValue::MustExistAndMatch(
-                gix::refs::Target::Object(head_id),
-            ),
-            new: gix::refs::Target::Object(prev),
-        },
-        name: branch_ref.clone(),
-        deref: false,
-    }).unwrap_or_else(|e| {
-        eprintln!("ERROR: update-ref: {e}");
-        std::process::exit(1);
-    });
+/// Point `branch`'s open PR at `base`, if it isn't already.
+fn retarget_pr(hub: &Hub, branch: &str, base: &str) -> Result<(), String> {
+    let Some(pr) = hub.pull(branch)?.filter(|pr| pr.is_open()) else { return Ok(()) };
+    if pr.base.branch == base {
+        return Ok(());
+    }
+    hub.api
+        .set_pull_base(&hub.base, pr.number, base)
+        .map_err(|e| format!("Failed to retarget PR #{} ({branch}) onto {base}: {e}", pr.number))?;
+    println!("PR #{} ({branch}) now targets {base}", pr.number);
+    Ok(())
+}
 
-    println!("branch updated: {} -> {prev}", branch_ref.as_bstr());
+/// One PR per layer, each against the layer below; existing ones get retargeted. The
+/// stack is synced and pushed first, so the PRs show what the layers hold now.
+fn stack_pr(draft: bool) -> Result<(), String> {
+    let repo = open_repo();
+    let current = current_branch(&repo).ok_or("Could not get current branch (detached HEAD?)")?;
+    let hub = Hub::open(&repo)?;
+    if hub.base_remote == "upstream" && load_stack(&repo, &current)?.len() > 1 {
+        // Layer branches live on origin only; upstream has nothing to stack a PR on.
+        return Err(format!(
+            "PRs go to upstream ({}), which has no layer branches to base them on; open the bottom layer's with `pr`",
+            hub.base
+        ));
+    }
+    let Some(current) = stack_sync(&current)? else { return Ok(()) };
+    stack_push(&current, false)?;
+    let repo = open_repo();
+    let stack = load_stack(&repo, &current)?;
+    for layer in &stack {
+        let (branch, base) = (&layer.branch, &layer.parent);
+        match hub.pull(branch)?.filter(|pr| pr.is_open()) {
+            Some(pr) if pr.base.branch == *base => println!("PR #{} ({branch}) -> {base}", pr.number),
+            Some(_) => retarget_pr(&hub, branch, base)?,
+            None => {
+                println!("Creating PR: {branch} -> {base}");
+                let (title, body) = hub.fill(branch, base);
+                let new = github::NewPull { title: &title, body: &body, head: &hub.head(branch), base, draft };
+                let created = hub.api.create_pull(&hub.base, &new).map_err(|e| format!("Failed to create PR for {branch}: {e}"))?;
+                println!("{}", created.html_url);
+            }
+        }
+    }
+    Ok(())
 }
 
-fn extract(commit: String) {
-    // Create/reset bak branch at current HEAD
-    // -f forces overwrite if it already exists
-    if !run_cmd("git", &["branch", "-f", "bak", "HEAD"]) {
-        eprintln!("ERROR: Failed to create/reset bak branch");
-        std::process::exit(1);
+/// Drop layers that have landed in the default branch (PR merged, or own commits already
+/// contained in it — a layer with none yet hasn't landed, it's just new): what sat on them moves down onto their parent, gets restacked
+/// there, and has its PR retargeted. Returns a branch still in the stack to continue
+/// from, None once all of it has merged.
+fn stack_sync(branch: &str) -> Result<Option<String>, String> {
+    let repo = open_repo();
+    let stack = load_stack(&repo, branch)?;
+    let trunk = stack[0].parent.clone();
+    gix_fetch(&repo, &format!("{trunk}:refs/remotes/origin/{trunk}")).map_err(|e| format!("Failed to fetch origin/{trunk}: {e}"))?;
+    let repo = open_repo();
+    let trunk_tip = ref_tip(&repo, &format!("refs/remotes/origin/{trunk}"));
+    // PR state counts only where there is a GitHub repo to ask.
+    let hub = Hub::open(&repo).ok();
+
+    let mut merged = Vec::new();
+    for layer in &stack {
+        let tip = ref_tip(&repo, &format!("refs/heads/{}", layer.branch));
+        let landed = match (tip, layer.base, trunk_tip) {
+            (Some(t), Some(base), Some(m)) => count_commits(&repo, t, base) > 0 && is_ancestor(&repo, t, m),
+            _ => false,
+        };
+        let pr_merged = match &hub {
+            Some(hub) => hub.pull(&layer.branch)?.is_some_and(|pr| pr.is_merged()),
+            None => false,
+        };
+        if landed || pr_merged {
+            merged.push(layer.branch.clone());
+        }
+    }
+    if merged.is_empty() {
+        return Ok(Some(branch.to_string()));
     }
 
-    // Remove the commit from history: replay everything after `commit` onto `commit^`
-    let onto = format!("{commit}^");
-    if !run_cmd("git", &["rebase", "--onto", &onto, &commit]) {
-        eprintln!("ERROR: rebase failed - bak branch preserves original state");
-        std::process::exit(1);
+    let mut moved = Vec::new();
+    for layer in &stack {
+        if merged.contains(&layer.branch) {
+            continue;
+        }
+        // First surviving ancestor; for the layer right above merged ones that's the default branch.
+        let mut parent = layer.parent.clone();
+        while let Some(below) = stack.iter().find(|l| l.branch == parent && merged.contains(&l.branch)) {
+            parent = below.parent.clone();
+        }
+        if parent != layer.parent {
+            // Keep the old base: it still marks where this layer's own commits start.
+            let base = layer.base.ok_or_else(|| format!("{} has no recorded base; `stack set` it again", layer.branch))?;
+            record_layer(&layer.branch, &parent, base)?;
+            println!("{} merged; {} now sits on {parent}", layer.parent, layer.branch);
+            moved.push((layer.branch.clone(), parent));
+        }
+    }
+    for b in &merged {
+        forget_layer(b);
     }
 
-    // Stage the commit's changes without creating a new commit
-    if !run_cmd("git", &["cherry-pick", "-n", &commit]) {
-        eprintln!("ERROR: cherry-pick failed");
-        std::process::exit(1);
+    let Some(rest) = stack.iter().find(|l| !merged.contains(&l.branch)) else {
+        println!("Whole stack merged into {trunk}");
+        return Ok(None);
+    };
+    stack_restack(&rest.branch)?;
+    if let Some(hub) = &hub {
+        for (b, parent) in &moved {
+            retarget_pr(hub, b, parent)?;
+        }
     }
+    Ok(Some(rest.branch.clone()))
+}
 
-    // Stash the staged changes
-    let stash_msg = format!("extracted {commit}");
-    if !run_cmd("git", &["stash", "push", "--staged", "-m", &stash_msg]) {
-        eprintln!("ERROR: stash failed");
+fn stack(cmd: StackCmd) {
+    let result = match cmd {
+        StackCmd::New { branch } => stack_new(branch),
+        StackCmd::Set { branches, onto } => stack_set(branches, onto),
+        StackCmd::Show => stack_show(),
+        StackCmd::Restack => current_branch(&open_repo())
+            .ok_or_else(|| "Could not get current branch (detached HEAD?)".to_string())
+            .and_then(|b| stack_restack(&b)),
+        StackCmd::Push { dry_run } => current_branch(&open_repo())
+            .ok_or_else(|| "Could not get current branch (detached HEAD?)".to_string())
+            .and_then(|b| stack_push(&b, dry_run)),
+        StackCmd::Pr { draft } => stack_pr(draft),
+        StackCmd::Sync => current_branch(&open_repo())
+            .ok_or_else(|| "Could not get current branch (detached HEAD?)".to_string())
+            .and_then(|b| stack_sync(&b))
+            .map(|_| ()),
+    };
+    if let Err(e) = result {
+        eprintln!("ERROR: {e}");
         std::process::exit(1);
     }
-
-    println!("Extracted {commit} into stash. Original state saved in 'bak' branch.");
 }
 
 fn main() {
@@ -1289,16 +3129,26 @@ fn main() {
             force_with_lease,
             force,
             dry_run,
+            explain,
+            json,
             args,
-        } => push(force_with_lease, force, dry_run, args),
+        } => push(force_with_lease, force, dry_run, explain, json, args),
         Commands::Delete { branch } => delete(branch),
         Commands::Reword { commit, message } => reword(commit, message),
-        Commands::Extract { commit } => extract(commit),
+        Commands::Drop { commits } => drop_commits(commits),
+        Commands::Squash { commit, into, message } => squash(commit, into, message),
+        Commands::Reorder { commit, after, before } => reorder(commit, after, before),
+        Commands::Extract { commit, stash } => extract(commit, stash),
+        Commands::Undo => undo(),
         Commands::Publish {
             repo_name,
             private,
             public,
             commit,
-        } => publish(repo_name, private, public, commit),
+            template,
+            sync,
+            dry_run,
+        } => publish(repo_name, private, public, commit, template, sync, dry_run),
+        Commands::Stack { cmd } => stack(cmd),
     }
 }
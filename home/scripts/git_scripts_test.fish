@@ -1,5 +1,7 @@
 #!/usr/bin/env fish
-# Integration tests for git_scripts.rs push auto-force-with-lease detection.
+# Integration tests for git_scripts.rs push auto-force-with-lease detection (and `stack`,
+# which relies on it), the drop/squash/reorder/extract/undo history rewrites, and GitHub calls
+# against a mock API.
 # Each scenario builds a fresh bare-remote + local clone, mutates state, then
 # invokes `git_scripts.rs push --dry-run` and asserts on the decision.
 
@@ -147,6 +149,220 @@ set out (run_dry_push | string collect)
 assert_no_force "divergent conflicting edit, different subjects" $out
 teardown
 
+function check --argument-names name
+    if $argv[2..]
+        echo "PASS: $name"
+        set -g pass (math $pass + 1)
+    else
+        echo "FAIL: $name"
+        set -g fail (math $fail + 1)
+    end
+end
+
+# --- scenario 6: stack restack after amending the lower layer ---
+# b is stacked on a; amending a must replay b's own commit onto a's new tip, and
+# the stack push then needs (and may take) force-with-lease for both layers. A new
+# layer off master has nothing of its own yet, so sync mustn't take it for landed.
+fresh_repo
+cd $tmpdir/local
+git switch -q -c a
+echo "a" >a.txt
+git add -A
+git commit -q -m "feat: a"
+$gp stack new b >/dev/null 2>&1
+echo "b" >b.txt
+git add -A
+git commit -q -m "feat: b"
+git push -q origin a b 2>/dev/null
+git switch -q a
+echo "a amended" >a.txt
+git commit -q -a --amend -m "feat: a"
+$gp stack restack >/dev/null 2>&1
+check "restack puts b on amended a" git merge-base --is-ancestor a b
+check "restack keeps b's own commit" test (git log --format=%s a..b | string collect) = "feat: b"
+set out ($gp stack push --dry-run 2>&1 | string collect)
+assert_force "stack push after restack" $out
+git switch -q master
+$gp stack new fresh >/dev/null 2>&1
+$gp stack sync >/dev/null 2>&1
+check "stack sync keeps a layer with no commits yet" test (git config branch.fresh.stackParent) = master
+teardown
+
+# --- scenario 7: --explain on the scenarios above ---
+# The conflicting edit is reported at risk (exit 2); the squashed fixup is preserved
+# by its subject (exit 0).
+fresh_repo
+cd $tmpdir/local
+sed -i 's/line2/line2 from remote/' file.txt
+git commit -q -am "feat: remote edit"
+git push -q
+git reset --hard HEAD~1 -q
+sed -i 's/line2/line2 from local/' file.txt
+git commit -q -am "feat: local edit"
+$gp push --explain >/dev/null 2>&1
+check "explain: conflicting edit exits 2" test $status -eq 2
+set out ($gp push --explain --json 2>/dev/null | string collect)
+check "explain: conflicting edit is at risk" string match -q '*"at_risk":1*' -- $out
+teardown
+
+fresh_repo
+cd $tmpdir/local
+echo "added by C1 (typo)" >>file.txt
+git commit -q -am "feat: add line"
+git push -q
+sed -i 's/typo/fixed/' file.txt
+git commit -q -a --fixup HEAD
+GIT_SEQUENCE_EDITOR=true git rebase -q -i --autosquash HEAD~2
+set out ($gp push --explain 2>/dev/null | string collect)
+check "explain: squashed fixup is preserved by subject" string match -q '*preserved (same subject as local*' -- $out
+teardown
+
+# --- scenario 8: history rewrites keep merges, abort on conflict, and undo ---
+fresh_repo
+cd $tmpdir/local
+for i in 1 2 3
+    echo $i >f$i.txt
+    git add -A
+    git commit -q -m "feat: $i"
+end
+git switch -q -c side HEAD~1
+echo side >side.txt
+git add -A
+git commit -q -m "feat: side"
+git switch -q -
+git merge -q --no-ff side -m "merge side"
+set before (git rev-parse HEAD)
+$gp drop HEAD~2 >/dev/null 2>&1
+check "drop: commit is gone" test (git log --first-parent --format=%s | string collect) = "merge side
+feat: 3
+feat: 1
+initial"
+check "drop: merge keeps its side parent" test (git rev-parse HEAD^2) = (git rev-parse side)
+$gp squash HEAD~1 -m "feat: 1+3" >/dev/null 2>&1
+check "squash: both changes in one commit" test (git log -1 --format=%s HEAD~1) = "feat: 1+3" -a -f f1.txt -a -f f3.txt
+$gp undo >/dev/null 2>&1
+check "undo: restores the pre-squash tip" test (git log -1 --format=%s HEAD~1) = "feat: 3"
+$gp squash HEAD -m "feat: 3+side" >/dev/null 2>&1
+check "squash: a merge hands its side parent to the commit it folds into" test (git rev-parse HEAD^2) = (git rev-parse side) -a -f side.txt
+$gp undo >/dev/null 2>&1
+$gp reorder HEAD~1 --before HEAD~2 >/dev/null 2>&1
+check "reorder --before: moves the commit below" test (git log --first-parent --format=%s | string collect) = "merge side
+feat: 1
+feat: 3
+initial"
+$gp reorder HEAD~1 --after HEAD~2 >/dev/null 2>&1
+check "reorder --after: moves the commit on top" test (git log --first-parent --format=%s | string collect) = "merge side
+feat: 3
+feat: 1
+initial"
+check "reorder: merge keeps its side parent" test (git rev-parse HEAD^2) = (git rev-parse side)
+set tip (git rev-parse HEAD)
+$gp extract HEAD~1 >/dev/null 2>&1
+check "extract: commit is gone" test (git log -1 --format=%s HEAD~1) = "feat: 1"
+check "extract: its changes are left staged" test (git diff --cached --name-only | string collect) = "f3.txt"
+git reset -q --hard $tip
+$gp extract HEAD~1 --stash >/dev/null 2>&1
+check "extract --stash: commit is gone" test (git log -1 --format=%s HEAD~1) = "feat: 1"
+check "extract --stash: nothing left staged" git diff --cached --quiet
+check "extract --stash: its changes are stashed" string match -q '*extracted *' -- (git stash list)
+git reset -q --hard $tip
+git stash drop -q
+echo x >f3.txt
+git commit -q -am "edit f3"
+set tip (git rev-parse HEAD)
+$gp drop HEAD~2 >/dev/null 2>&1
+check "drop: conflict exits 1" test $status -eq 1
+check "drop: conflict leaves the branch alone" test (git rev-parse HEAD) = $tip
+# A signature header stands in for a real one: a rewritten copy must not carry it.
+git reset -q --hard (git cat-file commit HEAD | sed '/^committer /a gpgsig not-a-real-signature' | git hash-object -t commit -w --stdin)
+$gp reorder HEAD~3 --before (git rev-list --max-parents=0 HEAD) >/dev/null 2>&1
+check "reorder --before the root: moves the commit to the bottom" test (git log --first-parent --format=%s | string collect) = "edit f3
+merge side
+feat: 3
+initial
+feat: 1"
+check "reorder: rewritten commits drop their signature" test (git cat-file commit HEAD | string match -c 'gpgsig*') -eq 0
+teardown
+
+# --- scenario 9: GitHub calls go to GITHUB_API_URL (a mock here), errors come back ---
+# pr --draft, and publish --sync --dry-run against a template.
+set mock '
+import json, sys, http.server
+class H(http.server.BaseHTTPRequestHandler):
+    def handle_any(self):
+        body = self.rfile.read(int(self.headers.get("Content-Length") or 0)).decode()
+        with open(sys.argv[2], "a") as log:
+            log.write(self.command + " " + self.path + " " + body + "\n")
+        repo = {"full_name": "me/proj", "clone_url": "", "default_branch": "master"}
+        if self.path == "/repos/me/proj":
+            code, out = 200, repo
+        elif self.path.startswith("/repos/me/proj/labels"):
+            # A full first page, so the one that matters is only found by paging on.
+            extra = [{"name": "extra %d" % i, "color": "ffffff", "description": ""} for i in range(100)]
+            code, out = 200, extra if self.path.endswith("&page=1") else [{"name": "bug", "color": "000000", "description": "Something is off"}]
+        elif "/actions/secrets" in self.path:
+            code, out = 403, {"message": "Resource not accessible by integration"}
+        elif self.command == "GET":
+            code, out = 200, []
+        elif "\"base\":\"down\"" in body:
+            code, out = 502, "<html><body>502 Bad Gateway</body></html>"
+        elif "\"base\":\"bad\"" in body:
+            code, out = 422, {"message": "Validation Failed", "errors": [{"resource": "PullRequest", "field": "base", "code": "invalid"}]}
+        else:
+            side = {"ref": "feat", "repo": repo}
+            code, out = 201, {"number": 1, "node_id": "PR_1", "state": "open", "draft": True, "merged_at": None,
+                              "html_url": "https://github.com/me/proj/pull/1", "base": side, "head": side}
+        data = (out if isinstance(out, str) else json.dumps(out)).encode()
+        self.send_response(code)
+        self.send_header("Content-Length", str(len(data)))
+        self.end_headers()
+        self.wfile.write(data)
+    do_GET = do_POST = handle_any
+    def log_message(self, *args):
+        pass
+http.server.HTTPServer(("127.0.0.1", int(sys.argv[1])), H).serve_forever()
+'
+fresh_repo
+cd $tmpdir/local
+git config remote.origin.pushurl https://github.com/me/proj.git
+git switch -q -c feat
+echo "feat" >feat.txt
+git add -A
+git commit -q -m "feat: thing"
+python3 -c $mock 18765 $tmpdir/requests.log &
+set mock_pid $last_pid
+sleep 0.5
+set -lx GITHUB_API_URL http://127.0.0.1:18765
+set -lx GITHUB_KEY test
+$gp pr --draft >/dev/null 2>&1
+check "pr --draft: exits 0" test $status -eq 0
+check "pr --draft: posts a draft from me:feat, filled from the commit" \
+    string match -q '*POST /repos/me/proj/pulls {"base":"master","body":"","draft":true,"head":"me:feat","title":"feat: thing"}*' -- (cat $tmpdir/requests.log)
+set out ($gp pr bad --draft 2>&1 | string collect)
+check "pr: API errors are reported" string match -q '*HTTP 422: Validation Failed; PullRequest base invalid*' -- $out
+set out ($gp pr down --draft 2>&1 | string collect)
+check "pr: a non-JSON error keeps its status and text" string match -q '*HTTP 502: <html><body>502 Bad Gateway</body></html>*' -- $out
+set -lx GITHUB_API_URL http://127.0.0.1:1
+set out ($gp pr --draft 2>&1 | string collect)
+check "pr: curl failures are reported" string match -q '*curl exited 7*' -- $out
+set -lx GITHUB_API_URL http://127.0.0.1:18765
+printf '%s\n' '[[label]]' 'name = "bug"' 'color = "d73a4a"' 'description = "Something is off"' \
+    '[[milestone]]' 'title = "1.0"' '[secrets]' >$tmpdir/publish.toml
+rm $tmpdir/requests.log
+$gp publish --sync --dry-run --template $tmpdir/publish.toml >/dev/null 2>&1
+check "publish --sync --dry-run: drift exits 2" test $status -eq 2
+set out ($gp publish --sync --dry-run --template $tmpdir/publish.toml 2>&1 | string collect)
+check "publish --sync --dry-run: reports the label color (from page 2)" string match -q '*label "bug": color 000000, template: d73a4a*' -- $out
+check "publish --sync --dry-run: reports the missing milestone" string match -q '*milestone "1.0": missing*' -- $out
+check "publish --sync --dry-run: changes nothing" test (grep -c -v '^GET ' $tmpdir/requests.log) -eq 0
+echo 'DEPLOY_KEY = "DEPLOY_KEY"' >>$tmpdir/publish.toml
+set out ($gp publish --sync --dry-run --template $tmpdir/publish.toml 2>&1 | string collect)
+check "publish --sync: a part that can't be read is reported, the rest still checked" \
+    string match -q '*milestone "1.0": missing*secrets: could not read, not checked: *HTTP 403*' -- $out
+set -e GITHUB_API_URL GITHUB_KEY
+kill $mock_pid
+teardown
+
 echo
 echo "==> $pass passed, $fail failed"
 exit $fail
@@ -6,9 +6,13 @@ edition = "2024"
 
 [dependencies]
 clap = { version = "4.5.49", features = ["derive"] }
+serde = { version = "1.0", features = ["derive"] }
+serde_json = "1.0"
 ---
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::process::{Command, Stdio};
 
 /// Smart shutdown with pre-shutdown cleanup
@@ -17,12 +21,36 @@ use std::process::{Command, Stdio};
 #[command(about = "Clean shutdown: terminates tmux, kills slow services, then shuts down")]
 struct Args {
     /// Skip the actual shutdown (dry run)
-    #[arg(short = 'n', long)]
+    #[arg(short = 'n', long, global = true)]
     dry_run: bool,
 
+    /// Shut down even if a pre-flight check objects (the report still prints)
+    #[arg(long)]
+    force: bool,
+
+    /// How long a pre-flight check that will clear by itself (a build, a download,
+    /// an active claude) may hold the shutdown back before it counts as a veto
+    #[arg(long, value_name = "MINUTES", default_value_t = 15)]
+    wait: u64,
+
+    /// Pre-flight checks to skip: git, builds, downloads, claude
+    #[arg(long, value_delimiter = ',')]
+    skip: Vec<String>,
+
     /// Internal flag: run as detached process (used when inside tmux)
     #[arg(long, hide = true)]
     detached: bool,
+
+    #[command(subcommand)]
+    cmd: Option<Cmd>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// Rebuild the tmux workspace the last shutdown recorded: sessions, windows,
+    /// pane layouts and cwds, with each claude resumed into its original pane.
+    /// Sessions already up are left alone.
+    Restore,
 }
 
 fn run_cmd_silent(cmd: &str, args: &[&str]) -> bool {
@@ -35,59 +63,723 @@ fn run_cmd_silent(cmd: &str, args: &[&str]) -> bool {
         .unwrap_or(false)
 }
 
-/// `path\tcount` of live claudes per project, consumed by fish's `restore_sessions` on boot.
-fn write_claude_inventory(dry_run: bool) {
-    let state_dir = std::env::var("XDG_STATE_HOME")
+/// What the tmux server looked like at shutdown, written to `workspace.json` for
+/// `smart_shutdown restore` on the next boot.
+#[derive(Serialize, Deserialize, Debug)]
+struct Workspace {
+    taken_at: u64,
+    sessions: Vec<Session>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Session {
+    name: String,
+    path: String,
+    windows: Vec<Window>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Window {
+    index: u32,
+    name: String,
+    /// tmux's `window_layout`, replayed with select-layout once the panes exist.
+    layout: String,
+    active: bool,
+    panes: Vec<Pane>,
+}
+
+impl Window {
+    /// The panes that get a claude back, by index, and what gets typed into each.
+    fn launches(&self) -> impl Iterator<Item = (usize, String)> + '_ {
+        self.panes.iter().enumerate().filter_map(|(n, p)| {
+            let command = match &p.claude.as_ref()?.resume {
+                Some(id) => format!("cl --resume {id}"),
+                None => "cl".to_string(),
+            };
+            Some((n, command))
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Pane {
+    cwd: String,
+    /// What was running, for the record — only claude gets relaunched.
+    command: String,
+    active: bool,
+    /// A claude pane: the conversation to resume, when claude_sessions knew it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    claude: Option<Claude>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Claude {
+    resume: Option<String>,
+}
+
+fn state_dir() -> String {
+    std::env::var("XDG_STATE_HOME")
         .or_else(|_| std::env::var("HOME").map(|h| format!("{h}/.local/state")))
-        .expect("neither XDG_STATE_HOME nor HOME set");
-    let path = format!("{state_dir}/claude_restore.tsv");
-
-    let out = match Command::new("tmux")
-        .args([
-            "list-panes",
-            "-a",
-            "-F",
-            "#{session_path}\t#{pane_current_command}",
-        ])
-        .output()
-    {
-        Ok(out) if out.status.success() => out,
-        // No tmux server -> no sessions to restore; a stale file would be worse than none.
-        _ if dry_run => {
-            println!("Dry run - no tmux panes; would clear {path}");
-            return;
+        .expect("neither XDG_STATE_HOME nor HOME set")
+}
+
+fn workspace_path() -> String {
+    format!("{}/workspace.json", state_dir())
+}
+
+fn claude_sessions_path() -> String {
+    std::env::var("HOME")
+        .map(|h| format!("{h}/nix/home/config/tmux/claude_sessions.rs"))
+        .unwrap_or_else(|_| "/home/v/nix/home/config/tmux/claude_sessions.rs".to_string())
+}
+
+fn tmux_lines(args: &[&str]) -> Option<Vec<Vec<String>>> {
+    let out = Command::new("tmux").args(args).output().ok().filter(|o| o.status.success())?;
+    Some(String::from_utf8_lossy(&out.stdout).lines().map(|l| l.split('\t').map(str::to_string).collect()).collect())
+}
+
+/// One window of `claude_sessions --json`, as much as shutdown cares about.
+#[derive(Deserialize)]
+struct ClaudeEntry {
+    #[serde(default)]
+    host: Option<String>,
+    name: String,
+    window_index: u32,
+    state: String,
+    #[serde(default)]
+    session_id: Option<String>,
+}
+
+impl ClaudeEntry {
+    fn key(&self) -> String {
+        format!("{}:{}", self.name, self.window_index)
+    }
+}
+
+/// The local server's claude windows; None when claude_sessions can't say.
+fn claude_entries() -> Option<Vec<ClaudeEntry>> {
+    let out = Command::new(claude_sessions_path()).arg("--json").output().ok().filter(|o| o.status.success())?;
+    let entries: Vec<ClaudeEntry> = serde_json::from_slice(&out.stdout).ok()?;
+    Some(entries.into_iter().filter(|e| e.host.is_none()).collect())
+}
+
+fn read_workspace(claudes: &[ClaudeEntry]) -> Option<Workspace> {
+    let windows = tmux_lines(&[
+        "list-windows",
+        "-a",
+        "-F",
+        "#{session_name}\t#{session_path}\t#{window_index}\t#{window_name}\t#{window_layout}\t#{window_active}",
+    ])?;
+    let panes = tmux_lines(&[
+        "list-panes",
+        "-a",
+        "-F",
+        "#{session_name}\t#{window_index}\t#{pane_current_path}\t#{pane_current_command}\t#{pane_active}",
+    ])?;
+    let taken_at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    Some(workspace(&windows, &panes, claudes, taken_at))
+}
+
+/// The snapshot from `read_workspace`'s list-windows and list-panes lines, split on tabs.
+fn workspace(windows: &[Vec<String>], panes: &[Vec<String>], claudes: &[ClaudeEntry], taken_at: u64) -> Workspace {
+    // session:window -> resume id, from claude_sessions' transcript mapping.
+    let resume_ids: HashMap<String, &String> = claudes.iter().filter_map(|e| Some((e.key(), e.session_id.as_ref()?))).collect();
+
+    let mut sessions: Vec<Session> = Vec::new();
+    for w in windows.iter().filter(|w| w.len() == 6) {
+        let key = format!("{}:{}", w[0], w[2]);
+        let mut panes: Vec<Pane> = panes
+            .iter()
+            .filter(|p| p.len() == 5 && format!("{}:{}", p[0], p[1]) == key)
+            .map(|p| Pane { cwd: p[2].clone(), command: p[3].clone(), active: p[4] == "1", claude: None })
+            .collect();
+        // claude_sessions reads a window, not a pane: its id goes to the claude pane
+        // there, or to a lone pane a claude exited in (the resume hint is all that's left).
+        let id = resume_ids.get(&key).map(|id| id.to_string());
+        let lone = panes.len() == 1;
+        let mut claimed = false;
+        for p in panes.iter_mut().filter(|p| p.command.contains("claude")) {
+            p.claude = Some(Claude { resume: if claimed { None } else { id.clone() } });
+            claimed = true;
+        }
+        if !claimed && lone && id.is_some() {
+            panes[0].claude = Some(Claude { resume: id });
         }
-        _ => {
+        let window = Window { index: w[2].parse().unwrap_or(0), name: w[3].clone(), layout: w[4].clone(), active: w[5] == "1", panes };
+        match sessions.iter_mut().find(|s| s.name == w[0]) {
+            Some(s) => s.windows.push(window),
+            None => sessions.push(Session { name: w[0].clone(), path: w[1].clone(), windows: vec![window] }),
+        }
+    }
+    Workspace { taken_at, sessions }
+}
+
+/// The tmux layout and every claude's conversation, consumed by `restore` on boot.
+fn write_workspace(dry_run: bool) {
+    let path = workspace_path();
+    // Without claude_sessions the claudes come back fresh rather than not at all.
+    let claudes = claude_entries().unwrap_or_else(|| {
+        eprintln!("Warning: claude_sessions failed; claudes will restore without their conversations");
+        Vec::new()
+    });
+    let Some(workspace) = read_workspace(&claudes) else {
+        // No tmux server -> nothing to restore; a stale file would be worse than none.
+        if dry_run {
+            println!("Dry run - no tmux panes; would clear {path}");
+        } else {
             let _ = std::fs::remove_file(&path);
             println!("No tmux panes; cleared {path}");
-            return;
         }
+        return;
+    };
+    let claudes = workspace.sessions.iter().flat_map(|s| &s.windows).flat_map(|w| &w.panes).filter(|p| p.claude.is_some()).count();
+    let summary = format!("{} session(s), {claudes} claude(s)", workspace.sessions.len());
+    let content = serde_json::to_string_pretty(&workspace).expect("workspace serializes");
+    if dry_run {
+        println!("Dry run - would record {summary} to {path}:\n{content}");
+        return;
+    }
+    std::fs::write(&path, &content).expect("failed to write workspace snapshot");
+    println!("Recorded {summary} to {path}");
+}
+
+fn tmux(args: &[&str]) -> Result<String, String> {
+    let out = Command::new("tmux").args(args).output().map_err(|e| format!("tmux: {e}"))?;
+    if out.status.success() {
+        Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+    } else {
+        Err(format!("tmux {}: {}", args.join(" "), String::from_utf8_lossy(&out.stderr).trim()))
+    }
+}
+
+/// Recreates one session window by window. Only claudes are relaunched, typed in
+/// through `cl` so its per-project model rules still apply.
+fn restore_session(session: &Session, dry_run: bool) -> Result<usize, String> {
+    let mut claudes = 0;
+    for (i, window) in session.windows.iter().enumerate() {
+        let target = format!("{}:{}", session.name, window.index);
+        let first_cwd = window.panes.first().map_or(session.path.as_str(), |p| p.cwd.as_str());
+        if dry_run {
+            println!("Dry run - would open {target} ({}) with {} pane(s)", window.name, window.panes.len());
+        } else if i == 0 {
+            // The session's first window lands on base-index; moved to its recorded slot after.
+            let made = tmux(&[
+                "new-session", "-d", "-P", "-F", "#{session_name}:#{window_index}", "-s", &session.name, "-c", first_cwd, "-n", &window.name,
+            ])?;
+            if made != target {
+                tmux(&["move-window", "-s", &made, "-t", &target])?;
+            }
+        } else {
+            tmux(&["new-window", "-d", "-t", &target, "-c", first_cwd, "-n", &window.name])?;
+        }
+        for pane in window.panes.iter().skip(1) {
+            if !dry_run {
+                tmux(&["split-window", "-d", "-t", &target, "-c", &pane.cwd])?;
+            }
+        }
+        if !dry_run && window.panes.len() > 1 {
+            // A layout from a differently-sized client still applies, scaled; a failure only costs the split sizes.
+            if let Err(e) = tmux(&["select-layout", "-t", &target, &window.layout]) {
+                eprintln!("Warning: {e}");
+            }
+        }
+        for (n, command) in window.launches() {
+            claudes += 1;
+            if dry_run {
+                println!("Dry run - would run `{command}` in {target}.{n}");
+                continue;
+            }
+            let pane_target = format!("{target}.{n}");
+            tmux(&["send-keys", "-t", &pane_target, &command, "Enter"])?;
+        }
+        if !dry_run && let Some(n) = window.panes.iter().position(|p| p.active) {
+            let _ = tmux(&["select-pane", "-t", &format!("{target}.{n}")]);
+        }
+    }
+    if !dry_run && let Some(w) = session.windows.iter().find(|w| w.active) {
+        let _ = tmux(&["select-window", "-t", &format!("{}:{}", session.name, w.index)]);
+    }
+    Ok(claudes)
+}
+
+fn restore(dry_run: bool) -> Result<(), String> {
+    let path = workspace_path();
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!("Nothing to restore: {path} absent (only smart_shutdown writes it)");
+            return Ok(());
+        }
+        Err(e) => return Err(format!("{path}: {e}")),
     };
+    let workspace: Workspace = serde_json::from_str(&text).map_err(|e| format!("{path}: {e}"))?;
+    let up: Vec<String> = tmux_lines(&["list-sessions", "-F", "#{session_name}"])
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|l| l.into_iter().next())
+        .collect();
 
-    let mut counts: Vec<(String, usize)> = Vec::new();
-    for line in String::from_utf8_lossy(&out.stdout).lines() {
-        let Some((session_path, cmd)) = line.split_once('\t') else {
+    let mut failed = false;
+    for session in &workspace.sessions {
+        // Already up (hand-made, or we ran late) — its windows are whatever they are now.
+        if up.contains(&session.name) {
+            println!("{}: already up, left alone", session.name);
             continue;
-        };
-        if !cmd.contains("claude") {
+        }
+        if let Some(w) = session.windows.iter().flat_map(|w| &w.panes).find(|p| !std::path::Path::new(&p.cwd).is_dir()) {
+            eprintln!("{}: skipped — {} is no longer a directory", session.name, w.cwd);
+            failed = true;
             continue;
         }
-        match counts.iter_mut().find(|(p, _)| p == session_path) {
-            Some((_, n)) => *n += 1,
-            None => counts.push((session_path.to_string(), 1)),
+        match restore_session(session, dry_run) {
+            Ok(claudes) => println!("{}: {} window(s), {claudes} claude(s)", session.name, session.windows.len()),
+            Err(e) => {
+                eprintln!("{}: {e}", session.name);
+                failed = true;
+            }
         }
     }
 
-    let content: String = counts
-        .iter()
-        .map(|(p, n)| format!("{p}\t{n}\n"))
-        .collect();
-    if dry_run {
-        println!("Dry run - would record {} project(s) to {path}:\n{content}", counts.len());
-        return;
+    if failed {
+        return Err(format!("some sessions failed; keeping {path} so a rerun can retry"));
+    }
+    if !dry_run {
+        std::fs::remove_file(&path).map_err(|e| format!("{path}: {e}"))?;
+    }
+    Ok(())
+}
+
+/// What has to be true before the machine goes down. Each check reports what it
+/// found; a `Wait` clears by itself (a build finishing) and holds the shutdown
+/// back up to --wait, a `Veto` needs a person and stops it outright.
+mod preflight {
+    use super::*;
+    use std::collections::BTreeSet;
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+
+    /// Exit status when pre-flight stops the shutdown; supervise_sessions takes
+    /// it as "leave the machine up" rather than a failure to force past.
+    pub const VETOED: i32 = 3;
+    const RECHECK: Duration = Duration::from_secs(30);
+    /// A partial download untouched this long is abandoned, not in progress.
+    const STALE_PARTIAL: Duration = Duration::from_secs(120);
+
+    pub enum Verdict {
+        /// Worth a line in the report, not worth holding up the shutdown.
+        Note(String),
+        Wait(String),
+        Veto(String),
+    }
+
+    /// Read once per round and shared by every check.
+    pub struct Context {
+        /// Every pane's cwd.
+        cwds: BTreeSet<String>,
+        claudes: Option<Vec<ClaudeEntry>>,
+        processes: Vec<Process>,
+        /// Where the browser writes its partials.
+        downloads: std::path::PathBuf,
+    }
+
+    struct Process {
+        pid: u32,
+        argv: Vec<String>,
+        cwd: Option<std::path::PathBuf>,
+    }
+
+    impl Process {
+        fn names(&self) -> impl Iterator<Item = &str> {
+            // A script's argv[0] is its interpreter; the script is argv[1].
+            self.argv.iter().take(2).map(|a| a.rsplit('/').next().unwrap_or(a))
+        }
+
+        /// The cargo/nix subcommand, past rustup's `+toolchain`.
+        fn subcommand(&self) -> &str {
+            self.argv.iter().skip(1).find(|a| !a.starts_with('+')).map_or("", String::as_str)
+        }
+
+        /// Output for a program rather than a person: an editor's background
+        /// check (rust-analyzer's flycheck), rerun on the next save anyway.
+        fn for_a_tool(&self) -> bool {
+            self.argv.iter().any(|a| a.starts_with("--message-format=json"))
+                || self.argv.windows(2).any(|w| w[0] == "--message-format" && w[1].starts_with("json"))
+        }
+
+        fn describe(&self) -> String {
+            let cmd: Vec<&str> = self.argv.iter().take(3).map(|a| a.rsplit('/').next().unwrap_or(a)).collect();
+            match &self.cwd {
+                Some(cwd) => format!("`{}` (pid {}) in {}", cmd.join(" "), self.pid, cwd.display()),
+                None => format!("`{}` (pid {})", cmd.join(" "), self.pid),
+            }
+        }
+    }
+
+    /// Our own ancestry is exempt: shutdown may well be running under cargo itself.
+    fn ancestors() -> BTreeSet<u32> {
+        let mut pids = BTreeSet::new();
+        let mut pid = std::process::id();
+        while pid > 1 && pids.insert(pid) {
+            let Ok(stat) = std::fs::read_to_string(format!("/proc/{pid}/stat")) else { break };
+            // comm may hold spaces and parens; ppid is the 2nd field after its closing paren.
+            pid = stat.rsplit_once(')').and_then(|(_, rest)| rest.split_whitespace().nth(1)?.parse().ok()).unwrap_or(0);
+        }
+        pids
+    }
+
+    fn processes() -> Vec<Process> {
+        let skip = ancestors();
+        let Ok(dir) = std::fs::read_dir("/proc") else { return Vec::new() };
+        dir.filter_map(|e| e.ok()?.file_name().to_str()?.parse::<u32>().ok())
+            .filter(|pid| !skip.contains(pid))
+            .filter_map(|pid| {
+                let raw = std::fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+                let argv: Vec<String> = raw.split(|&b| b == 0).filter(|a| !a.is_empty()).map(|a| String::from_utf8_lossy(a).into_owned()).collect();
+                let cwd = std::fs::read_link(format!("/proc/{pid}/cwd")).ok();
+                (!argv.is_empty()).then_some(Process { pid, argv, cwd })
+            })
+            .collect()
+    }
+
+    impl Context {
+        pub fn read() -> Context {
+            let claudes = claude_entries();
+            let cwds = tmux_lines(&["list-panes", "-a", "-F", "#{pane_current_path}"])
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|l| l.into_iter().next())
+                .collect();
+            let downloads = std::env::var("HOME").map(|h| Path::new(&h).join("Downloads")).unwrap_or_default();
+            Context { cwds, claudes, processes: processes(), downloads }
+        }
+    }
+
+    pub trait Check {
+        fn name(&self) -> &'static str;
+        /// Nothing found is an empty list.
+        fn run(&self, cx: &Context) -> Vec<Verdict>;
+    }
+
+    fn git(dir: &Path, args: &[&str]) -> Option<String> {
+        let out = Command::new("git").arg("-C").arg(dir).args(args).stderr(Stdio::null()).output().ok()?;
+        out.status.success().then(|| String::from_utf8_lossy(&out.stdout).trim().to_string())
+    }
+
+    /// Uncommitted changes to tracked files or commits on no remote, in any repo a
+    /// pane sits in. Untracked files are only noted.
+    pub struct Git;
+
+    impl Check for Git {
+        fn name(&self) -> &'static str {
+            "git"
+        }
+
+        fn run(&self, cx: &Context) -> Vec<Verdict> {
+            let repos: BTreeSet<String> = cx.cwds.iter().filter_map(|cwd| git(Path::new(cwd), &["rev-parse", "--show-toplevel"])).collect();
+            let mut found = Vec::new();
+            for repo in repos {
+                let dir = Path::new(&repo);
+                // Untracked files are scratch as often as not; one stray file
+                // mustn't hold every shutdown, so they're only mentioned.
+                let status = git(dir, &["status", "--porcelain"]).unwrap_or_default();
+                let (untracked, dirty) = status.lines().fold((0, 0), |(u, d), l| if l.starts_with("??") { (u + 1, d) } else { (u, d + 1) });
+                if dirty > 0 {
+                    found.push(Verdict::Veto(format!("{repo}: {dirty} uncommitted change(s)")));
+                }
+                if untracked > 0 {
+                    found.push(Verdict::Note(format!("{repo}: {untracked} untracked file(s)")));
+                }
+                // Covers both a branch ahead of its upstream and one that never had one.
+                let unpushed: usize = git(dir, &["rev-list", "--count", "HEAD", "--not", "--remotes"]).and_then(|n| n.parse().ok()).unwrap_or(0);
+                if unpushed > 0 {
+                    let branch = git(dir, &["branch", "--show-current"]).filter(|b| !b.is_empty()).unwrap_or_else(|| "HEAD".to_string());
+                    // A repo with no remote at all has nowhere to push; holding
+                    // the shutdown for it would hold every shutdown.
+                    if git(dir, &["remote"]).is_some_and(|r| r.is_empty()) {
+                        found.push(Verdict::Note(format!("{repo}: {unpushed} commit(s) on {branch}, no remote to push to")));
+                    } else {
+                        found.push(Verdict::Veto(format!("{repo}: {unpushed} commit(s) on {branch} not on any remote")));
+                    }
+                }
+            }
+            found
+        }
+    }
+
+    /// nixos-rebuild and cargo/nix builds: killed halfway they leave a broken
+    /// generation or a half-written target dir behind.
+    pub struct Builds;
+
+    impl Check for Builds {
+        fn name(&self) -> &'static str {
+            "builds"
+        }
+
+        fn run(&self, cx: &Context) -> Vec<Verdict> {
+            const CARGO: &[&str] = &["build", "check", "clippy", "test", "install", "bench", "doc"];
+            cx.processes
+                .iter()
+                .filter(|p| {
+                    let sub = p.subcommand();
+                    p.names().any(|n| n.starts_with("nixos-rebuild"))
+                        || (p.names().next() == Some("cargo") && CARGO.contains(&sub) && !p.for_a_tool())
+                        || (p.names().next() == Some("nix") && sub == "build")
+                })
+                .map(|p| Verdict::Wait(p.describe()))
+                .collect()
+        }
+    }
+
+    /// Downloaders still running, and browser partials still being written.
+    pub struct Downloads;
+
+    impl Check for Downloads {
+        fn name(&self) -> &'static str {
+            "downloads"
+        }
+
+        fn run(&self, cx: &Context) -> Vec<Verdict> {
+            const TOOLS: &[&str] = &["curl", "wget", "aria2c", "yt-dlp", "rsync", "scp"];
+            let mut found: Vec<Verdict> = cx
+                .processes
+                .iter()
+                .filter(|p| p.names().next().is_some_and(|n| TOOLS.contains(&n)))
+                .map(|p| Verdict::Wait(p.describe()))
+                .collect();
+            for entry in std::fs::read_dir(&cx.downloads).into_iter().flatten().flatten() {
+                let path = entry.path();
+                let partial = path.extension().and_then(|e| e.to_str()).is_some_and(|e| matches!(e, "part" | "crdownload" | "download"));
+                let fresh = entry.metadata().and_then(|m| m.modified()).is_ok_and(|t| t.elapsed().is_ok_and(|d| d < STALE_PARTIAL));
+                if partial && fresh {
+                    found.push(Verdict::Wait(format!("{} still growing", path.display())));
+                }
+            }
+            found
+        }
+    }
+
+    /// A claude mid-turn loses the turn.
+    pub struct Claudes;
+
+    impl Check for Claudes {
+        fn name(&self) -> &'static str {
+            "claude"
+        }
+
+        fn run(&self, cx: &Context) -> Vec<Verdict> {
+            let Some(claudes) = &cx.claudes else {
+                return vec![Verdict::Note("claude_sessions failed; can't tell what's running".to_string())];
+            };
+            claudes
+                .iter()
+                .filter(|e| matches!(e.state.as_str(), "active" | "planning"))
+                .map(|e| Verdict::Wait(format!("{} is {}", e.key(), e.state)))
+                .collect()
+        }
+    }
+
+    pub fn all() -> Vec<Box<dyn Check>> {
+        vec![Box::new(Git), Box::new(Builds), Box::new(Downloads), Box::new(Claudes)]
+    }
+
+    pub struct Report(Vec<(&'static str, Vec<Verdict>)>);
+
+    impl Report {
+        fn vetoed(&self) -> bool {
+            self.0.iter().flat_map(|(_, v)| v).any(|v| matches!(v, Verdict::Veto(_)))
+        }
+
+        fn waiting(&self) -> bool {
+            self.0.iter().flat_map(|(_, v)| v).any(|v| matches!(v, Verdict::Wait(_)))
+        }
+    }
+
+    impl std::fmt::Display for Report {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "pre-flight:")?;
+            for (name, verdicts) in &self.0 {
+                if verdicts.is_empty() {
+                    write!(f, "\n  ✓ {name}")?;
+                }
+                for v in verdicts {
+                    let (mark, what) = match v {
+                        Verdict::Note(s) => ("·", s),
+                        Verdict::Wait(s) => ("⏳", s),
+                        Verdict::Veto(s) => ("✗", s),
+                    };
+                    write!(f, "\n  {mark} {name}: {what}")?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn run(checks: &[Box<dyn Check>]) -> Report {
+        let cx = Context::read();
+        Report(checks.iter().map(|c| (c.name(), c.run(&cx))).collect())
+    }
+
+    fn tg(msg: &str) {
+        if let Err(e) = Command::new("tg").args(["send", "-c", "general", msg]).status() {
+            eprintln!("Warning: failed to run tg: {e}");
+        }
+    }
+
+    /// Runs the checks, re-running while something is only `Wait`ing, for up to
+    /// `wait`. Returns when shutdown may go ahead; exits VETOED when it may not.
+    /// `force` reports and goes ahead regardless.
+    pub fn gate(skip: &[String], wait: Duration, force: bool, dry_run: bool) {
+        let checks: Vec<Box<dyn Check>> = all().into_iter().filter(|c| !skip.iter().any(|s| s == c.name())).collect();
+        let deadline = Instant::now() + wait;
+        loop {
+            let report = run(&checks);
+            println!("{report}");
+            if !report.vetoed() && !report.waiting() {
+                return;
+            }
+            if force {
+                println!("--force: shutting down anyway");
+                return;
+            }
+            if dry_run {
+                println!("Dry run - a real run would not shut down here");
+                return;
+            }
+            if !report.vetoed() && Instant::now() + RECHECK <= deadline {
+                println!("Waiting {}s, then checking again...", RECHECK.as_secs());
+                std::thread::sleep(RECHECK);
+                continue;
+            }
+            // Raised from the tg gateway, nobody is looking at this terminal.
+            let why = if report.vetoed() { "vetoed" } else { "still waiting after --wait" };
+            tg(&format!("smart_shutdown: not shutting down — {why}\n{report}\n(--force to override)"));
+            eprintln!("Not shutting down: {why} (--force to override)");
+            std::process::exit(VETOED);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::path::PathBuf;
+
+        fn context(cwds: &[&Path], processes: &[&[&str]]) -> Context {
+            Context {
+                cwds: cwds.iter().map(|p| p.display().to_string()).collect(),
+                claudes: Some(Vec::new()),
+                processes: processes
+                    .iter()
+                    .enumerate()
+                    .map(|(pid, argv)| Process { pid: pid as u32, argv: argv.iter().map(|a| a.to_string()).collect(), cwd: None })
+                    .collect(),
+                downloads: PathBuf::new(),
+            }
+        }
+
+        fn scratch(name: &str) -> PathBuf {
+            let dir = std::env::temp_dir().join(format!("smart-shutdown-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            dir
+        }
+
+        fn sh(dir: &Path, line: &str) {
+            let ok = Command::new("sh").arg("-c").arg(line).current_dir(dir).stdout(Stdio::null()).stderr(Stdio::null()).status().unwrap().success();
+            assert!(ok, "{line}");
+        }
+
+        /// Veto, Wait or Note, and what it says — enough to assert on.
+        fn found(verdicts: Vec<Verdict>) -> Vec<(&'static str, String)> {
+            verdicts
+                .into_iter()
+                .map(|v| match v {
+                    Verdict::Note(s) => ("note", s),
+                    Verdict::Wait(s) => ("wait", s),
+                    Verdict::Veto(s) => ("veto", s),
+                })
+                .collect()
+        }
+
+        #[test]
+        fn git_vetoes_uncommitted_and_unpushed_work_but_not_untracked_files_or_a_repo_with_no_remote() {
+            let dir = scratch("git");
+            let repo = dir.join("repo");
+            std::fs::create_dir(&repo).unwrap();
+            sh(&repo, "git init -q && git -c user.name=t -c user.email=t -c commit.gpgsign=false commit -q --allow-empty -m one");
+            let cx = context(&[&repo], &[]);
+            let kinds = |cx: &Context| found(Git.run(cx)).into_iter().map(|(k, _)| k).collect::<Vec<_>>();
+
+            assert_eq!(kinds(&cx), ["note"], "a local-only repo has nowhere to push");
+
+            sh(&dir, "git init -q --bare origin.git");
+            sh(&repo, "git remote add origin ../origin.git");
+            assert_eq!(kinds(&cx), ["veto"], "a remote without the commit");
+
+            sh(&repo, "git push -q origin HEAD:main");
+            assert_eq!(kinds(&cx), Vec::<&str>::new());
+
+            std::fs::write(repo.join("new.txt"), "x").unwrap();
+            let untracked = found(Git.run(&cx));
+            sh(&repo, "git add new.txt");
+            let staged = found(Git.run(&cx));
+            std::fs::remove_dir_all(&dir).unwrap();
+            assert_eq!(untracked.len(), 1);
+            assert_eq!(untracked[0].0, "note", "a stray file is only mentioned");
+            assert!(untracked[0].1.ends_with("1 untracked file(s)"), "{}", untracked[0].1);
+            assert_eq!(staged.len(), 1);
+            assert_eq!(staged[0].0, "veto");
+            assert!(staged[0].1.ends_with("1 uncommitted change(s)"), "{}", staged[0].1);
+        }
+
+        #[test]
+        fn builds_wait_on_real_builds_only() {
+            let cx = context(
+                &[],
+                &[
+                    &["/nix/store/x-rustup/bin/cargo", "+nightly", "build"],
+                    &["cargo", "check", "--workspace", "--message-format=json-diagnostic-rendered-ansi"],
+                    &["cargo", "clippy", "--message-format", "json"],
+                    &["cargo", "run"],
+                    &["bash", "/run/current-system/sw/bin/nixos-rebuild", "switch"],
+                    &["nix", "build", ".#x"],
+                    &["nix", "develop"],
+                ],
+            );
+            let got: Vec<String> = found(Builds.run(&cx)).into_iter().map(|(k, s)| format!("{k} {s}")).collect();
+            assert_eq!(got, ["wait `cargo +nightly build` (pid 0)", "wait `bash nixos-rebuild switch` (pid 4)", "wait `nix build .#x` (pid 5)"]);
+        }
+
+        #[test]
+        fn downloads_wait_on_downloaders_and_fresh_partials() {
+            let dir = scratch("downloads");
+            std::fs::write(dir.join("movie.mkv.part"), "").unwrap();
+            std::fs::write(dir.join("done.pdf"), "").unwrap();
+            let mut cx = context(&[], &[&["curl", "-O", "https://example.com/big.iso"], &["vim", "notes"]]);
+            cx.downloads = dir.clone();
+            let got = found(Downloads.run(&cx));
+            std::fs::remove_dir_all(&dir).unwrap();
+            assert_eq!(got.len(), 2);
+            assert_eq!(got[0], ("wait", "`curl -O big.iso` (pid 0)".to_string()));
+            assert!(got[1].1.ends_with("movie.mkv.part still growing"), "{}", got[1].1);
+        }
+
+        #[test]
+        fn claudes_wait_on_a_turn_in_flight() {
+            let entry = |name: &str, state: &str| ClaudeEntry {
+                host: None,
+                name: name.to_string(),
+                window_index: 1,
+                state: state.to_string(),
+                session_id: None,
+            };
+            let mut cx = context(&[], &[]);
+            cx.claudes = Some(vec![entry("nix", "active"), entry("site", "finished"), entry("blog", "planning")]);
+            assert_eq!(found(Claudes.run(&cx)), [("wait", "nix:1 is active".to_string()), ("wait", "blog:1 is planning".to_string())]);
+
+            cx.claudes = None;
+            assert_eq!(found(Claudes.run(&cx))[0].0, "note");
+        }
     }
-    std::fs::write(&path, &content).expect("failed to write claude inventory");
-    println!("Recorded {} project(s) with claudes to {path}", counts.len());
 }
 
 fn main() {
@@ -101,6 +793,25 @@ fn main() {
         std::env::set_var("PATH", format!("/etc/profiles/per-user/v/bin:{path}"));
     }
 
+    if let Some(Cmd::Restore) = args.cmd {
+        if let Err(e) = restore(args.dry_run) {
+            eprintln!("restore: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Before detaching, so whoever asked sees the report and the exit status; the
+    // detached child has already been let through.
+    if !args.detached {
+        let names: Vec<&str> = preflight::all().iter().map(|c| c.name()).collect();
+        if let Some(bad) = args.skip.iter().find(|s| !names.contains(&s.as_str())) {
+            eprintln!("--skip: no check {bad:?} (one of {})", names.join(", "));
+            std::process::exit(2);
+        }
+        preflight::gate(&args.skip, std::time::Duration::from_secs(args.wait * 60), args.force, args.dry_run);
+    }
+
     // If we're inside tmux and not already detached, re-exec ourselves detached from tmux
     if !args.detached && std::env::var("TMUX").is_ok() {
         let exe = std::env::current_exe().expect("Failed to get current executable path");
@@ -155,11 +866,7 @@ fn main() {
 
     let claude_handle = std::thread::spawn(|| {
         println!("Saving claude sessions to telegram...");
-        let claude_sessions_path = std::env::var("HOME")
-            .map(|h| format!("{h}/nix/home/config/tmux/claude_sessions.rs"))
-            .unwrap_or_else(|_| "/home/v/nix/home/config/tmux/claude_sessions.rs".to_string());
-
-        let output = Command::new(&claude_sessions_path).output();
+        let output = Command::new(claude_sessions_path()).output();
 
         match output {
             Ok(out) if out.status.success() => {
@@ -195,12 +902,12 @@ fn main() {
 
     // Must run while the tmux server is still alive (see the kill-server below).
     let dry_run = args.dry_run;
-    let inventory_handle = std::thread::spawn(move || write_claude_inventory(dry_run));
+    let workspace_handle = std::thread::spawn(move || write_workspace(dry_run));
 
     // Wait for all three to finish before proceeding with shutdown
     tedi_handle.join().expect("tedi thread panicked");
     claude_handle.join().expect("claude_sessions thread panicked");
-    inventory_handle.join().expect("inventory thread panicked");
+    workspace_handle.join().expect("workspace thread panicked");
 
     // 2. Kill tmux sessions
     if args.dry_run {
@@ -243,3 +950,69 @@ fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<Vec<String>> {
+        text.lines().map(|l| l.split('\t').map(str::to_string).collect()).collect()
+    }
+
+    fn claude(name: &str, window_index: u32, session_id: Option<&str>) -> ClaudeEntry {
+        ClaudeEntry { host: None, name: name.to_string(), window_index, state: "finished".to_string(), session_id: session_id.map(str::to_string) }
+    }
+
+    #[test]
+    fn each_claude_pane_resumes_its_own_conversation_in_its_own_cwd() {
+        let windows = lines(
+            "nix\t/home/v/nix\t1\tbar\tlayout-a\t0\n\
+             nix\t/home/v/nix\t2\tsplit\tlayout-b\t1\n\
+             nix\t/home/v/nix\t3\texited\tlayout-c\t0\n\
+             nix\t/home/v/nix\t4\tfresh\tlayout-d\t0\n\
+             nix\t/home/v/nix\t5\ttwo\tlayout-e\t0\n\
+             web\t/home/v/web\t0\tshell\tlayout-f\t1",
+        );
+        let panes = lines(
+            "nix\t1\t/home/v/nix\tclaude\t1\n\
+             nix\t2\t/home/v/nix/home\tfish\t1\n\
+             nix\t2\t/home/v/nix/home/scripts\tclaude\t0\n\
+             nix\t3\t/home/v/nix/hosts\tfish\t1\n\
+             nix\t4\t/home/v/notes\tclaude\t1\n\
+             nix\t5\t/home/v/a\tclaude\t1\n\
+             nix\t5\t/home/v/b\tclaude\t0\n\
+             web\t0\t/home/v/web\tfish\t1",
+        );
+        let claudes = [
+            claude("nix", 1, Some("id-bar")),
+            claude("nix", 2, Some("id-split")),
+            claude("nix", 3, Some("id-exited")),
+            claude("nix", 4, None),
+            claude("nix", 5, Some("id-two")),
+            claude("gone", 1, Some("id-gone")),
+        ];
+        let ws = workspace(&windows, &panes, &claudes, 0);
+
+        let launched: Vec<(String, &str, String)> = ws
+            .sessions
+            .iter()
+            .flat_map(|s| s.windows.iter().map(move |w| (s, w)))
+            .flat_map(|(s, w)| w.launches().map(move |(n, command)| (format!("{}:{}.{n}", s.name, w.index), w.panes[n].cwd.as_str(), command)))
+            .collect();
+        let expected = [
+            ("nix:1.0", "/home/v/nix", "cl --resume id-bar"),
+            ("nix:2.1", "/home/v/nix/home/scripts", "cl --resume id-split"),
+            ("nix:3.0", "/home/v/nix/hosts", "cl --resume id-exited"),
+            ("nix:4.0", "/home/v/notes", "cl"),
+            ("nix:5.0", "/home/v/a", "cl --resume id-two"),
+            ("nix:5.1", "/home/v/b", "cl"),
+        ];
+        assert_eq!(launched.iter().map(|(t, c, cmd)| (t.as_str(), *c, cmd.as_str())).collect::<Vec<_>>(), expected);
+
+        let names: Vec<(&str, &str)> = ws.sessions.iter().map(|s| (s.name.as_str(), s.path.as_str())).collect();
+        assert_eq!(names, [("nix", "/home/v/nix"), ("web", "/home/v/web")]);
+        let split = &ws.sessions[0].windows[1];
+        assert_eq!((split.layout.as_str(), split.active), ("layout-b", true));
+        assert_eq!(split.panes.iter().map(|p| p.active).collect::<Vec<_>>(), [true, false]);
+    }
+}
@@ -7,198 +7,1120 @@ edition = "2024"
 clap = { version = "4.5.49", features = ["derive"] }
 serde = { version = "1.0", features = ["derive"] }
 serde_json = "1.0"
+toml = "0.9"
 libc = "0.2"
 ---
 
 use clap::Parser;
 use serde::Deserialize;
 use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 use std::time::Duration;
 
 const CLAUDE_SESSIONS: &str = "/home/v/nix/home/config/tmux/claude_sessions.rs";
 const SMART_SHUTDOWN: &str = "/home/v/nix/home/scripts/smart_shutdown.rs";
+const SMART_SHUTDOWN_VETOED: i32 = 3; // smart_shutdown's preflight::VETOED
 const TIMER: &str = "/home/v/nix/home/scripts/timer.rs";
 const TG: &str = "/etc/profiles/per-user/v/bin/tg"; // gateway PATH lacks the user profile; call it explicitly
 const POLL: Duration = Duration::from_secs(60);
 
 /// Babysit the Claude sessions that are active right now: report each one as it
 /// settles, then shut the machine down once none are left. A horizon on the main
-/// thread guarantees shutdown even if session polling is broken or hangs.
+/// thread guarantees shutdown even if session polling is broken or hangs. What
+/// "settled" means and what each settle triggers is the policy file's call.
 #[derive(Parser)]
 #[command(name = "supervise_sessions")]
 struct Args {
     /// Termination horizon: shut down this many minutes after start no matter what
     /// (even if session polling is broken or hangs). Early shutdown still happens
-    /// as soon as all sessions settle.
+    /// as soon as all sessions settle. Only a session waiting out a usage reset
+    /// moves it, up to the policy's `horizon_cap_m`.
     #[arg(long, default_value_t = 45)]
     timeout_m: u64,
 
     /// Consecutive idle reads (~1min apart) before a session that merely *looks* idle
     /// (finished/empty/error) is concluded done. Deterministic states (question, limit)
-    /// settle on the first read regardless.
-    #[arg(long, default_value_t = 3)]
-    idle_reads: u8,
+    /// settle on the first read regardless. Overrides the policy's `idle_reads`.
+    #[arg(long)]
+    idle_reads: Option<u8>,
+
+    /// Policy file [default: $XDG_CONFIG_HOME/supervise-sessions/policy.toml, if
+    /// it exists; else the built-in policy::DEFAULT]
+    #[arg(long)]
+    policy: Option<PathBuf>,
+
+    /// Park sessions that hit the usage limit until the 5h window resets, then
+    /// nudge them with "continue" and watch them again — the policy's limit rule,
+    /// ahead of any other.
+    #[arg(long)]
+    wait_limits: bool,
+
+    /// Where messages go, as kind[:arg] — tg[:channel], notify-send, command:CMD,
+    /// webhook:URL, file:PATH. Repeatable; replaces the policy's `notify` list.
+    #[arg(long = "notify", value_name = "SINK")]
+    notify: Vec<String>,
 
     /// Report as usual but don't actually shut down.
     #[arg(short = 'n', long)]
     dry_run: bool,
 }
 
+/// What happens as sessions settle, and once there's nothing left to watch. The
+/// built-in `DEFAULT` is the behavior this script always had; a policy file is
+/// read over it, so it only needs the keys it changes.
+mod policy {
+    use super::*;
+
+    pub const DEFAULT: &str = r#"
+# A deterministic state settles on its first read: the session is blocked and
+# won't resume on its own. Anything else only *looks* idle — finished/empty/error
+# flip back to active between tool calls — until it has read that way for
+# idle_reads polls in a row.
+idle_reads = 3
+immediate = ["question", "limit"]
+
+# Once every watched session has settled, and at the horizon:
+#   shutdown | suspend | notify (just report and stop) | command
+# command takes all_settled_command / horizon_command, run with sh -c.
+all_settled = "shutdown"
+horizon = "shutdown"
+
+# A session parked for the usage reset (a nudge with after_reset) pushes the
+# horizon out to a full --timeout-m past its nudge, but never past this many
+# minutes from the start.
+horizon_cap_m = 360
+
+# Where every message goes (--notify replaces the list), each kind[:arg]:
+#   tg[:channel] | notify-send | command:CMD (message on stdin and in $MESSAGE)
+#   | webhook:URL (POSTed as {"text": …}) | file:PATH (appended, one per line)
+notify = ["tg:general"]
+
+# Per session as it settles, first match wins; no match is `notify`.
+#   state   = ["…"]  any of these settled states; omitted = any state
+#   project = "…"    a piece of its project (path_to_project_name of its cwd)
+#   action  = notify | command | wait | nudge | suspend | shutdown
+#     command  — `command` via sh -c, with SESSION, STATE and PROJECT set
+#     wait     — not settled after all: keep watching it
+#     nudge    — type `message` (default "continue") into it and watch it again;
+#                with after_reset = true, only once the 5h usage window resets
+#     suspend / shutdown — right away, ending the watch
+#
+# [[rule]]
+# state = ["limit"]
+# action = "nudge"
+# after_reset = true
+"#;
+
+    /// Every state claude_sessions reports, plus "gone" for a closed window.
+    const STATES: &[&str] = &[
+        "empty", "active", "planning", "finished", "done", "draft", "question", "input", "error", "limit", "interrupted",
+        "stuck", "partial", "ongoing", "gone",
+    ];
+
+    #[derive(Deserialize, Default)]
+    #[serde(deny_unknown_fields)]
+    struct Raw {
+        idle_reads: Option<u8>,
+        immediate: Option<Vec<String>>,
+        all_settled: Option<String>,
+        all_settled_command: Option<String>,
+        horizon: Option<String>,
+        horizon_command: Option<String>,
+        horizon_cap_m: Option<u64>,
+        notify: Option<Vec<String>>,
+        #[serde(default)]
+        rule: Vec<RawRule>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct RawRule {
+        #[serde(default)]
+        state: Vec<String>,
+        project: Option<String>,
+        action: String,
+        command: Option<String>,
+        message: Option<String>,
+        #[serde(default)]
+        after_reset: bool,
+    }
+
+    /// What ends the watch.
+    #[derive(Clone, Debug)]
+    pub enum Final {
+        Shutdown,
+        Suspend,
+        Notify,
+        Command(String),
+    }
+
+    #[derive(Clone, Debug)]
+    pub enum Action {
+        Notify,
+        Command(String),
+        Wait,
+        Nudge { message: String, after_reset: bool },
+        End(Final),
+    }
+
+    #[derive(Clone, Debug)]
+    struct Rule {
+        states: Vec<String>,
+        project: Option<String>,
+        action: Action,
+    }
+
+    #[derive(Clone, Debug)]
+    pub struct Policy {
+        pub idle_reads: u8,
+        pub immediate: Vec<String>,
+        pub all_settled: Final,
+        pub horizon: Final,
+        pub horizon_cap_m: u64,
+        pub notify: Vec<String>,
+        rules: Vec<Rule>,
+    }
+
+    fn state_list(states: Vec<String>, what: &str) -> Result<Vec<String>, String> {
+        match states.iter().find(|s| !STATES.contains(&s.as_str())) {
+            Some(bad) => Err(format!("{what}: no state {bad:?} (one of {})", STATES.join(" "))),
+            None => Ok(states),
+        }
+    }
+
+    fn final_action(name: &str, command: Option<String>, what: &str) -> Result<Final, String> {
+        match (name, command) {
+            ("shutdown", _) => Ok(Final::Shutdown),
+            ("suspend", _) => Ok(Final::Suspend),
+            ("notify", _) => Ok(Final::Notify),
+            ("command", Some(c)) => Ok(Final::Command(c)),
+            ("command", None) => Err(format!("{what} = \"command\" needs {what}_command")),
+            (other, _) => Err(format!("{what}: no action {other:?} (shutdown suspend notify command)")),
+        }
+    }
+
+    fn rule(raw: RawRule, n: usize) -> Result<Rule, String> {
+        let what = format!("rule {n}");
+        let action = match (raw.action.as_str(), raw.command) {
+            ("notify", _) => Action::Notify,
+            ("command", Some(c)) => Action::Command(c),
+            ("command", None) => return Err(format!("{what}: action = \"command\" needs a command")),
+            ("wait", _) => Action::Wait,
+            ("nudge", _) => Action::Nudge {
+                message: raw.message.unwrap_or_else(|| "continue".to_string()),
+                after_reset: raw.after_reset,
+            },
+            ("suspend", _) => Action::End(Final::Suspend),
+            ("shutdown", _) => Action::End(Final::Shutdown),
+            (other, _) => return Err(format!("{what}: no action {other:?} (notify command wait nudge suspend shutdown)")),
+        };
+        Ok(Rule { states: state_list(raw.state, &what)?, project: raw.project, action })
+    }
+
+    fn default_path() -> PathBuf {
+        std::env::var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|_| std::env::var("HOME").map(|h| PathBuf::from(h).join(".config")))
+            .unwrap_or_default()
+            .join("supervise-sessions/policy.toml")
+    }
+
+    impl Policy {
+        /// DEFAULT, with the file's keys read over it. An explicit path must
+        /// exist; the default one may not.
+        pub fn load(explicit: Option<&Path>) -> Result<Policy, String> {
+            let path = explicit.map(Path::to_path_buf).unwrap_or_else(default_path);
+            match std::fs::read_to_string(&path) {
+                Ok(text) => Policy::parse(&text).map_err(|e| format!("{}: {e}", path.display())),
+                Err(e) if explicit.is_none() && e.kind() == std::io::ErrorKind::NotFound => Policy::parse(""),
+                Err(e) => Err(format!("{}: {e}", path.display())),
+            }
+        }
+
+        /// DEFAULT, with `text`'s keys read over it.
+        pub fn parse(text: &str) -> Result<Policy, String> {
+            let user: Raw = toml::from_str(text).map_err(|e| e.to_string())?;
+            let base: Raw = toml::from_str(DEFAULT).expect("policy::DEFAULT parses");
+            Ok(Policy {
+                idle_reads: user.idle_reads.or(base.idle_reads).unwrap_or(3),
+                immediate: state_list(user.immediate.or(base.immediate).unwrap_or_default(), "immediate")?,
+                all_settled: final_action(
+                    &user.all_settled.or(base.all_settled).unwrap_or_default(),
+                    user.all_settled_command,
+                    "all_settled",
+                )?,
+                horizon: final_action(&user.horizon.or(base.horizon).unwrap_or_default(), user.horizon_command, "horizon")?,
+                horizon_cap_m: user.horizon_cap_m.or(base.horizon_cap_m).unwrap_or_default(),
+                notify: user.notify.or(base.notify).unwrap_or_default(),
+                rules: user.rule.into_iter().enumerate().map(|(i, r)| rule(r, i + 1)).collect::<Result<_, _>>()?,
+            })
+        }
+
+        /// --wait-limits: a limit waits out the reset and is nudged, whatever the file says.
+        pub fn wait_limits(&mut self) {
+            let action = Action::Nudge { message: "continue".to_string(), after_reset: true };
+            self.rules.insert(0, Rule { states: vec!["limit".to_string()], project: None, action });
+        }
+
+        /// What a session that settled in `state` gets.
+        pub fn action(&self, state: &str, project: Option<&str>) -> Action {
+            self.rules
+                .iter()
+                .find(|r| {
+                    (r.states.is_empty() || r.states.iter().any(|s| s == state))
+                        && r.project.as_ref().is_none_or(|p| project.is_some_and(|project| project.contains(p.as_str())))
+                })
+                .map(|r| r.action.clone())
+                .unwrap_or(Action::Notify)
+        }
+    }
+}
+
+/// Where the supervisor's messages go. A sink that fails is reported on stderr
+/// and never holds up the watch or the shutdown.
+mod notify {
+    use super::*;
+    use std::io::Write;
+    use std::process::Stdio;
+
+    pub trait Notifier: Send + Sync {
+        fn send(&self, msg: &str) -> Result<(), String>;
+
+        // Best-effort: a failed notification must not block the shutdown.
+        fn say(&self, msg: &str) {
+            if let Err(e) = self.send(msg) {
+                eprintln!("notify: {e}");
+            }
+        }
+    }
+
+    fn status(cmd: &mut Command, what: &str) -> Result<(), String> {
+        match cmd.status() {
+            Ok(s) if s.success() => Ok(()),
+            Ok(s) => Err(format!("{what} exited {s}")),
+            Err(e) => Err(format!("{what}: {e}")),
+        }
+    }
+
+    /// Runs `cmd` with `input` on its stdin.
+    fn piped(cmd: &mut Command, input: &str, what: &str) -> Result<(), String> {
+        let mut child = cmd.stdin(Stdio::piped()).spawn().map_err(|e| format!("{what}: {e}"))?;
+        // A command that ignores its stdin may close it first; its status still decides.
+        let _ = child.stdin.take().expect("piped stdin").write_all(input.as_bytes());
+        match child.wait() {
+            Ok(s) if s.success() => Ok(()),
+            Ok(s) => Err(format!("{what} exited {s}")),
+            Err(e) => Err(format!("{what}: {e}")),
+        }
+    }
+
+    pub struct Tg {
+        channel: String,
+    }
+
+    impl Notifier for Tg {
+        fn send(&self, msg: &str) -> Result<(), String> {
+            status(Command::new(TG).args(["send", "-c", &self.channel, msg]), "tg send")
+        }
+    }
+
+    pub struct NotifySend;
+
+    impl Notifier for NotifySend {
+        fn send(&self, msg: &str) -> Result<(), String> {
+            let (summary, body) = msg.split_once('\n').unwrap_or((msg, ""));
+            status(Command::new("notify-send").args(["-a", "supervise_sessions", summary, body]), "notify-send")
+        }
+    }
+
+    pub struct Shell(String);
+
+    impl Notifier for Shell {
+        fn send(&self, msg: &str) -> Result<(), String> {
+            piped(Command::new("sh").arg("-c").arg(&self.0).env("MESSAGE", msg), msg, &format!("`{}`", self.0))
+        }
+    }
+
+    pub struct Webhook(String);
+
+    impl Notifier for Webhook {
+        fn send(&self, msg: &str) -> Result<(), String> {
+            let body = serde_json::json!({ "text": msg }).to_string();
+            piped(
+                Command::new("curl").args(["-fsS", "-m", "10", "-H", "content-type: application/json", "--data-binary", "@-", &self.0]),
+                &body,
+                &format!("webhook {}", self.0),
+            )
+        }
+    }
+
+    pub struct FileLog(PathBuf);
+
+    impl Notifier for FileLog {
+        fn send(&self, msg: &str) -> Result<(), String> {
+            let err = |e: std::io::Error| format!("{}: {e}", self.0.display());
+            if let Some(dir) = self.0.parent() {
+                std::fs::create_dir_all(dir).map_err(err)?;
+            }
+            let mut f = std::fs::OpenOptions::new().create(true).append(true).open(&self.0).map_err(err)?;
+            // Multi-line messages (the summary) stay one record: continuation lines indented under it.
+            writeln!(f, "{}\t{}", now_epoch(), msg.replace('\n', "\n\t")).map_err(err)
+        }
+    }
+
+    /// Prints every message, then hands it to each sink.
+    pub struct Fanout(pub Vec<Box<dyn Notifier>>);
+
+    impl Notifier for Fanout {
+        fn send(&self, msg: &str) -> Result<(), String> {
+            println!("{msg}");
+            for sink in &self.0 {
+                sink.say(msg);
+            }
+            Ok(())
+        }
+    }
+
+    /// kind[:arg] — tg[:channel], notify-send, command:CMD, webhook:URL, file:PATH.
+    pub fn parse(spec: &str) -> Result<Box<dyn Notifier>, String> {
+        let (kind, arg) = match spec.split_once(':') {
+            Some((kind, arg)) => (kind, Some(arg).filter(|a| !a.is_empty())),
+            None => (spec, None),
+        };
+        Ok(match (kind, arg) {
+            ("tg", channel) => Box::new(Tg { channel: channel.unwrap_or("general").to_string() }),
+            ("notify-send", None) => Box::new(NotifySend),
+            ("command", Some(cmd)) => Box::new(Shell(cmd.to_string())),
+            ("webhook", Some(url)) => Box::new(Webhook(url.to_string())),
+            ("file", Some(path)) => Box::new(FileLog(match path.strip_prefix("~/") {
+                Some(rest) => PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(rest),
+                None => PathBuf::from(path),
+            })),
+            _ => {
+                return Err(format!(
+                    "notify sink {spec:?}: expected tg[:channel], notify-send, command:CMD, webhook:URL or file:PATH"
+                ));
+            }
+        })
+    }
+
+    /// Keeps every message, for the settle tests.
+    #[cfg(test)]
+    #[derive(Default)]
+    pub struct Collect(pub std::sync::Mutex<Vec<String>>);
+
+    #[cfg(test)]
+    impl Notifier for Collect {
+        fn send(&self, msg: &str) -> Result<(), String> {
+            self.0.lock().unwrap().push(msg.to_string());
+            Ok(())
+        }
+    }
+}
+
+use notify::Notifier;
+use policy::{Action, Final, Policy};
+
 #[derive(Deserialize)]
 struct Entry {
     name: String,
     window_index: u32,
     state: String,
+    #[serde(default)]
+    project: Option<String>,
 }
 
-// Non-fatal: claude_sessions may be broken/hung. Any failure returns None so the
-// caller keeps waiting for the horizon instead of dying here.
-fn snapshot() -> Option<HashMap<String, String>> {
-    let out = Command::new(CLAUDE_SESSIONS).arg("--json").output().ok()?;
-    if !out.status.success() {
-        eprintln!("claude_sessions exited {}: {}", out.status, String::from_utf8_lossy(&out.stderr));
-        return None;
-    }
-    let entries: Vec<Entry> = serde_json::from_slice(&out.stdout).ok()?;
-    // Keyed by name:window — several claude windows can live in one tmux session,
-    // and keying by name alone collapsed them into a single watched entry.
-    Some(entries.into_iter().map(|e| (format!("{}:{}", e.name, e.window_index), e.state)).collect())
+/// One session as the last snapshot read it.
+#[derive(Clone)]
+struct Seen {
+    state: String,
+    project: Option<String>,
 }
 
-// Best-effort: a failed notification must not block the shutdown.
-fn tg(msg: &str) {
-    println!("{msg}");
-    if let Err(e) = Command::new(TG).args(["send", "-c", "general", msg]).status() {
-        eprintln!("tg send failed: {e}");
+/// Everything the watch reads from or does to the sessions — `Live` for real,
+/// a script of snapshots in the tests.
+trait Driver {
+    fn snapshot(&mut self) -> Option<HashMap<String, Seen>>;
+    /// Unix epoch seconds at which the 5h usage window resets. None when unknown.
+    fn resets_at(&mut self) -> Option<i64>;
+    fn nudge(&mut self, name: &str, message: &str) -> Result<(), String>;
+    fn command(&mut self, command: &str, env: &[(&str, &str)]) -> Result<(), String>;
+}
+
+struct Live {
+    dry_run: bool,
+}
+
+impl Driver for Live {
+    // Non-fatal: claude_sessions may be broken/hung. Any failure returns None so the
+    // caller keeps waiting for the horizon instead of dying here.
+    fn snapshot(&mut self) -> Option<HashMap<String, Seen>> {
+        let out = Command::new(CLAUDE_SESSIONS).arg("--json").output().ok()?;
+        if !out.status.success() {
+            eprintln!("claude_sessions exited {}: {}", out.status, String::from_utf8_lossy(&out.stderr));
+            return None;
+        }
+        let entries: Vec<Entry> = serde_json::from_slice(&out.stdout).ok()?;
+        // Keyed by name:window — several claude windows can live in one tmux session,
+        // and keying by name alone collapsed them into a single watched entry.
+        Some(
+            entries
+                .into_iter()
+                .map(|e| (format!("{}:{}", e.name, e.window_index), Seen { state: e.state, project: e.project }))
+                .collect(),
+        )
+    }
+
+    fn resets_at(&mut self) -> Option<i64> {
+        let out = Command::new(CLAUDE_SESSIONS).args(["usage", "--json"]).output().ok()?;
+        let v: serde_json::Value = serde_json::from_slice(&out.stdout).ok()?;
+        v["limits"]["total"]["resets_at"].as_i64()
+    }
+
+    // claude_sessions re-reads the pane and refuses unless it's at a prompt it can
+    // type into, so a nudge can't land in the middle of something.
+    fn nudge(&mut self, name: &str, message: &str) -> Result<(), String> {
+        if self.dry_run {
+            println!("[dry-run] would send {message:?} to {name}");
+            return Ok(());
+        }
+        let out = Command::new(CLAUDE_SESSIONS)
+            .args(["send", name, message])
+            .output()
+            .map_err(|e| format!("claude_sessions send: {e}"))?;
+        if out.status.success() { Ok(()) } else { Err(String::from_utf8_lossy(&out.stderr).trim().to_string()) }
     }
+
+    fn command(&mut self, command: &str, env: &[(&str, &str)]) -> Result<(), String> {
+        run_command(command, env, self.dry_run)
+    }
+}
+
+fn now_epoch() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
 }
 
 // smart_shutdown's exit status can't be trusted (it may report success while its
 // poweroff never happens), so we ignore it: if control ever returns here we are
-// still alive and force an unambiguous poweroff ourselves.
-fn shutdown(dry_run: bool) {
+// still alive and force an unambiguous poweroff ourselves — unless it returned a
+// pre-flight veto, which only an early shutdown heeds. At the horizon `force`
+// is set: the machine goes down, and the pre-flight report is just a report.
+// False when vetoed.
+fn shutdown(dry_run: bool, force: bool, notify: &dyn Notifier) -> bool {
     if dry_run {
         println!("[dry-run] would shut down now");
-        return;
+        return true;
+    }
+    let mut cmd = Command::new(SMART_SHUTDOWN);
+    if force {
+        cmd.arg("--force");
     }
-    match Command::new(SMART_SHUTDOWN).status() {
+    match cmd.status() {
         Ok(s) if s.success() => {}
-        Ok(s) => tg(&format!("supervise: smart_shutdown exited {s} — forcing poweroff")),
-        Err(e) => tg(&format!("supervise: smart_shutdown failed to spawn: {e} — forcing poweroff")),
+        // Its pre-flight found something a poweroff would lose (a rebuild, unpushed
+        // work) and has said so; the horizon still comes.
+        Ok(s) if s.code() == Some(SMART_SHUTDOWN_VETOED) => {
+            notify.say("supervise: smart_shutdown's pre-flight vetoed the early shutdown — leaving it to the horizon");
+            return false;
+        }
+        Ok(s) => notify.say(&format!("supervise: smart_shutdown exited {s} — forcing poweroff")),
+        Err(e) => notify.say(&format!("supervise: smart_shutdown failed to spawn: {e} — forcing poweroff")),
     }
     std::thread::sleep(Duration::from_secs(20)); // give smart_shutdown's cleanup a chance to take us down first
     if let Err(e) = Command::new("sudo").args(["systemctl", "poweroff"]).status() {
         eprintln!("`sudo systemctl poweroff` failed: {e}");
     }
+    true
 }
 
-// Runs on a background thread: shut down early once every initially-active session
-// settles. Exits the process on shutdown; a broken snapshot just returns, leaving
-// the main-thread horizon to handle it.
-fn poll_until_settled(idle_reads: u8, dry_run: bool) {
-    let Some(initial) = snapshot() else {
-        tg("supervise: can't read sessions — deferring to horizon");
+fn suspend(dry_run: bool, notify: &dyn Notifier) {
+    if dry_run {
+        println!("[dry-run] would suspend now");
         return;
-    };
-    // "planning" is "active" under plan mode — it says what the work is, not whether
-    // there is work, so every watch decision here treats the two identically.
-    let watched: BTreeSet<String> =
-        initial.into_iter().filter(|(_, s)| matches!(s.as_str(), "active" | "planning")).map(|(n, _)| n).collect();
-    if watched.is_empty() {
-        tg("supervise: no active sessions — shutting down");
-        shutdown(dry_run);
-        std::process::exit(0);
     }
-    tg(&format!("supervise: watching {} session(s): {}", watched.len(), watched.iter().cloned().collect::<Vec<_>>().join(", ")));
+    if let Err(e) = Command::new("systemctl").arg("suspend").status() {
+        notify.say(&format!("supervise: `systemctl suspend` failed: {e}"));
+    }
+}
 
-    let mut total = watched.len();
-    let mut remaining = watched;
-    let mut idle_streak: HashMap<String, u8> = HashMap::new();
+fn run_command(command: &str, env: &[(&str, &str)], dry_run: bool) -> Result<(), String> {
+    if dry_run {
+        println!("[dry-run] would run: {command}");
+        return Ok(());
+    }
+    match Command::new("sh").arg("-c").arg(command).envs(env.iter().copied()).status() {
+        Ok(s) if s.success() => Ok(()),
+        Ok(s) => Err(format!("`{command}` exited {s}")),
+        Err(e) => Err(format!("`{command}` failed to spawn: {e}")),
+    }
+}
 
-    loop {
-        std::thread::sleep(POLL);
+/// False when a shutdown was vetoed and the watch should carry on to the horizon.
+fn finish(how: &Final, dry_run: bool, at_horizon: bool, notify: &dyn Notifier) -> bool {
+    match how {
+        Final::Shutdown => return shutdown(dry_run, at_horizon, notify),
+        Final::Suspend => suspend(dry_run, notify),
+        Final::Notify => {}
+        Final::Command(c) => {
+            if let Err(e) = run_command(c, &[], dry_run) {
+                notify.say(&format!("supervise: {e}"));
+            }
+        }
+    }
+    true
+}
+
+fn describe(how: &Final) -> &'static str {
+    match how {
+        Final::Shutdown => "shutting down",
+        Final::Suspend => "suspending",
+        Final::Notify => "stopping",
+        Final::Command(_) => "running the final command",
+    }
+}
+
+/// Unknown reset time: look again after this long.
+const RESET_UNKNOWN_WAIT: i64 = 30 * 60;
+/// The window's reset is a moment, not a promise the pane is typeable at it.
+const RESET_GRACE: i64 = 60;
+/// A session that settles the same way after this many nudges is left settled.
+const MAX_NUDGES: u8 = 3;
+
+fn format_span(secs: i64) -> String {
+    let m = secs.max(0) / 60;
+    if m >= 60 { format!("{}h{:02}m", m / 60, m % 60) } else { format!("{m}m") }
+}
 
-        let Some(snap) = snapshot() else { continue }; // transient failure: retry; horizon still guaranteed
+/// How one session settled, for the summary.
+struct Outcome {
+    name: String,
+    state: String,
+    /// Seconds from when it was first watched.
+    after: i64,
+    nudges: u8,
+}
+
+/// Why the watch ended, and what to do about it.
+type Ending = (String, Final);
+
+/// The settle logic, one poll at a time. It holds no clock and does nothing
+/// itself: the caller supplies `now` and every snapshot, and the driver does
+/// the nudging.
+struct Watch {
+    policy: Policy,
+    started: i64,
+    total: usize,
+    remaining: BTreeSet<String>,
+    /// When each session was (last) picked up.
+    since: HashMap<String, i64>,
+    /// Each watched session's state at the latest read.
+    last: HashMap<String, String>,
+    /// Each watched session's project at the latest read that had one: a
+    /// closed window is gone from the snapshot, project and all.
+    projects: HashMap<String, String>,
+    idle_streak: HashMap<String, u8>,
+    /// Settled into a `wait` rule: still watched, announced once.
+    waiting: BTreeSet<String>,
+    /// Parked until the usage reset, then nudged: name -> (epoch, message).
+    parked: HashMap<String, (i64, String)>,
+    /// When the latest parked session was nudged.
+    resumed: Option<i64>,
+    nudges: HashMap<String, u8>,
+    settled: Vec<Outcome>,
+}
+
+impl Watch {
+    fn new(policy: Policy, now: i64) -> Watch {
+        Watch {
+            policy,
+            started: now,
+            total: 0,
+            remaining: BTreeSet::new(),
+            since: HashMap::new(),
+            last: HashMap::new(),
+            projects: HashMap::new(),
+            idle_streak: HashMap::new(),
+            waiting: BTreeSet::new(),
+            parked: HashMap::new(),
+            resumed: None,
+            nudges: HashMap::new(),
+            settled: Vec::new(),
+        }
+    }
+
+    /// Watch every session `initial` reads as working; ends right away when none is.
+    fn begin(&mut self, initial: HashMap<String, Seen>, notify: &dyn Notifier) -> Option<Ending> {
+        // "planning" is "active" under plan mode — it says what the work is, not whether
+        // there is work, so every watch decision here treats the two identically.
+        for (name, seen) in initial.into_iter().filter(|(_, s)| matches!(s.state.as_str(), "active" | "planning")) {
+            self.since.insert(name.clone(), self.started);
+            self.last.insert(name.clone(), seen.state);
+            if let Some(project) = seen.project {
+                self.projects.insert(name.clone(), project);
+            }
+            self.remaining.insert(name);
+        }
+        self.total = self.remaining.len();
+        if self.remaining.is_empty() {
+            return Some((format!("no active sessions — {}", describe(&self.policy.all_settled)), self.policy.all_settled.clone()));
+        }
+        notify.say(&format!(
+            "supervise: watching {} session(s): {}",
+            self.total,
+            self.remaining.iter().cloned().collect::<Vec<_>>().join(", ")
+        ));
+        None
+    }
+
+    /// Stop watching `name`; returns its "(k/total)".
+    fn settle(&mut self, name: &str, state: &str, now: i64) -> String {
+        self.remaining.remove(name);
+        self.idle_streak.remove(name);
+        self.settled.push(Outcome {
+            name: name.to_string(),
+            state: state.to_string(),
+            after: now - self.since.get(name).copied().unwrap_or(self.started),
+            nudges: self.nudges.get(name).copied().unwrap_or(0),
+        });
+        format!("({}/{})", self.total - self.remaining.len(), self.total)
+    }
+
+    /// One poll. `snap` is None when the read failed: parked nudges still go
+    /// out, nothing settles.
+    fn step(
+        &mut self,
+        snap: Option<HashMap<String, Seen>>,
+        now: i64,
+        driver: &mut dyn Driver,
+        notify: &dyn Notifier,
+    ) -> Option<Ending> {
+        // Nudged just now: `snap` predates the nudge, so it can't settle them.
+        let mut nudged = BTreeSet::new();
+        for (name, (at, message)) in self.parked.clone() {
+            if now < at {
+                continue;
+            }
+            self.parked.remove(&name);
+            self.idle_streak.remove(&name);
+            match driver.nudge(&name, &message) {
+                Ok(()) => {
+                    notify.say(&format!("supervise: ↻ {name} limit reset — sent {message:?}, watching it again"));
+                    self.resumed = Some(now);
+                    nudged.insert(name);
+                }
+                Err(e) => {
+                    let state = self.last.get(&name).cloned().unwrap_or_else(|| "limit".to_string());
+                    let progress = self.settle(&name, &state, now);
+                    notify.say(&format!("supervise: ⚠ {name} nudge after reset failed: {e} {progress}"));
+                }
+            }
+        }
+
+        let snap = snap?; // transient failure: retry; horizon still guaranteed
 
         // Adopt sessions that turned active after we started (also re-adopts a
         // settled one that woke back up — it must settle again before shutdown).
-        for name in snap.iter().filter(|(_, s)| matches!(s.as_str(), "active" | "planning")).map(|(n, _)| n) {
-            if remaining.insert(name.clone()) {
-                total += 1;
-                tg(&format!("supervise: + {name} became active — now watching {}", remaining.len()));
+        for (name, seen) in snap.iter().filter(|(_, s)| matches!(s.state.as_str(), "active" | "planning")) {
+            self.waiting.remove(name);
+            if self.remaining.insert(name.clone()) {
+                self.total += 1;
+                self.since.insert(name.clone(), now);
+                self.last.insert(name.clone(), seen.state.clone());
+                notify.say(&format!("supervise: + {name} became active — now watching {}", self.remaining.len()));
             }
         }
 
-        let settled: Vec<(String, String)> = remaining
+        let settled: Vec<(String, String)> = self
+            .remaining
             .iter()
+            .filter(|name| !self.parked.contains_key(*name) && !nudged.contains(*name))
             .filter_map(|name| {
                 // A session gone from the snapshot (window closed) settles as "gone".
-                let state = snap.get(name).map(String::as_str).unwrap_or("gone");
+                let seen = snap.get(name);
+                let state = seen.map(|s| s.state.as_str()).unwrap_or("gone");
+                self.last.insert(name.clone(), state.to_string());
+                if let Some(project) = seen.and_then(|s| s.project.clone()) {
+                    self.projects.insert(name.clone(), project);
+                }
                 match state {
                     "active" | "planning" => {
-                        idle_streak.insert(name.clone(), 0);
+                        self.idle_streak.insert(name.clone(), 0);
                         None
                     }
                     // Deterministic: the session is blocked and won't resume on its own.
-                    "question" | "limit" => Some((name.clone(), state.to_string())),
+                    s if self.policy.immediate.iter().any(|i| i == s) => Some((name.clone(), state.to_string())),
                     // Only *looks* idle — finished/empty/error can flip back to active
                     // between tool calls, so require enough consecutive idle reads first.
                     _ => {
-                        let s = idle_streak.entry(name.clone()).or_insert(0);
+                        let s = self.idle_streak.entry(name.clone()).or_insert(0);
                         *s += 1;
-                        (*s >= idle_reads).then(|| (name.clone(), state.to_string()))
+                        (*s >= self.policy.idle_reads).then(|| (name.clone(), state.to_string()))
                     }
                 }
             })
             .collect();
 
         for (name, state) in settled {
-            remaining.remove(&name);
-            idle_streak.remove(&name);
-            let k = total - remaining.len();
+            let project = self.projects.get(&name).cloned();
+            let mut action = self.policy.action(&state, project.as_deref());
+            if matches!(action, Action::Nudge { .. }) && self.nudges.get(&name).copied().unwrap_or(0) >= MAX_NUDGES {
+                notify.say(&format!("supervise: {name} settled as {state} after {MAX_NUDGES} nudges — leaving it"));
+                action = Action::Notify;
+            }
+            match &action {
+                Action::Wait => {
+                    if self.waiting.insert(name.clone()) {
+                        notify.say(&format!("supervise: … {name} → {state}, still watching it"));
+                    }
+                    continue;
+                }
+                Action::Nudge { message, after_reset } => {
+                    *self.nudges.entry(name.clone()).or_default() += 1;
+                    self.idle_streak.remove(&name);
+                    if *after_reset {
+                        let at = driver.resets_at().filter(|&t| t > now).map(|t| t + RESET_GRACE);
+                        let at = at.unwrap_or(now + RESET_UNKNOWN_WAIT);
+                        notify.say(&format!(
+                            "supervise: ⏸ {name} → {state}, nudging it in {} once usage resets",
+                            format_span(at - now)
+                        ));
+                        self.parked.insert(name, (at, message.clone()));
+                        continue;
+                    }
+                    match driver.nudge(&name, message) {
+                        Ok(()) => {
+                            notify.say(&format!("supervise: ↻ {name} → {state}, sent {message:?}, watching it again"));
+                            continue;
+                        }
+                        Err(e) => notify.say(&format!("supervise: {name} nudge failed: {e}")),
+                    }
+                }
+                _ => {}
+            }
+
+            let progress = self.settle(&name, &state, now);
             if state == "finished" {
-                tg(&format!("supervise: ✓ {name} finished ({k}/{total})"));
+                notify.say(&format!("supervise: ✓ {name} finished {progress}"));
             } else {
-                tg(&format!("supervise: ⚠ {name} → {state} ({k}/{total})"));
+                notify.say(&format!("supervise: ⚠ {name} → {state} {progress}"));
+            }
+            match action {
+                Action::Command(c) => {
+                    let project = project.as_deref().unwrap_or("");
+                    let env = [("SESSION", name.as_str()), ("STATE", state.as_str()), ("PROJECT", project)];
+                    if let Err(e) = driver.command(&c, &env) {
+                        notify.say(&format!("supervise: {e}"));
+                    }
+                }
+                Action::End(how) => return Some((format!("{name}'s policy — {}", describe(&how)), how)),
+                _ => {}
             }
         }
 
-        if remaining.is_empty() {
-            tg("supervise: all sessions settled — shutting down");
-            shutdown(dry_run);
-            std::process::exit(0);
+        if self.remaining.is_empty() {
+            return Some((
+                format!("all sessions settled — {}", describe(&self.policy.all_settled)),
+                self.policy.all_settled.clone(),
+            ));
+        }
+        None
+    }
+
+    /// The latest moment a usage reset hands work back: a pending nudge, or the
+    /// last one sent. The horizon owes the session its full length from there.
+    fn resumes_at(&self) -> Option<i64> {
+        self.parked.values().map(|(at, _)| *at).chain(self.resumed).max()
+    }
+
+    /// The closing report: the verdict, then how each session ended up.
+    fn summary(&self, verdict: &str, now: i64) -> String {
+        let mut out = format!("supervise: {verdict}");
+        if self.total > 0 {
+            out += &format!("\n{} session(s) over {}:", self.total, format_span(now - self.started));
+        }
+        for o in &self.settled {
+            let mark = if o.state == "finished" { "✓" } else { "⚠" };
+            out += &format!("\n  {mark} {} {} after {}", o.name, o.state, format_span(o.after));
+            if o.nudges > 0 {
+                out += &format!(", nudged {}×", o.nudges);
+            }
         }
+        for name in &self.remaining {
+            let state = self.last.get(name).map_or("unread", String::as_str);
+            let note = if self.parked.contains_key(name) {
+                ", parked for the usage reset"
+            } else if self.waiting.contains(name) {
+                ", waited on by policy"
+            } else {
+                ""
+            };
+            out += &format!("\n  … {name} still {state}{note}");
+        }
+        out
+    }
+}
+
+fn lock(watch: &Mutex<Watch>) -> MutexGuard<'_, Watch> {
+    watch.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Runs on a background thread: end early once every watched session settles.
+// Exits the process when it does; a broken first snapshot or a vetoed shutdown
+// just returns, leaving the main-thread horizon to handle it. After every step
+// the watch's `resumes_at` goes out through `resumes` (0: none) — a step holds
+// the lock across nudges and commands, and the horizon can't wait on those.
+fn poll_until_settled(watch: &Mutex<Watch>, resumes: &AtomicI64, driver: &mut dyn Driver, notify: &dyn Notifier, dry_run: bool) {
+    let Some(initial) = driver.snapshot() else {
+        notify.say("supervise: can't read sessions — deferring to horizon");
+        return;
+    };
+    let mut ending = lock(watch).begin(initial, notify);
+    while ending.is_none() {
+        std::thread::sleep(POLL);
+        // Read outside the lock: a hung claude_sessions must not hold up the horizon's summary.
+        let snap = driver.snapshot();
+        let mut w = lock(watch);
+        ending = w.step(snap, now_epoch(), driver, notify);
+        resumes.store(w.resumes_at().unwrap_or(0), Ordering::Relaxed);
+    }
+    let (verdict, how) = ending.expect("loop ends on Some");
+    notify.say(&lock(watch).summary(&verdict, now_epoch()));
+    if finish(&how, dry_run, false, notify) {
+        std::process::exit(0);
     }
 }
 
 // Owned countdown showing time-to-horizon. PR_SET_PDEATHSIG makes the kernel send
 // the timer SIGTERM the instant this supervisor dies (early shutdown exits the
 // process, horizon shutdown returns from main) — the timer then self-cleans.
-fn spawn_timer(timeout_m: u64) {
+fn spawn_timer(timeout_m: u64) -> Option<std::process::Child> {
     use std::os::unix::process::CommandExt;
     let mut cmd = Command::new(TIMER);
-    cmd.arg(format!("{timeout_m}m"));
+    // Named, so it stays out of the way of timers run by hand (a pomodoro holds "timer").
+    cmd.args([&format!("{timeout_m}m"), "--name", "supervise"]);
     unsafe {
         cmd.pre_exec(|| match libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM) {
             0 => Ok(()),
             _ => Err(std::io::Error::last_os_error()),
         });
     }
-    if let Err(e) = cmd.spawn() {
-        eprintln!("timer failed to spawn: {e}");
-    }
+    cmd.spawn().map_err(|e| eprintln!("timer failed to spawn: {e}")).ok()
 }
 
 fn main() {
     let args = Args::parse();
-    let horizon = Duration::from_secs(args.timeout_m * 60);
+    let fail = |e: String| -> ! {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    };
+    let mut policy = Policy::load(args.policy.as_deref()).unwrap_or_else(|e| fail(e));
+    if let Some(n) = args.idle_reads {
+        policy.idle_reads = n;
+    }
+    if args.wait_limits {
+        policy.wait_limits();
+    }
+    let specs = if args.notify.is_empty() { &policy.notify } else { &args.notify };
+    let sinks = specs.iter().map(|s| notify::parse(s)).collect::<Result<Vec<_>, _>>().unwrap_or_else(|e| fail(e));
+    let notify = Arc::new(notify::Fanout(sinks));
+    let started = now_epoch();
+    let length = args.timeout_m as i64 * 60;
+    let cap = started + (policy.horizon_cap_m as i64 * 60).max(length);
+    let at_horizon = policy.horizon.clone();
+    let watch = Arc::new(Mutex::new(Watch::new(policy, started)));
+    let resumes = Arc::new(AtomicI64::new(0));
 
-    spawn_timer(args.timeout_m);
-    std::thread::spawn(move || poll_until_settled(args.idle_reads, args.dry_run));
+    let mut timer = spawn_timer(args.timeout_m);
+    let (w, r, n, dry_run) = (watch.clone(), resumes.clone(), notify.clone(), args.dry_run);
+    std::thread::spawn(move || poll_until_settled(&w, &r, &mut Live { dry_run }, &*n, dry_run));
 
     // The main thread IS the termination horizon: a pure sleep nothing can block.
-    std::thread::sleep(horizon);
-    tg(&format!("supervise: {}m horizon reached — shutting down", args.timeout_m));
-    shutdown(args.dry_run);
+    // The only thing that moves it is a session waiting out a usage reset, and
+    // never past the cap.
+    let mut horizon = started + length;
+    loop {
+        std::thread::sleep(Duration::from_secs((horizon - now_epoch()).max(0) as u64));
+        // Not the watch itself: the poll thread may be wedged mid-step on a hung nudge.
+        let resumes_at = Some(resumes.load(Ordering::Relaxed)).filter(|&at| at > 0);
+        let Some(extended) = resumes_at.map(|at| (at + length).min(cap)).filter(|&t| t > horizon) else { break };
+        notify.say(&format!(
+            "supervise: horizon extended by {} for a usage reset — {} left (cap {})",
+            format_span(extended - horizon),
+            format_span(extended - now_epoch()),
+            format_span(cap - started)
+        ));
+        horizon = extended;
+        if let Some(mut old) = timer.take() {
+            let _ = old.kill();
+            let _ = old.wait();
+        }
+        timer = spawn_timer(((horizon - now_epoch()).max(0) as u64).div_ceil(60));
+    }
+    let verdict = format!("{} horizon reached — {}", format_span(horizon - started), describe(&at_horizon));
+    let report = match watch.try_lock() {
+        Ok(w) => w.summary(&verdict, now_epoch()),
+        Err(_) => format!("supervise: {verdict}"),
+    };
+    notify.say(&report);
+    finish(&at_horizon, args.dry_run, true, &*notify);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Snapshots in order, one per poll; `None` is a failed read.
+    #[derive(Default)]
+    struct Script {
+        snaps: VecDeque<Option<HashMap<String, Seen>>>,
+        resets_at: Option<i64>,
+        sent: Vec<(String, String)>,
+        commands: Vec<String>,
+    }
+
+    impl Driver for Script {
+        fn snapshot(&mut self) -> Option<HashMap<String, Seen>> {
+            self.snaps.pop_front().flatten()
+        }
+        fn resets_at(&mut self) -> Option<i64> {
+            self.resets_at
+        }
+        fn nudge(&mut self, name: &str, message: &str) -> Result<(), String> {
+            self.sent.push((name.to_string(), message.to_string()));
+            Ok(())
+        }
+        fn command(&mut self, command: &str, env: &[(&str, &str)]) -> Result<(), String> {
+            let env: Vec<String> = env.iter().map(|(k, v)| format!("{k}={v}")).collect();
+            self.commands.push(format!("{} {command}", env.join(" ")));
+            Ok(())
+        }
+    }
+
+    fn snap(sessions: &[(&str, &str)]) -> Option<HashMap<String, Seen>> {
+        Some(
+            sessions
+                .iter()
+                .map(|(name, state)| (name.to_string(), Seen { state: state.to_string(), project: Some("-home-v-nix".into()) }))
+                .collect(),
+        )
+    }
+
+    /// Plays `polls` a minute apart after `initial`; the ending, if the watch ended,
+    /// with everything it said.
+    fn play(
+        policy: &str,
+        initial: &[(&str, &str)],
+        polls: Vec<Option<HashMap<String, Seen>>>,
+        script: &mut Script,
+    ) -> (Option<Ending>, Watch, Vec<String>) {
+        let mut watch = Watch::new(Policy::parse(policy).unwrap(), 0);
+        let said = notify::Collect::default();
+        let mut ending = watch.begin(snap(initial).unwrap(), &said);
+        script.snaps.extend(polls);
+        let mut now = 0;
+        while ending.is_none() && !script.snaps.is_empty() {
+            now += 60;
+            let snap = script.snapshot();
+            ending = watch.step(snap, now, script, &said);
+        }
+        (ending, watch, said.0.into_inner().unwrap())
+    }
+
+    #[test]
+    fn blocked_states_settle_at_once_but_idle_ones_need_consecutive_reads() {
+        let mut script = Script::default();
+        let polls = vec![
+            snap(&[("a:0", "question"), ("b:1", "finished")]),
+            snap(&[("b:1", "active")]),
+            None,
+            snap(&[("b:1", "finished")]),
+            snap(&[("b:1", "finished")]),
+            snap(&[("b:1", "finished")]),
+        ];
+        let (ending, watch, said) = play("", &[("a:0", "active"), ("b:1", "planning")], polls, &mut script);
+        let (verdict, how) = ending.expect("ended");
+        assert_eq!(verdict, "all sessions settled — shutting down");
+        assert!(matches!(how, Final::Shutdown));
+        assert_eq!(said[1], "supervise: ⚠ a:0 → question (1/2)");
+        // The active read reset b's streak and the failed read didn't count: three more finished reads.
+        assert_eq!(said[2], "supervise: ✓ b:1 finished (2/2)");
+        assert_eq!(
+            watch.summary(&verdict, 360),
+            "supervise: all sessions settled — shutting down\n2 session(s) over 6m:\n  ⚠ a:0 question after 1m\n  ✓ b:1 finished after 6m"
+        );
+    }
+
+    #[test]
+    fn a_limit_parks_until_the_reset_then_is_nudged_and_watched_again() {
+        let policy = "[[rule]]\nstate = [\"limit\"]\naction = \"nudge\"\nafter_reset = true\n";
+        let mut script = Script { resets_at: Some(200), ..Script::default() };
+        let mut polls = vec![snap(&[("a:0", "limit")]); 4];
+        polls.extend([snap(&[("a:0", "active")]), snap(&[("a:0", "finished")]), snap(&[("a:0", "finished")])]);
+        polls.push(snap(&[("a:0", "finished")]));
+        let (ending, watch, said) = play(policy, &[("a:0", "active")], polls, &mut script);
+        // Parked at 60 for the reset at 200 (+ grace), so the nudge goes out at the 300 poll.
+        assert_eq!(said[1], "supervise: ⏸ a:0 → limit, nudging it in 3m once usage resets");
+        assert_eq!(script.sent, vec![("a:0".to_string(), "continue".to_string())]);
+        assert_eq!(said[2], "supervise: ↻ a:0 limit reset — sent \"continue\", watching it again");
+        assert!(ending.is_some());
+        assert!(watch.summary("x", 480).ends_with("✓ a:0 finished after 8m, nudged 1×"));
+    }
+
+    #[test]
+    fn wait_limits_overrides_the_file_and_the_reset_is_what_the_horizon_owes() {
+        let mut policy = Policy::parse("[[rule]]\nstate = [\"limit\"]\naction = \"shutdown\"\n").unwrap();
+        policy.wait_limits();
+        let mut watch = Watch::new(policy, 0);
+        let said = notify::Collect::default();
+        let mut script = Script { resets_at: Some(7200), ..Script::default() };
+        watch.begin(snap(&[("a:0", "active")]).unwrap(), &said);
+        assert_eq!(watch.step(snap(&[("a:0", "limit")]), 60, &mut script, &said).map(|e| e.0), None);
+        assert_eq!(watch.resumes_at(), Some(7260));
+        watch.step(snap(&[("a:0", "limit")]), 7300, &mut script, &said);
+        assert_eq!(watch.resumes_at(), Some(7300));
+        assert_eq!(script.sent.len(), 1);
+    }
+
+    #[test]
+    fn wait_keeps_a_session_watched_and_policy_actions_run_per_session() {
+        let policy = r#"
+all_settled = "notify"
+[[rule]]
+state = ["stuck"]
+action = "wait"
+[[rule]]
+state = ["gone"]
+project = "nix"
+action = "command"
+command = "echo gone"
+"#;
+        let mut script = Script::default();
+        let polls = vec![snap(&[("a:0", "stuck")]); 5];
+        let (ending, watch, said) = play(policy, &[("a:0", "active"), ("b:1", "active")], polls, &mut script);
+        assert!(ending.is_none());
+        assert_eq!(said.iter().filter(|m| m.contains("still watching")).count(), 1);
+        // A closed window still matches on the project it was last seen in.
+        assert_eq!(script.commands, ["SESSION=b:1 STATE=gone PROJECT=-home-v-nix echo gone"]);
+        assert!(said.contains(&"supervise: ⚠ b:1 → gone (1/2)".to_string()));
+        assert!(watch.summary("x", 300).ends_with("… a:0 still stuck, waited on by policy"));
+    }
+
+    #[test]
+    fn an_ending_rule_stops_the_watch_with_the_rest_still_listed() {
+        let policy = "[[rule]]\nstate = [\"error\"]\nproject = \"nix\"\naction = \"suspend\"\n";
+        let mut script = Script::default();
+        let polls = vec![snap(&[("a:0", "error"), ("b:1", "active")]); 3];
+        let (ending, watch, _) = play(policy, &[("a:0", "active"), ("b:1", "active")], polls, &mut script);
+        let (verdict, how) = ending.expect("ended");
+        assert_eq!(verdict, "a:0's policy — suspending");
+        assert!(matches!(how, Final::Suspend));
+        assert!(watch.summary(&verdict, 180).ends_with("… b:1 still active"));
+    }
 }
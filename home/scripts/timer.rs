@@ -8,19 +8,24 @@ edition = "2024"
 clap = { version = "4.5.49", features = ["derive"] }
 libc = "0.2"
 crossterm = "0.28"
+serde = { version = "1.0", features = ["derive"] }
+serde_json = "1.0"
 ---
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use serde::{Deserialize, Serialize};
 use std::{
     fs,
     io::{ErrorKind, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::Command,
     sync::atomic::{AtomicBool, Ordering},
     thread::sleep,
     time::Duration,
 };
 
+const BEEP: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/beep.rs");
+
 static INTERRUPTED: AtomicBool = AtomicBool::new(false);
 // so the signal handler and cleanup can undo raw mode without threading the flag through
 static RAW_MODE: AtomicBool = AtomicBool::new(false);
@@ -38,8 +43,152 @@ fn disable_raw() {
     }
 }
 
-fn control_file() -> PathBuf {
-    PathBuf::from("/tmp/timer_control")
+/// One timer's files, under `<runtime dir>/timer/<name>/`. `control` is the channel
+/// --halt/--resume/--cancel write to: "running" or "paused", and gone means
+/// cancelled. `state.json` is what the timer last said about itself, for `list`.
+struct Channel {
+    name: String,
+    dir: PathBuf,
+}
+
+/// What a running timer reports about itself, rewritten every tick.
+#[derive(Serialize, Deserialize, Debug)]
+struct State {
+    pid: u32,
+    /// Seconds; None for a count-up.
+    total: Option<i32>,
+    /// Seconds left (negative once overrun); elapsed for a count-up.
+    left: i32,
+    paused: bool,
+    /// --label, or the current phase's label for a sequence.
+    #[serde(default)]
+    label: Option<String>,
+    /// A sequence's current phase (1-based) and how many it has.
+    #[serde(default)]
+    phase: Option<(usize, usize)>,
+}
+
+impl State {
+    /// Phase and label, as shown before the time: `2/8 break`.
+    fn caption(&self) -> Option<String> {
+        match (self.phase, &self.label) {
+            (Some((i, n)), Some(l)) => Some(format!("{i}/{n} {l}")),
+            (Some((i, n)), None) => Some(format!("{i}/{n}")),
+            (None, l) => l.clone(),
+        }
+    }
+}
+
+impl Channel {
+    fn root() -> PathBuf {
+        match std::env::var("XDG_RUNTIME_DIR") {
+            Ok(dir) => PathBuf::from(dir).join("timer"),
+            Err(_) => PathBuf::from(format!("/tmp/timer-{}", unsafe { libc::getuid() })),
+        }
+    }
+
+    fn new(name: &str) -> Channel {
+        Channel { name: name.to_string(), dir: Channel::root().join(name) }
+    }
+
+    fn read_control(&self) -> Option<String> {
+        match fs::read_to_string(self.dir.join("control")) {
+            Ok(s) => Some(s.trim().to_string()),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(_) => None,
+        }
+    }
+
+    fn write_control(&self, state: &str) -> Result<(), String> {
+        fs::write(self.dir.join("control"), state).map_err(|e| e.to_string())
+    }
+
+    fn read_state(&self) -> Option<State> {
+        serde_json::from_str(&fs::read_to_string(self.dir.join("state.json")).ok()?).ok()
+    }
+
+    fn write_state(&self, state: &State) {
+        // Written aside and renamed in, so `list` never reads half a file.
+        let tmp = self.dir.join("state.json.tmp");
+        if fs::write(&tmp, serde_json::to_string(state).unwrap_or_default()).is_ok() {
+            let _ = fs::rename(tmp, self.dir.join("state.json"));
+        }
+    }
+
+    /// Its timer process is still there. A killed timer (SIGKILL skips cleanup)
+    /// leaves its directory behind; this is what tells the two apart.
+    fn alive(&self) -> bool {
+        self.read_state().is_some_and(|s| unsafe { libc::kill(s.pid as libc::pid_t, 0) } == 0)
+    }
+
+    /// Claims the name for this process; fails if a live timer holds it.
+    fn claim(&self, total: Option<i32>, label: Option<String>) -> Result<(), String> {
+        if self.alive() {
+            return Err(format!("A timer named {:?} is already running", self.name));
+        }
+        let _ = fs::remove_dir_all(&self.dir);
+        fs::create_dir_all(&self.dir).map_err(|e| format!("{}: {e}", self.dir.display()))?;
+        self.write_state(&State { pid: std::process::id(), total, left: total.unwrap_or(0), paused: false, label, phase: None });
+        self.write_control("running")
+    }
+
+    fn clear(&self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+
+    /// Every timer directory, live or not, by name.
+    fn dirs() -> Vec<Channel> {
+        let mut names: Vec<String> = fs::read_dir(Channel::root())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|e| e.file_name().into_string().ok())
+            .collect();
+        names.sort();
+        names.iter().map(|n| Channel::new(n)).collect()
+    }
+
+    /// Every live timer, by name. Touches nothing: `status --watch` runs this
+    /// twice a second, and a directory without its state.json yet is a timer
+    /// halfway through `claim`, not a dead one.
+    fn all() -> Vec<Channel> {
+        Channel::dirs().into_iter().filter(|c| c.alive()).collect()
+    }
+
+    /// Clears what killed timers left behind: a state.json naming a process
+    /// that's gone.
+    fn sweep() {
+        for c in Channel::dirs() {
+            if c.read_state().is_some() && !c.alive() {
+                c.clear();
+            }
+        }
+    }
+
+    /// First of timer, timer2, timer3... not already running.
+    fn unnamed() -> Channel {
+        (1..)
+            .map(|i| Channel::new(&if i == 1 { "timer".to_string() } else { format!("timer{i}") }))
+            .find(|c| !c.alive())
+            .expect("some name is free")
+    }
+
+    /// The timer --halt/--resume/--cancel mean: the named one, or the only one running.
+    fn target(name: Option<&str>) -> Result<Channel, String> {
+        if let Some(name) = name {
+            let c = Channel::new(name);
+            return if c.alive() { Ok(c) } else { Err(format!("No timer named {name:?} running")) };
+        }
+        let mut all = Channel::all();
+        match all.len() {
+            0 => Err("No timer running".to_string()),
+            1 => Ok(all.remove(0)),
+            _ => Err(format!(
+                "Several timers running ({}) — pick one with --name",
+                all.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", ")
+            )),
+        }
+    }
 }
 
 // Stolen from ~/s/v_utils/v_utils/src/trades/timeframe.rs
@@ -136,14 +285,316 @@ mod timeframe {
 }
 //,}}}1
 
+fn now() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Survives reboots, unlike the runtime dir: saved sequences and the interval log.
+fn state_dir() -> PathBuf {
+    let base = match std::env::var("XDG_STATE_HOME") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(".local/state"),
+    };
+    base.join("timer")
+}
+
+/// Sequence specs: comma-separated phases `DURATION [LABEL...] [@SOUND]`, where a
+/// bracketed group repeats with `xN`, e.g. `[25m work, 5m break]x4, 15m long break`.
+mod sequence {
+    use serde::{Deserialize, Serialize};
+    use std::path::PathBuf;
+
+    const SOUNDS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sound");
+    pub const DEFAULT_SOUND: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sound/Notification.mp3");
+
+    pub const PRESETS: &[(&str, &str)] = &[("pomodoro", "[25m work, 5m break]x4, 15m long break")];
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    pub struct Phase {
+        pub secs: i32,
+        pub label: Option<String>,
+        /// Played as the phase starts; a file under assets/sound, or a path.
+        pub sound: Option<String>,
+    }
+
+    impl Phase {
+        pub fn sound_path(&self) -> PathBuf {
+            match &self.sound {
+                None => PathBuf::from(DEFAULT_SOUND),
+                Some(s) if s.contains('/') => PathBuf::from(s),
+                Some(s) if s.contains('.') => PathBuf::from(SOUNDS).join(s),
+                Some(s) => PathBuf::from(SOUNDS).join(format!("{s}.mp3")),
+            }
+        }
+    }
+
+    pub fn parse(spec: &str) -> Result<Vec<Phase>, String> {
+        let spec = PRESETS.iter().find(|(name, _)| *name == spec.trim()).map_or(spec, |(_, s)| s);
+        let phases = list(spec)?;
+        if phases.is_empty() {
+            return Err("Sequence has no phases".to_string());
+        }
+        Ok(phases)
+    }
+
+    fn list(s: &str) -> Result<Vec<Phase>, String> {
+        let mut phases = Vec::new();
+        for item in split_top(s)? {
+            let item = item.trim();
+            if item.is_empty() {
+                continue;
+            }
+            match item.strip_prefix('[') {
+                Some(rest) => {
+                    let close = rest.rfind(']').ok_or_else(|| format!("Unclosed [ in {item:?}"))?;
+                    let group = list(&rest[..close])?;
+                    let count = rest[close + 1..].trim();
+                    let n = match count.strip_prefix(['x', '×', '*']) {
+                        Some(n) => n.trim().parse::<usize>().map_err(|_| format!("Bad repeat count in {item:?}"))?,
+                        None if count.is_empty() => 1,
+                        None => return Err(format!("Expected xN after ] in {item:?}")),
+                    };
+                    for _ in 0..n {
+                        phases.extend(group.iter().cloned());
+                    }
+                }
+                None => phases.push(phase(item)?),
+            }
+        }
+        Ok(phases)
+    }
+
+    /// Splits on the commas outside brackets.
+    fn split_top(s: &str) -> Result<Vec<&str>, String> {
+        let (mut depth, mut start, mut items) = (0i32, 0, Vec::new());
+        for (i, c) in s.char_indices() {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                ',' if depth == 0 => {
+                    items.push(&s[start..i]);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+            if depth < 0 {
+                return Err(format!("Unmatched ] in {s:?}"));
+            }
+        }
+        items.push(&s[start..]);
+        Ok(items)
+    }
+
+    fn phase(item: &str) -> Result<Phase, String> {
+        let mut words = item.split_whitespace();
+        let time = words.next().ok_or("Empty phase")?;
+        let secs = super::parse_time(time).map_err(|e| format!("{item:?}: {e}"))?;
+        if secs <= 0 {
+            return Err(format!("{item:?}: phases need a positive duration"));
+        }
+        let (mut label, mut sound) = (Vec::new(), None);
+        for w in words {
+            match w.strip_prefix('@') {
+                Some(s) => sound = Some(s.to_string()),
+                None => label.push(w),
+            }
+        }
+        let label = (!label.is_empty()).then(|| label.join(" "));
+        Ok(Phase { secs, label, sound })
+    }
+}
+
+/// A sequence's progress on disk. Position is derived from wall-clock (minus time
+/// spent paused), so a sequence picked up after a reboot lands where it would be had
+/// it kept running.
+#[derive(Serialize, Deserialize, Debug)]
+struct Progress {
+    name: String,
+    spec: String,
+    phases: Vec<sequence::Phase>,
+    started_at: u64,
+    paused_total: u64,
+    paused_since: Option<u64>,
+    /// Phases already written to the interval log, and when the next one began.
+    logged: usize,
+    phase_began: u64,
+}
+
+impl Progress {
+    fn new(name: &str, spec: &str) -> Result<Progress, String> {
+        let phases = sequence::parse(spec)?;
+        let t = now();
+        Ok(Progress { name: name.to_string(), spec: spec.to_string(), phases, started_at: t, paused_total: 0, paused_since: None, logged: 0, phase_began: t })
+    }
+
+    fn path(name: &str) -> PathBuf {
+        state_dir().join(format!("seq-{name}.json"))
+    }
+
+    fn load(name: &str) -> Option<Progress> {
+        serde_json::from_str(&fs::read_to_string(Progress::path(name)).ok()?).ok()
+    }
+
+    /// Every saved sequence, running or not.
+    fn all() -> Vec<Progress> {
+        let mut all: Vec<Progress> = fs::read_dir(state_dir())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|e| e.file_name().into_string().ok())
+            .filter_map(|f| Progress::load(f.strip_prefix("seq-")?.strip_suffix(".json")?))
+            .collect();
+        all.sort_by(|a, b| a.name.cmp(&b.name));
+        all
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let path = Progress::path(&self.name);
+        fs::create_dir_all(state_dir()).map_err(|e| e.to_string())?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self).map_err(|e| e.to_string())?).map_err(|e| format!("{}: {e}", tmp.display()))?;
+        fs::rename(tmp, &path).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    fn forget(name: &str) {
+        let _ = fs::remove_file(Progress::path(name));
+    }
+
+    /// Phase under way at `now` and seconds left in it; None once the last one is over.
+    fn position(&self, now: u64) -> Option<(usize, i32)> {
+        let elapsed = self.paused_since.unwrap_or(now).saturating_sub(self.started_at + self.paused_total) as i64;
+        let mut end = 0i64;
+        for (i, p) in self.phases.iter().enumerate() {
+            end += p.secs as i64;
+            if elapsed < end {
+                return Some((i, (end - elapsed) as i32));
+            }
+        }
+        None
+    }
+
+    /// Wall time phase `i` ended, valid once it has (pauses only ever push it later).
+    fn ended_at(&self, i: usize) -> u64 {
+        self.started_at + self.paused_total + self.phases[..=i].iter().map(|p| p.secs as u64).sum::<u64>()
+    }
+
+    /// Every phase finished by `now` and not yet logged, marked as logged.
+    /// `skipped`: they ended while no timer was running.
+    fn finished(&mut self, now: u64, skipped: bool) -> Vec<Interval> {
+        let upto = self.position(now).map_or(self.phases.len(), |(i, _)| i);
+        let mut out = Vec::new();
+        while self.logged < upto {
+            let i = self.logged;
+            let ended_at = self.ended_at(i);
+            out.push(Interval {
+                timer: self.name.clone(),
+                label: self.phases[i].label.clone(),
+                phase: Some(i + 1),
+                planned: self.phases[i].secs,
+                started_at: self.phase_began,
+                ended_at,
+                skipped,
+            });
+            self.phase_began = ended_at;
+            self.logged += 1;
+        }
+        out
+    }
+
+    /// Writes every phase finished by `now` to the interval log; true if any were.
+    fn log_finished(&mut self, now: u64, skipped: bool) -> bool {
+        let finished = self.finished(now, skipped);
+        finished.iter().for_each(Interval::append);
+        !finished.is_empty()
+    }
+
+    fn pause(&mut self, now: u64) {
+        self.paused_since.get_or_insert(now);
+    }
+
+    fn unpause(&mut self, now: u64) {
+        if let Some(since) = self.paused_since.take() {
+            self.paused_total += now.saturating_sub(since);
+        }
+    }
+}
+
+/// One finished interval, a line of `intervals.jsonl` in the state dir.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Interval {
+    timer: String,
+    label: Option<String>,
+    /// 1-based position in its sequence; None for a plain countdown.
+    phase: Option<usize>,
+    planned: i32,
+    started_at: u64,
+    ended_at: u64,
+    /// A sequence phase that ran out while no timer was running (machine off,
+    /// terminal closed): on the clock, not sat through.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    skipped: bool,
+}
+
+impl Interval {
+    fn path() -> PathBuf {
+        state_dir().join("intervals.jsonl")
+    }
+
+    /// Best effort: a full disk shouldn't take the timer down with it.
+    fn append(&self) {
+        let _ = fs::create_dir_all(state_dir());
+        let Ok(line) = serde_json::to_string(self) else { return };
+        if let Ok(mut f) = fs::OpenOptions::new().create(true).append(true).open(Interval::path()) {
+            let _ = writeln!(f, "{line}");
+        }
+    }
+
+    fn read() -> Vec<Interval> {
+        fs::read_to_string(Interval::path())
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|l| serde_json::from_str(l).ok())
+            .collect()
+    }
+}
+
+/// `2026-01-31T09:00:00Z` for a unix timestamp; days to civil date per
+/// Howard Hinnant's algorithm, to avoid pulling in chrono for one format.
+fn iso8601(ts: u64) -> String {
+    let (days, secs) = ((ts / 86_400) as i64, ts % 86_400);
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    format!("{y:04}-{m:02}-{d:02}T{:02}:{:02}:{:02}Z", secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) { format!("\"{}\"", s.replace('"', "\"\"")) } else { s.to_string() }
+}
+
 /// Countdown timer with visual feedback and notifications
 #[derive(Parser, Debug)]
 #[command(name = "timer")]
 #[command(about = "Countdown timer with visual feedback and notifications")]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    cmd: Option<Cmd>,
+
     /// Time: seconds (90), mm:ss (1:30), hh:mm:ss (1:30:00), or timeframe (5m, 1h, 30s). Omit to count up from 0:00.
     time: Option<String>,
 
+    /// Name this timer, or pick which one --halt/--resume/--cancel act on
+    /// (default: the only one running). Unnamed timers get timer, timer2...
+    #[arg(short, long)]
+    name: Option<String>,
+
     /// Halt (pause) the running timer
     #[arg(long, short = 'H')]
     halt: bool,
@@ -152,9 +603,64 @@ struct Args {
     #[arg(short, long)]
     resume: bool,
 
+    /// Stop the running timer
+    #[arg(long)]
+    cancel: bool,
+
     /// Quiet mode (shows persistent notification instead of beeping)
     #[arg(short, long)]
     quiet: bool,
+
+    /// What this timer is for; shown next to it and kept in the interval log
+    #[arg(short, long)]
+    label: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// Every running timer: name, time left, paused; and stopped sequences
+    List,
+    /// Run a sequence of phases. Progress is saved, so rerunning without SPEC after a
+    /// reboot or a closed terminal picks it up where the clock says it should be.
+    Seq {
+        /// Comma-separated `DURATION [LABEL] [@SOUND]` phases, `[...]xN` repeating a group,
+        /// e.g. "[25m work, 5m break @bell]x4, 15m long break"; or a preset: pomodoro.
+        /// Omit to resume the saved one.
+        spec: Option<String>,
+
+        /// Which sequence (also its timer name)
+        #[arg(short, long, default_value = "seq")]
+        name: String,
+
+        /// Drop the saved progress instead of running
+        #[arg(long)]
+        forget: bool,
+
+        /// Quiet mode (notifications only, no sounds)
+        #[arg(short, long)]
+        quiet: bool,
+    },
+    /// Running timers as the bar shows them; with --json, what eww listens to
+    Status {
+        /// One JSON array of {name, left, total, paused, label, phase, display}
+        #[arg(long)]
+        json: bool,
+
+        /// Keep going, printing again whenever it changes (for eww's deflisten)
+        #[arg(short, long)]
+        watch: bool,
+    },
+    /// Finished intervals, oldest first. Sequence phases that ran out while no
+    /// timer was running (machine off) are marked skipped
+    Log {
+        /// Only this timer's / sequence's
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// CSV with a header row, for spreadsheets
+        #[arg(long)]
+        csv: bool,
+    },
 }
 
 fn parse_time(input: &str) -> Result<i32, String> {
@@ -187,22 +693,6 @@ fn parse_time(input: &str) -> Result<i32, String> {
     }
 }
 
-fn read_control() -> Option<String> {
-    match fs::read_to_string(control_file()) {
-        Ok(s) => Some(s.trim().to_string()),
-        Err(e) if e.kind() == ErrorKind::NotFound => None,
-        Err(_) => None,
-    }
-}
-
-fn write_control(state: &str) -> Result<(), String> {
-    fs::write(control_file(), state).map_err(|e| e.to_string())
-}
-
-fn clear_control() {
-    let _ = fs::remove_file(control_file());
-}
-
 fn format_time(left: i32) -> String {
     let abs = left.unsigned_abs();
     let hours = abs / 3600;
@@ -216,9 +706,10 @@ fn format_time(left: i32) -> String {
     }
 }
 
-fn draw_line(left: i32, paused: bool) {
+fn draw_line(left: i32, paused: bool, label: Option<&str>) {
     let tag = if paused { " [paused]" } else { "" };
-    print!("\r\x1b[K{}{tag}", format_time(left));
+    let label = label.map(|l| format!("{l}  ")).unwrap_or_default();
+    print!("\r\x1b[K{label}{}{tag}", format_time(left));
     let _ = std::io::stdout().flush();
 }
 
@@ -226,12 +717,9 @@ extern "C" fn handle_signal(_: libc::c_int) {
     INTERRUPTED.store(true, Ordering::Relaxed);
 }
 
-fn cleanup() {
+fn cleanup(channel: &Channel) {
     disable_raw();
-    clear_control();
-    let _ = Command::new("eww")
-        .args(["update", "timer="])
-        .status();
+    channel.clear();
 }
 
 /// non-blocking: returns true if Enter was pressed within `dur`.
@@ -250,19 +738,16 @@ fn enter_pressed_within(dur: Duration) -> Result<bool, String> {
     Ok(false)
 }
 
-fn toggle_pause() -> Result<(), String> {
-    match read_control().as_deref() {
-        Some("running") => write_control("paused"),
-        Some("paused") => write_control("running"),
+fn toggle_pause(channel: &Channel) -> Result<(), String> {
+    match channel.read_control().as_deref() {
+        Some("running") => channel.write_control("paused"),
+        Some("paused") => channel.write_control("running"),
         _ => Ok(()),
     }
 }
 
-fn timer(initial: Option<i32>, quiet: bool) -> Result<(), String> {
-    let mut left = initial.unwrap_or(0);
-    let notify = initial.is_some();
-    let mut notified = false;
-
+/// Signal handlers, and raw mode when on a terminal. Returns whether it is one.
+fn start_terminal() -> Result<bool, String> {
     unsafe {
         let handler = handle_signal as *const () as libc::sighandler_t;
         libc::signal(libc::SIGINT, handler);
@@ -275,20 +760,80 @@ fn timer(initial: Option<i32>, quiet: bool) -> Result<(), String> {
         RAW_MODE.store(true, Ordering::Relaxed);
         println!("Enter: pause/resume, Ctrl-C: quit\r");
     }
+    Ok(tui)
+}
+
+/// Sits out `dur` while staying responsive to Enter, which toggles pause and ends the wait.
+fn wait(channel: &Channel, tui: bool, dur: Duration) -> Result<(), String> {
+    if !tui {
+        sleep(dur);
+        return Ok(());
+    }
+    let end = std::time::Instant::now() + dur;
+    loop {
+        let now = std::time::Instant::now();
+        if now >= end || INTERRUPTED.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        if enter_pressed_within(end - now)? {
+            return toggle_pause(channel);
+        }
+    }
+}
+
+/// Beeps through beep.rs with `sound`, or only notifies when quiet. `long` keeps the
+/// notification up until dismissed.
+fn announce(sound: &Path, message: &str, long: bool, quiet: bool) -> Result<(), String> {
+    let mut cmd = if quiet {
+        let mut cmd = Command::new("notify-send");
+        cmd.arg(message);
+        if long {
+            cmd.args(["-t", "2147483647"]);
+        }
+        cmd
+    } else {
+        let mut cmd = Command::new(BEEP);
+        cmd.arg(sound).arg(message);
+        if long {
+            cmd.args(["--long", "600"]);
+        }
+        cmd
+    };
+    cmd.status().map_err(|e| e.to_string())?;
+    Ok(())
+}
 
-    // Mark as running
-    write_control("running")?;
+fn timer(channel: &Channel, initial: Option<i32>, label: Option<String>, quiet: bool) -> Result<(), String> {
+    let mut left = initial.unwrap_or(0);
+    let notify = initial.is_some();
+    let mut notified = false;
+
+    // Mark as running — before the terminal goes raw, so a refused claim
+    // leaves the shell as it was.
+    channel.claim(initial, label.clone())?;
+    let tui = match start_terminal() {
+        Ok(tui) => tui,
+        Err(e) => {
+            cleanup(channel);
+            return Err(e);
+        }
+    };
+    let started_at = now();
+    let report = |left: i32, paused: bool| channel.write_state(&State { pid: std::process::id(), total: initial, left, paused, label: label.clone(), phase: None });
 
     loop {
         if INTERRUPTED.load(Ordering::Relaxed) {
             break;
         }
         // Check control file for pause state
-        while read_control().as_deref() == Some("paused") {
+        if channel.read_control().as_deref() == Some("paused") {
+            report(left, true);
+        }
+        while channel.read_control().as_deref() == Some("paused") {
             if tui {
-                draw_line(left, true);
+                draw_line(left, true, label.as_deref());
                 if enter_pressed_within(Duration::from_millis(100))? {
-                    toggle_pause()?;
+                    toggle_pause(channel)?;
                 }
             } else {
                 sleep(Duration::from_millis(100));
@@ -298,81 +843,316 @@ fn timer(initial: Option<i32>, quiet: bool) -> Result<(), String> {
             }
         }
         // Timer was cancelled externally
-        if read_control().is_none() || INTERRUPTED.load(Ordering::Relaxed) {
+        if channel.read_control().is_none() || INTERRUPTED.load(Ordering::Relaxed) {
             break;
         }
-
-        let display = format_time(left);
-        Command::new("eww")
-            .args(["update", &format!("timer={display}")])
-            .status()
-            .map_err(|e| e.to_string())?;
+        report(left, false);
         if tui {
-            draw_line(left, false);
-            // consume the 1s tick while staying responsive to Enter
-            let end = std::time::Instant::now() + Duration::from_secs(1);
-            loop {
-                let now = std::time::Instant::now();
-                if now >= end || INTERRUPTED.load(Ordering::Relaxed) {
-                    break;
-                }
-                if enter_pressed_within(end - now)? {
-                    toggle_pause()?;
-                    break;
-                }
-            }
-        } else {
-            sleep(Duration::from_secs(1));
+            draw_line(left, false, label.as_deref());
         }
+        // consume the 1s tick while staying responsive to Enter
+        wait(channel, tui, Duration::from_secs(1))?;
         left -= 1;
 
         if left < 0 && notify && !notified {
             notified = true;
-            if quiet {
-                Command::new("notify-send")
-                    .args(["timer finished", "-t", "2147483647"])
-                    .status()
-                    .map_err(|e| e.to_string())?;
-            } else {
-                Command::new("fish")
-                    .args(["-c", "beep --long 600 time"])
-                    .status()
-                    .map_err(|e| e.to_string())?;
+            Interval {
+                timer: channel.name.clone(),
+                label: label.clone(),
+                phase: None,
+                planned: initial.unwrap_or(0),
+                started_at,
+                ended_at: now(),
+                skipped: false,
             }
+            .append();
+            let message = label.as_deref().unwrap_or(if quiet { "timer finished" } else { "time" });
+            announce(Path::new(sequence::DEFAULT_SOUND), message, true, quiet)?;
         }
     }
 
-    cleanup();
+    cleanup(channel);
     Ok(())
 }
 
-fn main() {
-    let args = Args::parse();
+/// Runs (or picks up) a saved sequence. Unlike a plain countdown, what's on screen is
+/// recomputed from the clock every tick, so any time spent not running still counts.
+fn run_sequence(channel: &Channel, mut prog: Progress, quiet: bool) -> Result<(), String> {
+    // Before touching the log, so a second copy can't write the same phases again.
+    channel.claim(None, None)?;
+    if prog.paused_since.is_some() {
+        channel.write_control("paused")?;
+    }
+    // A running sequence logs each phase within the second it ends; any still
+    // unlogged at pickup ran out while nothing was running.
+    prog.log_finished(now(), true);
+    prog.save()?;
+    let tui = match start_terminal() {
+        Ok(tui) => tui,
+        Err(e) => {
+            cleanup(channel);
+            return Err(e);
+        }
+    };
 
-    if args.halt {
-        match read_control().as_deref() {
-            Some("running") => {
-                if let Err(e) = write_control("paused") {
-                    eprintln!("{e}");
-                    std::process::exit(1);
+    let n = prog.phases.len();
+    let (mut done, mut cancelled) = (false, false);
+    let result = (|| -> Result<(), String> {
+        loop {
+            if INTERRUPTED.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            let t = now();
+            match channel.read_control().as_deref() {
+                None => {
+                    cancelled = true;
+                    return Ok(());
                 }
+                Some("paused") if prog.paused_since.is_none() => {
+                    prog.pause(t);
+                    prog.save()?;
+                }
+                Some("running") if prog.paused_since.is_some() => {
+                    prog.unpause(t);
+                    prog.save()?;
+                }
+                _ => {}
+            }
+
+            // Phases that ended since the last tick.
+            let moved = prog.log_finished(t, false);
+            if moved {
+                prog.save()?;
             }
-            Some("paused") => eprintln!("Timer already paused"),
-            _ => eprintln!("No timer running"),
+            let Some((i, left)) = prog.position(t) else {
+                done = true;
+                return Ok(());
+            };
+            let phase = &prog.phases[i];
+            // Only transitions make a sound; the phase you start (or resume) in needs no announcing.
+            if moved {
+                let message = format!("{} ({}) {}/{n}", phase.label.as_deref().unwrap_or("next"), format_time(phase.secs), i + 1);
+                announce(&phase.sound_path(), &message, false, quiet)?;
+            }
+
+            let paused = prog.paused_since.is_some();
+            let state = State { pid: std::process::id(), total: Some(phase.secs), left, paused, label: phase.label.clone(), phase: Some((i + 1, n)) };
+            channel.write_state(&state);
+            if tui {
+                draw_line(left, paused, state.caption().as_deref());
+            }
+            // Up to the next wall-clock second, so the display steps with `now()`.
+            let into = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.subsec_millis()).unwrap_or(0);
+            wait(channel, tui, Duration::from_millis(u64::from(1000 - into)))?;
         }
+    })();
+
+    cleanup(channel);
+    if done || cancelled {
+        Progress::forget(&prog.name);
+    } else {
+        // Saved on every change already; this catches a pause toggled on the way out.
+        let _ = prog.save();
+    }
+    result?;
+    if done {
+        announce(Path::new(sequence::DEFAULT_SOUND), &format!("{} done", prog.name), true, quiet)?;
+    } else if !cancelled {
+        eprintln!("Sequence {0:?} saved and its clock keeps running: `timer seq -n {0}` picks it up, `timer seq -n {0} --forget` drops it", prog.name);
+    }
+    Ok(())
+}
+
+fn list() {
+    Channel::sweep();
+    let all = Channel::all();
+    let stopped: Vec<Progress> = Progress::all().into_iter().filter(|p| !all.iter().any(|c| c.name == p.name)).collect();
+    if all.is_empty() && stopped.is_empty() {
+        println!("No timer running");
         return;
     }
+    let width = all.iter().map(|c| c.name.len()).chain(stopped.iter().map(|p| p.name.len())).max().unwrap_or(0);
+    for c in all {
+        let Some(state) = c.read_state() else { continue };
+        let left = match state.total {
+            Some(total) => format!("{} of {}", format_time(state.left), format_time(total)),
+            None => format!("{} up", format_time(state.left)),
+        };
+        let tag = if state.paused { " [paused]" } else { "" };
+        let label = state.caption().map(|l| format!("  {l}")).unwrap_or_default();
+        println!("{:width$}  {left}{tag}{label}", c.name);
+    }
+    for p in stopped {
+        let at = match p.position(now()) {
+            Some((i, left)) => format!("phase {}/{}, {} left", i + 1, p.phases.len(), format_time(left)),
+            None => "finished".to_string(),
+        };
+        let tag = if p.paused_since.is_some() { " [paused]" } else { "" };
+        println!("{:width$}  not running ({at}{tag}) — `timer seq -n {}` picks it up", p.name, p.name);
+    }
+}
 
-    if args.resume {
-        match read_control().as_deref() {
-            Some("paused") => {
-                if let Err(e) = write_control("running") {
-                    eprintln!("{e}");
-                    std::process::exit(1);
-                }
+/// One running timer, for `status`.
+#[derive(Serialize)]
+struct Status {
+    name: String,
+    /// Seconds left (negative once overrun); elapsed for a count-up.
+    left: i32,
+    total: Option<i32>,
+    paused: bool,
+    label: Option<String>,
+    phase: Option<(usize, usize)>,
+    /// Caption and time, ready to put in the bar.
+    display: String,
+}
+
+fn statuses() -> Vec<Status> {
+    Channel::all()
+        .into_iter()
+        .filter_map(|c| {
+            let state = c.read_state()?;
+            let display = match state.caption() {
+                Some(caption) => format!("{caption} {}", format_time(state.left)),
+                None => format_time(state.left),
+            };
+            Some(Status { display, name: c.name, left: state.left, total: state.total, paused: state.paused, label: state.label, phase: state.phase })
+        })
+        .collect()
+}
+
+fn status(json: bool, watch: bool) -> Result<(), String> {
+    let render = |all: &[Status]| -> Result<String, String> {
+        if json {
+            return serde_json::to_string(all).map_err(|e| e.to_string());
+        }
+        Ok(all.iter().map(|s| if s.paused { format!("{} [paused]", s.display) } else { s.display.clone() }).collect::<Vec<_>>().join("  "))
+    };
+    let mut last = None;
+    loop {
+        let out = render(&statuses())?;
+        if last.as_ref() != Some(&out) {
+            let mut stdout = std::io::stdout().lock();
+            // A closed pipe means the listener went away; that's our cue to stop.
+            if writeln!(stdout, "{out}").and_then(|_| stdout.flush()).is_err() {
+                return Ok(());
             }
-            Some("running") => eprintln!("Timer already running"),
-            _ => eprintln!("No timer to resume"),
+            last = Some(out);
+        }
+        if !watch {
+            return Ok(());
+        }
+        sleep(Duration::from_millis(500));
+    }
+}
+
+fn log(name: Option<&str>, csv: bool) {
+    let intervals = Interval::read().into_iter().filter(|i| name.is_none_or(|n| i.timer == n));
+    if csv {
+        println!("started,ended,seconds,planned,timer,phase,label,skipped");
+    }
+    for i in intervals {
+        let secs = i.ended_at.saturating_sub(i.started_at);
+        let label = i.label.as_deref().unwrap_or("");
+        if csv {
+            let phase = i.phase.map(|p| p.to_string()).unwrap_or_default();
+            println!(
+                "{},{},{secs},{},{},{phase},{},{}",
+                iso8601(i.started_at),
+                iso8601(i.ended_at),
+                i.planned,
+                csv_field(&i.timer),
+                csv_field(label),
+                i.skipped
+            );
+        } else {
+            let skipped = if i.skipped { "  (skipped)" } else { "" };
+            println!("{}  {:>8}  {}  {label}{skipped}", iso8601(i.started_at), format_time(secs as i32), i.timer);
+        }
+    }
+}
+
+fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        return Err(format!("Timer name {name:?} must be a plain word"));
+    }
+    Ok(())
+}
+
+fn seq(spec: Option<String>, name: String, forget: bool, quiet: bool) -> Result<(), String> {
+    check_name(&name)?;
+    let channel = Channel::new(&name);
+    if forget {
+        if channel.alive() {
+            return Err(format!("Sequence {name:?} is running; `timer --cancel -n {name}` stops and drops it"));
+        }
+        Progress::forget(&name);
+        return Ok(());
+    }
+    let prog = match spec {
+        Some(spec) => {
+            if channel.alive() {
+                return Err(format!("A timer named {name:?} is already running"));
+            }
+            Progress::new(&name, &spec)?
+        }
+        None => Progress::load(&name).ok_or_else(|| format!("No saved sequence {name:?}; give one, e.g. `timer seq pomodoro`"))?,
+    };
+    if let Some(missing) = prog.phases.iter().map(|p| p.sound_path()).find(|p| !p.exists()) {
+        return Err(format!("No such sound: {}", missing.display()));
+    }
+    run_sequence(&channel, prog, quiet)
+}
+
+fn main() {
+    let args = Args::parse();
+
+    match args.cmd {
+        Some(Cmd::List) => {
+            list();
+            return;
+        }
+        Some(Cmd::Status { json, watch }) => {
+            if let Err(e) = status(json, watch) {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Cmd::Log { name, csv }) => {
+            log(name.as_deref(), csv);
+            return;
+        }
+        Some(Cmd::Seq { spec, name, forget, quiet }) => {
+            if let Err(e) = seq(spec, name, forget, quiet) {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        None => {}
+    }
+
+    if args.halt || args.resume || args.cancel {
+        let channel = match Channel::target(args.name.as_deref()) {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        };
+        let name = &channel.name;
+        let result = match (channel.read_control().as_deref(), args.halt, args.resume) {
+            (Some("running"), true, _) => channel.write_control("paused"),
+            (Some("paused"), true, _) => Err(format!("Timer {name:?} already paused")),
+            (Some("paused"), _, true) => channel.write_control("running"),
+            (Some("running"), _, true) => Err(format!("Timer {name:?} already running")),
+            // Removing the control file is the cancel signal; the timer cleans up the rest.
+            _ if args.cancel => fs::remove_file(channel.dir.join("control")).map_err(|e| e.to_string()),
+            _ => Err(format!("Timer {name:?} is shutting down")),
+        };
+        if let Err(e) = result {
+            eprintln!("{e}");
+            std::process::exit(1);
         }
         return;
     }
@@ -388,8 +1168,111 @@ fn main() {
         None => None,
     };
 
-    if let Err(e) = timer(initial, args.quiet) {
+    let channel = match &args.name {
+        Some(name) => match check_name(name) {
+            Ok(()) => Channel::new(name),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        },
+        None => Channel::unnamed(),
+    };
+    if let Err(e) = timer(&channel, initial, args.label, args.quiet) {
         eprintln!("{e}");
         std::process::exit(1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seconds(phases: &[sequence::Phase]) -> Vec<i32> {
+        phases.iter().map(|p| p.secs).collect()
+    }
+
+    fn progress(secs: &[i32]) -> Progress {
+        let phases = secs.iter().map(|&secs| sequence::Phase { secs, label: None, sound: None }).collect();
+        Progress { name: "seq".into(), spec: String::new(), phases, started_at: 1000, paused_total: 0, paused_since: None, logged: 0, phase_began: 1000 }
+    }
+
+    #[test]
+    fn a_preset_expands_to_its_phases() {
+        let phases = sequence::parse("pomodoro").unwrap();
+        assert_eq!(seconds(&phases), [1500, 300, 1500, 300, 1500, 300, 1500, 300, 900]);
+        assert_eq!(phases[1].label.as_deref(), Some("break"));
+        assert_eq!(phases[8].label.as_deref(), Some("long break"));
+    }
+
+    #[test]
+    fn groups_nest_and_repeat() {
+        let phases = sequence::parse("[[1m a, 2m b]x2, 3m c @bell]x2, 10s").unwrap();
+        assert_eq!(seconds(&phases), [60, 120, 60, 120, 180, 60, 120, 60, 120, 180, 10]);
+        assert_eq!(phases[4].label.as_deref(), Some("c"));
+        assert_eq!(phases[4].sound.as_deref(), Some("bell"));
+        assert_eq!(phases[10].label, None);
+        // A bare group is one pass.
+        assert_eq!(seconds(&sequence::parse("[1m, 2m]").unwrap()), [60, 120]);
+    }
+
+    #[test]
+    fn malformed_specs_are_refused() {
+        for bad in ["", " , ", "[5m work", "5m work]", "[5m]y3", "[5m]xmany", "0m nothing", "work 5m", "[]x3"] {
+            assert!(sequence::parse(bad).is_err(), "{bad:?} parsed");
+        }
+    }
+
+    #[test]
+    fn position_turns_over_exactly_at_a_phase_boundary() {
+        let p = progress(&[60, 30]);
+        assert_eq!(p.position(1000), Some((0, 60)));
+        assert_eq!(p.position(1059), Some((0, 1)));
+        assert_eq!(p.position(1060), Some((1, 30)));
+        assert_eq!(p.position(1089), Some((1, 1)));
+        assert_eq!(p.position(1090), None);
+        assert_eq!((p.ended_at(0), p.ended_at(1)), (1060, 1090));
+    }
+
+    #[test]
+    fn time_paused_holds_the_position_and_pushes_phase_ends_back() {
+        let mut p = progress(&[60, 30]);
+        p.pause(1030);
+        assert_eq!(p.position(5000), Some((0, 30)));
+        p.unpause(1130);
+        assert_eq!(p.position(1130), Some((0, 30)));
+        assert_eq!(p.ended_at(0), 1160);
+    }
+
+    #[test]
+    fn phases_are_logged_once_and_those_missed_while_away_say_so() {
+        let mut p = progress(&[60, 30, 60]);
+        assert!(p.finished(1059, false).is_empty());
+        let first = p.finished(1060, false);
+        assert_eq!(first.len(), 1);
+        assert_eq!((first[0].phase, first[0].started_at, first[0].ended_at, first[0].skipped), (Some(1), 1000, 1060, false));
+        assert!(p.finished(1061, false).is_empty());
+
+        // Picked up after the rest of the sequence ran out with nothing running.
+        let away = p.finished(9000, true);
+        let got: Vec<_> = away.iter().map(|i| (i.phase, i.started_at, i.ended_at, i.skipped)).collect();
+        assert_eq!(got, [(Some(2), 1060, 1090, true), (Some(3), 1090, 1150, true)]);
+    }
+
+    #[test]
+    fn iso8601_on_known_epochs() {
+        assert_eq!(iso8601(0), "1970-01-01T00:00:00Z");
+        assert_eq!(iso8601(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(iso8601(1_709_251_199), "2024-02-29T23:59:59Z");
+        assert_eq!(iso8601(1_709_251_200), "2024-03-01T00:00:00Z");
+        assert_eq!(iso8601(4_107_542_400), "2100-03-01T00:00:00Z"); // 2100 isn't a leap year
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_only_when_they_need_it() {
+        assert_eq!(csv_field("deep work"), "deep work");
+        assert_eq!(csv_field("a, b"), "\"a, b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+}
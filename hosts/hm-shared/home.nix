@@ -160,6 +160,24 @@
     };
   };
 
+  systemd.user.services.claude-sessions = {
+    Unit = {
+      Description =
+        "claude_sessions --daemon: one resident poller behind the bar and supervise_sessions";
+      After = [ "sway-session.target" ];
+      PartOf = [ "sway-session.target" ];
+    };
+    Install = { WantedBy = [ "sway-session.target" ]; };
+    Service = {
+      # through fish for the same reason as claude-session-restore: nix-run-cached
+      # and tmux both want the login env, which a bare unit doesn't have.
+      ExecStart =
+        "${pkgs.fish}/bin/fish -c 'exec ${config.home.homeDirectory}/nix/home/config/tmux/claude_sessions.rs --daemon'";
+      Restart = "always";
+      RestartSec = 5;
+    };
+  };
+
   systemd.user.services.claude-session-restore = {
     Unit = {
       Description =